@@ -0,0 +1,20 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "proto")]
+    compile_protos();
+}
+
+/// Generates `src/proto.rs`'s messages with the vendored protoc, so no system install is needed.
+#[cfg(feature = "proto")]
+fn compile_protos() {
+    println!("cargo:rerun-if-changed=proto/market_data.proto");
+
+    let protoc =
+        protoc_bin_vendored::protoc_bin_path().expect("No vendored protoc for this target");
+
+    prost_build::Config::new()
+        .protoc_executable(protoc)
+        .compile_protos(&["proto/market_data.proto"], &["proto"])
+        .expect("Failed to compile proto/market_data.proto");
+}
@@ -0,0 +1,29 @@
+#ifndef HL_UTILS_H
+#define HL_UTILS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define HL_FEED_PERPS 0
+#define HL_FEED_SPOT 1
+
+#define HL_OK 0
+#define HL_ERR_INVALID_ARGUMENT -1
+#define HL_ERR_NOT_FOUND -2
+
+typedef struct HlPriceFeed HlPriceFeed;
+
+/* Returns NULL on failure. Release with hl_price_feed_stop. */
+HlPriceFeed *hl_price_feed_start(int kind);
+
+/* Returns HL_ERR_NOT_FOUND until the coin has been received at least once. */
+int hl_price_feed_get_price(const HlPriceFeed *feed, const char *coin, double *out_price);
+
+void hl_price_feed_stop(HlPriceFeed *feed);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,520 @@
+use std::{collections::HashMap, num::ParseFloatError, time::Duration};
+
+use alloy::primitives::Address;
+use anyhow::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info, warn};
+
+use crate::{
+    price_data::{perps::parse_string_to_float, spot::SpotMeta},
+    prices::{build_info_http_client, post_info},
+    types::SpotAssetMeta,
+};
+
+pub const ACCOUNT_STATE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct AccountState {
+    pub positions: Vec<Position>,
+    pub account_value: f64,
+    pub total_margin_used: f64,
+    pub total_notional: f64,
+    pub cross_maintenance_margin_used: f64,
+    pub withdrawable: f64,
+    pub time: u64,
+}
+
+impl AccountState {
+    /// Fraction of the account value used as margin, 0.0 for an empty account.
+    pub fn get_margin_usage(&self) -> f64 {
+        if self.account_value <= 0.0 {
+            return 0.0_f64;
+        }
+
+        self.total_margin_used / self.account_value
+    }
+
+    pub fn get_position(&self, coin: &str) -> Option<&Position> {
+        self.positions.iter().find(|position| position.coin == coin)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub coin: String,
+    /// Signed size, negative when short
+    pub size: f64,
+    pub entry_price: f64,
+    pub position_value: f64,
+    pub unrealized_pnl: f64,
+    pub return_on_equity: f64,
+    pub margin_used: f64,
+    /// 0.0 when the position can't be liquidated
+    pub liquidation_price: f64,
+    pub leverage: u32,
+    pub is_isolated: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClearinghouseState {
+    asset_positions: Vec<AssetPosition>,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    cross_maintenance_margin_used: f64,
+    margin_summary: MarginSummary,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    withdrawable: f64,
+    time: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarginSummary {
+    #[serde(deserialize_with = "parse_string_to_float")]
+    account_value: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    total_margin_used: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    total_ntl_pos: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssetPosition {
+    position: PositionData,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PositionData {
+    coin: String,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    szi: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    entry_px: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    position_value: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    unrealized_pnl: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    return_on_equity: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    margin_used: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    liquidation_px: f64,
+    leverage: LeverageData,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeverageData {
+    #[serde(rename = "type")]
+    type_: String,
+    value: u32,
+}
+
+impl From<ClearinghouseState> for AccountState {
+    fn from(state: ClearinghouseState) -> Self {
+        AccountState {
+            positions: state
+                .asset_positions
+                .into_iter()
+                .map(|asset_position| {
+                    let position = asset_position.position;
+
+                    Position {
+                        coin: position.coin,
+                        size: position.szi,
+                        entry_price: position.entry_px,
+                        position_value: position.position_value,
+                        unrealized_pnl: position.unrealized_pnl,
+                        return_on_equity: position.return_on_equity,
+                        margin_used: position.margin_used,
+                        liquidation_price: position.liquidation_px,
+                        leverage: position.leverage.value,
+                        is_isolated: position.leverage.type_ == "isolated",
+                    }
+                })
+                .collect(),
+            account_value: state.margin_summary.account_value,
+            total_margin_used: state.margin_summary.total_margin_used,
+            total_notional: state.margin_summary.total_ntl_pos,
+            cross_maintenance_margin_used: state.cross_maintenance_margin_used,
+            withdrawable: state.withdrawable,
+            time: state.time,
+        }
+    }
+}
+
+/// Address whose state and fills to follow: the vault when acting on behalf of one, the signing
+/// wallet otherwise.
+pub fn get_account_address(user: Address, vault_address: Option<Address>) -> Address {
+    vault_address.unwrap_or(user)
+}
+
+pub async fn get_account_state(client: &Client, user: Address) -> Result<AccountState, Error> {
+    let state: ClearinghouseState = post_info(
+        client,
+        json!({ "type": "clearinghouseState", "user": user }),
+    )
+    .await?;
+
+    Ok(AccountState::from(state))
+}
+
+/// A token balance as returned by the `spotClearinghouseState` info request
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", try_from = "RawSpotBalance")]
+pub struct SpotBalance {
+    /// Token name, e.g. "PURR"
+    pub coin: String,
+    /// Token index
+    pub token: u16,
+    pub total: f64,
+    /// Part of `total` locked in open orders
+    pub hold: f64,
+    /// USD cost of the balance
+    pub entry_ntl: f64,
+    /// `total` as the API sent it, so [`TokenBalance`] converts it to wei exactly
+    #[serde(skip)]
+    pub total_decimal: String,
+    /// `hold` as the API sent it
+    #[serde(skip)]
+    pub hold_decimal: String,
+}
+
+impl SpotBalance {
+    /// The decimal of `amount` as the API sent it, or formatted from `value` for balances built
+    /// by hand. Formatting is only exact up to f64's 15 significant digits.
+    fn get_decimal(amount: &str, value: f64) -> String {
+        if amount.is_empty() {
+            value.to_string()
+        } else {
+            amount.to_string()
+        }
+    }
+}
+
+/// The wire format of [`SpotBalance`], amounts are decimal strings or null
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSpotBalance {
+    coin: String,
+    token: u16,
+    total: Option<String>,
+    hold: Option<String>,
+    entry_ntl: Option<String>,
+}
+
+impl TryFrom<RawSpotBalance> for SpotBalance {
+    type Error = ParseFloatError;
+
+    fn try_from(raw: RawSpotBalance) -> Result<Self, Self::Error> {
+        let parse = |amount: &Option<String>| amount.as_deref().map_or(Ok(0.0), str::parse);
+
+        Ok(SpotBalance {
+            total: parse(&raw.total)?,
+            hold: parse(&raw.hold)?,
+            entry_ntl: parse(&raw.entry_ntl)?,
+            coin: raw.coin,
+            token: raw.token,
+            total_decimal: raw.total.unwrap_or_default(),
+            hold_decimal: raw.hold.unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct SpotClearinghouseState {
+    pub(crate) balances: Vec<SpotBalance>,
+}
+
+pub async fn get_spot_balances(client: &Client, user: Address) -> Result<Vec<SpotBalance>, Error> {
+    let state: SpotClearinghouseState = post_info(
+        client,
+        json!({ "type": "spotClearinghouseState", "user": user }),
+    )
+    .await?;
+
+    Ok(state.balances)
+}
+
+/// A [`SpotBalance`] converted with its token's [`SpotAssetMeta`]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct TokenBalance {
+    pub coin: String,
+    pub token: u16,
+    pub meta: SpotAssetMeta,
+    /// `total` in the token's smallest unit
+    pub total_wei: u128,
+    /// `hold` in the token's smallest unit
+    pub hold_wei: u128,
+    /// Rounded to the token's wei decimals
+    pub total: f64,
+    /// Rounded to the token's wei decimals
+    pub hold: f64,
+    pub entry_ntl: f64,
+}
+
+impl TokenBalance {
+    /// Errors if the amounts aren't plain non-negative decimals, see [`SpotAssetMeta::to_wei`].
+    pub fn new(balance: &SpotBalance, meta: SpotAssetMeta) -> Result<Self, Error> {
+        let total_wei = meta.to_wei(&SpotBalance::get_decimal(
+            &balance.total_decimal,
+            balance.total,
+        ))?;
+        let hold_wei = meta.to_wei(&SpotBalance::get_decimal(
+            &balance.hold_decimal,
+            balance.hold,
+        ))?;
+
+        Ok(TokenBalance {
+            coin: balance.coin.clone(),
+            token: balance.token,
+            total_wei,
+            hold_wei,
+            total: meta.from_wei(total_wei),
+            hold: meta.from_wei(hold_wei),
+            entry_ntl: balance.entry_ntl,
+            meta,
+        })
+    }
+
+    /// The part of the balance not locked in open orders
+    pub fn get_available_wei(&self) -> u128 {
+        self.total_wei.saturating_sub(self.hold_wei)
+    }
+
+    pub fn get_available(&self) -> f64 {
+        self.meta.from_wei(self.get_available_wei())
+    }
+}
+
+/// Converts `balances` with the token metas of [`SpotMeta::get_token_metas`]. Balances of tokens
+/// missing from `token_metas`, or whose amounts can't be converted, are left out.
+pub fn get_token_balances(
+    balances: &[SpotBalance],
+    token_metas: &HashMap<u16, SpotAssetMeta>,
+) -> Vec<TokenBalance> {
+    balances
+        .iter()
+        .filter_map(|balance| {
+            let Some(meta) = token_metas.get(&balance.token) else {
+                warn!("Skipping balance of unknown token {}", balance.token);
+                return None;
+            };
+
+            match TokenBalance::new(balance, meta.clone()) {
+                Ok(balance) => Some(balance),
+                Err(err) => {
+                    warn!("Skipping balance of {}: {err:?}", balance.coin);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+async fn get_token_metas(client: &Client) -> Result<HashMap<u16, SpotAssetMeta>, Error> {
+    let spot_meta: SpotMeta = post_info(client, json!({ "type": "spotMeta" })).await?;
+
+    Ok(spot_meta.get_token_metas())
+}
+
+/// Fetches the spot balances of `user` along with the spot meta to convert them.
+pub async fn get_spot_token_balances(
+    client: &Client,
+    user: Address,
+) -> Result<Vec<TokenBalance>, Error> {
+    let (balances, token_metas) =
+        tokio::try_join!(get_spot_balances(client, user), get_token_metas(client))?;
+
+    Ok(get_token_balances(&balances, &token_metas))
+}
+
+/// Polls the spot balances of `user` (or of the vault, see [`get_account_address`]) every
+/// `interval`. The spot meta is fetched again whenever the task restarts, or when a balance
+/// shows up in a token it doesn't know yet.
+pub async fn start_spot_balances_task(
+    user: Address,
+    vault_address: Option<Address>,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<Vec<TokenBalance>>> {
+    let user = get_account_address(user, vault_address);
+    let (balances_sender, balances_recv) = watch::channel(vec![]);
+
+    tokio::spawn(async move {
+        let b_s = balances_sender;
+        loop {
+            info!("spot_balances_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let mut interval = tokio::time::interval(interval);
+            let mut token_metas: HashMap<u16, SpotAssetMeta> = HashMap::new();
+
+            let err = loop {
+                interval.tick().await;
+
+                let balances = match get_spot_balances(&client, user).await {
+                    Ok(balances) => balances,
+                    Err(err) => break err,
+                };
+
+                if balances.iter().any(|b| !token_metas.contains_key(&b.token)) {
+                    token_metas = match get_token_metas(&client).await {
+                        Ok(token_metas) => token_metas,
+                        Err(err) => break err,
+                    };
+                }
+
+                if b_s
+                    .send(get_token_balances(&balances, &token_metas))
+                    .is_err()
+                {
+                    info!("spot_balances_task: All receivers dropped, stopping...");
+                    return;
+                }
+            };
+
+            error!("spot_balances_task: Error: {err:?}");
+            info!("spot_balances_task: Resetting...");
+
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(balances_recv)
+}
+
+/// A resting order as returned by the `openOrders` info request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenOrder {
+    pub coin: String,
+    /// "B" for bids, "A" for asks
+    pub side: String,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub limit_px: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub sz: f64,
+    pub oid: u64,
+    /// ms since epoch
+    pub timestamp: u64,
+}
+
+impl OpenOrder {
+    pub fn is_buy(&self) -> bool {
+        self.side == "B"
+    }
+}
+
+pub async fn get_open_orders(client: &Client, user: Address) -> Result<Vec<OpenOrder>, Error> {
+    post_info(client, json!({ "type": "openOrders", "user": user })).await
+}
+
+/// Polls the state of `user`, or of `vault_address` if set.
+pub async fn start_account_state_task(
+    user: Address,
+    vault_address: Option<Address>,
+) -> anyhow::Result<watch::Receiver<AccountState>> {
+    let user = get_account_address(user, vault_address);
+    let (state_sender, state_recv) = watch::channel(AccountState::default());
+
+    tokio::spawn(async move {
+        let s_s = state_sender;
+        loop {
+            info!("account_state_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let mut interval = tokio::time::interval(ACCOUNT_STATE_POLL_INTERVAL);
+
+            let err = loop {
+                interval.tick().await;
+
+                match get_account_state(&client, user).await {
+                    Ok(state) => {
+                        if s_s.send(state).is_err() {
+                            info!("account_state_task: All receivers dropped, stopping...");
+                            return;
+                        }
+                    }
+                    Err(err) => break err,
+                }
+            };
+
+            error!("account_state_task: Error: {err:?}");
+            info!("account_state_task: Resetting...");
+
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(state_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::types::SpotAssetMeta;
+
+    use super::{get_token_balances, SpotBalance};
+
+    #[test]
+    fn converts_balances_to_wei() {
+        let mut balances: Vec<SpotBalance> = serde_json::from_str(
+            r#"[
+                {"coin": "USDC", "token": 0, "total": "14.62548523", "hold": "4.1", "entryNtl": "0.0"},
+                {"coin": "NEW", "token": 9, "total": "1.0", "hold": "0.0", "entryNtl": "0.0"},
+                {"coin": "WHALE", "token": 1, "total": "123456789012.12345678", "hold": null, "entryNtl": "0.0"}
+            ]"#,
+        )
+        .unwrap();
+        balances.push(SpotBalance {
+            coin: "HAND".to_string(),
+            token: 2,
+            total: 0.1,
+            ..Default::default()
+        });
+
+        let meta = |index: u16| SpotAssetMeta {
+            sz_decimals: 8,
+            wei_decimals: 8,
+            name: format!("TOKEN{index}"),
+            index,
+        };
+        let token_metas = HashMap::from([(0, meta(0)), (1, meta(1)), (2, meta(2))]);
+
+        let balances = get_token_balances(&balances, &token_metas);
+
+        assert_eq!(balances.len(), 3);
+        // Exact, beyond what f64 holds
+        assert_eq!(balances[1].total_wei, 12_345_678_901_212_345_678);
+        assert_eq!(balances[1].hold_wei, 0);
+        assert_eq!(balances[2].total_wei, 10_000_000);
+
+        assert_eq!(balances[0].total_wei, 1_462_548_523);
+        assert_eq!(balances[0].hold_wei, 410_000_000);
+        assert_eq!(balances[0].get_available_wei(), 1_052_548_523);
+        assert_eq!(balances[0].get_available(), 10.52548523);
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+#[cfg(feature = "live")]
+use std::time::Duration;
+
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+use crate::{
+    analytics::{get_aligned_returns, get_covariance},
+    history::PriceHistory,
+};
+
+pub type CoinToBetaMap = HashMap<String, f64>;
+
+/// Beta of `returns` against `benchmark_returns`, `None` if the benchmark is flat or the series
+/// are too short.
+pub fn get_beta(returns: &[f64], benchmark_returns: &[f64]) -> Option<f64> {
+    let len = returns.len().min(benchmark_returns.len());
+    let variance = get_covariance(&benchmark_returns[..len], &benchmark_returns[..len]);
+
+    if variance == 0.0 {
+        return None;
+    }
+
+    Some(get_covariance(&returns[..len], &benchmark_returns[..len]) / variance)
+}
+
+/// Beta of every coin in the history against `benchmark` (e.g. "BTC") over the last `window`
+/// returns, each coin aligned with the benchmark on the times both have a price.
+pub fn get_beta_map(history: &PriceHistory, benchmark: &str, window: usize) -> CoinToBetaMap {
+    history
+        .get_coins()
+        .into_iter()
+        .filter(|coin| coin.as_str() != benchmark)
+        .filter_map(|coin| {
+            let returns =
+                get_aligned_returns(history, &[coin.clone(), benchmark.to_string()], window);
+
+            let beta = get_beta(returns.get(coin)?, returns.get(benchmark)?)?;
+            Some((coin.clone(), beta))
+        })
+        .collect()
+}
+
+/// Recomputes [`get_beta_map`] from the latest history every `interval`.
+#[cfg(feature = "live")]
+pub async fn start_beta_task(
+    history_receiver: watch::Receiver<PriceHistory>,
+    benchmark: String,
+    window: usize,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<CoinToBetaMap>> {
+    let (beta_sender, beta_recv) = watch::channel(CoinToBetaMap::new());
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+
+        info!("beta_task: Starting...");
+
+        loop {
+            interval.tick().await;
+
+            if history_receiver.has_changed().is_err() {
+                info!("beta_task: History channel closed, stopping...");
+                return;
+            }
+
+            let betas = get_beta_map(&history_receiver.borrow(), &benchmark, window);
+
+            if beta_sender.send(betas).is_err() {
+                info!("beta_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(beta_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{PriceHistory, PricePoint};
+
+    use super::{get_beta, get_beta_map};
+
+    #[test]
+    fn beta_against_the_benchmark() {
+        let beta = get_beta(&[0.02, -0.04, 0.06, 0.0], &[0.01, -0.02, 0.03, 0.0]).unwrap();
+        assert!((beta - 2.0).abs() < 1e-9);
+        assert_eq!(get_beta(&[0.01, 0.02], &[0.01, 0.01]), None);
+
+        let mut history = PriceHistory::new(10);
+        for (time, btc) in [60000.0, 61000.0, 59000.0, 62000.0].into_iter().enumerate() {
+            let time = time as i64;
+            history.push("BTC", PricePoint { time, price: btc });
+            history.push(
+                "ETH",
+                PricePoint {
+                    time,
+                    price: btc / 30.0,
+                },
+            );
+            history.push("FLAT", PricePoint { time, price: 1.0 });
+        }
+
+        let betas = get_beta_map(&history, "BTC", 10);
+
+        assert!(!betas.contains_key("BTC"));
+        assert!((betas["ETH"] - 1.0).abs() < 1e-9);
+        assert!(betas["FLAT"].abs() < 1e-12);
+    }
+}
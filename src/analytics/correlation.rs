@@ -0,0 +1,165 @@
+#[cfg(feature = "live")]
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+#[cfg(feature = "live")]
+use crate::types::NameToPriceMap;
+use crate::{
+    analytics::{get_aligned_returns, get_correlation},
+    history::PriceHistory,
+};
+
+/// Pairwise return correlations, `values[i][j]` being the correlation of `coins[i]` and
+/// `coins[j]`. Pairs that can't be computed (flat or too short series) are `None`.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct CorrelationMatrix {
+    pub coins: Vec<String>,
+    pub values: Vec<Vec<Option<f64>>>,
+    /// Number of returns each correlation was computed over
+    pub samples: usize,
+}
+
+impl CorrelationMatrix {
+    pub fn get(&self, a: &str, b: &str) -> Option<f64> {
+        let i = self.coins.iter().position(|coin| coin == a)?;
+        let j = self.coins.iter().position(|coin| coin == b)?;
+
+        self.values[i][j]
+    }
+
+    /// Every pair once, most correlated first.
+    pub fn get_ranked_pairs(&self) -> Vec<(String, String, f64)> {
+        let mut pairs: Vec<(String, String, f64)> = vec![];
+
+        for (i, row) in self.values.iter().enumerate() {
+            for (j, value) in row.iter().enumerate().skip(i + 1) {
+                if let Some(value) = value {
+                    pairs.push((self.coins[i].clone(), self.coins[j].clone(), *value));
+                }
+            }
+        }
+
+        pairs.sort_by(|a, b| b.2.total_cmp(&a.2));
+        pairs
+    }
+}
+
+/// Correlations of the last `window` aligned returns of `coins`. Coins missing from the history
+/// are left out of the matrix.
+pub fn get_correlation_matrix(
+    history: &PriceHistory,
+    coins: &[String],
+    window: usize,
+) -> CorrelationMatrix {
+    let returns = get_aligned_returns(history, coins, window);
+    let coins: Vec<String> = coins
+        .iter()
+        .filter(|coin| returns.contains_key(*coin))
+        .cloned()
+        .collect();
+
+    let values = coins
+        .iter()
+        .map(|a| {
+            coins
+                .iter()
+                .map(|b| get_correlation(&returns[a], &returns[b]))
+                .collect()
+        })
+        .collect();
+
+    CorrelationMatrix {
+        samples: returns.values().map(Vec::len).next().unwrap_or(0),
+        coins,
+        values,
+    }
+}
+
+#[cfg(feature = "live")]
+#[derive(Clone, Debug)]
+pub struct CorrelationConfig {
+    pub coins: Vec<String>,
+    /// Number of returns the correlations are computed over
+    pub window: usize,
+    /// How often prices are sampled, which is also the return period and how often the matrix is
+    /// republished
+    pub sample_interval: Duration,
+}
+
+/// Samples `price_receiver` every `sample_interval` and publishes the correlation matrix of the
+/// configured coins over the last `window` sampled returns.
+#[cfg(feature = "live")]
+pub async fn start_correlation_task(
+    price_receiver: watch::Receiver<NameToPriceMap>,
+    config: CorrelationConfig,
+) -> anyhow::Result<watch::Receiver<CorrelationMatrix>> {
+    let (matrix_sender, matrix_recv) = watch::channel(CorrelationMatrix::default());
+
+    tokio::spawn(async move {
+        let mut history = PriceHistory::new(config.window + 1);
+        let mut interval = tokio::time::interval(config.sample_interval);
+
+        info!("correlation_task: Starting...");
+
+        loop {
+            interval.tick().await;
+
+            if price_receiver.has_changed().is_err() {
+                info!("correlation_task: Price channel closed, stopping...");
+                return;
+            }
+
+            let time = chrono::Utc::now().timestamp_millis();
+            history.record(&price_receiver.borrow(), time);
+
+            let matrix = get_correlation_matrix(&history, &config.coins, config.window);
+
+            if matrix_sender.send(matrix).is_err() {
+                info!("correlation_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(matrix_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{PriceHistory, PricePoint};
+
+    use super::get_correlation_matrix;
+
+    #[test]
+    fn correlated_and_anti_correlated() {
+        let mut history = PriceHistory::new(10);
+
+        for (time, (a, b, c)) in [
+            (100.0, 10.0, 50.0),
+            (110.0, 11.0, 45.0),
+            (105.0, 10.5, 47.0),
+            (120.0, 12.0, 40.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let time = time as i64;
+            history.push("A", PricePoint { time, price: a });
+            history.push("B", PricePoint { time, price: b });
+            history.push("C", PricePoint { time, price: c });
+        }
+
+        let coins = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let matrix = get_correlation_matrix(&history, &coins, 10);
+
+        assert_eq!(matrix.samples, 3);
+        assert!((matrix.get("A", "B").unwrap() - 1.0).abs() < 1e-9);
+        assert!(matrix.get("A", "C").unwrap() < -0.9);
+        assert_eq!(matrix.get_ranked_pairs()[0].0, "A");
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+use crate::history::{PriceHistory, PricePoint};
+
+/// Drawdowns of every coin, one per window in the order the windows were given
+pub type CoinToDrawdownsMap = HashMap<String, Vec<Drawdown>>;
+
+/// Drawdowns over the points of a window, as fractions of the peak (0.1 == 10% below it).
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Drawdown {
+    pub window: Duration,
+    pub samples: usize,
+    /// Highest price of the window
+    pub peak: f64,
+    /// Of the last price from the highest one before it
+    pub current: f64,
+    /// Largest fall from a running peak to a later price
+    pub max: f64,
+}
+
+impl Drawdown {
+    /// `None` if `points` is empty
+    pub fn from_points(points: &[PricePoint], window: Duration) -> Option<Self> {
+        let last = points.last()?.price;
+        let prices: Vec<f64> = points.iter().map(|point| point.price).collect();
+        let peak = prices.iter().copied().fold(f64::MIN, f64::max);
+        let current = if peak > 0.0 {
+            (peak - last) / peak
+        } else {
+            0.0
+        };
+
+        Some(Drawdown {
+            window,
+            samples: points.len(),
+            peak,
+            current,
+            max: get_max_drawdown(&prices),
+        })
+    }
+}
+
+/// Largest peak to trough fall of `prices` as a fraction of the peak, 0.0 if they never fall.
+pub fn get_max_drawdown(prices: &[f64]) -> f64 {
+    let mut peak = f64::MIN;
+
+    prices.iter().fold(0.0_f64, |max, price| {
+        peak = peak.max(*price);
+
+        if peak > 0.0 {
+            max.max((peak - price) / peak)
+        } else {
+            max
+        }
+    })
+}
+
+/// Drawdown of every coin in the history over each of `windows`, each ending at the coin's latest
+/// point. Windows longer than the history cover all of it.
+pub fn get_drawdown_map(history: &PriceHistory, windows: &[Duration]) -> CoinToDrawdownsMap {
+    history
+        .iter()
+        .filter_map(|(coin, points)| {
+            let points: Vec<PricePoint> = points.iter().copied().collect();
+            let last_time = points.last()?.time;
+
+            let drawdowns = windows
+                .iter()
+                .filter_map(|window| {
+                    let start = last_time - window.as_millis() as i64;
+                    let from = points.partition_point(|point| point.time < start);
+
+                    Drawdown::from_points(&points[from..], *window)
+                })
+                .collect();
+
+            Some((coin.clone(), drawdowns))
+        })
+        .collect()
+}
+
+/// Recomputes [`get_drawdown_map`] from the latest history every `interval`.
+#[cfg(feature = "live")]
+pub async fn start_drawdown_task(
+    history_receiver: watch::Receiver<PriceHistory>,
+    windows: Vec<Duration>,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<CoinToDrawdownsMap>> {
+    let (drawdown_sender, drawdown_recv) = watch::channel(CoinToDrawdownsMap::new());
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+
+        info!("drawdown_task: Starting...");
+
+        loop {
+            interval.tick().await;
+
+            if history_receiver.has_changed().is_err() {
+                info!("drawdown_task: History channel closed, stopping...");
+                return;
+            }
+
+            let drawdowns = get_drawdown_map(&history_receiver.borrow(), &windows);
+
+            if drawdown_sender.send(drawdowns).is_err() {
+                info!("drawdown_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(drawdown_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::history::{PriceHistory, PricePoint};
+
+    use super::{get_drawdown_map, get_max_drawdown};
+
+    #[test]
+    fn drawdowns_over_windows() {
+        assert_eq!(get_max_drawdown(&[100.0, 80.0, 120.0, 90.0, 110.0]), 0.25);
+        assert_eq!(get_max_drawdown(&[1.0, 2.0, 3.0]), 0.0);
+
+        let mut history = PriceHistory::new(10);
+        for (time, price) in [(0, 100.0), (1000, 50.0), (2000, 80.0), (3000, 60.0)] {
+            history.push("ETH", PricePoint { time, price });
+        }
+
+        let windows = [Duration::from_secs(2), Duration::from_secs(60)];
+        let drawdowns = &get_drawdown_map(&history, &windows)["ETH"];
+
+        // The last 2 seconds only see 50.0 -> 80.0 -> 60.0
+        assert_eq!(drawdowns[0].samples, 3);
+        assert_eq!(drawdowns[0].peak, 80.0);
+        assert_eq!(drawdowns[0].max, 0.25);
+        assert_eq!(drawdowns[0].current, 0.25);
+
+        assert_eq!(drawdowns[1].samples, 4);
+        assert_eq!(drawdowns[1].max, 0.5);
+        assert_eq!(drawdowns[1].current, 0.4);
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    events::{emit, ConnectionEvents, FeedEventKind},
+    trades::{Trade, TradesStream},
+    types::{Bbo, NameToOrderbookMap, Symbol},
+};
+
+pub type CoinToEffectiveSpreadMap = HashMap<String, EffectiveSpreadStats>;
+
+/// How a trade compares with the quote prevailing when it printed, all in basis points of that
+/// quote's mid.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct TradeQuality {
+    pub coin: String,
+    pub time: u64,
+    pub is_buy: bool,
+    pub price: f64,
+    pub size: f64,
+    pub mid: f64,
+    /// Twice the distance from the mid the taker paid, the round trip cost of the trade
+    pub effective_spread_bps: f64,
+    pub quoted_spread_bps: f64,
+    /// How much better than the quoted side the taker got, negative when it walked the book
+    pub price_improvement_bps: f64,
+}
+
+/// Notional weighted averages over every trade matched with a quote
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct EffectiveSpreadStats {
+    pub trades: u64,
+    pub notional: f64,
+    pub effective_spread_bps: f64,
+    pub quoted_spread_bps: f64,
+    pub price_improvement_bps: f64,
+    /// Trades that printed inside the quote
+    pub improved_trades: u64,
+}
+
+impl EffectiveSpreadStats {
+    fn add(&mut self, quality: &TradeQuality) {
+        let notional = quality.price * quality.size;
+        let total = self.notional + notional;
+
+        if total <= 0.0 {
+            return;
+        }
+
+        let average = |prev: f64, value: f64| (prev * self.notional + value * notional) / total;
+
+        self.effective_spread_bps =
+            average(self.effective_spread_bps, quality.effective_spread_bps);
+        self.quoted_spread_bps = average(self.quoted_spread_bps, quality.quoted_spread_bps);
+        self.price_improvement_bps =
+            average(self.price_improvement_bps, quality.price_improvement_bps);
+
+        self.notional = total;
+        self.trades += 1;
+        if quality.price_improvement_bps > 0.0 {
+            self.improved_trades += 1;
+        }
+    }
+
+    /// Fraction of the trades that printed inside the quote, 0.0 before the first trade
+    pub fn get_improvement_rate(&self) -> f64 {
+        if self.trades == 0 {
+            return 0.0_f64;
+        }
+
+        self.improved_trades as f64 / self.trades as f64
+    }
+
+    /// Effective over quoted spread, below 1.0 when takers do better than the quote on average.
+    /// `None` before the first trade.
+    pub fn get_effective_to_quoted_ratio(&self) -> Option<f64> {
+        (self.quoted_spread_bps > 0.0).then_some(self.effective_spread_bps / self.quoted_spread_bps)
+    }
+}
+
+/// Matches trades with the BBO prevailing at their time, i.e. the latest one strictly before it,
+/// for execution quality reports. A quote stamped with the trade's time may already show the
+/// book the trade left behind.
+#[derive(Clone, Debug)]
+pub struct EffectiveSpreadEstimator {
+    max_quote_age_ms: u64,
+    quotes: HashMap<String, VecDeque<Bbo>>,
+    stats: CoinToEffectiveSpreadMap,
+}
+
+impl EffectiveSpreadEstimator {
+    /// Trades printing more than `max_quote_age` after the last quote are skipped.
+    pub fn new(max_quote_age: Duration) -> Self {
+        EffectiveSpreadEstimator {
+            max_quote_age_ms: max_quote_age.as_millis() as u64,
+            quotes: HashMap::new(),
+            stats: CoinToEffectiveSpreadMap::new(),
+        }
+    }
+
+    /// Skipped if either side is empty or the quote is crossed.
+    pub fn push_quote(&mut self, bbo: Bbo) {
+        match (&bbo.bid, &bbo.ask) {
+            (Some(bid), Some(ask)) if bid.price < ask.price => (),
+            _ => return,
+        }
+
+        let quotes = self.quotes.entry(bbo.coin.clone()).or_default();
+        let cutoff = bbo.time.saturating_sub(self.max_quote_age_ms);
+
+        quotes.push_back(bbo);
+
+        // Trades older than the window can't be matched anymore, but one quote always stays
+        while quotes.len() > 1 && quotes.get(1).is_some_and(|quote| quote.time < cutoff) {
+            quotes.pop_front();
+        }
+    }
+
+    fn get_prevailing_quote(&self, coin: &str, time: u64) -> Option<&Bbo> {
+        self.quotes
+            .get(coin)?
+            .iter()
+            .rev()
+            .find(|quote| quote.time < time)
+            .filter(|quote| time - quote.time <= self.max_quote_age_ms)
+    }
+
+    /// Adds `trade` to the stats of its coin. `None` if there's no quote to match it with.
+    pub fn push_trade(&mut self, trade: &Trade) -> Option<TradeQuality> {
+        let quote = self.get_prevailing_quote(&trade.coin, trade.time)?;
+        let (bid, ask) = (quote.bid.as_ref()?.price, quote.ask.as_ref()?.price);
+
+        let mid = (bid + ask) / 2.0;
+        let side = if trade.is_buy { 1.0 } else { -1.0 };
+        let quoted_side = if trade.is_buy { ask } else { bid };
+
+        let quality = TradeQuality {
+            coin: trade.coin.clone(),
+            time: trade.time,
+            is_buy: trade.is_buy,
+            price: trade.price,
+            size: trade.size,
+            mid,
+            effective_spread_bps: 2.0 * side * (trade.price - mid) / mid * 10_000.0,
+            quoted_spread_bps: (ask - bid) / mid * 10_000.0,
+            price_improvement_bps: side * (quoted_side - trade.price) / mid * 10_000.0,
+        };
+
+        self.stats
+            .entry(trade.coin.clone())
+            .or_default()
+            .add(&quality);
+
+        Some(quality)
+    }
+
+    pub fn get_stats(&self, coin: &str) -> Option<&EffectiveSpreadStats> {
+        self.stats.get(coin)
+    }
+
+    pub fn get_stats_map(&self) -> CoinToEffectiveSpreadMap {
+        self.stats.clone()
+    }
+}
+
+/// Publishes the effective spread stats of the trades of `coins`, matched with the BBOs of
+/// `book_receiver`, which has to carry the books of the same coins.
+pub async fn start_effective_spread_task(
+    coins: Vec<String>,
+    mut book_receiver: watch::Receiver<NameToOrderbookMap>,
+    max_quote_age: Duration,
+) -> anyhow::Result<watch::Receiver<CoinToEffectiveSpreadMap>> {
+    let (stats_sender, stats_recv) = watch::channel(CoinToEffectiveSpreadMap::new());
+
+    tokio::spawn(async move {
+        let s_s = stats_sender;
+        let mut estimator = EffectiveSpreadEstimator::new(max_quote_age);
+        let mut last_times: HashMap<Symbol, u64> = HashMap::new();
+
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("effective_spread_task: Starting...");
+
+            let mut trades_stream = match TradesStream::new(&coins).await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Error while getting TradesStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("effective_spread_task");
+
+            let err = loop {
+                tokio::select! {
+                    trades = trades_stream.get_next_trades() => match trades {
+                        Ok(Some(trades)) => {
+                            for trade in &trades {
+                                estimator.push_trade(trade);
+                            }
+
+                            if s_s.send(estimator.get_stats_map()).is_err() {
+                                info!("effective_spread_task: All receivers dropped, stopping...");
+                                let _ = trades_stream.unsub().await;
+                                return;
+                            }
+                        }
+                        Ok(None) => continue,
+                        Err(err) => break err,
+                    },
+                    changed = book_receiver.changed() => {
+                        if changed.is_err() {
+                            info!("effective_spread_task: Book channel closed, stopping...");
+                            let _ = trades_stream.unsub().await;
+                            return;
+                        }
+
+                        for (coin, book) in book_receiver.borrow_and_update().iter() {
+                            // Only the books that changed since the last update
+                            if last_times.get(coin) != Some(&book.time) {
+                                last_times.insert(coin.clone(), book.time);
+                                estimator.push_quote(book.get_bbo());
+                            }
+                        }
+                    }
+                }
+            };
+
+            error!("effective_spread_task: Error: {err:?}");
+            emit(
+                "effective_spread_task",
+                FeedEventKind::SubscriptionDropped {
+                    reason: format!("{err:?}"),
+                },
+            );
+            info!("effective_spread_task: Resetting...");
+
+            let _ = trades_stream.unsub().await;
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(stats_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        trades::Trade,
+        types::{Bbo, BookLevel},
+    };
+
+    use super::EffectiveSpreadEstimator;
+
+    fn bbo(time: u64, bid: f64, ask: f64) -> Bbo {
+        let level = |price: f64| BookLevel {
+            price,
+            size: 1.0,
+            orders: 1,
+        };
+
+        Bbo {
+            coin: "ETH".to_string(),
+            time,
+            bid: Some(level(bid)),
+            ask: Some(level(ask)),
+        }
+    }
+
+    fn trade(time: u64, is_buy: bool, price: f64) -> Trade {
+        Trade {
+            coin: "ETH".to_string(),
+            is_buy,
+            price,
+            size: 1.0,
+            time,
+            tid: time,
+        }
+    }
+
+    #[test]
+    fn matches_trades_with_the_prevailing_quote() {
+        let mut estimator = EffectiveSpreadEstimator::new(Duration::from_secs(1));
+
+        assert!(estimator.push_trade(&trade(0, true, 100.0)).is_none());
+
+        estimator.push_quote(bbo(1000, 99.0, 101.0));
+        estimator.push_quote(bbo(2000, 99.5, 100.5));
+
+        // Matched with the first quote, inside its ask
+        let quality = estimator.push_trade(&trade(1500, true, 100.5)).unwrap();
+        assert_eq!(quality.mid, 100.0);
+        assert!((quality.effective_spread_bps - 100.0).abs() < 1e-9);
+        assert!((quality.quoted_spread_bps - 200.0).abs() < 1e-9);
+        assert!((quality.price_improvement_bps - 50.0).abs() < 1e-9);
+
+        // The quote of the same ms may already reflect the trade, the first one prevailed
+        let quality = estimator.push_trade(&trade(2000, true, 101.0)).unwrap();
+        assert_eq!(quality.mid, 100.0);
+
+        // Walks through the second quote's bid
+        let quality = estimator.push_trade(&trade(2500, false, 99.0)).unwrap();
+        assert!((quality.price_improvement_bps + 50.0).abs() < 1e-9);
+
+        // Too long after the last quote
+        assert!(estimator.push_trade(&trade(3500, true, 100.5)).is_none());
+
+        let stats = estimator.get_stats("ETH").unwrap();
+        assert_eq!(stats.trades, 3);
+        assert!((stats.get_improvement_rate() - 1.0 / 3.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,311 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analytics::{get_correlation, get_covariance, get_mean},
+    recorder::{RecordFormat, RecordReader},
+    trades::Trade,
+    types::{NameToOrderbookMap, Orderbook},
+};
+
+pub type CoinToImpactModelMap = HashMap<String, ImpactModel>;
+
+/// One taker order, i.e. the trades of a coin printed at the same time on the same side, and how
+/// far its average price was from the mid prevailing before it.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImpactSample {
+    pub coin: String,
+    pub time: u64,
+    pub is_buy: bool,
+    /// In units of the coin
+    pub size: f64,
+    pub avg_price: f64,
+    pub mid: f64,
+    /// Signed so that paying away from the mid is positive
+    pub impact_bps: f64,
+}
+
+/// Square-root law fit of the impact of a coin:
+/// `impact_bps = intercept_bps + coefficient * sqrt(size)`, with `size` in units of the coin and
+/// the intercept roughly the half spread.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImpactModel {
+    pub coin: String,
+    pub intercept_bps: f64,
+    pub coefficient: f64,
+    pub samples: usize,
+    pub r_squared: f64,
+}
+
+impl ImpactModel {
+    /// Expected distance of the average fill price of `size` from the mid, in basis points
+    pub fn get_impact_bps(&self, size: f64) -> f64 {
+        self.intercept_bps + self.coefficient * size.max(0.0).sqrt()
+    }
+
+    /// Largest size expected to average at most `max_slippage` (0.01 == 1%) away from the mid.
+    /// `None` if the fit doesn't grow with size or the intercept alone is above the tolerance.
+    pub fn get_max_size(&self, max_slippage: f64) -> Option<f64> {
+        let max_bps = max_slippage * 10_000.0;
+
+        if self.coefficient <= 0.0 || max_bps <= self.intercept_bps {
+            return None;
+        }
+
+        Some(((max_bps - self.intercept_bps) / self.coefficient).powi(2))
+    }
+}
+
+/// Fits [`ImpactModel`]s from recorded books and trades. Books and trades can be pushed in any
+/// order, they're matched by exchange time when fitting.
+#[derive(Clone, Debug)]
+pub struct ImpactCalibrator {
+    max_quote_age_ms: u64,
+    /// (time, mid) per coin
+    mids: HashMap<String, Vec<(u64, f64)>>,
+    trades: HashMap<String, Vec<Trade>>,
+}
+
+impl ImpactCalibrator {
+    /// Trades printing more than `max_quote_age` after the last book are skipped.
+    pub fn new(max_quote_age: Duration) -> Self {
+        ImpactCalibrator {
+            max_quote_age_ms: max_quote_age.as_millis() as u64,
+            mids: HashMap::new(),
+            trades: HashMap::new(),
+        }
+    }
+
+    /// Skipped if either side is empty or the book is crossed.
+    pub fn push_book(&mut self, book: &Orderbook) {
+        match book.get_mid() {
+            Some(mid) if !book.is_crossed() => self
+                .mids
+                .entry(book.coin.clone())
+                .or_default()
+                .push((book.time, mid)),
+            _ => (),
+        }
+    }
+
+    pub fn push_trade(&mut self, trade: Trade) {
+        self.trades
+            .entry(trade.coin.clone())
+            .or_default()
+            .push(trade);
+    }
+
+    pub fn get_coins(&self) -> Vec<&String> {
+        self.trades.keys().collect()
+    }
+
+    /// The taker orders of `coin` matched with the latest mid strictly before them, oldest first.
+    pub fn get_samples(&self, coin: &str) -> Vec<ImpactSample> {
+        let (Some(trades), Some(mids)) = (self.trades.get(coin), self.mids.get(coin)) else {
+            return vec![];
+        };
+
+        let mut mids = mids.clone();
+        mids.sort_by_key(|(time, _)| *time);
+
+        let mut trades = trades.clone();
+        trades.sort_by_key(|trade| (trade.time, trade.tid));
+
+        // Trades of the same taker order print at the same time on the same side
+        let mut orders: Vec<(u64, bool, f64, f64)> = vec![];
+        for trade in &trades {
+            match orders.last_mut() {
+                Some((time, is_buy, size, notional))
+                    if *time == trade.time && *is_buy == trade.is_buy =>
+                {
+                    *size += trade.size;
+                    *notional += trade.get_notional();
+                }
+                _ => orders.push((trade.time, trade.is_buy, trade.size, trade.get_notional())),
+            }
+        }
+
+        orders
+            .into_iter()
+            .filter(|(_, _, size, _)| *size > 0.0)
+            .filter_map(|(time, is_buy, size, notional)| {
+                // A book at the same time may already reflect the trade
+                let index = mids.partition_point(|(mid_time, _)| *mid_time < time);
+                let (mid_time, mid) = *mids.get(index.checked_sub(1)?)?;
+
+                if time - mid_time > self.max_quote_age_ms {
+                    return None;
+                }
+
+                let avg_price = notional / size;
+                let side = if is_buy { 1.0 } else { -1.0 };
+
+                Some(ImpactSample {
+                    coin: coin.to_string(),
+                    time,
+                    is_buy,
+                    size,
+                    avg_price,
+                    mid,
+                    impact_bps: side * (avg_price - mid) / mid * 10_000.0,
+                })
+            })
+            .collect()
+    }
+
+    /// Least squares fit of the impact of `coin` against the square root of the order size.
+    /// `None` with fewer than two samples or if they all have the same size.
+    pub fn fit(&self, coin: &str) -> Option<ImpactModel> {
+        let samples = self.get_samples(coin);
+
+        let sqrt_sizes: Vec<f64> = samples.iter().map(|sample| sample.size.sqrt()).collect();
+        let impacts: Vec<f64> = samples.iter().map(|sample| sample.impact_bps).collect();
+
+        let variance = get_covariance(&sqrt_sizes, &sqrt_sizes);
+        if variance == 0.0 {
+            return None;
+        }
+
+        let coefficient = get_covariance(&sqrt_sizes, &impacts) / variance;
+
+        Some(ImpactModel {
+            coin: coin.to_string(),
+            intercept_bps: get_mean(&impacts) - coefficient * get_mean(&sqrt_sizes),
+            coefficient,
+            samples: samples.len(),
+            r_squared: get_correlation(&sqrt_sizes, &impacts)
+                .map(|r| r * r)
+                .unwrap_or(0.0),
+        })
+    }
+
+    /// [`ImpactCalibrator::fit`] of every coin with trades, skipping the ones that can't be fitted
+    pub fn fit_all(&self) -> CoinToImpactModelMap {
+        self.get_coins()
+            .into_iter()
+            .filter_map(|coin| Some((coin.clone(), self.fit(coin)?)))
+            .collect()
+    }
+}
+
+/// Fits the impact of every coin from a recording of a book feed (e.g.
+/// [`crate::orderbook::start_orderbook_sender_task`]) and one of trade batches, both written by
+/// [`crate::recorder::start_recorder_task`] in `format`.
+pub fn calibrate_impact_from_records(
+    books_path: &Path,
+    trades_path: &Path,
+    format: RecordFormat,
+    max_quote_age: Duration,
+) -> Result<CoinToImpactModelMap, Error> {
+    let mut calibrator = ImpactCalibrator::new(max_quote_age);
+
+    for record in RecordReader::<NameToOrderbookMap>::open(books_path, format)? {
+        for book in record?.data.values() {
+            calibrator.push_book(book);
+        }
+    }
+
+    for record in RecordReader::<Vec<Trade>>::open(trades_path, format)? {
+        for trade in record?.data {
+            calibrator.push_trade(trade);
+        }
+    }
+
+    Ok(calibrator.fit_all())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        trades::Trade,
+        types::{BookLevel, Orderbook},
+    };
+
+    use super::{ImpactCalibrator, ImpactModel};
+
+    fn book(time: u64) -> Orderbook {
+        let level = |price: f64| BookLevel {
+            price,
+            size: 100.0,
+            orders: 1,
+        };
+
+        Orderbook {
+            coin: "ETH".to_string(),
+            time,
+            bids: vec![level(99.99)],
+            asks: vec![level(100.01)],
+        }
+    }
+
+    fn trade(time: u64, tid: u64, is_buy: bool, price: f64, size: f64) -> Trade {
+        Trade {
+            coin: "ETH".to_string(),
+            is_buy,
+            price,
+            size,
+            time,
+            tid,
+        }
+    }
+
+    #[test]
+    fn fits_the_square_root_law() {
+        let mut calibrator = ImpactCalibrator::new(Duration::from_secs(1));
+
+        // Mid 100.0, impact = 1 + 2 * sqrt(size) bps
+        for (i, size) in [1.0, 4.0, 9.0, 16.0].into_iter().enumerate() {
+            let time = 1000 * (i as u64 + 1);
+            let impact = (1.0 + 2.0 * f64::sqrt(size)) / 10_000.0;
+
+            calibrator.push_book(&book(time - 10));
+            calibrator.push_trade(trade(
+                time,
+                2 * i as u64,
+                true,
+                100.0 * (1.0 + impact),
+                size / 2.0,
+            ));
+            calibrator.push_trade(trade(
+                time,
+                2 * i as u64 + 1,
+                true,
+                100.0 * (1.0 + impact),
+                size / 2.0,
+            ));
+        }
+
+        // No book before it
+        calibrator.push_trade(trade(0, 100, false, 99.0, 1.0));
+
+        let samples = calibrator.get_samples("ETH");
+        assert_eq!(samples.len(), 4);
+        assert_eq!(samples[1].size, 4.0);
+
+        let model = calibrator.fit("ETH").unwrap();
+        assert!((model.intercept_bps - 1.0).abs() < 1e-6);
+        assert!((model.coefficient - 2.0).abs() < 1e-6);
+        assert!((model.r_squared - 1.0).abs() < 1e-9);
+        assert!((model.get_max_size(0.0011).unwrap() - 25.0).abs() < 1e-3);
+
+        assert!(calibrator.fit("BTC").is_none());
+    }
+
+    #[test]
+    fn max_size_needs_a_growing_impact() {
+        let model = ImpactModel {
+            coin: "ETH".to_string(),
+            intercept_bps: 5.0,
+            coefficient: 0.0,
+            samples: 10,
+            r_squared: 0.0,
+        };
+
+        assert_eq!(model.get_max_size(0.01), None);
+        assert_eq!(model.get_impact_bps(100.0), 5.0);
+    }
+}
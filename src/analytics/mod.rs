@@ -0,0 +1,20 @@
+mod returns;
+mod correlation;
+mod beta;
+mod zscore;
+mod spread;
+mod drawdown;
+#[cfg(feature = "live")]
+mod effective_spread;
+#[cfg(feature = "live")]
+mod impact;
+pub use returns::*;
+pub use correlation::*;
+pub use beta::*;
+pub use zscore::*;
+pub use spread::*;
+pub use drawdown::*;
+#[cfg(feature = "live")]
+pub use effective_spread::*;
+#[cfg(feature = "live")]
+pub use impact::*;
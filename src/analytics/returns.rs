@@ -0,0 +1,101 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::history::PriceHistory;
+
+/// Log returns between consecutive prices, non-positive prices are skipped.
+pub fn get_log_returns(prices: &[f64]) -> Vec<f64> {
+    prices
+        .windows(2)
+        .filter(|pair| pair[0] > 0.0 && pair[1] > 0.0)
+        .map(|pair| (pair[1] / pair[0]).ln())
+        .collect()
+}
+
+/// Log returns of every coin over the last `window` returns, using only the times at which all of
+/// `coins` have a price so the series line up. Coins missing from the history are left out and
+/// everything is empty if fewer than two common points exist.
+pub fn get_aligned_returns(
+    history: &PriceHistory,
+    coins: &[String],
+    window: usize,
+) -> HashMap<String, Vec<f64>> {
+    let series: Vec<(&String, HashMap<i64, f64>)> = coins
+        .iter()
+        .filter_map(|coin| {
+            let points = history.get(coin)?;
+            Some((
+                coin,
+                points
+                    .iter()
+                    .map(|point| (point.time, point.price))
+                    .collect(),
+            ))
+        })
+        .collect();
+
+    let mut times: Vec<i64> = match series.first() {
+        Some((_, first)) => first
+            .keys()
+            .filter(|time| series.iter().all(|(_, prices)| prices.contains_key(time)))
+            .copied()
+            .collect::<HashSet<i64>>()
+            .into_iter()
+            .collect(),
+        None => return HashMap::new(),
+    };
+    times.sort_unstable();
+
+    let skip = times.len().saturating_sub(window + 1);
+    let times = &times[skip..];
+
+    series
+        .into_iter()
+        .map(|(coin, prices)| {
+            let prices: Vec<f64> = times.iter().map(|time| prices[time]).collect();
+            (coin.clone(), get_log_returns(&prices))
+        })
+        .collect()
+}
+
+pub fn get_mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sample covariance, 0.0 with fewer than two values.
+pub fn get_covariance(a: &[f64], b: &[f64]) -> f64 {
+    let len = a.len().min(b.len());
+
+    if len < 2 {
+        return 0.0;
+    }
+
+    let (a, b) = (&a[..len], &b[..len]);
+    let (mean_a, mean_b) = (get_mean(a), get_mean(b));
+
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - mean_a) * (y - mean_b))
+        .sum::<f64>()
+        / (len - 1) as f64
+}
+
+/// Sample standard deviation, 0.0 with fewer than two values.
+pub fn get_std_dev(values: &[f64]) -> f64 {
+    get_covariance(values, values).sqrt()
+}
+
+/// Pearson correlation, `None` if either series is flat or too short.
+pub fn get_correlation(a: &[f64], b: &[f64]) -> Option<f64> {
+    let len = a.len().min(b.len());
+    let (std_a, std_b) = (get_std_dev(&a[..len]), get_std_dev(&b[..len]));
+
+    if std_a == 0.0 || std_b == 0.0 {
+        return None;
+    }
+
+    Some(get_covariance(a, b) / (std_a * std_b))
+}
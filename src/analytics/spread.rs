@@ -0,0 +1,200 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+use crate::{analytics::get_mean, types::Bbo};
+
+pub type CoinToSpreadStatsMap = HashMap<String, SpreadStats>;
+
+/// Value at quantile `q` (0.0 to 1.0) of `sorted`, interpolated linearly between the closest
+/// ranks. `None` if `sorted` is empty.
+pub fn get_percentile(sorted: &[f64], q: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let rank = q.clamp(0.0, 1.0) * last as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+/// Spreads in basis points of the mid over the tracker's window
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct SpreadStats {
+    pub samples: usize,
+    pub last: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub p25: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+impl SpreadStats {
+    /// `None` if `spreads` is empty
+    pub fn from_spreads(spreads: &[f64]) -> Option<Self> {
+        let last = *spreads.last()?;
+
+        let mut sorted = spreads.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        Some(SpreadStats {
+            samples: spreads.len(),
+            last,
+            mean: get_mean(spreads),
+            median: get_percentile(&sorted, 0.5)?,
+            p25: get_percentile(&sorted, 0.25)?,
+            p75: get_percentile(&sorted, 0.75)?,
+            p95: get_percentile(&sorted, 0.95)?,
+        })
+    }
+}
+
+/// Last `window` spreads per coin, for quoting thresholds that adapt to the usual spread, e.g.
+/// only quoting inside the p75. A window of 0 keeps nothing.
+#[derive(Clone, Debug, Default)]
+pub struct SpreadTracker {
+    window: usize,
+    map: HashMap<String, VecDeque<f64>>,
+}
+
+impl SpreadTracker {
+    pub fn new(window: usize) -> Self {
+        SpreadTracker {
+            window,
+            map: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, coin: &str, spread_bps: f64) {
+        if self.window == 0 {
+            return;
+        }
+
+        let spreads = self
+            .map
+            .entry(coin.to_string())
+            .or_insert_with(|| VecDeque::with_capacity(self.window));
+
+        if spreads.len() == self.window {
+            spreads.pop_front();
+        }
+
+        spreads.push_back(spread_bps);
+    }
+
+    /// Skipped if either side is empty.
+    pub fn record(&mut self, bbo: &Bbo) {
+        if let (Some(bid), Some(ask)) = (&bbo.bid, &bbo.ask) {
+            let mid = (bid.price + ask.price) / 2.0;
+
+            if mid > 0.0 {
+                self.push(&bbo.coin, (ask.price - bid.price) / mid * 10_000.0);
+            }
+        }
+    }
+
+    pub fn get_stats(&self, coin: &str) -> Option<SpreadStats> {
+        let spreads: Vec<f64> = self.map.get(coin)?.iter().copied().collect();
+
+        SpreadStats::from_spreads(&spreads)
+    }
+
+    pub fn get_stats_map(&self) -> CoinToSpreadStatsMap {
+        self.map
+            .keys()
+            .filter_map(|coin| Some((coin.clone(), self.get_stats(coin)?)))
+            .collect()
+    }
+}
+
+/// Publishes the spread stats over the last `window` BBO changes of every coin in
+/// `book_receiver`.
+#[cfg(feature = "live")]
+pub async fn start_spread_stats_task(
+    mut book_receiver: watch::Receiver<crate::types::NameToOrderbookMap>,
+    window: usize,
+) -> anyhow::Result<watch::Receiver<CoinToSpreadStatsMap>> {
+    let (stats_sender, stats_recv) = watch::channel(CoinToSpreadStatsMap::new());
+
+    tokio::spawn(async move {
+        let mut tracker = SpreadTracker::new(window);
+        let mut last_bbos: HashMap<crate::types::Symbol, Bbo> = HashMap::new();
+
+        info!("spread_stats_task: Starting...");
+
+        while book_receiver.changed().await.is_ok() {
+            for (coin, book) in book_receiver.borrow_and_update().iter() {
+                let bbo = book.get_bbo();
+
+                // Only the books that changed since the last update
+                if last_bbos.get(coin).map(|last| last.time) != Some(bbo.time) {
+                    tracker.record(&bbo);
+                    last_bbos.insert(coin.clone(), bbo);
+                }
+            }
+
+            let _ = stats_sender.send(tracker.get_stats_map());
+
+            if stats_sender.is_closed() {
+                info!("spread_stats_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+
+        info!("spread_stats_task: Book channel closed, stopping...");
+    });
+
+    Ok(stats_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Bbo, BookLevel};
+
+    use super::{get_percentile, SpreadStats, SpreadTracker};
+
+    #[test]
+    fn percentiles_interpolate() {
+        let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+
+        assert_eq!(get_percentile(&sorted, 0.5), Some(3.0));
+        assert_eq!(get_percentile(&sorted, 0.25), Some(2.0));
+        assert!((get_percentile(&sorted, 0.95).unwrap() - 4.8).abs() < 1e-9);
+        assert_eq!(get_percentile(&[], 0.5), None);
+
+        let stats = SpreadStats::from_spreads(&[5.0, 1.0, 3.0]).unwrap();
+        assert_eq!(stats.median, 3.0);
+        assert_eq!(stats.last, 3.0);
+    }
+
+    #[test]
+    fn tracks_a_window_of_spreads() {
+        let level = |price: f64| BookLevel {
+            price,
+            size: 1.0,
+            orders: 1,
+        };
+        let bbo = |bid: f64, ask: f64| Bbo {
+            coin: "ETH".to_string(),
+            time: 0,
+            bid: Some(level(bid)),
+            ask: Some(level(ask)),
+        };
+
+        let mut tracker = SpreadTracker::new(2);
+        tracker.record(&bbo(99.0, 101.0));
+        tracker.record(&bbo(99.95, 100.05));
+        tracker.record(&bbo(99.9, 100.1));
+
+        let stats = tracker.get_stats("ETH").unwrap();
+        assert_eq!(stats.samples, 2);
+        assert!((stats.mean - 15.0).abs() < 1e-6);
+
+        let mut tracker = SpreadTracker::new(0);
+        tracker.record(&bbo(99.0, 101.0));
+        assert_eq!(tracker.get_stats("ETH"), None);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analytics::{get_mean, get_std_dev},
+    history::PriceHistory,
+};
+
+/// Z-score of the last value against the last `lookback` values (including itself). `None` with
+/// fewer than two values or a flat series.
+pub fn get_zscore(values: &[f64], lookback: usize) -> Option<f64> {
+    let window = &values[values.len().saturating_sub(lookback)..];
+    let last = *window.last()?;
+    let std_dev = get_std_dev(window);
+
+    if std_dev == 0.0 {
+        return None;
+    }
+
+    Some((last - get_mean(window)) / std_dev)
+}
+
+/// Z-score of every point once `lookback` points are available, oldest first.
+pub fn get_rolling_zscores(values: &[f64], lookback: usize) -> Vec<Option<f64>> {
+    (lookback.max(1)..=values.len())
+        .map(|end| get_zscore(&values[..end], lookback))
+        .collect()
+}
+
+/// Z-score of the latest price of every coin in the history.
+pub fn get_zscore_map(history: &PriceHistory, lookback: usize) -> HashMap<String, f64> {
+    history
+        .get_coins()
+        .into_iter()
+        .filter_map(|coin| {
+            Some((
+                coin.clone(),
+                get_zscore(&history.get_prices(coin), lookback)?,
+            ))
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MeanReversionPosition {
+    #[default]
+    Flat,
+    /// Entered when the series is cheap, z-score at or below `-entry_z`
+    Long,
+    /// Entered when the series is rich, z-score at or above `entry_z`
+    Short,
+}
+
+/// Entry/exit state machine with hysteresis: a position is entered once |z| reaches `entry_z` and
+/// only exited once the z-score has reverted to within `exit_z` of the mean, so noise around a
+/// single threshold doesn't flip the signal back and forth.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MeanReversionSignal {
+    pub entry_z: f64,
+    pub exit_z: f64,
+    position: MeanReversionPosition,
+}
+
+impl MeanReversionSignal {
+    /// `exit_z` is capped at `entry_z`.
+    pub fn new(entry_z: f64, exit_z: f64) -> Self {
+        MeanReversionSignal {
+            entry_z: entry_z.abs(),
+            exit_z: exit_z.abs().min(entry_z.abs()),
+            position: MeanReversionPosition::Flat,
+        }
+    }
+
+    pub fn get_position(&self) -> MeanReversionPosition {
+        self.position
+    }
+
+    /// Feeds the latest z-score and returns the resulting position.
+    pub fn update(&mut self, zscore: f64) -> MeanReversionPosition {
+        self.position = match self.position {
+            MeanReversionPosition::Flat if zscore >= self.entry_z => MeanReversionPosition::Short,
+            MeanReversionPosition::Flat if zscore <= -self.entry_z => MeanReversionPosition::Long,
+            MeanReversionPosition::Long if zscore >= -self.exit_z => MeanReversionPosition::Flat,
+            MeanReversionPosition::Short if zscore <= self.exit_z => MeanReversionPosition::Flat,
+            position => position,
+        };
+
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_zscore, MeanReversionPosition, MeanReversionSignal};
+
+    #[test]
+    fn zscore_and_hysteresis() {
+        let values = [1.0, 2.0, 3.0, 4.0, 10.0];
+        assert!(get_zscore(&values, 5).unwrap() > 1.5);
+        assert_eq!(get_zscore(&[1.0, 1.0], 2), None);
+
+        let mut signal = MeanReversionSignal::new(2.0, 0.5);
+        assert_eq!(signal.update(1.9), MeanReversionPosition::Flat);
+        assert_eq!(signal.update(2.1), MeanReversionPosition::Short);
+        // Still rich, stays in
+        assert_eq!(signal.update(1.0), MeanReversionPosition::Short);
+        assert_eq!(signal.update(0.4), MeanReversionPosition::Flat);
+        assert_eq!(signal.update(-2.5), MeanReversionPosition::Long);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Error};
+use clap::{Parser, Subcommand};
+use hyperliquid_rust_sdk_utils::{
+    analytics::calibrate_impact_from_records,
+    config::Config,
+    endpoints::probe_endpoints,
+    export::meta::{export_meta, MetaFormat},
+    funding::get_funding_rate_map,
+    price_data::{perps::PerpsPriceData, spot::SpotPriceData},
+    prices::{Prices, TESTNET_API_URL},
+    recorder::RecordFormat,
+    types::{Price, Symbol},
+};
+
+/// Quick checks of what the library computes against the live API
+#[derive(Parser)]
+#[command(name = "hlutil")]
+struct Cli {
+    /// TOML config to read the network, URL and HTTP settings from, `HL_*` environment variables
+    /// override it
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Base URL of the API, mainnet by default
+    #[arg(long, global = true)]
+    url: Option<String>,
+
+    /// Shorthand for the testnet URL
+    #[arg(long, global = true, conflicts_with = "url")]
+    testnet: bool,
+
+    /// Proxy for the REST requests and websockets, e.g. "socks5h://127.0.0.1:1080"
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Live(LiveCommand),
+    /// Writes the merged perps and spot meta to a file, as CSV if it ends in .csv and JSON
+    /// otherwise
+    ExportMeta { path: PathBuf },
+    /// Latency and health of the API hosts, fastest first
+    Endpoints,
+    /// Fits the square-root impact model of every coin from a recording of books and one of
+    /// trades, without touching the API
+    Impact {
+        books: PathBuf,
+        trades: PathBuf,
+        /// The recordings are MessagePack instead of JSON lines
+        #[arg(long)]
+        msgpack: bool,
+        /// Trades printing longer than this after the last book are skipped
+        #[arg(long, default_value_t = 1000)]
+        max_quote_age_ms: u64,
+        /// Also writes the models to this JSON file, for `plan_slices_with_impact`
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Commands that read the meta and prices through a [`Prices`]
+#[derive(Subcommand)]
+enum LiveCommand {
+    /// Mid price of a perp ("ETH") or spot pair ("PURR/USDC" or "@107")
+    Price { coin: String },
+    /// Top levels of a book
+    Book {
+        coin: String,
+        #[arg(long, default_value_t = 10)]
+        levels: usize,
+    },
+    /// Names, indices and decimals of the universe
+    Meta {
+        #[arg(long, conflicts_with = "perps")]
+        spot: bool,
+        #[arg(long)]
+        perps: bool,
+    },
+    /// Rounds a price with the coin's tick rules
+    Round { price: f64, coin: String },
+    /// Current hourly funding rates, highest first
+    Funding {
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+    },
+}
+
+/// The perp or spot price of `coin`, in any case, spot pairs can be given as "TOKEN1/TOKEN2".
+fn find_price(coin: &str, perps: &PerpsPriceData, spot: &SpotPriceData) -> Result<Price, Error> {
+    let symbol: Symbol = coin.parse()?;
+
+    if let Some(price) = perps.map.get(&symbol) {
+        return Ok(price.clone());
+    }
+
+    let name = spot.get_symbol_from_pair(&symbol).unwrap_or(symbol);
+
+    spot.map
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Unknown coin {coin}"))
+}
+
+fn print_meta(price: &Price) {
+    let meta = price.get_meta();
+
+    println!(
+        "{:<12} {:>6} {:>12} {:>12}",
+        meta.get_name(),
+        meta.get_asset_index(),
+        meta.get_sz_decimals(),
+        meta.get_max_leverage()
+    );
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+
+    let mut config = Config::load(cli.config.as_deref())?;
+
+    if cli.testnet {
+        config.api_url = Some(TESTNET_API_URL.to_string());
+    } else if let Some(url) = &cli.url {
+        config.api_url = Some(url.clone());
+    }
+
+    if let Some(proxy) = &cli.proxy {
+        config.http.proxy = Some(proxy.clone());
+    }
+
+    config.apply()?;
+
+    match cli.command {
+        Command::Live(command) => {
+            let mut prices = Prices::new().await?;
+            run_live(&mut prices, command).await?;
+
+            prices.unsub().await
+        }
+        Command::ExportMeta { path } => export_meta_file(&path).await,
+        Command::Endpoints => print_endpoints().await,
+        Command::Impact {
+            books,
+            trades,
+            msgpack,
+            max_quote_age_ms,
+            output,
+        } => {
+            let format = if msgpack {
+                RecordFormat::MessagePack
+            } else {
+                RecordFormat::JsonLines
+            };
+
+            print_impact(
+                &books,
+                &trades,
+                format,
+                Duration::from_millis(max_quote_age_ms),
+                output.as_deref(),
+            )
+        }
+    }
+}
+
+async fn export_meta_file(path: &Path) -> Result<(), Error> {
+    let format = if path.extension().is_some_and(|ext| ext == "csv") {
+        MetaFormat::Csv
+    } else {
+        MetaFormat::Json
+    };
+
+    export_meta(path, format).await
+}
+
+fn print_impact(
+    books: &Path,
+    trades: &Path,
+    format: RecordFormat,
+    max_quote_age: Duration,
+    output: Option<&Path>,
+) -> Result<(), Error> {
+    let models = calibrate_impact_from_records(books, trades, format, max_quote_age)?;
+
+    let mut sorted: Vec<_> = models.values().collect();
+    sorted.sort_by(|a, b| a.coin.cmp(&b.coin));
+
+    println!(
+        "{:<12} {:>10} {:>14} {:>12} {:>8}",
+        "coin", "samples", "intercept_bps", "coefficient", "r2"
+    );
+    for model in sorted {
+        println!(
+            "{:<12} {:>10} {:>14.3} {:>12.4} {:>8.3}",
+            model.coin, model.samples, model.intercept_bps, model.coefficient, model.r_squared
+        );
+    }
+
+    if let Some(path) = output {
+        fs::write(path, serde_json::to_string_pretty(&models)?)?;
+    }
+
+    Ok(())
+}
+
+async fn print_endpoints() -> Result<(), Error> {
+    let mut health = probe_endpoints().await?;
+    health.sort_by(|a, b| {
+        let latency = |ms: Option<f64>| ms.unwrap_or(f64::INFINITY);
+        latency(a.latency_ms).total_cmp(&latency(b.latency_ms))
+    });
+
+    println!("{:<40} {:>12} {}", "url", "latency", "error");
+    for endpoint in health {
+        println!(
+            "{:<40} {:>12} {}",
+            endpoint.url,
+            endpoint
+                .latency_ms
+                .map(|ms| format!("{ms:.1}ms"))
+                .unwrap_or_default(),
+            endpoint
+                .last_error
+                .filter(|_| endpoint.consecutive_failures > 0)
+                .unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+async fn run_live(prices: &mut Prices, command: LiveCommand) -> Result<(), Error> {
+    match command {
+        LiveCommand::Price { coin } => {
+            let (spot, perps) = prices.init_all().await?;
+            println!("{}", find_price(&coin, &perps, &spot)?);
+        }
+        LiveCommand::Book { coin, levels } => {
+            let (spot, _) = prices.init_all().await?;
+            let name = spot
+                .get_pair_to_name_map()
+                .get(&coin)
+                .cloned()
+                .unwrap_or(coin);
+
+            let book = prices.get_l2_book(&name).await?;
+
+            println!(
+                "{:>14} {:>14} | {:<14} {:<14}",
+                "bid size", "bid", "ask", "ask size"
+            );
+            for i in 0..levels {
+                let bid = book.bids.get(i);
+                let ask = book.asks.get(i);
+
+                println!(
+                    "{:>14} {:>14} | {:<14} {:<14}",
+                    bid.map(|l| l.size.to_string()).unwrap_or_default(),
+                    bid.map(|l| l.price.to_string()).unwrap_or_default(),
+                    ask.map(|l| l.price.to_string()).unwrap_or_default(),
+                    ask.map(|l| l.size.to_string()).unwrap_or_default(),
+                );
+            }
+        }
+        LiveCommand::Meta { spot, perps } => {
+            let (spot_data, perps_data) = prices.init_all().await?;
+
+            let mut metas: Vec<&Price> = vec![];
+            if !spot {
+                metas.extend(perps_data.map.values());
+            }
+            if !perps {
+                metas.extend(spot_data.map.values());
+            }
+            metas.sort_by_key(|price| price.get_meta().get_asset_index());
+
+            println!(
+                "{:<12} {:>6} {:>12} {:>12}",
+                "name", "asset", "sz_decimals", "max_leverage"
+            );
+            metas.into_iter().for_each(print_meta);
+        }
+        LiveCommand::Round { price, coin } => {
+            let (spot, perps) = prices.init_all().await?;
+            let rounded = find_price(&coin, &perps, &spot)?.get_true_price_for_asset(price);
+
+            println!("{rounded}");
+        }
+        LiveCommand::Funding { top } => {
+            let ctxs = prices
+                .get_perps_meta_and_asset_ctxs()
+                .await?
+                .get_name_to_ctx_map();
+
+            let mut rates: Vec<(String, f64)> = get_funding_rate_map(&ctxs).into_iter().collect();
+            rates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            println!("{:<12} {:>12} {:>10}", "coin", "hourly", "apr");
+            for (coin, rate) in rates.into_iter().take(top) {
+                println!(
+                    "{coin:<12} {:>11.5}% {:>9.2}%",
+                    rate * 100.0,
+                    rate * 24.0 * 365.0 * 100.0
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
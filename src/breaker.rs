@@ -0,0 +1,421 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::events::{emit, FeedEventKind};
+
+/// Info requests whose responses are the same for every user and can be served stale while the
+/// circuit is open: meta, candles and market snapshots. Account state never is.
+pub const CACHEABLE_INFO_TYPES: [&str; 7] = [
+    "meta",
+    "spotMeta",
+    "metaAndAssetCtxs",
+    "spotMetaAndAssetCtxs",
+    "candleSnapshot",
+    "l2Book",
+    "allMids",
+];
+
+/// Whether the response to `request` may be answered from the breaker's cache
+pub fn is_cacheable_info(request: &Value) -> bool {
+    request["type"]
+        .as_str()
+        .is_some_and(|request_type| CACHEABLE_INFO_TYPES.contains(&request_type))
+}
+
+/// Whether `err` says the API is unreachable or failing: a connect error, a timeout or a 5xx.
+/// Anything else (4xx, bad responses) means the API answered.
+pub fn is_outage(err: &Error) -> bool {
+    err.chain().any(|cause| {
+        cause.downcast_ref::<reqwest::Error>().is_some_and(|err| {
+            err.is_connect()
+                || err.is_timeout()
+                || err.status().is_some_and(|status| status.is_server_error())
+        })
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BreakerConfig {
+    /// Consecutive failures that open the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a probe request is let through
+    pub open_duration: Duration,
+    /// Responses kept to serve while the circuit is open, the oldest is dropped first
+    pub cache_capacity: usize,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        BreakerConfig {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            cache_capacity: 256,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BreakerState {
+    Closed,
+    /// Requests are answered from the cache without reaching the API
+    Open,
+    /// A single probe request is in flight, the others are answered from the cache
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    cache: HashMap<String, Vec<u8>>,
+    /// Cache keys, oldest first
+    cache_order: VecDeque<String>,
+}
+
+/// What happens to a request given the state of the circuit
+enum Admission {
+    Send,
+    /// The request is the half-open probe
+    Probe,
+    Refuse,
+}
+
+/// Reopens the circuit if the probe is dropped before it finished, e.g. when its caller timed
+/// out, so the breaker doesn't stay half-open with no probe in flight.
+struct ProbeGuard<'a> {
+    breaker: &'a CircuitBreaker,
+    finished: bool,
+}
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let mut inner = self.breaker.inner.lock().unwrap();
+
+        if inner.state == BreakerState::HalfOpen {
+            warn!("{}: Probe dropped, reopening", self.breaker.name);
+
+            // Without an opening time the next request probes right away
+            inner.state = BreakerState::Open;
+            inner.opened_at = None;
+        }
+    }
+}
+
+/// Stops requests from reaching a degraded API after `failure_threshold` consecutive outages (see
+/// [`is_outage`]), answering cacheable requests from the last successful response to the same
+/// request instead. Once `open_duration` passed, the next request probes the API and closes the
+/// circuit again if the API answers.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: String,
+    config: BreakerConfig,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    /// `name` is the feed of the emitted `CircuitOpened`/`CircuitClosed` events.
+    pub fn new(name: &str, config: BreakerConfig) -> Self {
+        CircuitBreaker {
+            name: name.to_string(),
+            config,
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                cache: HashMap::new(),
+                cache_order: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn get_state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    pub fn get_consecutive_failures(&self) -> u32 {
+        self.inner.lock().unwrap().consecutive_failures
+    }
+
+    /// Whether the request should reach the API, moving an open circuit to half-open once
+    /// `open_duration` passed.
+    fn admit(&self) -> Admission {
+        let mut inner = self.inner.lock().unwrap();
+
+        match inner.state {
+            BreakerState::Closed => Admission::Send,
+            BreakerState::HalfOpen => Admission::Refuse,
+            BreakerState::Open => {
+                let elapsed = inner.opened_at.map_or(Duration::MAX, |at| at.elapsed());
+
+                if elapsed < self.config.open_duration {
+                    return Admission::Refuse;
+                }
+
+                info!("{}: Probing the API...", self.name);
+                inner.state = BreakerState::HalfOpen;
+                Admission::Probe
+            }
+        }
+    }
+
+    /// The API answered, even if with an error.
+    fn on_answer(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.state != BreakerState::Closed {
+            info!("{}: Circuit closed", self.name);
+            emit(&self.name, FeedEventKind::CircuitClosed);
+        }
+
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+    }
+
+    fn on_success(&self, key: Option<&str>, response: &[u8]) {
+        self.on_answer();
+
+        let Some(key) = key.filter(|_| self.config.cache_capacity > 0) else {
+            return;
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        let key = key.to_string();
+
+        if inner.cache.insert(key.clone(), response.to_vec()).is_none() {
+            inner.cache_order.push_back(key);
+
+            if inner.cache_order.len() > self.config.cache_capacity {
+                if let Some(oldest) = inner.cache_order.pop_front() {
+                    inner.cache.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    fn on_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.consecutive_failures += 1;
+
+        let should_open = match inner.state {
+            BreakerState::HalfOpen => true,
+            BreakerState::Closed => inner.consecutive_failures >= self.config.failure_threshold,
+            BreakerState::Open => false,
+        };
+
+        if should_open {
+            warn!(
+                "{}: Circuit open after {} consecutive failures",
+                self.name, inner.consecutive_failures
+            );
+            emit(
+                &self.name,
+                FeedEventKind::CircuitOpened {
+                    failures: inner.consecutive_failures,
+                },
+            );
+
+            inner.state = BreakerState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+
+    fn get_cached(&self, key: Option<&str>) -> Option<Vec<u8>> {
+        self.inner.lock().unwrap().cache.get(key?).cloned()
+    }
+
+    /// Runs `request` unless the circuit is open, in which case (and when `request` fails while
+    /// the circuit opens) the cached response for `key` is returned if there's one. `key` is
+    /// `None` for requests that must never be answered stale, see [`is_cacheable_info`].
+    pub async fn call<F, Fut>(&self, key: Option<&str>, request: F) -> Result<Vec<u8>, Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, Error>>,
+    {
+        let mut probe = match self.admit() {
+            Admission::Send => None,
+            Admission::Probe => Some(ProbeGuard {
+                breaker: self,
+                finished: false,
+            }),
+            Admission::Refuse => {
+                return self.get_cached(key).ok_or_else(|| {
+                    anyhow!(
+                        "{}: Circuit open and nothing cached for {}",
+                        self.name,
+                        key.unwrap_or("an uncacheable request")
+                    )
+                });
+            }
+        };
+
+        let result = request().await;
+
+        if let Some(probe) = &mut probe {
+            probe.finished = true;
+        }
+
+        match result {
+            Ok(response) => {
+                self.on_success(key, &response);
+                Ok(response)
+            }
+            Err(err) if is_outage(&err) => {
+                self.on_failure();
+
+                match self.get_state() {
+                    BreakerState::Closed => Err(err),
+                    _ => self.get_cached(key).ok_or(err),
+                }
+            }
+            Err(err) => {
+                self.on_answer();
+                Err(err)
+            }
+        }
+    }
+}
+
+static INFO_BREAKER_CONFIG: OnceLock<BreakerConfig> = OnceLock::new();
+
+/// Sets the config of the breaker in front of every info request. Fails once the breaker was
+/// built, which happens on the first info request.
+pub fn set_info_breaker_config(config: BreakerConfig) -> Result<(), Error> {
+    INFO_BREAKER_CONFIG
+        .set(config)
+        .map_err(|_| anyhow!("The info breaker was already built"))
+}
+
+/// The breaker every REST info request goes through, only the ones [`is_cacheable_info`] allows
+/// are served from its cache
+pub fn get_info_breaker() -> &'static CircuitBreaker {
+    static BREAKER: OnceLock<CircuitBreaker> = OnceLock::new();
+
+    BREAKER.get_or_init(|| {
+        let config = *INFO_BREAKER_CONFIG.get_or_init(BreakerConfig::default);
+
+        CircuitBreaker::new("info_breaker", config)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::pending, net::TcpListener, time::Duration};
+
+    use anyhow::{anyhow, Error};
+    use serde_json::json;
+
+    use super::{is_cacheable_info, is_outage, BreakerConfig, BreakerState, CircuitBreaker};
+
+    /// A connect error, from a port nothing listens on
+    async fn get_connect_error() -> Error {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        reqwest::get(url).await.unwrap_err().into()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn opens_serves_the_cache_and_recovers() {
+        let breaker = CircuitBreaker::new(
+            "test",
+            BreakerConfig {
+                failure_threshold: 2,
+                open_duration: Duration::from_secs(10),
+                cache_capacity: 8,
+            },
+        );
+
+        let ok = || async { Ok(b"meta".to_vec()) };
+        let fail = || async { Err(get_connect_error().await) };
+
+        assert_eq!(breaker.call(Some("meta"), ok).await.unwrap(), b"meta");
+
+        assert!(breaker.call(Some("meta"), fail).await.is_err());
+        // Opens on the second failure and falls back to the cache
+        assert_eq!(breaker.call(Some("meta"), fail).await.unwrap(), b"meta");
+        assert_eq!(breaker.get_state(), BreakerState::Open);
+
+        // Not even sent while open
+        assert_eq!(breaker.call(Some("meta"), fail).await.unwrap(), b"meta");
+        assert!(breaker.call(Some("candles"), ok).await.is_err());
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+
+        // The failed probe reopens it
+        assert!(breaker.call(Some("candles"), fail).await.is_err());
+        assert_eq!(breaker.get_state(), BreakerState::Open);
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+
+        assert!(breaker.call(Some("candles"), ok).await.is_ok());
+        assert_eq!(breaker.get_state(), BreakerState::Closed);
+        assert_eq!(breaker.get_consecutive_failures(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_outages_count_and_account_state_is_never_stale() {
+        let breaker = CircuitBreaker::new(
+            "test",
+            BreakerConfig {
+                failure_threshold: 1,
+                open_duration: Duration::from_secs(10),
+                cache_capacity: 8,
+            },
+        );
+
+        let ok = || async { Ok(b"state".to_vec()) };
+
+        assert!(is_cacheable_info(&json!({ "type": "candleSnapshot" })));
+        assert!(!is_cacheable_info(
+            &json!({ "type": "clearinghouseState", "user": "0x0" })
+        ));
+
+        // The API answered, with an error
+        let rejected = anyhow!("422 Unprocessable Entity");
+        assert!(!is_outage(&rejected));
+        assert!(breaker
+            .call(None, || async { Err(rejected) })
+            .await
+            .is_err());
+        assert_eq!(breaker.get_consecutive_failures(), 0);
+
+        assert!(breaker.call(None, ok).await.is_ok());
+        assert!(breaker
+            .call(None, || async { Err(get_connect_error().await) })
+            .await
+            .is_err());
+        assert_eq!(breaker.get_state(), BreakerState::Open);
+        // Nothing was cached for it
+        assert!(breaker.call(None, ok).await.is_err());
+
+        tokio::time::advance(Duration::from_secs(11)).await;
+
+        // A probe dropped before it finished doesn't leave the circuit half-open
+        let probe = breaker.call(Some("meta"), pending);
+        assert!(tokio::time::timeout(Duration::from_secs(1), probe)
+            .await
+            .is_err());
+        assert_eq!(breaker.get_state(), BreakerState::Open);
+
+        assert!(breaker.call(Some("meta"), ok).await.is_ok());
+        assert_eq!(breaker.get_state(), BreakerState::Closed);
+    }
+}
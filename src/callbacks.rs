@@ -0,0 +1,144 @@
+use std::{
+    future::Future,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use futures::FutureExt;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+
+/// A callback registered on a feed. Dropping the handle unregisters the callback.
+#[derive(Debug)]
+pub struct CallbackHandle {
+    task: JoinHandle<()>,
+}
+
+impl CallbackHandle {
+    /// Unregisters the callback, an update being handled is cancelled at its next await point.
+    pub fn cancel(&self) {
+        self.task.abort();
+    }
+
+    /// False once the callback was cancelled or the feed closed
+    pub fn is_active(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for CallbackHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Calls `callback` with the value `select` picks out of `receiver` every time that value
+/// changes. Updates where `select` returns `None` are skipped. Callbacks run one at a time, so a
+/// slow callback only sees the latest value once it's done. A panic in `callback` (or the future
+/// it returns) skips that update instead of unregistering the callback.
+pub fn on_update<T, V, S, F, Fut>(
+    name: &str,
+    mut receiver: watch::Receiver<T>,
+    select: S,
+    mut callback: F,
+) -> CallbackHandle
+where
+    T: Send + Sync + 'static,
+    V: Clone + PartialEq + Send + 'static,
+    S: Fn(&T) -> Option<V> + Send + 'static,
+    F: FnMut(V) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let name = name.to_string();
+
+    let task = tokio::spawn(async move {
+        info!("{name}: Starting...");
+
+        let mut last: Option<V> = None;
+
+        loop {
+            if receiver.changed().await.is_err() {
+                info!("{name}: Feed closed, stopping...");
+                return;
+            }
+
+            let value = match select(&receiver.borrow_and_update()) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            if last.as_ref() == Some(&value) {
+                continue;
+            }
+            last = Some(value.clone());
+
+            let future = match catch_unwind(AssertUnwindSafe(|| callback(value))) {
+                Ok(future) => future,
+                Err(_) => {
+                    error!("{name}: Callback panicked, skipping update");
+                    continue;
+                }
+            };
+
+            if AssertUnwindSafe(future).catch_unwind().await.is_err() {
+                error!("{name}: Callback panicked, skipping update");
+            }
+        }
+    });
+
+    CallbackHandle { task }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::{mpsc::unbounded_channel, watch};
+
+    use super::on_update;
+
+    #[tokio::test]
+    async fn callback_survives_panics() {
+        let (sender, receiver) = watch::channel(0_u32);
+        let (called_sender, mut called) = unbounded_channel();
+        let (seen_sender, mut seen) = unbounded_channel();
+
+        let _handle = on_update(
+            "test_callback",
+            receiver,
+            |v| (*v != 0).then_some(*v),
+            move |v| {
+                called_sender.send(v).unwrap();
+                if v == 3 {
+                    panic!("bad update while building the future");
+                }
+
+                let seen_sender = seen_sender.clone();
+                async move {
+                    if v == 1 {
+                        panic!("bad update inside the future");
+                    }
+                    seen_sender.send(v).unwrap();
+                }
+            },
+        );
+
+        sender.send(1).unwrap();
+        assert_eq!(called.recv().await, Some(1));
+
+        sender.send(2).unwrap();
+        assert_eq!(called.recv().await, Some(2));
+        assert_eq!(seen.recv().await, Some(2));
+
+        sender.send(3).unwrap();
+        assert_eq!(called.recv().await, Some(3));
+
+        sender.send(4).unwrap();
+        assert_eq!(called.recv().await, Some(4));
+        assert_eq!(seen.recv().await, Some(4));
+
+        // An unchanged value doesn't call the callback again
+        sender.send(4).unwrap();
+        sender.send(5).unwrap();
+        assert_eq!(called.recv().await, Some(5));
+        assert_eq!(seen.recv().await, Some(5));
+        assert!(seen.try_recv().is_err());
+    }
+}
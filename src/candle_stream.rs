@@ -0,0 +1,309 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Error};
+use hyperliquid_rust_sdk::{CandleData, Message, Subscription};
+use reqwest::Client;
+use serde_json::json;
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        watch,
+    },
+    task::JoinHandle,
+    time::sleep,
+};
+use tracing::{error, info};
+
+use crate::{
+    candles::{parse_interval, resample, Candle, CandleBuffer, CandleResampler},
+    counters::{Counter, FeedCounter, FeedCounters},
+    events::{emit, ConnectionEvents, FeedEventKind},
+    prices::{build_info_http_client, post_info},
+    subscription::SubscriptionGuard,
+    transport::{LiveTransport, Transport},
+};
+
+/// Candles of `coin` between `start_time` and `end_time` (unix ms), oldest first.
+pub async fn get_candle_snapshot(
+    client: &Client,
+    coin: &str,
+    interval: &str,
+    start_time: u64,
+    end_time: u64,
+) -> Result<Vec<Candle>, Error> {
+    let data: Vec<CandleData> = post_info(
+        client,
+        json!({
+            "type": "candleSnapshot",
+            "req": {
+                "coin": coin,
+                "interval": interval,
+                "startTime": start_time,
+                "endTime": end_time,
+            }
+        }),
+    )
+    .await?;
+
+    data.into_iter().map(Candle::try_from).collect()
+}
+
+/// Fetches 1m candles and resamples them into `interval`, for intervals the API doesn't serve or
+/// to keep history consistent with [`start_resampled_candle_task`].
+pub async fn get_resampled_candle_snapshot(
+    client: &Client,
+    coin: &str,
+    interval: &str,
+    start_time: u64,
+    end_time: u64,
+    fill_gaps: bool,
+) -> Result<Vec<Candle>, Error> {
+    let candles = get_candle_snapshot(client, coin, "1m", start_time, end_time).await?;
+
+    resample(&candles, interval, fill_gaps)
+}
+
+pub struct CandleStream {
+    subscriptions: SubscriptionGuard,
+    candle_receiver: UnboundedReceiver<Message>,
+    counter: FeedCounter,
+}
+
+impl CandleStream {
+    /// Subscribes through a new mainnet connection.
+    pub async fn new(coins: &[String], interval: &str) -> Result<Self, Error> {
+        CandleStream::with_transport(Arc::new(LiveTransport::new().await?), coins, interval).await
+    }
+
+    pub async fn with_transport(
+        transport: Arc<dyn Transport>,
+        coins: &[String],
+        interval: &str,
+    ) -> Result<Self, Error> {
+        let mut subscriptions = SubscriptionGuard::with_transport(transport);
+
+        let (sender, receiver) = unbounded_channel();
+
+        for coin in coins {
+            subscriptions
+                .subscribe(
+                    Subscription::Candle {
+                        coin: coin.clone(),
+                        interval: interval.to_string(),
+                    },
+                    sender.clone(),
+                )
+                .await
+                .with_context(|| {
+                    format!("Couldn't subscribe to the {interval} candles of {coin}")
+                })?;
+        }
+
+        Ok(CandleStream {
+            subscriptions,
+            candle_receiver: receiver,
+            counter: FeedCounter::new("candles"),
+        })
+    }
+
+    /// Counts on `counter`, which the candle task keeps across its streams.
+    pub fn with_counter(mut self, counter: FeedCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    pub fn get_counters(&self) -> FeedCounters {
+        self.counter.get_counters()
+    }
+
+    pub async fn get_next_candle(&mut self) -> anyhow::Result<Option<Candle>> {
+        let msg = self.candle_receiver.recv().await;
+        if msg.is_some() {
+            self.counter.count(Counter::Received);
+        }
+
+        match msg {
+            Some(msg) => match msg {
+                Message::NoData => {
+                    error!("Couldn't recieve candle data");
+                    Err(anyhow::anyhow!("No data found"))
+                }
+                Message::HyperliquidError(err) => {
+                    error!("Hyperliquid error while getting candle data: {err:?}");
+                    Err(anyhow::anyhow!("Hyperliquid error found"))
+                }
+                Message::Candle(candle) => match Candle::try_from(candle.data) {
+                    Ok(candle) => Ok(Some(candle)),
+                    Err(err) => {
+                        self.counter.count(Counter::DeserializeFailures);
+                        Err(err)
+                    }
+                },
+                s => {
+                    error!("Got something else: {s:?}");
+                    self.counter.count(Counter::Dropped);
+                    Ok(None)
+                }
+            },
+            None => Err(anyhow::anyhow!("Candle channel closed")),
+        }
+    }
+
+    pub async fn unsub(&mut self) -> anyhow::Result<()> {
+        self.subscriptions.unsubscribe_all().await
+    }
+}
+
+/// Keeps the last `capacity` `interval` candles of every coin in `coins`, backfilled from the
+/// REST snapshot so the buffer isn't empty until enough candles have streamed in.
+pub async fn start_candle_task(
+    coins: Vec<String>,
+    interval: String,
+    capacity: usize,
+) -> anyhow::Result<watch::Receiver<CandleBuffer>> {
+    Ok(spawn_candle_task(
+        coins,
+        interval,
+        capacity,
+        false,
+        FeedCounter::new("candles"),
+    )?
+    .0)
+}
+
+/// Same as [`start_candle_task`] but streams 1m candles and resamples them into `interval`. The
+/// backfill is limited to the 5000 most recent 1m candles the snapshot endpoint returns.
+pub async fn start_resampled_candle_task(
+    coins: Vec<String>,
+    interval: String,
+    capacity: usize,
+) -> anyhow::Result<watch::Receiver<CandleBuffer>> {
+    Ok(spawn_candle_task(coins, interval, capacity, true, FeedCounter::new("candles"))?.0)
+}
+
+fn push_candle(buffer: &mut CandleBuffer, resampler: Option<&mut CandleResampler>, candle: Candle) {
+    let resampler = match resampler {
+        Some(resampler) => resampler,
+        None => {
+            buffer.push(candle);
+            return;
+        }
+    };
+
+    let coin = candle.coin.clone();
+
+    if let Some(closed) = resampler.push(candle) {
+        buffer.push(closed);
+    }
+
+    if let Some(current) = resampler.get_current(&coin) {
+        buffer.push(current);
+    }
+}
+
+/// [`start_candle_task`], or [`start_resampled_candle_task`] if `resampled`, with the handle of
+/// the task, for owners that stop it, counting the messages of every stream on `counter`.
+pub(crate) fn spawn_candle_task(
+    coins: Vec<String>,
+    interval: String,
+    capacity: usize,
+    resampled: bool,
+    counter: FeedCounter,
+) -> anyhow::Result<(watch::Receiver<CandleBuffer>, JoinHandle<()>)> {
+    let client = build_info_http_client()?;
+    let backfill_span = parse_interval(&interval)? * capacity as u64;
+    let source_interval = if resampled { "1m" } else { interval.as_str() }.to_string();
+
+    let (candle_sender, candle_recv) = watch::channel(CandleBuffer::new(capacity));
+
+    let task = tokio::spawn(async move {
+        let c_s = candle_sender;
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("candle_task: Starting...");
+
+            let mut stream = match CandleStream::new(&coins, &source_interval).await {
+                Ok(s) => s.with_counter(counter.clone()),
+                Err(e) => {
+                    error!("Error while getting CandleStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("candle_task");
+
+            let mut resampler = if resampled {
+                CandleResampler::new(&interval).ok()
+            } else {
+                None
+            };
+
+            let end_time = chrono::Utc::now().timestamp_millis() as u64;
+            let start_time = end_time.saturating_sub(backfill_span);
+
+            for coin in coins.iter() {
+                let candles = match get_candle_snapshot(
+                    &client,
+                    coin,
+                    &source_interval,
+                    start_time,
+                    end_time,
+                )
+                .await
+                {
+                    Ok(candles) => candles,
+                    Err(err) => {
+                        error!("candle_task: Couldn't backfill {coin}: {err:?}");
+                        continue;
+                    }
+                };
+
+                c_s.send_modify(|buffer| {
+                    // After a restart, only what was missed while disconnected is added
+                    let last_open_time = buffer
+                        .get(coin)
+                        .and_then(|candles| candles.back())
+                        .map(|candle| candle.open_time)
+                        .unwrap_or(0);
+
+                    candles
+                        .into_iter()
+                        .filter(|candle| candle.open_time >= last_open_time)
+                        .for_each(|candle| push_candle(buffer, resampler.as_mut(), candle));
+                });
+            }
+
+            let err = loop {
+                let candle = match stream.get_next_candle().await {
+                    Ok(Some(candle)) => candle,
+                    Ok(None) => continue,
+                    Err(err) => break err,
+                };
+
+                c_s.send_modify(|buffer| push_candle(buffer, resampler.as_mut(), candle));
+                counter.count(Counter::Sends);
+
+                if c_s.is_closed() {
+                    info!("candle_task: All receivers dropped, stopping...");
+                    let _ = stream.unsub().await;
+                    return;
+                }
+            };
+
+            error!("candle_task: Error: {err:?}");
+            emit(
+                "candle_task",
+                FeedEventKind::SubscriptionDropped {
+                    reason: format!("{err:?}"),
+                },
+            );
+            info!("candle_task: Resetting...");
+
+            let _ = stream.unsub().await;
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok((candle_recv, task))
+}
@@ -0,0 +1,366 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+#[cfg(feature = "live")]
+use anyhow::Context;
+use anyhow::{anyhow, bail, Error};
+#[cfg(feature = "live")]
+use hyperliquid_rust_sdk::CandleData;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Candle {
+    pub coin: String,
+    /// e.g. "1m", "1h"
+    pub interval: String,
+    /// Unix milliseconds
+    pub open_time: u64,
+    /// Unix milliseconds
+    pub close_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trades: u64,
+}
+
+#[cfg(feature = "live")]
+impl TryFrom<CandleData> for Candle {
+    type Error = Error;
+
+    fn try_from(data: CandleData) -> Result<Self, Self::Error> {
+        Ok(Candle {
+            open: data
+                .open
+                .parse::<f64>()
+                .context("Couldn't parse candle open")?,
+            high: data
+                .high
+                .parse::<f64>()
+                .context("Couldn't parse candle high")?,
+            low: data
+                .low
+                .parse::<f64>()
+                .context("Couldn't parse candle low")?,
+            close: data
+                .close
+                .parse::<f64>()
+                .context("Couldn't parse candle close")?,
+            volume: data
+                .volume
+                .parse::<f64>()
+                .context("Couldn't parse candle volume")?,
+            trades: data.num_trades,
+            open_time: data.time_open,
+            close_time: data.time_close,
+            interval: data.interval,
+            coin: data.coin,
+        })
+    }
+}
+
+/// Fixed capacity per coin buffers of candles, oldest first. A capacity of 0 keeps nothing.
+#[derive(Clone, Debug, Default)]
+pub struct CandleBuffer {
+    capacity: usize,
+    map: HashMap<String, VecDeque<Candle>>,
+}
+
+impl CandleBuffer {
+    pub fn new(capacity: usize) -> Self {
+        CandleBuffer {
+            capacity,
+            map: HashMap::new(),
+        }
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Appends the candle, or replaces the last one if it's an update of the same period, which is
+    /// how the live candle stream reports the candle that's still open.
+    pub fn push(&mut self, candle: Candle) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let candles = self
+            .map
+            .entry(candle.coin.clone())
+            .or_insert_with(|| VecDeque::with_capacity(self.capacity));
+
+        if let Some(last) = candles.back_mut() {
+            if last.open_time == candle.open_time {
+                *last = candle;
+                return;
+            }
+        }
+
+        if candles.len() == self.capacity {
+            candles.pop_front();
+        }
+
+        candles.push_back(candle);
+    }
+
+    pub fn get(&self, coin: &str) -> Option<&VecDeque<Candle>> {
+        self.map.get(coin)
+    }
+
+    pub fn remove(&mut self, coin: &str) -> Option<VecDeque<Candle>> {
+        self.map.remove(coin)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &VecDeque<Candle>)> {
+        self.map.iter()
+    }
+}
+
+/// Length of a candle interval such as "1m", "15m", "4h", "1d" or "1w" in milliseconds.
+pub fn parse_interval(interval: &str) -> Result<u64, Error> {
+    let unit_start = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("Interval {interval:?} has no unit"))?;
+    let (count, unit) = interval.split_at(unit_start);
+
+    let count = count
+        .parse::<u64>()
+        .map_err(|_| anyhow!("Interval {interval:?} has no count"))?;
+
+    let unit_ms = match unit {
+        "m" => 60_000,
+        "h" => 60 * 60_000,
+        "d" => 24 * 60 * 60_000,
+        "w" => 7 * 24 * 60 * 60_000,
+        _ => bail!("Unsupported interval unit in {interval:?}"),
+    };
+
+    if count == 0 {
+        bail!("Interval {interval:?} is empty");
+    }
+
+    Ok(count * unit_ms)
+}
+
+/// Merges candles, sorted by open time, into a single candle covering `open_time` for
+/// `interval_ms`.
+fn merge_candles<'a>(
+    candles: impl IntoIterator<Item = &'a Candle>,
+    interval: &str,
+    open_time: u64,
+    interval_ms: u64,
+) -> Option<Candle> {
+    let mut candles = candles.into_iter();
+    let first = candles.next()?;
+
+    let mut merged = Candle {
+        interval: interval.to_string(),
+        open_time,
+        close_time: open_time + interval_ms - 1,
+        ..first.clone()
+    };
+
+    for candle in candles {
+        merged.high = merged.high.max(candle.high);
+        merged.low = merged.low.min(candle.low);
+        merged.close = candle.close;
+        merged.volume += candle.volume;
+        merged.trades += candle.trades;
+    }
+
+    Some(merged)
+}
+
+/// Resamples candles of a single coin (e.g. 1m) into `interval` candles aligned on the epoch.
+/// Buckets without any candle are left out, or with `fill_gaps` filled with a flat candle at the
+/// previous close and no volume.
+pub fn resample(candles: &[Candle], interval: &str, fill_gaps: bool) -> Result<Vec<Candle>, Error> {
+    let interval_ms = parse_interval(interval)?;
+
+    let mut buckets: BTreeMap<u64, Vec<&Candle>> = BTreeMap::new();
+    for candle in candles {
+        buckets
+            .entry(candle.open_time - candle.open_time % interval_ms)
+            .or_default()
+            .push(candle);
+    }
+
+    let mut resampled: Vec<Candle> = Vec::with_capacity(buckets.len());
+
+    for (open_time, mut bucket) in buckets {
+        bucket.sort_by_key(|candle| candle.open_time);
+
+        if fill_gaps {
+            if let Some(prev) = resampled.last().cloned() {
+                let mut gap_open_time = prev.open_time + interval_ms;
+
+                while gap_open_time < open_time {
+                    resampled.push(Candle {
+                        open_time: gap_open_time,
+                        close_time: gap_open_time + interval_ms - 1,
+                        open: prev.close,
+                        high: prev.close,
+                        low: prev.close,
+                        close: prev.close,
+                        volume: 0.0,
+                        trades: 0,
+                        ..prev.clone()
+                    });
+                    gap_open_time += interval_ms;
+                }
+            }
+        }
+
+        resampled.extend(merge_candles(bucket, interval, open_time, interval_ms));
+    }
+
+    Ok(resampled)
+}
+
+/// Incremental [`resample`] for the live candle stream, which keeps resending the candle that's
+/// still open. Per coin, the source candles of the current bucket are kept by open time so updates
+/// replace each other instead of being counted twice.
+#[derive(Clone, Debug)]
+pub struct CandleResampler {
+    interval: String,
+    interval_ms: u64,
+    current: HashMap<String, (u64, BTreeMap<u64, Candle>)>,
+}
+
+impl CandleResampler {
+    pub fn new(interval: &str) -> Result<Self, Error> {
+        Ok(CandleResampler {
+            interval: interval.to_string(),
+            interval_ms: parse_interval(interval)?,
+            current: HashMap::new(),
+        })
+    }
+
+    /// Adds or updates a source candle. Returns the previous bucket of the coin once a candle of
+    /// a later bucket shows up. Candles of earlier buckets are ignored.
+    pub fn push(&mut self, candle: Candle) -> Option<Candle> {
+        let bucket_open_time = candle.open_time - candle.open_time % self.interval_ms;
+
+        let (open_time, candles) = self
+            .current
+            .entry(candle.coin.clone())
+            .or_insert_with(|| (bucket_open_time, BTreeMap::new()));
+
+        if bucket_open_time < *open_time {
+            return None;
+        }
+
+        let mut closed = None;
+
+        if bucket_open_time > *open_time {
+            closed = merge_candles(
+                candles.values(),
+                &self.interval,
+                *open_time,
+                self.interval_ms,
+            );
+            *open_time = bucket_open_time;
+            candles.clear();
+        }
+
+        candles.insert(candle.open_time, candle);
+
+        closed
+    }
+
+    /// The coin's bucket that's still open, merged from what has been pushed so far.
+    pub fn get_current(&self, coin: &str) -> Option<Candle> {
+        let (open_time, candles) = self.current.get(coin)?;
+
+        merge_candles(
+            candles.values(),
+            &self.interval,
+            *open_time,
+            self.interval_ms,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{PriceHistory, PricePoint};
+
+    use super::{resample, Candle, CandleBuffer, CandleResampler};
+
+    fn candle(minute: u64, open: f64, close: f64, volume: f64) -> Candle {
+        Candle {
+            coin: "BTC".to_string(),
+            interval: "1m".to_string(),
+            open_time: minute * 60_000,
+            close_time: minute * 60_000 + 59_999,
+            open,
+            high: open.max(close) + 1.0,
+            low: open.min(close) - 1.0,
+            close,
+            volume,
+            trades: 1,
+        }
+    }
+
+    #[test]
+    fn resamples_with_gaps() {
+        let candles = vec![
+            candle(0, 10.0, 11.0, 1.0),
+            candle(4, 11.0, 9.0, 2.0),
+            // 5..10 is missing
+            candle(11, 12.0, 13.0, 3.0),
+        ];
+
+        let resampled = resample(&candles, "5m", false).unwrap();
+        assert_eq!(resampled.len(), 2);
+        assert_eq!(resampled[0].open, 10.0);
+        assert_eq!(resampled[0].close, 9.0);
+        assert_eq!(resampled[0].high, 12.0);
+        assert_eq!(resampled[0].low, 8.0);
+        assert_eq!(resampled[0].volume, 3.0);
+        assert_eq!(resampled[0].close_time, 299_999);
+
+        let filled = resample(&candles, "5m", true).unwrap();
+        assert_eq!(filled.len(), 3);
+        assert_eq!(filled[1].open, 9.0);
+        assert_eq!(filled[1].volume, 0.0);
+        assert_eq!(filled[2].open_time, 600_000);
+    }
+
+    #[test]
+    fn live_updates_replace_each_other() {
+        let mut resampler = CandleResampler::new("5m").unwrap();
+
+        assert!(resampler.push(candle(0, 10.0, 11.0, 1.0)).is_none());
+        assert!(resampler.push(candle(0, 10.0, 12.0, 2.0)).is_none());
+        assert_eq!(resampler.get_current("BTC").unwrap().volume, 2.0);
+
+        let closed = resampler.push(candle(5, 12.0, 12.0, 1.0)).unwrap();
+        assert_eq!(closed.close, 12.0);
+        assert_eq!(closed.volume, 2.0);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let mut buffer = CandleBuffer::new(0);
+        buffer.push(candle(0, 10.0, 11.0, 1.0));
+        assert!(buffer.get("BTC").is_none());
+
+        let mut buffer = CandleBuffer::new(2);
+        for minute in 0..3 {
+            buffer.push(candle(minute, 10.0, 11.0, 1.0));
+        }
+        assert_eq!(buffer.get("BTC").unwrap().len(), 2);
+
+        let mut history = PriceHistory::new(0);
+        history.push(
+            "BTC",
+            PricePoint {
+                time: 0,
+                price: 1.0,
+            },
+        );
+        assert!(history.get_prices("BTC").is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+use std::{env, fs, path::Path, time::Duration};
+
+use anyhow::{bail, Context, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    endpoints::set_api_mirrors,
+    prices::{set_api_url, set_shared_http_config, HttpConfig, MAINNET_API_URL, TESTNET_API_URL},
+    service::{MarketDataConfig, MarketDataService},
+};
+
+/// Prefix of the environment variables read by [`Config::apply_env`]
+pub const ENV_PREFIX: &str = "HL_";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Testnet,
+}
+
+/// Which feeds [`MarketDataService`] starts
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedsConfig {
+    pub perps_prices: bool,
+    pub spot_prices: bool,
+    pub book_coins: Vec<String>,
+    pub candle_coins: Vec<String>,
+    pub candle_interval: String,
+    pub candle_capacity: usize,
+    /// 0 starts no asset context task
+    pub ctx_interval_secs: u64,
+}
+
+impl Default for FeedsConfig {
+    fn default() -> Self {
+        let defaults = MarketDataConfig::default();
+
+        FeedsConfig {
+            perps_prices: defaults.perps_prices,
+            spot_prices: defaults.spot_prices,
+            book_coins: defaults.book_coins,
+            candle_coins: defaults.candle_coins,
+            candle_interval: defaults.candle_interval,
+            candle_capacity: defaults.candle_capacity,
+            ctx_interval_secs: defaults.ctx_interval.map_or(0, |i| i.as_secs()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpSettings {
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    pub pool_max_idle_per_host: usize,
+    pub http2_prior_knowledge: bool,
+    /// e.g. "socks5h://127.0.0.1:1080", see [`HttpConfig::proxy`]
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        let defaults = HttpConfig::default();
+
+        HttpSettings {
+            timeout_secs: defaults.timeout.map_or(0, |t| t.as_secs()),
+            connect_timeout_secs: defaults.connect_timeout.map_or(0, |t| t.as_secs()),
+            pool_max_idle_per_host: defaults.pool_max_idle_per_host,
+            http2_prior_knowledge: defaults.http2_prior_knowledge,
+            proxy: defaults.proxy,
+        }
+    }
+}
+
+/// Deployment settings, read from a TOML file and overridden by `HL_*` environment variables, so
+/// parameters change without recompiling.
+///
+/// ```toml
+/// network = "testnet"
+///
+/// [feeds]
+/// book_coins = ["BTC", "ETH"]
+/// ctx_interval_secs = 30
+///
+/// [http]
+/// timeout_secs = 10
+/// ```
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub network: Network,
+    /// Overrides the network's API URL
+    pub api_url: Option<String>,
+    /// Hosts info requests fail over to after the official ones
+    pub api_mirrors: Vec<String>,
+    pub feeds: FeedsConfig,
+    pub http: HttpSettings,
+}
+
+/// "BTC, ETH" to ["BTC", "ETH"]
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|coin| !coin.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_var<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, Error>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid value {value:?} for {ENV_PREFIX}{key}"))
+}
+
+impl Config {
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+
+        Config::from_toml(&contents)
+    }
+
+    /// The file at `path` if given (defaults otherwise) with the environment applied on top.
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let mut config = match path {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+
+        config.apply_env(env::vars())?;
+
+        Ok(config)
+    }
+
+    /// Overrides fields with the `HL_*` variables in `vars`: `HL_NETWORK`, `HL_API_URL`,
+    /// `HL_API_MIRRORS`, `HL_PERPS_PRICES`, `HL_SPOT_PRICES`, `HL_BOOK_COINS` and
+    /// `HL_CANDLE_COINS` (lists are comma separated), `HL_CANDLE_INTERVAL`, `HL_CANDLE_CAPACITY`,
+    /// `HL_CTX_INTERVAL_SECS`, `HL_HTTP_TIMEOUT_SECS`, `HL_HTTP_CONNECT_TIMEOUT_SECS` and
+    /// `HL_HTTP_PROXY_URL`. Unknown `HL_*` variables are ignored.
+    pub fn apply_env(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), Error> {
+        for (key, value) in vars {
+            let key = match key.strip_prefix(ENV_PREFIX) {
+                Some(key) => key.to_string(),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "NETWORK" => {
+                    self.network = match value.trim().to_lowercase().as_str() {
+                        "mainnet" => Network::Mainnet,
+                        "testnet" => Network::Testnet,
+                        _ => bail!("Invalid value {value:?} for {ENV_PREFIX}NETWORK"),
+                    }
+                }
+                "API_URL" => self.api_url = Some(value.trim().to_string()),
+                "API_MIRRORS" => self.api_mirrors = parse_list(&value),
+                "PERPS_PRICES" => self.feeds.perps_prices = parse_var(&key, &value)?,
+                "SPOT_PRICES" => self.feeds.spot_prices = parse_var(&key, &value)?,
+                "BOOK_COINS" => self.feeds.book_coins = parse_list(&value),
+                "CANDLE_COINS" => self.feeds.candle_coins = parse_list(&value),
+                "CANDLE_INTERVAL" => self.feeds.candle_interval = value.trim().to_string(),
+                "CANDLE_CAPACITY" => self.feeds.candle_capacity = parse_var(&key, &value)?,
+                "CTX_INTERVAL_SECS" => self.feeds.ctx_interval_secs = parse_var(&key, &value)?,
+                "HTTP_TIMEOUT_SECS" => self.http.timeout_secs = parse_var(&key, &value)?,
+                "HTTP_PROXY_URL" => self.http.proxy = Some(value.trim().to_string()),
+                "HTTP_CONNECT_TIMEOUT_SECS" => {
+                    self.http.connect_timeout_secs = parse_var(&key, &value)?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_api_url(&self) -> &str {
+        match (&self.api_url, self.network) {
+            (Some(url), _) => url,
+            (None, Network::Mainnet) => MAINNET_API_URL,
+            (None, Network::Testnet) => TESTNET_API_URL,
+        }
+    }
+
+    pub fn get_market_data_config(&self) -> MarketDataConfig {
+        let feeds = &self.feeds;
+
+        MarketDataConfig {
+            perps_prices: feeds.perps_prices,
+            spot_prices: feeds.spot_prices,
+            book_coins: feeds.book_coins.clone(),
+            candle_coins: feeds.candle_coins.clone(),
+            candle_interval: feeds.candle_interval.clone(),
+            candle_capacity: feeds.candle_capacity,
+            ctx_interval: (feeds.ctx_interval_secs > 0)
+                .then_some(Duration::from_secs(feeds.ctx_interval_secs)),
+        }
+    }
+
+    /// 0 disables a timeout
+    pub fn get_http_config(&self) -> HttpConfig {
+        let secs = |secs: u64| (secs > 0).then_some(Duration::from_secs(secs));
+
+        HttpConfig {
+            timeout: secs(self.http.timeout_secs),
+            connect_timeout: secs(self.http.connect_timeout_secs),
+            pool_max_idle_per_host: self.http.pool_max_idle_per_host,
+            http2_prior_knowledge: self.http.http2_prior_knowledge,
+            proxy: self.http.proxy.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the API URL, its mirrors and the shared HTTP client's config. Has to run before the
+    /// first request, see [`set_api_url`], [`set_api_mirrors`] and [`set_shared_http_config`].
+    pub fn apply(&self) -> Result<(), Error> {
+        set_api_url(self.get_api_url())?;
+        set_api_mirrors(&self.api_mirrors)?;
+        set_shared_http_config(&self.get_http_config())
+    }
+
+    /// Applies the config and starts the feeds it enables.
+    pub async fn start_market_data(&self) -> Result<MarketDataService, Error> {
+        self.apply()?;
+
+        MarketDataService::start(self.get_market_data_config()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Config, Network, TESTNET_API_URL};
+
+    #[test]
+    fn env_overrides_the_file() {
+        let mut config = Config::from_toml(
+            r#"
+            network = "testnet"
+
+            [feeds]
+            book_coins = ["BTC"]
+            ctx_interval_secs = 0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.get_api_url(), TESTNET_API_URL);
+        assert!(config.feeds.perps_prices);
+        assert_eq!(config.get_market_data_config().ctx_interval, None);
+
+        config
+            .apply_env([
+                ("HL_BOOK_COINS".to_string(), "ETH, SOL".to_string()),
+                ("HL_CTX_INTERVAL_SECS".to_string(), "30".to_string()),
+                ("PATH".to_string(), "/bin".to_string()),
+            ])
+            .unwrap();
+
+        let market_data = config.get_market_data_config();
+        assert_eq!(market_data.book_coins, vec!["ETH", "SOL"]);
+        assert_eq!(market_data.ctx_interval, Some(Duration::from_secs(30)));
+
+        assert!(config
+            .apply_env([("HL_SPOT_PRICES".to_string(), "maybe".to_string())])
+            .is_err());
+    }
+}
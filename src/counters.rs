@@ -0,0 +1,131 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Message counts of a feed, summed over reconnects, see [`FeedCounter`].
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct FeedCounters {
+    pub feed: String,
+    /// Websocket messages taken off the subscription channel
+    pub received: u64,
+    /// Received but never published on their own, e.g. unexpected message kinds, invalid books or
+    /// messages of coins that were just unsubscribed
+    pub dropped: u64,
+    /// Entries of a message that couldn't be parsed and were skipped (mids, trades, fills or
+    /// candles)
+    pub deserialize_failures: u64,
+    /// Updates published to the feed's watch channel
+    pub sends: u64,
+}
+
+impl FeedCounters {
+    /// Share of the received messages that were dropped, 0.0 before the first message
+    pub fn get_drop_ratio(&self) -> f64 {
+        if self.received == 0 {
+            return 0.0;
+        }
+
+        self.dropped as f64 / self.received as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Counter {
+    Received,
+    Dropped,
+    DeserializeFailures,
+    Sends,
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    received: AtomicU64,
+    dropped: AtomicU64,
+    deserialize_failures: AtomicU64,
+    sends: AtomicU64,
+}
+
+/// Counts the messages of one stream or task. Clones add to the same counts, so the handle of a
+/// task reads what the task counted, and a task counts on through reconnects.
+#[derive(Clone, Debug)]
+pub struct FeedCounter {
+    feed: Arc<str>,
+    counts: Arc<Counts>,
+}
+
+impl FeedCounter {
+    pub fn new(feed: &str) -> Self {
+        FeedCounter {
+            feed: feed.into(),
+            counts: Arc::new(Counts::default()),
+        }
+    }
+
+    /// Adds `count` to `counter`.
+    pub(crate) fn add(&self, counter: Counter, count: u64) {
+        let counts = &self.counts;
+
+        let value = match counter {
+            Counter::Received => &counts.received,
+            Counter::Dropped => &counts.dropped,
+            Counter::DeserializeFailures => &counts.deserialize_failures,
+            Counter::Sends => &counts.sends,
+        };
+
+        value.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn count(&self, counter: Counter) {
+        self.add(counter, 1);
+    }
+
+    pub fn get_counters(&self) -> FeedCounters {
+        let counts = &self.counts;
+
+        FeedCounters {
+            feed: self.feed.to_string(),
+            received: counts.received.load(Ordering::Relaxed),
+            dropped: counts.dropped.load(Ordering::Relaxed),
+            deserialize_failures: counts.deserialize_failures.load(Ordering::Relaxed),
+            sends: counts.sends.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counter, FeedCounter};
+
+    #[test]
+    fn clones_share_their_counts() {
+        let counter = FeedCounter::new("orderbook");
+        let task_counter = counter.clone();
+
+        task_counter.count(Counter::Received);
+        task_counter.count(Counter::Received);
+        task_counter.count(Counter::Received);
+        task_counter.count(Counter::Dropped);
+        task_counter.add(Counter::DeserializeFailures, 4);
+        task_counter.add(Counter::Sends, 0);
+        task_counter.count(Counter::Sends);
+
+        let counters = counter.get_counters();
+        assert_eq!(counters.feed, "orderbook");
+        assert_eq!(
+            (
+                counters.received,
+                counters.dropped,
+                counters.deserialize_failures,
+                counters.sends
+            ),
+            (3, 1, 4, 1)
+        );
+        assert!((counters.get_drop_ratio() - 1.0 / 3.0).abs() < 1e-12);
+
+        // Another stream of the same feed counts on its own
+        assert_eq!(FeedCounter::new("orderbook").get_counters().received, 0);
+    }
+}
@@ -0,0 +1,464 @@
+use std::{
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Error};
+use chrono::Utc;
+use futures::future::join_all;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{sync::watch, time::Instant};
+use tracing::{error, info, warn};
+
+use crate::{
+    breaker::is_outage,
+    prices::{build_info_http_client, get_api_url, MAINNET_API_URL},
+};
+
+/// The other official mainnet host, serving the same API as [`MAINNET_API_URL`]
+pub const MAINNET_ALT_API_URL: &str = "https://api.hyperliquid.xyz";
+
+/// How long a failed endpoint is tried after the healthy ones by default
+pub const DEFAULT_ENDPOINT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Weight of the newest probe in [`EndpointHealth::latency_ms`]
+const LATENCY_SMOOTHING: f64 = 0.25;
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct EndpointHealth {
+    pub url: String,
+    pub consecutive_failures: u32,
+    pub successes: u64,
+    pub failures: u64,
+    pub last_error: Option<String>,
+    /// ms since epoch
+    pub last_success: Option<i64>,
+    /// ms since epoch
+    pub last_failure: Option<i64>,
+    /// Round trip of the latency probes, smoothed over the last few. `None` until probed.
+    pub latency_ms: Option<f64>,
+}
+
+impl EndpointHealth {
+    fn new(url: &str) -> Self {
+        EndpointHealth {
+            url: url.trim_end_matches('/').to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Failed last time and was tried less than `cooldown` ago.
+    pub fn is_cooling_down(&self, cooldown: Duration, now: i64) -> bool {
+        self.consecutive_failures > 0
+            && self
+                .last_failure
+                .is_some_and(|at| now - at < cooldown.as_millis() as i64)
+    }
+}
+
+/// REST hosts serving the same API, tried in order until one answers. An endpoint that errors or
+/// times out is moved behind the healthy ones for `cooldown`, then tried first again. Once
+/// [`EndpointPool::probe`] measured their latencies, the healthy ones are tried fastest first.
+#[derive(Debug)]
+pub struct EndpointPool {
+    endpoints: Mutex<Vec<EndpointHealth>>,
+    cooldown: Duration,
+}
+
+impl EndpointPool {
+    /// Duplicates are dropped, the first URL is the preferred one.
+    pub fn new(urls: &[String], cooldown: Duration) -> Self {
+        let mut endpoints: Vec<EndpointHealth> = vec![];
+
+        for url in urls {
+            let endpoint = EndpointHealth::new(url);
+
+            if !endpoints.iter().any(|e| e.url == endpoint.url) {
+                endpoints.push(endpoint);
+            }
+        }
+
+        EndpointPool {
+            endpoints: Mutex::new(endpoints),
+            cooldown,
+        }
+    }
+
+    /// URLs in the order they should be tried at `now` (ms since epoch): the ones not cooling
+    /// down fastest first (unprobed ones last, in their configured order), then the cooling ones
+    /// with the fewest failures first.
+    fn get_ordered_urls_at(&self, now: i64) -> Vec<String> {
+        let endpoints = self.endpoints.lock().unwrap();
+
+        let (mut cooling, mut healthy): (Vec<&EndpointHealth>, Vec<&EndpointHealth>) = endpoints
+            .iter()
+            .partition(|e| e.is_cooling_down(self.cooldown, now));
+
+        healthy.sort_by(|a, b| match (a.latency_ms, b.latency_ms) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        cooling.sort_by_key(|e| e.consecutive_failures);
+
+        healthy
+            .into_iter()
+            .chain(cooling)
+            .map(|e| e.url.clone())
+            .collect()
+    }
+
+    pub fn get_ordered_urls(&self) -> Vec<String> {
+        self.get_ordered_urls_at(Utc::now().timestamp_millis())
+    }
+
+    /// The endpoint requests go to first
+    pub fn get_fastest_url(&self) -> Option<String> {
+        self.get_ordered_urls().into_iter().next()
+    }
+
+    fn update(&self, url: &str, f: impl FnOnce(&mut EndpointHealth)) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+
+        if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+            f(endpoint);
+        }
+    }
+
+    pub fn on_success(&self, url: &str) {
+        self.update(url, |endpoint| {
+            endpoint.consecutive_failures = 0;
+            endpoint.successes += 1;
+            endpoint.last_success = Some(Utc::now().timestamp_millis());
+        });
+    }
+
+    pub fn on_failure(&self, url: &str, err: &Error) {
+        self.update(url, |endpoint| {
+            endpoint.consecutive_failures += 1;
+            endpoint.failures += 1;
+            endpoint.last_error = Some(err.to_string());
+            endpoint.last_failure = Some(Utc::now().timestamp_millis());
+        });
+    }
+
+    pub fn on_latency(&self, url: &str, latency: Duration) {
+        let latency_ms = latency.as_micros() as f64 / 1000.0;
+
+        self.update(url, |endpoint| {
+            endpoint.latency_ms = Some(match endpoint.latency_ms {
+                Some(prev) => prev + LATENCY_SMOOTHING * (latency_ms - prev),
+                None => latency_ms,
+            });
+        });
+    }
+
+    pub fn get_health(&self) -> Vec<EndpointHealth> {
+        self.endpoints.lock().unwrap().clone()
+    }
+
+    /// Any endpoint's latency was measured
+    pub fn is_probed(&self) -> bool {
+        self.endpoints
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|e| e.latency_ms.is_some())
+    }
+
+    /// Times an `allMids` request to every endpoint concurrently, updating their latencies and
+    /// health. Returns the health after the probe.
+    pub async fn probe(&self, client: &Client) -> Vec<EndpointHealth> {
+        let urls: Vec<String> = self.get_health().into_iter().map(|e| e.url).collect();
+        let data = json!({ "type": "allMids" });
+
+        let results = join_all(urls.iter().map(|url| async {
+            let start = Instant::now();
+            let result = post(client, url, &data).await;
+
+            (url, result.map(|_| start.elapsed()))
+        }))
+        .await;
+
+        for (url, result) in results {
+            match result {
+                Ok(latency) => {
+                    self.on_latency(url, latency);
+                    self.on_success(url);
+                }
+                Err(err) => {
+                    warn!("Latency probe of {url} failed: {err:?}");
+                    self.on_failure(url, &err);
+                }
+            }
+        }
+
+        self.get_health()
+    }
+
+    /// Posts `data` to `{url}/info` of each endpoint until one answers with a success status.
+    /// Only outages (see [`is_outage`]) move on to the next endpoint, a request the API rejects
+    /// would be rejected by the others too and errors right away. Errors with the last
+    /// endpoint's error if none answers.
+    pub async fn post_info(&self, client: &Client, data: &Value) -> Result<Vec<u8>, Error> {
+        let mut last_err = anyhow!("No API endpoint configured");
+
+        for url in self.get_ordered_urls() {
+            match post(client, &url, data).await {
+                Ok(bytes) => {
+                    self.on_success(&url);
+                    return Ok(bytes);
+                }
+                Err(err) if !is_outage(&err) => return Err(err),
+                Err(err) => {
+                    warn!("Info request to {url} failed, trying the next endpoint: {err:?}");
+                    self.on_failure(&url, &err);
+                    last_err = err;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
+async fn post(client: &Client, url: &str, data: &Value) -> Result<Vec<u8>, Error> {
+    let response = client
+        .post(Url::parse(&format!("{url}/info"))?)
+        .json(data)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+static API_MIRRORS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Adds mirrors tried after the official hosts when they fail. Fails once the endpoints were
+/// used, which happens on the first info request.
+pub fn set_api_mirrors(urls: &[String]) -> Result<(), Error> {
+    API_MIRRORS
+        .set(urls.to_vec())
+        .map_err(|_| anyhow!("The API endpoints were already used"))
+}
+
+/// [`get_api_url`] first, then the other official host when it's one of the mainnet ones, then
+/// the mirrors set with [`set_api_mirrors`].
+pub fn get_api_urls() -> Vec<String> {
+    let primary = get_api_url();
+    let mut urls = vec![primary.to_string()];
+
+    if primary == MAINNET_API_URL {
+        urls.push(MAINNET_ALT_API_URL.to_string());
+    } else if primary == MAINNET_ALT_API_URL {
+        urls.push(MAINNET_API_URL.to_string());
+    }
+
+    urls.extend(API_MIRRORS.get_or_init(Vec::new).iter().cloned());
+
+    urls
+}
+
+/// The endpoints every info request goes through
+pub fn get_info_endpoints() -> &'static EndpointPool {
+    static ENDPOINTS: OnceLock<EndpointPool> = OnceLock::new();
+
+    ENDPOINTS.get_or_init(|| EndpointPool::new(&get_api_urls(), DEFAULT_ENDPOINT_COOLDOWN))
+}
+
+/// Health and latency of the info endpoints, in their configured order.
+pub fn get_endpoint_health() -> Vec<EndpointHealth> {
+    get_info_endpoints().get_health()
+}
+
+/// Probes the info endpoints once, e.g. at startup so the first requests already go to the
+/// fastest one.
+pub async fn probe_endpoints() -> Result<Vec<EndpointHealth>, Error> {
+    let client = build_info_http_client()?;
+
+    Ok(get_info_endpoints().probe(&client).await)
+}
+
+/// Probes the info endpoints right away and then every `interval`, sending their health after
+/// each probe.
+///
+/// REST requests follow the selection right away. Websockets connect to the fastest endpoint
+/// when they are opened or reconnected, see [`crate::transport::LiveTransport`].
+pub async fn start_endpoint_probe_task(
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<Vec<EndpointHealth>>> {
+    let (health_sender, health_recv) = watch::channel(get_endpoint_health());
+
+    tokio::spawn(async move {
+        let h_s = health_sender;
+        loop {
+            info!("endpoint_probe_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let mut interval = tokio::time::interval(interval);
+
+            loop {
+                interval.tick().await;
+
+                let health = get_info_endpoints().probe(&client).await;
+
+                if let Some(fastest) = get_info_endpoints().get_fastest_url() {
+                    info!("endpoint_probe_task: Fastest endpoint is {fastest}");
+                }
+
+                if h_s.send(health).is_err() {
+                    info!("endpoint_probe_task: All receivers dropped, stopping...");
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(health_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+    use reqwest::Client;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use super::EndpointPool;
+
+    /// Answers one request with `response`, returns the URL and whether it was requested
+    async fn serve_once(response: &'static str) -> (String, JoinHandle<bool>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = tokio::spawn(async move {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return false;
+            };
+
+            // The JSON body is the end of the request
+            let mut request = vec![];
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"}") {
+                let len = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..len]);
+            }
+
+            stream.write_all(response.as_bytes()).await.unwrap();
+            true
+        });
+
+        (url, server)
+    }
+
+    /// A URL nothing listens on
+    async fn get_dead_url() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    #[test]
+    fn failed_endpoints_go_last_until_cooled_down() {
+        let pool = EndpointPool::new(
+            &[
+                "https://a/".to_string(),
+                "https://b".to_string(),
+                "https://c".to_string(),
+                "https://a".to_string(),
+            ],
+            Duration::from_secs(30),
+        );
+
+        assert_eq!(pool.get_health().len(), 3);
+
+        pool.on_failure("https://a", &anyhow!("timeout"));
+        pool.on_failure("https://a", &anyhow!("timeout"));
+        pool.on_failure("https://b", &anyhow!("502"));
+
+        let now = pool.get_health()[0].last_failure.unwrap();
+
+        assert_eq!(
+            pool.get_ordered_urls_at(now),
+            vec!["https://c", "https://b", "https://a"]
+        );
+        assert_eq!(
+            pool.get_ordered_urls_at(now + 60_000),
+            vec!["https://a", "https://b", "https://c"]
+        );
+
+        pool.on_success("https://a");
+        let health = &pool.get_health()[0];
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.failures, 2);
+        assert_eq!(health.last_error.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn healthy_endpoints_go_fastest_first() {
+        let urls = ["https://a", "https://b", "https://c"].map(str::to_string);
+        let pool = EndpointPool::new(&urls, Duration::from_secs(30));
+
+        pool.on_latency("https://b", Duration::from_millis(80));
+        pool.on_latency("https://c", Duration::from_millis(20));
+
+        assert_eq!(
+            pool.get_ordered_urls(),
+            vec!["https://c", "https://b", "https://a"]
+        );
+
+        // Smoothed, so one slow probe doesn't flip the order right away
+        pool.on_latency("https://c", Duration::from_millis(120));
+        assert_eq!(pool.get_health()[2].latency_ms, Some(45.0));
+        assert_eq!(pool.get_fastest_url().as_deref(), Some("https://c"));
+
+        pool.on_failure("https://c", &anyhow!("timeout"));
+        assert_eq!(pool.get_fastest_url().as_deref(), Some("https://b"));
+    }
+
+    #[tokio::test]
+    async fn only_outages_fail_over() {
+        let data = json!({ "type": "meta" });
+        let client = Client::new();
+
+        let (ok, server) =
+            serve_once("HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}").await;
+        let dead = get_dead_url().await;
+        let pool = EndpointPool::new(&[dead, ok], Duration::from_secs(30));
+
+        assert_eq!(pool.post_info(&client, &data).await.unwrap(), b"{}");
+        assert!(server.await.unwrap());
+        assert_eq!(pool.get_health()[0].consecutive_failures, 1);
+
+        let (rejecting, _) = serve_once(
+            "HTTP/1.1 422 Unprocessable Entity\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        )
+        .await;
+        let (other, server) = serve_once("HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await;
+        let pool = EndpointPool::new(&[rejecting, other], Duration::from_secs(30));
+
+        assert!(pool.post_info(&client, &data).await.is_err());
+        assert_eq!(pool.get_health()[0].consecutive_failures, 0);
+
+        // The other endpoint was never tried
+        server.abort();
+        assert!(server.await.is_err());
+    }
+}
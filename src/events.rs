@@ -0,0 +1,114 @@
+use std::sync::OnceLock;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Events buffered per receiver before the slowest one starts lagging
+pub const EVENT_BUS_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FeedEventKind {
+    /// First successful subscription of a task
+    Connected,
+    /// Subscribed again after the connection was dropped
+    Reconnected { attempt: u32 },
+    /// The subscription errored out and the task is about to reconnect
+    SubscriptionDropped { reason: String },
+    /// Two consecutive updates were further apart than expected
+    PriceGap { gap_ms: i64 },
+    /// No message arrived within the heartbeat timeout
+    Stale { silent_ms: i64 },
+    /// A book was crossed, locked or older than the previous one, so it was dropped and its coin
+    /// resubscribed
+    InvalidBook { coin: String, reason: String },
+    /// Consecutive REST failures opened a circuit breaker, requests are answered from its cache
+    CircuitOpened { failures: u32 },
+    /// A probe request succeeded and the circuit breaker let requests through again
+    CircuitClosed,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedEvent {
+    /// ms since epoch
+    pub time: i64,
+    /// Name of the task that emitted the event, e.g. "perps_sender_task"
+    pub feed: String,
+    pub kind: FeedEventKind,
+}
+
+fn get_bus() -> &'static broadcast::Sender<FeedEvent> {
+    static BUS: OnceLock<broadcast::Sender<FeedEvent>> = OnceLock::new();
+
+    BUS.get_or_init(|| broadcast::channel(EVENT_BUS_CAPACITY).0)
+}
+
+/// Receives the lifecycle events of every feed task started after this call.
+pub fn subscribe_feed_events() -> broadcast::Receiver<FeedEvent> {
+    get_bus().subscribe()
+}
+
+/// Dropped silently when nobody listens.
+pub(crate) fn emit(feed: &str, kind: FeedEventKind) {
+    let _ = get_bus().send(FeedEvent {
+        time: Utc::now().timestamp_millis(),
+        feed: feed.to_string(),
+        kind,
+    });
+}
+
+/// Emits `Connected` for the first connection of a task and `Reconnected` afterwards.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConnectionEvents {
+    attempts: u32,
+}
+
+impl ConnectionEvents {
+    pub(crate) fn connected(&mut self, feed: &str) {
+        let kind = match self.attempts {
+            0 => FeedEventKind::Connected,
+            attempt => FeedEventKind::Reconnected { attempt },
+        };
+
+        self.attempts += 1;
+        emit(feed, kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{emit, subscribe_feed_events, ConnectionEvents, FeedEventKind};
+
+    #[test]
+    fn connections_then_drops() {
+        let mut receiver = subscribe_feed_events();
+        let mut events = ConnectionEvents::default();
+
+        events.connected("events_test");
+        emit(
+            "events_test",
+            FeedEventKind::SubscriptionDropped {
+                reason: "closed".to_string(),
+            },
+        );
+        events.connected("events_test");
+
+        let mut kinds = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            if event.feed == "events_test" {
+                kinds.push(event.kind);
+            }
+        }
+
+        assert_eq!(
+            kinds,
+            [
+                FeedEventKind::Connected,
+                FeedEventKind::SubscriptionDropped {
+                    reason: "closed".to_string()
+                },
+                FeedEventKind::Reconnected { attempt: 1 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+
+use alloy::{primitives::Address, signers::local::PrivateKeySigner};
+use anyhow::{bail, Context, Error};
+use chrono::Utc;
+use hyperliquid_rust_sdk::{ExchangeClient, ExchangeResponseStatus};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// An approved agent (API) wallet. It can trade for the account that approved it but can't
+/// withdraw.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentKey {
+    pub address: Address,
+    /// Hex encoded, with the 0x prefix
+    pub private_key: String,
+    /// ms since epoch, 0 when loaded from a [`AgentKeyFormat::Hex`] file
+    pub approved_at: i64,
+}
+
+impl AgentKey {
+    pub fn from_private_key(private_key: &str, approved_at: i64) -> Result<Self, Error> {
+        let signer: PrivateKeySigner = private_key
+            .trim()
+            .parse()
+            .context("Invalid agent private key")?;
+
+        Ok(AgentKey {
+            address: signer.address(),
+            private_key: private_key.trim().to_string(),
+            approved_at,
+        })
+    }
+
+    /// The wallet to build the agent's `ExchangeClient` with
+    pub fn get_signer(&self) -> Result<PrivateKeySigner, Error> {
+        self.private_key
+            .parse()
+            .context("Invalid agent private key")
+    }
+}
+
+/// Leaves the private key out, so keys can be logged
+impl fmt::Debug for AgentKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AgentKey")
+            .field("address", &self.address)
+            .field("private_key", &"<redacted>")
+            .field("approved_at", &self.approved_at)
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgentKeyFormat {
+    /// Just the hex private key, as most tools expect it
+    Hex,
+    /// The whole [`AgentKey`]
+    Json,
+}
+
+/// Where an agent key is kept. Files are written to a temporary file first and renamed, so a crash
+/// never leaves a truncated key behind, and are only readable by the owner on unix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AgentKeyStore {
+    path: PathBuf,
+    format: AgentKeyFormat,
+}
+
+impl AgentKeyStore {
+    pub fn new(path: impl Into<PathBuf>, format: AgentKeyFormat) -> Self {
+        AgentKeyStore {
+            path: path.into(),
+            format,
+        }
+    }
+
+    pub fn get_path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// `None` if there's no key file yet.
+    pub fn load(&self) -> Result<Option<AgentKey>, Error> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&self.path)
+            .with_context(|| format!("Couldn't read {}", self.path.display()))?;
+
+        let key = match self.format {
+            AgentKeyFormat::Hex => AgentKey::from_private_key(&contents, 0)?,
+            AgentKeyFormat::Json => serde_json::from_str(&contents)?,
+        };
+
+        Ok(Some(key))
+    }
+
+    pub fn save(&self, key: &AgentKey) -> Result<(), Error> {
+        let contents = match self.format {
+            AgentKeyFormat::Hex => format!("{}\n", key.private_key),
+            AgentKeyFormat::Json => serde_json::to_string_pretty(key)?,
+        };
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut tmp_name = self
+            .path
+            .file_name()
+            .context("The agent key path has no file name")?
+            .to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = self.path.with_file_name(tmp_name);
+
+        // A leftover of a crashed save could have looser permissions, which opening it wouldn't
+        // change
+        match fs::remove_file(&tmp_path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options
+            .open(&tmp_path)
+            .with_context(|| format!("Couldn't create {}", tmp_path.display()))?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+/// Generates a new agent wallet, approves it with the account of `exchange_client` and persists
+/// it to `store`. The exchange keeps a single unnamed agent per account, so this also rotates
+/// the previous one out.
+pub async fn approve_agent(
+    exchange_client: &ExchangeClient,
+    store: &AgentKeyStore,
+) -> Result<AgentKey, Error> {
+    let (private_key, response) = exchange_client.approve_agent(None).await?;
+
+    if let ExchangeResponseStatus::Err(err) = response {
+        bail!("Agent approval failed: {err}");
+    }
+
+    let private_key = if private_key.starts_with("0x") {
+        private_key
+    } else {
+        format!("0x{private_key}")
+    };
+
+    let key = AgentKey::from_private_key(&private_key, Utc::now().timestamp_millis())?;
+    store.save(&key)?;
+
+    info!(
+        "Approved agent {} and saved it to {}",
+        key.address,
+        store.get_path().display()
+    );
+
+    Ok(key)
+}
+
+/// The agent in `store`, approving a new one if there's none yet.
+pub async fn load_or_approve_agent(
+    exchange_client: &ExchangeClient,
+    store: &AgentKeyStore,
+) -> Result<AgentKey, Error> {
+    match store.load()? {
+        Some(key) => Ok(key),
+        None => approve_agent(exchange_client, store).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AgentKey, AgentKeyFormat, AgentKeyStore};
+
+    const KEY: &str = "0xe908f86dbb4d55ac876378565aafeabc187f6690f046459397b17d9b9a19688e";
+
+    #[test]
+    fn saves_and_loads_keys() {
+        let dir = std::env::temp_dir().join(format!("hl_agent_{}", std::process::id()));
+        let key = AgentKey::from_private_key(KEY, 1).unwrap();
+        assert!(!format!("{key:?}").contains(&KEY[2..]));
+
+        // A leftover temporary file is replaced
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("agent.hex.tmp"), "garbage").unwrap();
+
+        for (file, format) in [
+            ("agent.hex", AgentKeyFormat::Hex),
+            ("agent.json", AgentKeyFormat::Json),
+        ] {
+            let store = AgentKeyStore::new(dir.join(file), format);
+            store.save(&key).unwrap();
+
+            let loaded = store.load().unwrap().unwrap();
+            assert_eq!(loaded.address, key.address);
+            assert_eq!(loaded.private_key, KEY);
+            assert!(!dir.join(format!("{file}.tmp")).exists());
+        }
+
+        assert!(AgentKeyStore::new(dir.join("missing"), AgentKeyFormat::Hex)
+            .load()
+            .unwrap()
+            .is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
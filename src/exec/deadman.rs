@@ -0,0 +1,230 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{bail, Error};
+use chrono::Utc;
+use futures::future::BoxFuture;
+use hyperliquid_rust_sdk::{ExchangeClient, ExchangeResponseStatus};
+use tokio::{sync::watch, task::JoinHandle, time::sleep};
+use tracing::{error, info, warn};
+
+/// The exchange refuses deadlines less than 5 seconds away.
+pub const MIN_DEAD_MAN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeadManConfig {
+    /// How far ahead the cancel is scheduled, the longest orders outlive the process
+    pub timeout: Duration,
+    /// How often the deadline is pushed back, has to be well below `timeout` to survive a few
+    /// failed refreshes
+    pub refresh_interval: Duration,
+}
+
+impl DeadManConfig {
+    /// Errors if `timeout` is below [`MIN_DEAD_MAN_TIMEOUT`] or not above `refresh_interval`.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.timeout < MIN_DEAD_MAN_TIMEOUT {
+            bail!(
+                "Dead man timeout must be at least {MIN_DEAD_MAN_TIMEOUT:?}, got {:?}",
+                self.timeout
+            );
+        }
+
+        if self.refresh_interval >= self.timeout {
+            bail!("Dead man refresh interval must be below the timeout");
+        }
+
+        Ok(())
+    }
+
+    /// The deadline to schedule at `now`, both ms since epoch
+    pub fn get_deadline(&self, now: u64) -> u64 {
+        now + self.timeout.as_millis() as u64
+    }
+}
+
+impl Default for DeadManConfig {
+    fn default() -> Self {
+        DeadManConfig {
+            timeout: Duration::from_secs(60),
+            refresh_interval: Duration::from_secs(15),
+        }
+    }
+}
+
+/// Where a [`DeadManSwitch`] sends its deadlines. Implemented by `ExchangeClient`; a fake lets the
+/// refresh loop be tested offline.
+pub trait CancelScheduler: Send + Sync + 'static {
+    /// Schedules the cancel of every open order at `time`, ms since epoch, or clears it if `None`.
+    fn schedule_cancel(&self, time: Option<u64>) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+impl CancelScheduler for ExchangeClient {
+    fn schedule_cancel(&self, time: Option<u64>) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            match ExchangeClient::schedule_cancel(self, time, None).await? {
+                ExchangeResponseStatus::Ok(_) => Ok(()),
+                ExchangeResponseStatus::Err(err) => bail!("scheduleCancel failed: {err}"),
+            }
+        })
+    }
+}
+
+/// Keeps Hyperliquid's scheduleCancel deadline of a wallet (or of its vault) in the future, so
+/// every open order is canceled by the exchange if the process dies or loses its connection.
+///
+/// [`DeadManSwitch::shutdown`] clears the deadline for a deliberate stop, also done by
+/// [`crate::service::MarketDataService::shutdown`] once the switch is handed to
+/// [`crate::service::MarketDataService::with_dead_man_switch`]. Dropping the switch only stops
+/// refreshing, leaving the last deadline to cancel the orders.
+pub struct DeadManSwitch {
+    scheduler: Arc<dyn CancelScheduler>,
+    deadline: watch::Receiver<u64>,
+    task: JoinHandle<()>,
+}
+
+impl DeadManSwitch {
+    /// Fails if `config.timeout` is below [`MIN_DEAD_MAN_TIMEOUT`] or the first deadline can't
+    /// be set.
+    pub async fn start(
+        exchange_client: Arc<ExchangeClient>,
+        config: DeadManConfig,
+    ) -> Result<Self, Error> {
+        DeadManSwitch::start_with_scheduler(exchange_client, config).await
+    }
+
+    /// [`DeadManSwitch::start`] sending the deadlines to `scheduler`.
+    pub async fn start_with_scheduler(
+        scheduler: Arc<dyn CancelScheduler>,
+        config: DeadManConfig,
+    ) -> Result<Self, Error> {
+        config.check()?;
+
+        let get_deadline = move || config.get_deadline(Utc::now().timestamp_millis() as u64);
+
+        let deadline = get_deadline();
+        scheduler.schedule_cancel(Some(deadline)).await?;
+
+        let (deadline_sender, deadline_recv) = watch::channel(deadline);
+
+        let task_scheduler = scheduler.clone();
+        let task = tokio::spawn(async move {
+            info!("dead_man_task: Starting with {config:?}");
+
+            loop {
+                sleep(config.refresh_interval).await;
+
+                let deadline = get_deadline();
+
+                match task_scheduler.schedule_cancel(Some(deadline)).await {
+                    Ok(()) => {
+                        let _ = deadline_sender.send(deadline);
+                    }
+                    Err(err) => warn!("dead_man_task: Couldn't refresh the deadline: {err:?}"),
+                }
+            }
+        });
+
+        Ok(DeadManSwitch {
+            scheduler,
+            deadline: deadline_recv,
+            task,
+        })
+    }
+
+    /// Time the orders get canceled at if the switch stops refreshing, ms since epoch
+    pub fn get_deadline(&self) -> u64 {
+        *self.deadline.borrow()
+    }
+
+    /// Stops refreshing and clears the scheduled cancel, keeping the open orders.
+    pub async fn shutdown(self) -> Result<(), Error> {
+        info!("dead_man_task: Shutting down...");
+
+        self.task.abort();
+
+        if let Err(err) = self.scheduler.schedule_cancel(None).await {
+            error!("dead_man_task: Couldn't clear the deadline: {err:?}");
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for DeadManSwitch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use anyhow::Error;
+    use futures::future::BoxFuture;
+
+    use super::{CancelScheduler, DeadManConfig, DeadManSwitch};
+
+    /// Records every deadline it's sent
+    #[derive(Default)]
+    struct FakeScheduler {
+        times: Mutex<Vec<Option<u64>>>,
+    }
+
+    impl CancelScheduler for FakeScheduler {
+        fn schedule_cancel(&self, time: Option<u64>) -> BoxFuture<'_, Result<(), Error>> {
+            self.times.lock().unwrap().push(time);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[test]
+    fn timeouts_the_exchange_refuses_are_rejected() {
+        let config = DeadManConfig::default();
+        assert!(config.check().is_ok());
+        assert_eq!(config.get_deadline(1_000), 61_000);
+
+        let too_short = DeadManConfig {
+            timeout: Duration::from_secs(4),
+            refresh_interval: Duration::from_secs(1),
+        };
+        assert!(too_short.check().is_err());
+
+        let never_refreshed = DeadManConfig {
+            timeout: Duration::from_secs(30),
+            refresh_interval: Duration::from_secs(30),
+        };
+        assert!(never_refreshed.check().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deadlines_are_refreshed_until_cleared_on_shutdown() -> Result<(), Error> {
+        let scheduler = Arc::new(FakeScheduler::default());
+        let config = DeadManConfig {
+            timeout: Duration::from_secs(10),
+            refresh_interval: Duration::from_secs(2),
+        };
+
+        let switch = DeadManSwitch::start_with_scheduler(scheduler.clone(), config).await?;
+        let first = switch.get_deadline();
+        assert_eq!(*scheduler.times.lock().unwrap(), vec![Some(first)]);
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let refreshed = scheduler.times.lock().unwrap().clone();
+        assert_eq!(refreshed.len(), 3);
+        assert!(refreshed.iter().all(Option::is_some));
+        assert_eq!(refreshed.last(), Some(&Some(switch.get_deadline())));
+
+        switch.shutdown().await?;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let times = scheduler.times.lock().unwrap().clone();
+        assert_eq!(times.len(), 4);
+        assert_eq!(times.last(), Some(&None));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use anyhow::{bail, Error};
+use tokio::sync::watch;
+use tracing::warn;
+
+use crate::price_data::perps::NameToPerpQuoteMap;
+
+/// Refuses trading in coins whose mid or mark dislocated from the oracle.
+///
+/// A coin's circuit opens once its deviation exceeds `max_deviation_bps` and only closes again
+/// once the deviation is back within `close_deviation_bps`, so a price hovering around the limit
+/// doesn't flip it on every check. Coins without an asset context yet are refused as well.
+pub struct OracleGuard {
+    quote_receiver: watch::Receiver<NameToPerpQuoteMap>,
+    max_deviation_bps: f64,
+    close_deviation_bps: f64,
+    open: Mutex<HashSet<String>>,
+}
+
+impl OracleGuard {
+    /// `close_deviation_bps` is capped at `max_deviation_bps`.
+    pub fn new(
+        quote_receiver: watch::Receiver<NameToPerpQuoteMap>,
+        max_deviation_bps: f64,
+        close_deviation_bps: f64,
+    ) -> Self {
+        OracleGuard {
+            quote_receiver,
+            max_deviation_bps,
+            close_deviation_bps: close_deviation_bps.min(max_deviation_bps),
+            open: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Latest deviation of `coin` from its oracle in bps, `None` without a quote or context
+    pub fn get_deviation_bps(&self, coin: &str) -> Option<f64> {
+        self.quote_receiver
+            .borrow()
+            .get(coin)?
+            .get_oracle_deviation_bps()
+    }
+
+    /// Whether both the mid and mark of `coin` are within `bps` of the oracle
+    pub fn is_within(&self, coin: &str, bps: f64) -> bool {
+        self.get_deviation_bps(coin)
+            .is_some_and(|deviation| deviation <= bps)
+    }
+
+    /// Whether trading in `coin` is currently refused, as of the last check.
+    pub fn is_open(&self, coin: &str) -> bool {
+        self.open.lock().unwrap().contains(coin)
+    }
+
+    /// Opens the circuit of `coin` by hand, e.g. on an external signal. It closes again like an
+    /// automatically opened one.
+    pub fn trip(&self, coin: &str) {
+        self.open.lock().unwrap().insert(coin.to_string());
+    }
+
+    /// Updates the circuit of `coin` with the latest quote and errors if it's open.
+    pub fn check(&self, coin: &str) -> Result<(), Error> {
+        let deviation = match self.get_deviation_bps(coin) {
+            Some(deviation) => deviation,
+            None => bail!("No oracle price for {coin}"),
+        };
+
+        let mut open = self.open.lock().unwrap();
+
+        if open.contains(coin) {
+            if deviation > self.close_deviation_bps {
+                bail!("Circuit open for {coin}, {deviation:.1}bps from the oracle");
+            }
+
+            open.remove(coin);
+        } else if deviation > self.max_deviation_bps {
+            warn!("Opening the circuit for {coin}, {deviation:.1}bps from the oracle");
+            open.insert(coin.to_string());
+            bail!("Circuit open for {coin}, {deviation:.1}bps from the oracle");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::watch;
+
+    use crate::{
+        price_data::perps::{NameToPerpQuoteMap, PerpQuote, PerpsAssetCtx},
+        types::{Meta, Price},
+    };
+
+    use super::OracleGuard;
+
+    fn quotes(mid: f64) -> NameToPerpQuoteMap {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 4,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        };
+
+        NameToPerpQuoteMap::from([(
+            "ETH".to_string(),
+            PerpQuote {
+                price: Price::new_perp(mid, meta),
+                ctx: Some(PerpsAssetCtx {
+                    mark_px: 2000.0,
+                    oracle_px: 2000.0,
+                    ..Default::default()
+                }),
+                ctx_time: Some(0),
+            },
+        )])
+    }
+
+    #[test]
+    fn circuit_opens_and_closes_with_hysteresis() {
+        let (sender, receiver) = watch::channel(quotes(2000.0));
+        let guard = OracleGuard::new(receiver, 50.0, 20.0);
+
+        assert!(guard.check("ETH").is_ok());
+        assert!(guard.check("BTC").is_err());
+
+        // 100bps off
+        sender.send(quotes(2020.0)).unwrap();
+        assert!(!guard.is_within("ETH", 50.0));
+        assert!(guard.check("ETH").is_err());
+
+        // 30bps, back under the limit but not under the close threshold
+        sender.send(quotes(2006.0)).unwrap();
+        assert!(guard.check("ETH").is_err());
+        assert!(guard.is_open("ETH"));
+
+        sender.send(quotes(2002.0)).unwrap();
+        assert!(guard.check("ETH").is_ok());
+        assert!(!guard.is_open("ETH"));
+    }
+}
@@ -0,0 +1,20 @@
+mod order;
+mod twap;
+mod slicing;
+mod guard;
+mod utils;
+mod tracker;
+mod validate;
+mod deadman;
+mod agent;
+mod native_twap;
+pub use order::*;
+pub use twap::*;
+pub use slicing::*;
+pub use guard::*;
+pub use utils::*;
+pub use tracker::*;
+pub use validate::*;
+pub use deadman::*;
+pub use agent::*;
+pub use native_twap::*;
@@ -0,0 +1,368 @@
+use std::{collections::HashMap, time::Duration};
+
+use alloy::primitives::Address;
+use anyhow::Error;
+use hyperliquid_rust_sdk::TradeInfo;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    account::get_account_address,
+    fills::Fill,
+    price_data::perps::parse_string_to_float,
+    prices::{build_info_http_client, post_info},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NativeTwapStatus {
+    Activated,
+    Finished,
+    /// Canceled by the user
+    Terminated,
+    Error(String),
+}
+
+impl NativeTwapStatus {
+    fn new(status: &str, description: Option<String>) -> Self {
+        match status {
+            "activated" => NativeTwapStatus::Activated,
+            "finished" => NativeTwapStatus::Finished,
+            "terminated" => NativeTwapStatus::Terminated,
+            _ => NativeTwapStatus::Error(description.unwrap_or_else(|| status.to_string())),
+        }
+    }
+}
+
+/// A TWAP order run by the exchange itself, as opposed to [`crate::exec::TwapExecutor`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NativeTwap {
+    /// Missing on TWAPs older than the ids
+    pub twap_id: Option<u64>,
+    pub coin: String,
+    pub is_buy: bool,
+    pub size: f64,
+    pub executed_size: f64,
+    pub executed_notional: f64,
+    pub minutes: u32,
+    pub reduce_only: bool,
+    pub randomize: bool,
+    /// ms since epoch
+    pub started_at: u64,
+    /// When the status last changed, ms since epoch
+    pub updated_at: u64,
+    pub status: NativeTwapStatus,
+}
+
+impl NativeTwap {
+    pub fn is_active(&self) -> bool {
+        self.status == NativeTwapStatus::Activated
+    }
+
+    /// Fraction of the size executed, between 0.0 and 1.0
+    pub fn get_progress(&self) -> f64 {
+        if self.size <= 0.0 {
+            return 0.0_f64;
+        }
+
+        (self.executed_size / self.size).min(1.0)
+    }
+
+    /// Average execution price so far, 0.0 before the first slice filled
+    pub fn get_avg_price(&self) -> f64 {
+        if self.executed_size <= 0.0 {
+            return 0.0_f64;
+        }
+
+        self.executed_notional / self.executed_size
+    }
+
+    /// When the TWAP is scheduled to end, ms since epoch
+    pub fn get_end_time(&self) -> u64 {
+        self.started_at + self.minutes as u64 * 60_000
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TwapState {
+    coin: String,
+    side: String,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    sz: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    executed_sz: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    executed_ntl: f64,
+    minutes: u32,
+    reduce_only: bool,
+    randomize: bool,
+    timestamp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TwapStatusData {
+    status: String,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TwapHistoryEntry {
+    /// Seconds since epoch
+    time: u64,
+    state: TwapState,
+    status: TwapStatusData,
+    twap_id: Option<u64>,
+}
+
+impl From<TwapHistoryEntry> for NativeTwap {
+    fn from(entry: TwapHistoryEntry) -> Self {
+        NativeTwap {
+            twap_id: entry.twap_id,
+            coin: entry.state.coin,
+            is_buy: entry.state.side == "B",
+            size: entry.state.sz,
+            executed_size: entry.state.executed_sz,
+            executed_notional: entry.state.executed_ntl,
+            minutes: entry.state.minutes,
+            reduce_only: entry.state.reduce_only,
+            randomize: entry.state.randomize,
+            started_at: entry.state.timestamp,
+            updated_at: entry.time * 1000,
+            status: NativeTwapStatus::new(&entry.status.status, entry.status.description),
+        }
+    }
+}
+
+/// A fill of one of the slices of a native TWAP
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TwapSliceFill {
+    pub twap_id: u64,
+    pub fill: Fill,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TwapSliceFillData {
+    fill: TradeInfo,
+    twap_id: u64,
+}
+
+/// Keeps the latest entry of every TWAP of `history`, which has one entry per status change.
+/// Sorted by start time, oldest first.
+fn get_latest_twaps(history: Vec<NativeTwap>) -> Vec<NativeTwap> {
+    let mut latest: HashMap<(Option<u64>, u64), NativeTwap> = HashMap::new();
+
+    for twap in history {
+        let key = (twap.twap_id, twap.started_at);
+
+        match latest.get(&key) {
+            Some(prev) if prev.updated_at > twap.updated_at => (),
+            _ => {
+                latest.insert(key, twap);
+            }
+        }
+    }
+
+    let mut twaps: Vec<NativeTwap> = latest.into_values().collect();
+    twaps.sort_by_key(|twap| twap.started_at);
+
+    twaps
+}
+
+/// The native TWAPs of `user` with their latest status, oldest first.
+pub async fn get_twap_history(client: &Client, user: Address) -> Result<Vec<NativeTwap>, Error> {
+    let history: Vec<TwapHistoryEntry> =
+        post_info(client, json!({ "type": "twapHistory", "user": user })).await?;
+
+    Ok(get_latest_twaps(
+        history.into_iter().map(NativeTwap::from).collect(),
+    ))
+}
+
+/// The recent fills of the slices of the native TWAPs of `user`. Malformed fills are skipped.
+pub async fn get_twap_slice_fills(
+    client: &Client,
+    user: Address,
+) -> Result<Vec<TwapSliceFill>, Error> {
+    Ok(get_counted_twap_slice_fills(client, user).await?.0)
+}
+
+/// [`get_twap_slice_fills`] with the number of malformed fills that were skipped
+async fn get_counted_twap_slice_fills(
+    client: &Client,
+    user: Address,
+) -> Result<(Vec<TwapSliceFill>, usize), Error> {
+    let fills: Vec<TwapSliceFillData> = post_info(
+        client,
+        json!({ "type": "userTwapSliceFills", "user": user }),
+    )
+    .await?;
+    let received = fills.len();
+
+    let fills: Vec<TwapSliceFill> = fills
+        .into_iter()
+        .filter_map(|data| match Fill::try_from(data.fill) {
+            Ok(fill) => Some(TwapSliceFill {
+                twap_id: data.twap_id,
+                fill,
+            }),
+            Err(err) => {
+                error!(
+                    "Skipping malformed slice fill of TWAP {}: {err:?}",
+                    data.twap_id
+                );
+                None
+            }
+        })
+        .collect();
+    let skipped = received - fills.len();
+
+    Ok((fills, skipped))
+}
+
+/// The native TWAPs of a user and the fills of their slices
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct NativeTwapState {
+    pub twaps: Vec<NativeTwap>,
+    pub slice_fills: Vec<TwapSliceFill>,
+    /// Slice fills of the last poll that were skipped as malformed
+    pub skipped_slice_fills: usize,
+}
+
+impl NativeTwapState {
+    pub fn get_active(&self) -> Vec<&NativeTwap> {
+        self.twaps.iter().filter(|twap| twap.is_active()).collect()
+    }
+
+    pub fn get_twap(&self, twap_id: u64) -> Option<&NativeTwap> {
+        self.twaps.iter().find(|twap| twap.twap_id == Some(twap_id))
+    }
+
+    pub fn get_slice_fills(&self, twap_id: u64) -> Vec<&Fill> {
+        self.slice_fills
+            .iter()
+            .filter(|slice| slice.twap_id == twap_id)
+            .map(|slice| &slice.fill)
+            .collect()
+    }
+}
+
+pub async fn get_native_twap_state(
+    client: &Client,
+    user: Address,
+) -> Result<NativeTwapState, Error> {
+    let (twaps, (slice_fills, skipped_slice_fills)) = tokio::try_join!(
+        get_twap_history(client, user),
+        get_counted_twap_slice_fills(client, user),
+    )?;
+
+    Ok(NativeTwapState {
+        twaps,
+        slice_fills,
+        skipped_slice_fills,
+    })
+}
+
+/// Polls the native TWAPs of `user` (or of `vault_address` if set) every `interval`, to follow
+/// their execution.
+pub async fn start_native_twap_task(
+    user: Address,
+    vault_address: Option<Address>,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<NativeTwapState>> {
+    let user = get_account_address(user, vault_address);
+    let (state_sender, state_recv) = watch::channel(NativeTwapState::default());
+
+    tokio::spawn(async move {
+        loop {
+            info!("native_twap_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let mut interval = tokio::time::interval(interval);
+
+            let err = loop {
+                interval.tick().await;
+
+                match get_native_twap_state(&client, user).await {
+                    Ok(state) => {
+                        let changed = state_sender.send_if_modified(|current| {
+                            if *current == state {
+                                return false;
+                            }
+
+                            *current = state;
+                            true
+                        });
+
+                        if changed {
+                            for twap in state_sender.borrow().get_active() {
+                                info!(
+                                    "native_twap_task: {} {:.1}% executed at {}",
+                                    twap.coin,
+                                    twap.get_progress() * 100.0,
+                                    twap.get_avg_price()
+                                );
+                            }
+                        }
+
+                        if state_sender.is_closed() {
+                            info!("native_twap_task: All receivers dropped, stopping...");
+                            return;
+                        }
+                    }
+                    Err(err) => break err,
+                }
+            };
+
+            error!("native_twap_task: Error: {err:?}");
+            info!("native_twap_task: Resetting...");
+
+            sleep(Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(state_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_latest_twaps, NativeTwap, NativeTwapStatus, TwapHistoryEntry};
+
+    #[test]
+    fn keeps_the_latest_status_of_every_twap() {
+        let history: Vec<TwapHistoryEntry> = serde_json::from_str(
+            r#"[
+                {"time": 1739371403, "state": {"coin": "HYPE", "user": "0x0", "side": "B", "sz": "10.0", "executedSz": "0.0", "executedNtl": "0.0", "minutes": 30, "reduceOnly": false, "randomize": false, "timestamp": 1739371402645}, "status": {"status": "activated"}, "twapId": 67},
+                {"time": 1739373203, "state": {"coin": "HYPE", "user": "0x0", "side": "B", "sz": "10.0", "executedSz": "10.0", "executedNtl": "250.0", "minutes": 30, "reduceOnly": false, "randomize": false, "timestamp": 1739371402645}, "status": {"status": "finished"}, "twapId": 67},
+                {"time": 1739380000, "state": {"coin": "ETH", "user": "0x0", "side": "A", "sz": "2.0", "executedSz": "0.5", "executedNtl": "1000.0", "minutes": 60, "reduceOnly": true, "randomize": true, "timestamp": 1739379999000}, "status": {"status": "error", "description": "Insufficient margin"}, "twapId": 68}
+            ]"#,
+        )
+        .unwrap();
+
+        let twaps = get_latest_twaps(history.into_iter().map(NativeTwap::from).collect());
+
+        assert_eq!(twaps.len(), 2);
+        assert_eq!(twaps[0].status, NativeTwapStatus::Finished);
+        assert_eq!(twaps[0].get_avg_price(), 25.0);
+        assert_eq!(twaps[0].get_end_time(), 1739373202645);
+        assert_eq!(
+            twaps[1].status,
+            NativeTwapStatus::Error("Insufficient margin".to_string())
+        );
+        assert!(!twaps[1].is_buy);
+        assert_eq!(twaps[1].get_progress(), 0.25);
+    }
+}
@@ -0,0 +1,298 @@
+use alloy::primitives::Address;
+use anyhow::{bail, Error};
+use hyperliquid_rust_sdk::{
+    BuilderInfo, ClientLimit, ClientOrder, ClientOrderRequest, ExchangeClient, ExchangeDataStatus,
+    ExchangeResponseStatus,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::types::Price;
+pub use crate::types::MIN_ORDER_NOTIONAL;
+
+pub const TIF_IOC: &str = "Ioc";
+pub const TIF_GTC: &str = "Gtc";
+pub const TIF_ALO: &str = "Alo";
+
+/// Highest builder fee the exchange accepts on perps orders, in tenths of a basis point (0.1%)
+pub const MAX_PERP_BUILDER_FEE: u32 = 100;
+/// Highest builder fee the exchange accepts on spot orders, in tenths of a basis point (1%)
+pub const MAX_SPOT_BUILDER_FEE: u32 = 1000;
+
+/// Builder credited with a fee on every order it's attached to. The user has to approve the
+/// builder for at least `fee` with an approveBuilderFee action first.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BuilderConfig {
+    pub address: Address,
+    /// Tenths of a basis point, e.g. 10 == 1bp
+    pub fee: u32,
+}
+
+impl BuilderConfig {
+    pub fn new(address: Address, fee: u32) -> Self {
+        BuilderConfig { address, fee }
+    }
+
+    /// Errors if `fee` is above what the exchange accepts for the asset.
+    pub fn check(&self, is_spot: bool) -> Result<(), Error> {
+        let max_fee = if is_spot {
+            MAX_SPOT_BUILDER_FEE
+        } else {
+            MAX_PERP_BUILDER_FEE
+        };
+
+        if self.fee > max_fee {
+            bail!("Builder fee {} is above the max of {max_fee}", self.fee);
+        }
+
+        Ok(())
+    }
+
+    pub fn to_builder_info(&self) -> BuilderInfo {
+        BuilderInfo {
+            builder: self.address.to_string().to_lowercase(),
+            fee: self.fee as u64,
+        }
+    }
+}
+
+/// An order with its price and size already rounded to what the exchange accepts.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderPayload {
+    pub coin: String,
+    pub asset: u32,
+    pub is_buy: bool,
+    pub limit_px: String,
+    pub sz: String,
+    pub reduce_only: bool,
+    pub tif: String,
+}
+
+impl OrderPayload {
+    /// Builds an IoC order for `notional` USD worth of the asset, priced `slippage` (0.01 == 1%)
+    /// through the current price so it crosses the book.
+    pub fn from_notional(
+        price: &Price,
+        notional: f64,
+        slippage: f64,
+        is_buy: bool,
+    ) -> Result<Self, Error> {
+        if let Price::None = price {
+            bail!("Can't build an order without a price");
+        }
+
+        if !(0.0..1.0).contains(&slippage) {
+            bail!("Slippage must be in [0, 1), got {slippage}");
+        }
+
+        let limit_px = price.get_value_after_slippage(slippage, is_buy);
+        let sz = price.get_asset_denom_size(notional);
+
+        Self::new(price, limit_px, sz, is_buy, false, TIF_IOC)
+    }
+
+    /// Rounds `limit_px` and `sz` with the asset's rules and validates the result.
+    pub fn new(
+        price: &Price,
+        limit_px: f64,
+        sz: f64,
+        is_buy: bool,
+        reduce_only: bool,
+        tif: &str,
+    ) -> Result<Self, Error> {
+        if let Price::None = price {
+            bail!("Can't build an order without a price");
+        }
+
+        let meta = price.get_meta();
+        let limit_px = price.get_true_price_for_asset(limit_px);
+        let sz = price.get_true_size(sz);
+
+        if !limit_px.is_finite() || limit_px <= 0.0 {
+            bail!("Invalid limit price {limit_px} for {}", meta.get_name());
+        }
+
+        if !sz.is_finite() || sz <= 0.0 {
+            bail!(
+                "Size rounds to zero for {} with {} sz_decimals",
+                meta.get_name(),
+                meta.get_sz_decimals()
+            );
+        }
+
+        if !reduce_only && sz * limit_px < MIN_ORDER_NOTIONAL {
+            bail!(
+                "Order value {} is below the minimum of {MIN_ORDER_NOTIONAL} for {}",
+                sz * limit_px,
+                meta.get_name()
+            );
+        }
+
+        Ok(OrderPayload {
+            coin: meta.get_name().clone(),
+            asset: meta.get_asset_index(),
+            is_buy,
+            limit_px: limit_px.to_string(),
+            sz: sz.to_string(),
+            reduce_only,
+            tif: tif.to_string(),
+        })
+    }
+
+    pub fn get_limit_px(&self) -> f64 {
+        self.limit_px.parse::<f64>().unwrap_or(0.0_f64)
+    }
+
+    pub fn get_sz(&self) -> f64 {
+        self.sz.parse::<f64>().unwrap_or(0.0_f64)
+    }
+
+    pub fn to_client_order_request(&self) -> ClientOrderRequest {
+        ClientOrderRequest {
+            asset: self.coin.clone(),
+            is_buy: self.is_buy,
+            reduce_only: self.reduce_only,
+            limit_px: self.get_limit_px(),
+            sz: self.get_sz(),
+            cloid: None,
+            order_type: ClientOrder::Limit(ClientLimit {
+                tif: self.tif.clone(),
+            }),
+        }
+    }
+
+    pub async fn submit(
+        &self,
+        exchange_client: &ExchangeClient,
+    ) -> Result<ExchangeResponseStatus, Error> {
+        Ok(exchange_client
+            .order(self.to_client_order_request(), None)
+            .await?)
+    }
+
+    /// Same as [`OrderPayload::submit`] with `builder` credited with its fee.
+    pub async fn submit_with_builder(
+        &self,
+        exchange_client: &ExchangeClient,
+        builder: &BuilderConfig,
+    ) -> Result<ExchangeResponseStatus, Error> {
+        builder.check(self.asset >= 10_000)?;
+
+        Ok(exchange_client
+            .order_with_builder(
+                self.to_client_order_request(),
+                None,
+                builder.to_builder_info(),
+            )
+            .await?)
+    }
+}
+
+/// What happened to a single order sent with [`OrderPayload::submit`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OrderOutcome {
+    Filled { oid: u64, size: f64, avg_price: f64 },
+    Resting { oid: u64 },
+    Rejected(String),
+}
+
+impl OrderOutcome {
+    pub fn from_response(response: &ExchangeResponseStatus) -> Self {
+        let statuses = match response {
+            ExchangeResponseStatus::Err(err) => return OrderOutcome::Rejected(err.clone()),
+            ExchangeResponseStatus::Ok(response) => match &response.data {
+                Some(data) => &data.statuses,
+                None => return OrderOutcome::Rejected("Empty exchange response".to_string()),
+            },
+        };
+
+        match statuses.first() {
+            Some(ExchangeDataStatus::Filled(filled)) => OrderOutcome::Filled {
+                oid: filled.oid,
+                size: filled.total_sz.parse::<f64>().unwrap_or(0.0_f64),
+                avg_price: filled.avg_px.parse::<f64>().unwrap_or(0.0_f64),
+            },
+            Some(ExchangeDataStatus::Resting(resting)) => {
+                OrderOutcome::Resting { oid: resting.oid }
+            }
+            Some(ExchangeDataStatus::Error(err)) => OrderOutcome::Rejected(err.clone()),
+            Some(s) => OrderOutcome::Rejected(format!("Unexpected order status: {s:?}")),
+            None => OrderOutcome::Rejected("No order status returned".to_string()),
+        }
+    }
+
+    /// Filled size, 0.0 unless the order filled.
+    pub fn get_filled_size(&self) -> f64 {
+        match self {
+            OrderOutcome::Filled { size, .. } => *size,
+            _ => 0.0_f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Address;
+
+    use crate::types::{Meta, Price};
+
+    use super::{BuilderConfig, OrderPayload, MAX_PERP_BUILDER_FEE, TIF_GTC, TIF_IOC};
+
+    fn eth(price: f64) -> Price {
+        Price::new_perp(
+            price,
+            Meta::Perp {
+                name: "ETH".to_string(),
+                index: 1,
+                sz_decimals: 4,
+                max_leverage: 25,
+                only_isolated: None,
+                is_delisted: None,
+            },
+        )
+    }
+
+    #[test]
+    fn builds_rounded_ioc_orders_from_notional() {
+        let payload = OrderPayload::from_notional(&eth(2000.0), 100.0, 0.01, true).unwrap();
+
+        assert_eq!(payload.asset, 1);
+        assert_eq!(payload.tif, TIF_IOC);
+        assert_eq!(payload.limit_px, "2020");
+        assert_eq!(payload.sz, "0.05");
+        assert!(!payload.reduce_only);
+
+        let sell = OrderPayload::from_notional(&eth(2000.0), 100.0, 0.01, false).unwrap();
+        assert_eq!(sell.get_limit_px(), 1980.0);
+    }
+
+    #[test]
+    fn rejects_orders_the_exchange_would() {
+        // Below the minimum notional
+        assert!(OrderPayload::from_notional(&eth(2000.0), 5.0, 0.01, true).is_err());
+        // Reduce only orders are exempt from it
+        assert!(OrderPayload::new(&eth(2000.0), 2000.0, 0.001, false, true, TIF_GTC).is_ok());
+        // Rounds to zero
+        assert!(OrderPayload::new(&eth(2000.0), 2000.0, 0.00001, true, true, TIF_GTC).is_err());
+        assert!(OrderPayload::from_notional(&eth(2000.0), 100.0, 1.5, true).is_err());
+        assert!(OrderPayload::from_notional(&Price::None, 100.0, 0.01, true).is_err());
+    }
+
+    #[test]
+    fn builder_fees_are_capped_per_market() {
+        let address: Address = "0x00000000000000000000000000000000000000Ab"
+            .parse()
+            .unwrap();
+
+        let builder = BuilderConfig::new(address, MAX_PERP_BUILDER_FEE);
+        assert!(builder.check(false).is_ok());
+
+        let builder = BuilderConfig::new(address, 500);
+        assert!(builder.check(false).is_err());
+        assert!(builder.check(true).is_ok());
+        assert!(BuilderConfig::new(address, 1001).check(true).is_err());
+
+        let info = builder.to_builder_info();
+        assert_eq!(info.builder, "0x00000000000000000000000000000000000000ab");
+        assert_eq!(info.fee, 500);
+    }
+}
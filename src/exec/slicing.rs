@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{analytics::ImpactModel, types::Orderbook};
+
+/// How to work `total_size` into the book without any child order averaging worse than the
+/// slippage tolerance, assuming the book refills between children.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct SlicePlan {
+    /// Largest child order that stays within tolerance on the current book
+    pub max_child_size: f64,
+    /// Expected average fill price of a child of `max_child_size`
+    pub child_avg_price: f64,
+    /// Child sizes adding up to the total, the first being the largest
+    pub slices: Vec<f64>,
+}
+
+impl SlicePlan {
+    pub fn get_slice_count(&self) -> usize {
+        self.slices.len()
+    }
+
+    /// Whether the whole size can be sent as a single order
+    pub fn is_single_order(&self) -> bool {
+        self.slices.len() <= 1
+    }
+}
+
+/// Splits `total_size` (in units of the coin) into children of at most the size the book can
+/// absorb within `max_slippage` (0.01 == 1%) of the mid. The remainder after the first, largest
+/// child is split into equal slices. `None` if the book is empty on either side or can't absorb
+/// anything within tolerance.
+pub fn plan_slices(
+    book: &Orderbook,
+    is_buy: bool,
+    total_size: f64,
+    max_slippage: f64,
+) -> Option<SlicePlan> {
+    let (max_child_size, child_avg_price) =
+        book.get_max_size_within_slippage(is_buy, max_slippage)?;
+
+    split_slices(total_size, max_child_size, child_avg_price)
+}
+
+/// Like [`plan_slices`], but sizes the children with an impact model fitted from past trades
+/// (see [`crate::analytics::ImpactCalibrator`]) instead of the current book, which doesn't show
+/// the liquidity that refills as the children trade. `None` if the model can't fit any size
+/// within tolerance.
+pub fn plan_slices_with_impact(
+    model: &ImpactModel,
+    mid: f64,
+    is_buy: bool,
+    total_size: f64,
+    max_slippage: f64,
+) -> Option<SlicePlan> {
+    let max_child_size = model.get_max_size(max_slippage)?;
+    let impact = model.get_impact_bps(max_child_size) / 10_000.0;
+
+    let child_avg_price = if is_buy {
+        mid * (1.0 + impact)
+    } else {
+        mid * (1.0 - impact)
+    };
+
+    split_slices(total_size, max_child_size, child_avg_price)
+}
+
+fn split_slices(total_size: f64, max_child_size: f64, child_avg_price: f64) -> Option<SlicePlan> {
+    if max_child_size <= 0.0 {
+        return None;
+    }
+
+    if total_size <= max_child_size {
+        return Some(SlicePlan {
+            max_child_size,
+            child_avg_price,
+            slices: vec![total_size],
+        });
+    }
+
+    let remainder = total_size - max_child_size;
+    let count = (remainder / max_child_size).ceil() as usize;
+
+    let mut slices = Vec::with_capacity(count + 1);
+    slices.push(max_child_size);
+    slices.resize(count + 1, remainder / count as f64);
+
+    Some(SlicePlan {
+        max_child_size,
+        child_avg_price,
+        slices,
+    })
+}
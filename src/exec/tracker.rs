@@ -0,0 +1,433 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::primitives::Address;
+use anyhow::{anyhow, Error};
+use hyperliquid_rust_sdk::{Message, Subscription};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc::unbounded_channel, watch},
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+use tracing::{error, info};
+
+use crate::{
+    exec::{ExchangeUtils, OrderOutcome, OrderPayload},
+    fills::{Fill, UserFillsStream},
+    subscription::SubscriptionGuard,
+    transport::{LiveTransport, Transport},
+};
+
+/// How long updates and fills of unknown oids wait for the response of their order. Orders placed
+/// outside the tracker never get one, so their events are dropped after this.
+pub const EARLY_EVENT_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OrderState {
+    /// Sent, no response yet
+    Pending,
+    Resting,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Rejected(String),
+}
+
+impl OrderState {
+    /// Whether the order can't change anymore
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            OrderState::Filled | OrderState::Canceled | OrderState::Rejected(_)
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderStatus {
+    /// `None` while pending and for orders rejected before getting one
+    pub oid: Option<u64>,
+    pub coin: String,
+    pub is_buy: bool,
+    pub size: f64,
+    pub filled: f64,
+    /// Volume weighted average fill price, 0.0 before the first fill
+    pub avg_price: f64,
+    pub state: OrderState,
+}
+
+impl OrderStatus {
+    fn add_fill(&mut self, fill: &Fill) {
+        let notional = self.avg_price * self.filled + fill.price * fill.size;
+        self.filled += fill.size;
+        self.avg_price = notional / self.filled;
+
+        if self.filled >= self.size {
+            self.state = OrderState::Filled;
+        } else if !self.state.is_done() {
+            self.state = OrderState::PartiallyFilled;
+        }
+    }
+
+    fn set_update(&mut self, status: &str) {
+        self.state = match status {
+            "open" if self.filled > 0.0 => OrderState::PartiallyFilled,
+            "open" => OrderState::Resting,
+            "filled" => OrderState::Filled,
+            "rejected" => OrderState::Rejected("Rejected by the exchange".to_string()),
+            // "canceled", "marginCanceled", "reduceOnlyCanceled" and the other cancel reasons
+            status if status.ends_with("anceled") => OrderState::Canceled,
+            _ => return,
+        };
+    }
+}
+
+/// Follows an order submitted through an [`OrderTracker`].
+#[derive(Clone, Debug)]
+pub struct OrderHandle {
+    receiver: watch::Receiver<OrderStatus>,
+}
+
+impl OrderHandle {
+    pub fn get_status(&self) -> OrderStatus {
+        self.receiver.borrow().clone()
+    }
+
+    /// Resolves once the order is filled, canceled or rejected.
+    pub async fn wait_done(&mut self) -> Result<OrderStatus, Error> {
+        let status = self
+            .receiver
+            .wait_for(|status| status.state.is_done())
+            .await
+            .map_err(|_| anyhow!("The order tracker was dropped"))?;
+
+        Ok(status.clone())
+    }
+}
+
+#[derive(Default)]
+struct TrackerState {
+    orders: HashMap<u64, watch::Sender<OrderStatus>>,
+    /// Updates and fills of oids that aren't known yet, as they can arrive before the response to
+    /// the order, with when the first one arrived
+    early_updates: HashMap<u64, (Instant, Vec<String>)>,
+    early_fills: HashMap<u64, (Instant, Vec<Fill>)>,
+    /// Oid of every fill applied or waiting, by tid
+    seen_tids: HashMap<u64, u64>,
+}
+
+/// Correlates submitted orders with the order updates and fills streams, keeping a
+/// [`OrderState`] per order.
+#[derive(Clone, Default)]
+pub struct OrderTracker {
+    state: Arc<Mutex<TrackerState>>,
+}
+
+impl OrderTracker {
+    pub fn new() -> Self {
+        OrderTracker::default()
+    }
+
+    /// Submits `order` through `exchange_utils`, with its builder, referral code and action budget,
+    /// and tracks it from the response on.
+    pub async fn submit(
+        &self,
+        exchange_utils: &ExchangeUtils,
+        order: &OrderPayload,
+    ) -> OrderHandle {
+        let (sender, receiver) = watch::channel(OrderStatus {
+            oid: None,
+            coin: order.coin.clone(),
+            is_buy: order.is_buy,
+            size: order.get_sz(),
+            filled: 0.0,
+            avg_price: 0.0,
+            state: OrderState::Pending,
+        });
+
+        let outcome = match exchange_utils.submit(order).await {
+            Ok(response) => OrderOutcome::from_response(&response),
+            Err(err) => OrderOutcome::Rejected(err.to_string()),
+        };
+
+        self.on_outcome(sender, &outcome);
+
+        OrderHandle { receiver }
+    }
+
+    fn on_outcome(&self, sender: watch::Sender<OrderStatus>, outcome: &OrderOutcome) {
+        let oid = match outcome {
+            OrderOutcome::Rejected(err) => {
+                sender.send_modify(|status| status.state = OrderState::Rejected(err.clone()));
+                return;
+            }
+            OrderOutcome::Resting { oid } => {
+                sender.send_modify(|status| {
+                    status.oid = Some(*oid);
+                    status.state = OrderState::Resting;
+                });
+                *oid
+            }
+            // The fill itself comes through the fills stream
+            OrderOutcome::Filled { oid, .. } => {
+                sender.send_modify(|status| {
+                    status.oid = Some(*oid);
+                    status.state = OrderState::Resting;
+                });
+                *oid
+            }
+        };
+
+        let mut state = self.state.lock().unwrap();
+
+        if let Some((_, updates)) = state.early_updates.remove(&oid) {
+            for update in updates {
+                sender.send_modify(|status| status.set_update(&update));
+            }
+        }
+        if let Some((_, fills)) = state.early_fills.remove(&oid) {
+            for fill in fills {
+                sender.send_modify(|status| status.add_fill(&fill));
+            }
+        }
+
+        state.orders.insert(oid, sender);
+    }
+
+    /// Applies an update of the order updates stream, e.g. "open", "filled" or "canceled".
+    pub fn on_order_update(&self, oid: u64, status: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        match state.orders.get(&oid) {
+            Some(sender) => sender.send_modify(|order| order.set_update(status)),
+            None => state
+                .early_updates
+                .entry(oid)
+                .or_insert_with(|| (Instant::now(), vec![]))
+                .1
+                .push(status.to_string()),
+        }
+    }
+
+    /// Applies a fill, fills already seen are ignored.
+    pub fn on_fill(&self, fill: &Fill) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.seen_tids.insert(fill.tid, fill.oid).is_some() {
+            return;
+        }
+
+        match state.orders.get(&fill.oid) {
+            Some(sender) => sender.send_modify(|order| order.add_fill(fill)),
+            None => state
+                .early_fills
+                .entry(fill.oid)
+                .or_insert_with(|| (Instant::now(), vec![]))
+                .1
+                .push(fill.clone()),
+        }
+    }
+
+    pub fn get_status(&self, oid: u64) -> Option<OrderStatus> {
+        let state = self.state.lock().unwrap();
+
+        Some(state.orders.get(&oid)?.borrow().clone())
+    }
+
+    /// Statuses of the orders that aren't done yet
+    pub fn get_open_orders(&self) -> Vec<OrderStatus> {
+        let state = self.state.lock().unwrap();
+
+        state
+            .orders
+            .values()
+            .map(|sender| sender.borrow().clone())
+            .filter(|status| !status.state.is_done())
+            .collect()
+    }
+
+    /// Forgets the orders that are done, the events of unknown oids older than
+    /// [`EARLY_EVENT_TTL`] and the fills of both.
+    pub fn prune(&self) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        state
+            .orders
+            .retain(|_, sender| !sender.borrow().state.is_done());
+        state
+            .early_updates
+            .retain(|_, (first_seen, _)| first_seen.elapsed() < EARLY_EVENT_TTL);
+        state
+            .early_fills
+            .retain(|_, (first_seen, _)| first_seen.elapsed() < EARLY_EVENT_TTL);
+
+        let (orders, early_fills) = (&state.orders, &state.early_fills);
+        state
+            .seen_tids
+            .retain(|_, oid| orders.contains_key(oid) || early_fills.contains_key(oid));
+    }
+
+    /// Feeds the order updates and fills of `user` into the tracker, resubscribing on errors.
+    /// Runs until the returned handle is aborted. Orders placed for a vault need the vault as
+    /// `user`, see [`crate::exec::ExchangeUtils::get_user`].
+    pub fn start(&self, user: Address) -> JoinHandle<()> {
+        self.spawn(user, None)
+    }
+
+    /// [`OrderTracker::start`] through `transport` instead of a new mainnet connection.
+    pub fn start_with_transport(
+        &self,
+        user: Address,
+        transport: Arc<dyn Transport>,
+    ) -> JoinHandle<()> {
+        self.spawn(user, Some(transport))
+    }
+
+    fn spawn(&self, user: Address, transport: Option<Arc<dyn Transport>>) -> JoinHandle<()> {
+        let tracker = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                info!("order_tracker: Starting...");
+
+                if let Err(err) = tracker.run(user, transport.as_ref()).await {
+                    error!("order_tracker: Error: {err:?}");
+                }
+
+                info!("order_tracker: Resetting...");
+                sleep(Duration::from_secs(5)).await;
+            }
+        })
+    }
+
+    async fn run(
+        &self,
+        user: Address,
+        transport: Option<&Arc<dyn Transport>>,
+    ) -> Result<(), Error> {
+        let transport = match transport {
+            Some(transport) => transport.clone(),
+            None => Arc::new(LiveTransport::new().await?),
+        };
+
+        let mut fills_stream =
+            UserFillsStream::with_transport(transport.clone(), user, None).await?;
+
+        let mut subscriptions = SubscriptionGuard::with_transport(transport);
+        let (sender, mut receiver) = unbounded_channel();
+        subscriptions
+            .subscribe(Subscription::OrderUpdates { user }, sender)
+            .await?;
+
+        loop {
+            tokio::select! {
+                fills = fills_stream.get_next_fills() => {
+                    // The snapshot after a reconnect has the fills missed in between, fills seen
+                    // before are skipped by their tid
+                    if let Some(update) = fills? {
+                        update.fills.iter().for_each(|fill| self.on_fill(fill));
+                    }
+                }
+                message = receiver.recv() => match message {
+                    Some(Message::OrderUpdates(updates)) => {
+                        for update in updates.data {
+                            self.on_order_update(update.order.oid, &update.status);
+                        }
+                    }
+                    Some(Message::NoData) | None => {
+                        let _ = fills_stream.unsub().await;
+                        return Err(anyhow!("Order updates channel closed"));
+                    }
+                    Some(_) => {}
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::watch;
+
+    use crate::{exec::OrderOutcome, fills::Fill};
+
+    use super::{OrderHandle, OrderState, OrderStatus, OrderTracker, EARLY_EVENT_TTL};
+
+    fn pending(tracker: &OrderTracker, outcome: OrderOutcome) -> OrderHandle {
+        let (sender, receiver) = watch::channel(OrderStatus {
+            oid: None,
+            coin: "ETH".to_string(),
+            is_buy: true,
+            size: 2.0,
+            filled: 0.0,
+            avg_price: 0.0,
+            state: OrderState::Pending,
+        });
+        tracker.on_outcome(sender, &outcome);
+
+        OrderHandle { receiver }
+    }
+
+    fn fill(oid: u64, tid: u64, price: f64, size: f64) -> Fill {
+        Fill {
+            coin: "ETH".to_string(),
+            is_buy: true,
+            price,
+            size,
+            tid,
+            oid,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_the_order_lifecycle() {
+        let tracker = OrderTracker::new();
+
+        // The first fill arrives before the response
+        tracker.on_fill(&fill(7, 1, 100.0, 1.0));
+        let mut handle = pending(&tracker, OrderOutcome::Resting { oid: 7 });
+        assert_eq!(handle.get_status().state, OrderState::PartiallyFilled);
+
+        // Duplicates are ignored
+        tracker.on_fill(&fill(7, 1, 100.0, 1.0));
+        tracker.on_fill(&fill(7, 2, 102.0, 1.0));
+
+        let status = handle.wait_done().await.unwrap();
+        assert_eq!(status.state, OrderState::Filled);
+        assert_eq!(status.avg_price, 101.0);
+
+        let handle = pending(&tracker, OrderOutcome::Resting { oid: 8 });
+        tracker.on_order_update(8, "marginCanceled");
+        assert_eq!(handle.get_status().state, OrderState::Canceled);
+        assert!(tracker.get_open_orders().is_empty());
+
+        let handle = pending(&tracker, OrderOutcome::Rejected("Bad price".to_string()));
+        assert!(handle.get_status().state.is_done());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn prune_expires_events_of_unknown_orders() {
+        let tracker = OrderTracker::new();
+
+        tracker.on_fill(&fill(9, 1, 100.0, 1.0));
+        tracker.on_order_update(9, "open");
+        let handle = pending(&tracker, OrderOutcome::Resting { oid: 7 });
+        tracker.on_fill(&fill(7, 2, 100.0, 2.0));
+        assert_eq!(handle.get_status().state, OrderState::Filled);
+
+        tokio::time::sleep(EARLY_EVENT_TTL).await;
+        tracker.prune();
+
+        let state = tracker.state.lock().unwrap();
+        assert!(state.orders.is_empty());
+        assert!(state.early_fills.is_empty());
+        assert!(state.early_updates.is_empty());
+        assert!(state.seen_tids.is_empty());
+    }
+}
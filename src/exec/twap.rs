@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tracing::{error, info};
+
+use crate::exec::{ExchangeUtils, OrderOutcome, MIN_ORDER_NOTIONAL};
+
+#[derive(Clone, Debug)]
+pub struct TwapConfig {
+    pub coin: String,
+    pub is_buy: bool,
+    /// Total USD notional to execute
+    pub notional: f64,
+    pub duration: Duration,
+    pub slices: u32,
+    /// Slippage applied to each child order, 0.01 == 1%
+    pub slippage: f64,
+}
+
+impl TwapConfig {
+    /// Errors if the slices would be empty, below the minimum order value or too close together
+    /// to schedule.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.slices == 0 {
+            bail!("A TWAP needs at least one slice");
+        }
+
+        if self.duration / self.slices == Duration::ZERO {
+            bail!(
+                "A duration of {:?} is too short for {} slices",
+                self.duration,
+                self.slices
+            );
+        }
+
+        if self.notional / (self.slices as f64) < MIN_ORDER_NOTIONAL {
+            bail!(
+                "Slices of {} are below the minimum order value of {MIN_ORDER_NOTIONAL}",
+                self.notional / self.slices as f64
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TwapProgress {
+    /// 0 based index of the slice that was just sent
+    pub slice: u32,
+    pub slices: u32,
+    pub outcome: OrderOutcome,
+    pub filled_size: f64,
+    pub filled_notional: f64,
+    pub remaining_notional: f64,
+    /// Volume weighted average fill price so far, 0.0 before the first fill
+    pub avg_price: f64,
+}
+
+/// Splits `notional` into `slices` IoC child orders spread evenly over `duration`. Notional that
+/// doesn't fill is carried over to the following slices.
+///
+/// Slices are priced from the feed of `exchange_utils` and sent through it, so they carry its
+/// builder fee and referral code, wait for its action budget and are rejected without being sent
+/// while its oracle guard holds the coin's circuit open.
+pub struct TwapExecutor {
+    exchange_utils: ExchangeUtils,
+    config: TwapConfig,
+}
+
+impl TwapExecutor {
+    pub fn new(exchange_utils: ExchangeUtils, config: TwapConfig) -> Result<Self, Error> {
+        config.check()?;
+
+        Ok(TwapExecutor {
+            exchange_utils,
+            config,
+        })
+    }
+
+    /// Spawns the execution and returns a channel reporting the progress after every slice. The
+    /// channel closes once the last slice has been sent.
+    pub fn start(self) -> UnboundedReceiver<TwapProgress> {
+        let (progress_sender, progress_recv) = unbounded_channel();
+
+        tokio::spawn(async move {
+            let config = self.config;
+            let mut interval = tokio::time::interval(config.duration / config.slices);
+
+            let mut filled_size = 0.0_f64;
+            let mut filled_notional = 0.0_f64;
+
+            info!(
+                "twap: Starting {} slices for {}",
+                config.slices, config.coin
+            );
+
+            for slice in 0..config.slices {
+                interval.tick().await;
+
+                let remaining_notional = config.notional - filled_notional;
+                if remaining_notional < MIN_ORDER_NOTIONAL {
+                    break;
+                }
+
+                let slice_notional = (remaining_notional / (config.slices - slice) as f64)
+                    .max(MIN_ORDER_NOTIONAL)
+                    .min(remaining_notional);
+
+                let order = self.exchange_utils.get_market_order(
+                    &config.coin,
+                    slice_notional,
+                    config.slippage,
+                    config.is_buy,
+                );
+
+                let outcome = match order {
+                    Ok(order) => match self.exchange_utils.submit(&order).await {
+                        Ok(response) => OrderOutcome::from_response(&response),
+                        Err(err) => OrderOutcome::Rejected(err.to_string()),
+                    },
+                    Err(err) => OrderOutcome::Rejected(err.to_string()),
+                };
+
+                if let OrderOutcome::Filled {
+                    size, avg_price, ..
+                } = outcome
+                {
+                    filled_size += size;
+                    filled_notional += size * avg_price;
+                } else {
+                    error!(
+                        "twap: Slice {slice} for {} failed: {outcome:?}",
+                        config.coin
+                    );
+                }
+
+                let progress = TwapProgress {
+                    slice,
+                    slices: config.slices,
+                    outcome,
+                    filled_size,
+                    filled_notional,
+                    remaining_notional: (config.notional - filled_notional).max(0.0),
+                    avg_price: if filled_size > 0.0 {
+                        filled_notional / filled_size
+                    } else {
+                        0.0
+                    },
+                };
+
+                if progress_sender.send(progress).is_err() {
+                    info!("twap: Progress receiver dropped, stopping...");
+                    return;
+                }
+            }
+
+            info!("twap: Done for {}", config.coin);
+        });
+
+        progress_recv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::TwapConfig;
+
+    #[test]
+    fn config_needs_schedulable_slices() {
+        let config = TwapConfig {
+            coin: "ETH".to_string(),
+            is_buy: true,
+            notional: 1000.0,
+            duration: Duration::from_secs(60),
+            slices: 10,
+            slippage: 0.01,
+        };
+        assert!(config.check().is_ok());
+
+        for bad in [
+            TwapConfig {
+                slices: 0,
+                ..config.clone()
+            },
+            TwapConfig {
+                duration: Duration::ZERO,
+                ..config.clone()
+            },
+            TwapConfig {
+                duration: Duration::from_nanos(5),
+                ..config.clone()
+            },
+            TwapConfig {
+                notional: 50.0,
+                ..config.clone()
+            },
+        ] {
+            assert!(bad.check().is_err(), "{bad:?}");
+        }
+    }
+}
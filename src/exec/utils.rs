@@ -0,0 +1,482 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use alloy::{primitives::Address, signers::local::PrivateKeySigner};
+use anyhow::{anyhow, Error};
+use hyperliquid_rust_sdk::{
+    BaseUrl, ClientCancelRequest, ExchangeClient, ExchangeDataStatus, ExchangeResponseStatus,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc::UnboundedSender, watch};
+use tracing::{error, info, warn};
+
+use crate::{
+    account::{get_account_address, get_open_orders, OpenOrder},
+    exec::{BuilderConfig, OracleGuard, OrderOutcome, OrderPayload},
+    prices::build_info_http_client,
+    ratelimit::{get_action_weight, get_rest_rate_limiter, ActionBudget},
+    types::{NameToPriceMap, Price},
+};
+
+/// Orders per cancel request. Every 40 orders in a batch add one to the request's rate limit
+/// weight, so larger batches don't save anything.
+pub const CANCEL_BATCH_SIZE: usize = 40;
+
+/// Pause between cancel requests, keeping a full cancel well under the per IP weight limit of
+/// 1200 per minute
+pub const CANCEL_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reported after every batch of [`ExchangeUtils::cancel_all`]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct CancelProgress {
+    pub total: usize,
+    pub canceled: usize,
+    pub failed: usize,
+    /// Errors of the orders that couldn't be canceled, e.g. because they filled in the meantime
+    pub errors: Vec<String>,
+}
+
+impl CancelProgress {
+    pub fn is_done(&self) -> bool {
+        self.canceled + self.failed >= self.total
+    }
+
+    /// Counts the statuses of a batch of `batch_len` cancels. Orders without a status are counted
+    /// as failed.
+    fn add_statuses(&mut self, batch_len: usize, statuses: &[ExchangeDataStatus]) {
+        for status in statuses.iter().take(batch_len) {
+            match status {
+                ExchangeDataStatus::Error(err) => {
+                    self.failed += 1;
+                    self.errors.push(err.clone());
+                }
+                _ => self.canceled += 1,
+            }
+        }
+
+        self.failed += batch_len.saturating_sub(statuses.len());
+    }
+}
+
+/// (coin, oid) of the `orders` of the coins in `coin_filter`, every coin if it's empty
+fn get_cancels(orders: Vec<OpenOrder>, coin_filter: &[String]) -> Vec<(String, u64)> {
+    orders
+        .into_iter()
+        .filter(|order| coin_filter.is_empty() || coin_filter.contains(&order.coin))
+        .map(|order| (order.coin, order.oid))
+        .collect()
+}
+
+/// Whose orders `signer` trades: the vault if set, otherwise the account it's an agent of, if any.
+fn get_order_owner(
+    signer: Address,
+    account: Option<Address>,
+    vault_address: Option<Address>,
+) -> Address {
+    get_account_address(account.unwrap_or(signer), vault_address)
+}
+
+/// The IoC order for `notional` USD of `coin` at its price in `prices`, unless `guard` holds the
+/// coin's circuit open.
+fn get_market_order(
+    prices: &NameToPriceMap,
+    guard: Option<&OracleGuard>,
+    coin: &str,
+    notional: f64,
+    slippage: f64,
+    is_buy: bool,
+) -> Result<OrderPayload, Error> {
+    if let Some(guard) = guard {
+        guard.check(coin)?;
+    }
+
+    let price = prices
+        .get(coin)
+        .ok_or_else(|| anyhow!("No price for {coin}"))?;
+
+    OrderPayload::from_notional(price, notional, slippage, is_buy)
+}
+
+/// Trades through an `ExchangeClient` with sizes and prices taken from a price feed and rounded
+/// with the crate's rules, e.g. the receiver of [`crate::prices::start_perps_sender_task`].
+#[derive(Clone)]
+pub struct ExchangeUtils {
+    exchange_client: Arc<ExchangeClient>,
+    price_receiver: watch::Receiver<NameToPriceMap>,
+    /// The master account when the wallet is an agent
+    account: Option<Address>,
+    guard: Option<Arc<OracleGuard>>,
+    builder: Option<BuilderConfig>,
+    referral_code: Option<String>,
+    /// Whether the referral code was already sent, shared by the clones
+    referral_sent: Arc<AtomicBool>,
+    action_budget: Option<Arc<ActionBudget>>,
+}
+
+impl ExchangeUtils {
+    pub fn new(
+        exchange_client: Arc<ExchangeClient>,
+        price_receiver: watch::Receiver<NameToPriceMap>,
+    ) -> Self {
+        ExchangeUtils {
+            exchange_client,
+            price_receiver,
+            account: None,
+            guard: None,
+            builder: None,
+            referral_code: None,
+            referral_sent: Arc::new(AtomicBool::new(false)),
+            action_budget: None,
+        }
+    }
+
+    /// Connects an `ExchangeClient` to `base_url` signing with `wallet`, acting on behalf of
+    /// `vault_address` if set.
+    pub async fn connect(
+        wallet: PrivateKeySigner,
+        vault_address: Option<Address>,
+        base_url: BaseUrl,
+        price_receiver: watch::Receiver<NameToPriceMap>,
+    ) -> Result<Self, Error> {
+        let exchange_client = ExchangeClient::new(
+            Some(build_info_http_client()?),
+            wallet,
+            Some(base_url),
+            None,
+            vault_address,
+        )
+        .await?;
+
+        Ok(ExchangeUtils::new(
+            Arc::new(exchange_client),
+            price_receiver,
+        ))
+    }
+
+    /// The account the wallet signs for. Required when the wallet is an agent (see
+    /// [`crate::exec::load_or_approve_agent`]), which holds no orders of its own, so
+    /// [`ExchangeUtils::cancel_all`] would find nothing to cancel without it.
+    pub fn with_account(mut self, account: Address) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    /// Orders are refused without being sent while the guard's circuit is open for the coin.
+    pub fn with_oracle_guard(mut self, guard: Arc<OracleGuard>) -> Self {
+        self.guard = Some(guard);
+        self
+    }
+
+    /// Credits `builder` with its fee on every order sent through the helper.
+    pub fn with_builder(mut self, builder: BuilderConfig) -> Self {
+        self.builder = Some(builder);
+        self
+    }
+
+    /// Referral codes apply to the account rather than to orders, so the code is set once before
+    /// the first order sent through the helper.
+    pub fn with_referral_code(mut self, code: &str) -> Self {
+        self.referral_code = Some(code.to_string());
+        self
+    }
+
+    /// Paces orders and cancels to the account's remaining allowance, e.g. a budget kept in sync
+    /// by [`crate::ratelimit::start_user_rate_limit_task`].
+    pub fn with_action_budget(mut self, budget: Arc<ActionBudget>) -> Self {
+        self.action_budget = Some(budget);
+        self
+    }
+
+    /// Waits for the IP weight and the account allowance of a request carrying `actions` orders
+    /// or cancels.
+    async fn throttle(&self, actions: usize) {
+        get_rest_rate_limiter()
+            .acquire(get_action_weight(actions))
+            .await;
+
+        if let Some(budget) = &self.action_budget {
+            budget.acquire(actions as u64).await;
+        }
+    }
+
+    pub fn get_builder(&self) -> Option<&BuilderConfig> {
+        self.builder.as_ref()
+    }
+
+    pub fn get_exchange_client(&self) -> &ExchangeClient {
+        &self.exchange_client
+    }
+
+    /// Latest price of `coin` from the feed
+    pub fn get_price(&self, coin: &str) -> Result<Price, Error> {
+        self.price_receiver
+            .borrow()
+            .get(coin)
+            .cloned()
+            .ok_or_else(|| anyhow!("No price for {coin}"))
+    }
+
+    /// Builds the IoC order [`ExchangeUtils::market_order`] would send, without sending it.
+    pub fn get_market_order(
+        &self,
+        coin: &str,
+        notional: f64,
+        slippage: f64,
+        is_buy: bool,
+    ) -> Result<OrderPayload, Error> {
+        get_market_order(
+            &self.price_receiver.borrow(),
+            self.guard.as_deref(),
+            coin,
+            notional,
+            slippage,
+            is_buy,
+        )
+    }
+
+    /// Buys or sells `notional` USD worth of `coin` with an IoC order priced `slippage`
+    /// (0.01 == 1%) through the current price, sized and rounded per the exchange's rules.
+    pub async fn market_order(
+        &self,
+        coin: &str,
+        notional: f64,
+        slippage: f64,
+        is_buy: bool,
+    ) -> Result<OrderOutcome, Error> {
+        let order = self.get_market_order(coin, notional, slippage, is_buy)?;
+
+        info!(
+            "Sending market order for {} {coin} at {}",
+            order.sz, order.limit_px
+        );
+        let response = self.submit(&order).await?;
+
+        Ok(OrderOutcome::from_response(&response))
+    }
+
+    /// Sends `order` with the helper's builder, if any.
+    pub async fn submit(&self, order: &OrderPayload) -> Result<ExchangeResponseStatus, Error> {
+        self.set_referrer().await;
+        self.throttle(1).await;
+
+        match &self.builder {
+            Some(builder) => {
+                order
+                    .submit_with_builder(&self.exchange_client, builder)
+                    .await
+            }
+            None => order.submit(&self.exchange_client).await,
+        }
+    }
+
+    /// Sets the referral code on the first call. Failing to set it, e.g. because the account
+    /// already has a referrer, never blocks the order.
+    async fn set_referrer(&self) {
+        let code = match &self.referral_code {
+            Some(code) => code,
+            None => return,
+        };
+
+        if self.referral_sent.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        match self.exchange_client.set_referrer(code.clone(), None).await {
+            Ok(ExchangeResponseStatus::Ok(_)) => info!("Set referral code {code}"),
+            Ok(ExchangeResponseStatus::Err(err)) => {
+                warn!("Couldn't set referral code {code}: {err}")
+            }
+            Err(err) => warn!("Couldn't set referral code {code}: {err:?}"),
+        }
+    }
+
+    pub fn get_vault_address(&self) -> Option<Address> {
+        self.exchange_client.vault_address
+    }
+
+    /// The account orders are placed for: the vault if one is set, otherwise the account set
+    /// with [`ExchangeUtils::with_account`], otherwise the wallet itself.
+    pub fn get_user(&self) -> Address {
+        get_order_owner(
+            self.exchange_client.wallet.address(),
+            self.account,
+            self.exchange_client.vault_address,
+        )
+    }
+
+    /// Cancels every open order of the coins in `coin_filter` (every coin if empty) in batches
+    /// spaced to respect the exchange's rate limits. Meant for shutdown paths, so it keeps going
+    /// when a batch fails and reports the failures at the end. `progress` receives the state after
+    /// every batch.
+    pub async fn cancel_all(
+        &self,
+        coin_filter: &[String],
+        progress: Option<UnboundedSender<CancelProgress>>,
+    ) -> Result<CancelProgress, Error> {
+        let orders = get_open_orders(&build_info_http_client()?, self.get_user()).await?;
+
+        let cancels = get_cancels(orders, coin_filter);
+
+        let mut report = CancelProgress {
+            total: cancels.len(),
+            ..Default::default()
+        };
+
+        info!("cancel_all: Canceling {} orders", report.total);
+
+        for (i, batch) in cancels.chunks(CANCEL_BATCH_SIZE).enumerate() {
+            if i > 0 {
+                tokio::time::sleep(CANCEL_BATCH_INTERVAL).await;
+            }
+
+            self.throttle(batch.len()).await;
+
+            let requests = batch
+                .iter()
+                .map(|(coin, oid)| ClientCancelRequest {
+                    asset: coin.clone(),
+                    oid: *oid,
+                })
+                .collect();
+
+            match self.exchange_client.bulk_cancel(requests, None).await {
+                Ok(ExchangeResponseStatus::Ok(response)) => {
+                    let statuses = response.data.map(|data| data.statuses).unwrap_or_default();
+                    report.add_statuses(batch.len(), &statuses);
+                }
+                Ok(ExchangeResponseStatus::Err(err)) => {
+                    report.failed += batch.len();
+                    report.errors.push(err);
+                }
+                Err(err) => {
+                    error!("cancel_all: Batch {i} failed: {err:?}");
+                    report.failed += batch.len();
+                    report.errors.push(err.to_string());
+                }
+            }
+
+            if let Some(progress) = &progress {
+                let _ = progress.send(report.clone());
+            }
+        }
+
+        info!(
+            "cancel_all: Canceled {} of {} orders",
+            report.canceled, report.total
+        );
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Address;
+    use hyperliquid_rust_sdk::ExchangeDataStatus;
+    use tokio::sync::watch;
+
+    use crate::{
+        account::OpenOrder,
+        exec::OracleGuard,
+        price_data::perps::NameToPerpQuoteMap,
+        types::{Meta, NameToPriceMap, Price, Symbol},
+    };
+
+    use super::{get_cancels, get_market_order, get_order_owner, CancelProgress};
+
+    fn order(coin: &str, oid: u64) -> OpenOrder {
+        OpenOrder {
+            coin: coin.to_string(),
+            side: "B".to_string(),
+            limit_px: 100.0,
+            sz: 1.0,
+            oid,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn market_orders_are_sized_from_the_feed() {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 4,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        };
+        let prices: NameToPriceMap = [(Symbol::from_api("ETH"), Price::new_perp(2000.0, meta))]
+            .into_iter()
+            .collect();
+
+        let order = get_market_order(&prices, None, "ETH", 100.0, 0.01, true).unwrap();
+        assert_eq!(
+            (order.limit_px.as_str(), order.sz.as_str()),
+            ("2020", "0.05")
+        );
+        assert!(get_market_order(&prices, None, "BTC", 100.0, 0.01, true).is_err());
+
+        let (_sender, receiver) = watch::channel(NameToPerpQuoteMap::new());
+        // Refuses without an oracle price to compare with
+        let guard = OracleGuard::new(receiver, 50.0, 20.0);
+        assert!(get_market_order(&prices, Some(&guard), "ETH", 100.0, 0.01, true).is_err());
+    }
+
+    #[test]
+    fn cancels_are_filtered_and_counted() {
+        let orders = vec![order("ETH", 1), order("BTC", 2), order("ETH", 3)];
+
+        assert_eq!(get_cancels(orders.clone(), &[]).len(), 3);
+        assert_eq!(
+            get_cancels(orders, &["ETH".to_string()]),
+            [("ETH".to_string(), 1), ("ETH".to_string(), 3)]
+        );
+
+        let mut progress = CancelProgress {
+            total: 3,
+            ..Default::default()
+        };
+        progress.add_statuses(
+            3,
+            &[
+                ExchangeDataStatus::Success,
+                ExchangeDataStatus::Error("Order was never placed".to_string()),
+            ],
+        );
+
+        assert_eq!((progress.canceled, progress.failed), (1, 2));
+        assert_eq!(progress.errors, ["Order was never placed"]);
+        assert!(progress.is_done());
+    }
+
+    #[test]
+    fn agents_cancel_the_orders_of_their_account() {
+        let agent = Address::repeat_byte(1);
+        let master = Address::repeat_byte(2);
+        let vault = Address::repeat_byte(3);
+
+        // The openOrders response of each user, an agent holds no orders of its own
+        let open_orders = |user: Address| {
+            if user == master {
+                vec![order("ETH", 1), order("BTC", 2)]
+            } else {
+                vec![]
+            }
+        };
+
+        let owner = get_order_owner(agent, Some(master), None);
+        assert_eq!(
+            get_cancels(open_orders(owner), &[]),
+            [("ETH".to_string(), 1), ("BTC".to_string(), 2)]
+        );
+
+        // Without the account, the agent's own empty book would be canceled
+        assert!(get_cancels(open_orders(get_order_owner(agent, None, None)), &[]).is_empty());
+        assert_eq!(get_order_owner(agent, Some(master), Some(vault)), vault);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Meta, Price, Symbol, MIN_ORDER_NOTIONAL};
+
+/// An order as the caller wants it, before any rounding.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct OrderIntent {
+    pub coin: String,
+    pub is_buy: bool,
+    pub limit_px: f64,
+    pub sz: f64,
+    pub reduce_only: bool,
+    /// Leverage the position is meant to use, unchecked if `None`
+    pub leverage: Option<u16>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OrderIssue {
+    UnknownCoin,
+    Delisted,
+    InvalidPrice,
+    InvalidSize,
+    /// The price has more significant figures or decimals than allowed, `rounded` would pass
+    TickSize {
+        rounded: f64,
+    },
+    /// The size has more than `sz_decimals` decimals, `rounded` would pass
+    SizeDecimals {
+        rounded: f64,
+    },
+    BelowMinNotional {
+        notional: f64,
+    },
+    /// Reduce-only order on a coin without a position, or on the same side as the position
+    ReduceOnlyNoPosition,
+    /// Reduce-only orders of the batch add up to more than the position
+    ReduceOnlyTooLarge {
+        position: f64,
+    },
+    LeverageTooHigh {
+        max_leverage: u16,
+    },
+}
+
+impl fmt::Display for OrderIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderIssue::UnknownCoin => write!(f, "unknown coin"),
+            OrderIssue::Delisted => write!(f, "coin is delisted"),
+            OrderIssue::InvalidPrice => write!(f, "price must be positive"),
+            OrderIssue::InvalidSize => write!(f, "size must be positive"),
+            OrderIssue::TickSize { rounded } => {
+                write!(f, "price not on tick, closest is {rounded}")
+            }
+            OrderIssue::SizeDecimals { rounded } => {
+                write!(f, "size has too many decimals, closest is {rounded}")
+            }
+            OrderIssue::BelowMinNotional { notional } => {
+                write!(
+                    f,
+                    "value {notional} is below the minimum of {MIN_ORDER_NOTIONAL}"
+                )
+            }
+            OrderIssue::ReduceOnlyNoPosition => {
+                write!(f, "reduce-only without a position to reduce")
+            }
+            OrderIssue::ReduceOnlyTooLarge { position } => {
+                write!(f, "reduce-only orders exceed the position of {position}")
+            }
+            OrderIssue::LeverageTooHigh { max_leverage } => {
+                write!(f, "leverage above the max of {max_leverage}")
+            }
+        }
+    }
+}
+
+/// Issues found with the order at `index` of the validated batch.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderDiagnostics {
+    pub index: usize,
+    pub coin: String,
+    pub issues: Vec<OrderIssue>,
+}
+
+impl OrderDiagnostics {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks orders against the exchange's rules without sending them, so a batch can be fixed or
+/// dropped as a whole instead of being partially rejected.
+///
+/// Reduce-only orders are only checked once positions are set with
+/// [`OrderValidator::with_positions`].
+#[derive(Clone, Debug, Default)]
+pub struct OrderValidator {
+    metas: HashMap<Symbol, Meta>,
+    /// Signed sizes, negative for shorts
+    positions: Option<HashMap<String, f64>>,
+}
+
+impl OrderValidator {
+    /// `metas` can come from [`crate::feeds::get_name_to_meta_map`].
+    pub fn new(metas: HashMap<Symbol, Meta>) -> Self {
+        OrderValidator {
+            metas,
+            positions: None,
+        }
+    }
+
+    /// Signed position sizes by coin, negative for shorts.
+    pub fn with_positions(mut self, positions: HashMap<String, f64>) -> Self {
+        self.positions = Some(positions);
+        self
+    }
+
+    /// One entry per order, in the order of `orders`. Reduce-only orders of the same coin are
+    /// checked together against the position.
+    pub fn validate(&self, orders: &[OrderIntent]) -> Vec<OrderDiagnostics> {
+        // Size still left to reduce per coin
+        let mut reducible = self.positions.clone().unwrap_or_default();
+
+        orders
+            .iter()
+            .enumerate()
+            .map(|(index, order)| OrderDiagnostics {
+                index,
+                coin: order.coin.clone(),
+                issues: self.get_issues(order, &mut reducible),
+            })
+            .collect()
+    }
+
+    /// Whether every order of `orders` passes
+    pub fn is_valid(&self, orders: &[OrderIntent]) -> bool {
+        self.validate(orders).iter().all(|d| d.is_valid())
+    }
+
+    fn get_issues(
+        &self,
+        order: &OrderIntent,
+        reducible: &mut HashMap<String, f64>,
+    ) -> Vec<OrderIssue> {
+        let meta = match self.metas.get(order.coin.as_str()) {
+            Some(meta) => meta,
+            None => return vec![OrderIssue::UnknownCoin],
+        };
+
+        let mut issues = vec![];
+
+        if let Meta::Perp {
+            is_delisted: Some(true),
+            ..
+        } = meta
+        {
+            issues.push(OrderIssue::Delisted);
+        }
+
+        let price = Price::from_meta(order.limit_px, meta);
+
+        if !order.limit_px.is_finite() || order.limit_px <= 0.0 {
+            issues.push(OrderIssue::InvalidPrice);
+        } else {
+            let rounded = price.get_true_price_for_asset(order.limit_px);
+
+            if rounded != order.limit_px {
+                issues.push(OrderIssue::TickSize { rounded });
+            }
+        }
+
+        if !order.sz.is_finite() || order.sz <= 0.0 {
+            issues.push(OrderIssue::InvalidSize);
+        } else {
+            let rounded = price.get_true_size(order.sz);
+
+            if rounded != order.sz {
+                issues.push(OrderIssue::SizeDecimals { rounded });
+            }
+        }
+
+        let notional = order.limit_px * order.sz;
+        if !order.reduce_only && notional < MIN_ORDER_NOTIONAL {
+            issues.push(OrderIssue::BelowMinNotional { notional });
+        }
+
+        if let Some(leverage) = order.leverage {
+            let max_leverage = meta.get_max_leverage();
+
+            if leverage > max_leverage {
+                issues.push(OrderIssue::LeverageTooHigh { max_leverage });
+            }
+        }
+
+        if order.reduce_only && self.positions.is_some() {
+            let position = self
+                .positions
+                .as_ref()
+                .and_then(|positions| positions.get(&order.coin))
+                .copied()
+                .unwrap_or(0.0);
+            let left = reducible.entry(order.coin.clone()).or_insert(0.0);
+
+            // A buy reduces a short and the other way around
+            if position == 0.0 || (position < 0.0) != order.is_buy {
+                issues.push(OrderIssue::ReduceOnlyNoPosition);
+            } else if order.sz > left.abs() {
+                issues.push(OrderIssue::ReduceOnlyTooLarge { position });
+            } else {
+                *left -= order.sz.copysign(*left);
+            }
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::types::{Meta, Symbol};
+
+    use super::{OrderIntent, OrderIssue, OrderValidator};
+
+    fn validator() -> OrderValidator {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 4,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        };
+
+        OrderValidator::new(HashMap::from([(Symbol::from_api("ETH"), meta)]))
+            .with_positions(HashMap::from([("ETH".to_string(), -1.0)]))
+    }
+
+    fn order(limit_px: f64, sz: f64, reduce_only: bool) -> OrderIntent {
+        OrderIntent {
+            coin: "ETH".to_string(),
+            is_buy: true,
+            limit_px,
+            sz,
+            reduce_only,
+            leverage: None,
+        }
+    }
+
+    #[test]
+    fn reports_issues_per_order() {
+        let diagnostics = validator().validate(&[
+            order(2000.5, 0.01, false),
+            order(2000.56, 0.00001, false),
+            order(2000.0, 0.6, true),
+            order(2000.0, 0.6, true),
+            OrderIntent {
+                coin: "BTC".to_string(),
+                ..order(2000.0, 1.0, false)
+            },
+            OrderIntent {
+                leverage: Some(50),
+                ..order(2000.0, 1.0, false)
+            },
+        ]);
+
+        assert!(diagnostics[0].is_valid());
+        assert_eq!(
+            diagnostics[1].issues,
+            vec![
+                OrderIssue::TickSize { rounded: 2000.6 },
+                OrderIssue::SizeDecimals { rounded: 0.0 },
+                OrderIssue::BelowMinNotional {
+                    notional: 2000.56 * 0.00001
+                },
+            ]
+        );
+        // The short of 1.0 only covers the first one
+        assert!(diagnostics[2].is_valid());
+        assert_eq!(
+            diagnostics[3].issues,
+            vec![OrderIssue::ReduceOnlyTooLarge { position: -1.0 }]
+        );
+        assert_eq!(diagnostics[4].issues, vec![OrderIssue::UnknownCoin]);
+        assert_eq!(
+            diagnostics[5].issues,
+            vec![OrderIssue::LeverageTooHigh { max_leverage: 25 }]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, Utc};
+use object_store::{
+    aws::AmazonS3Builder, gcp::GoogleCloudStorageBuilder, local::LocalFileSystem,
+    path::Path as ObjectPath, ObjectStore, PutPayload,
+};
+use tokio::{task::JoinHandle, time::interval};
+use tracing::{error, info};
+
+use crate::recorder::is_recording_to;
+
+#[derive(Clone, Debug)]
+pub enum ArchiveStore {
+    /// Credentials and region come from the usual `AWS_*` environment variables
+    S3 { bucket: String },
+    /// Credentials come from `GOOGLE_SERVICE_ACCOUNT` or the other `GOOGLE_*` environment
+    /// variables
+    Gcs { bucket: String },
+    /// Another directory, e.g. a mounted network drive
+    Local { dir: PathBuf },
+}
+
+#[derive(Clone, Debug)]
+pub struct ArchiveConfig {
+    /// Where the recorder writes its files, see [`crate::recorder::RecorderConfig::with_rotation`]
+    pub dir: PathBuf,
+    pub store: ArchiveStore,
+    /// Objects are stored as `{prefix}/{YYYY-MM-DD}/{file name}`, the date being the day the file
+    /// was last written to
+    pub prefix: String,
+    /// Files are considered complete once they weren't written to for this long. Needs to be
+    /// longer than the recorder's flush interval.
+    pub min_age: Duration,
+    pub scan_interval: Duration,
+    /// Removes the local file once it's uploaded
+    pub delete_after_upload: bool,
+}
+
+impl ArchiveConfig {
+    pub fn new(dir: impl Into<PathBuf>, store: ArchiveStore) -> Self {
+        ArchiveConfig {
+            dir: dir.into(),
+            store,
+            prefix: "recordings".to_string(),
+            min_age: Duration::from_secs(5 * 60),
+            scan_interval: Duration::from_secs(60),
+            delete_after_upload: true,
+        }
+    }
+}
+
+/// The object key of a file named `file_name` last written to at `modified`
+pub fn get_object_key(prefix: &str, file_name: &str, modified: DateTime<Utc>) -> String {
+    let date = modified.format("%Y-%m-%d");
+    let prefix = prefix.trim_matches('/');
+
+    if prefix.is_empty() {
+        format!("{date}/{file_name}")
+    } else {
+        format!("{prefix}/{date}/{file_name}")
+    }
+}
+
+/// Uploads completed recordings to object storage so long running capture nodes don't fill their
+/// disks.
+pub struct Archiver {
+    store: Arc<dyn ObjectStore>,
+    config: ArchiveConfig,
+}
+
+impl Archiver {
+    pub fn new(config: ArchiveConfig) -> Result<Self, Error> {
+        let store: Arc<dyn ObjectStore> = match &config.store {
+            ArchiveStore::S3 { bucket } => Arc::new(
+                AmazonS3Builder::from_env()
+                    .with_bucket_name(bucket)
+                    .build()?,
+            ),
+            ArchiveStore::Gcs { bucket } => Arc::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_bucket_name(bucket)
+                    .build()?,
+            ),
+            ArchiveStore::Local { dir } => {
+                fs::create_dir_all(dir)?;
+                Arc::new(LocalFileSystem::new_with_prefix(dir)?)
+            }
+        };
+
+        Ok(Archiver { store, config })
+    }
+
+    pub fn get_config(&self) -> &ArchiveConfig {
+        &self.config
+    }
+
+    /// Files of the recording dir that weren't written to for `min_age`, oldest first. Files a
+    /// recorder of this process still writes to are left out, see [`is_recording_to`].
+    pub fn get_completed_files(&self) -> Result<Vec<(PathBuf, SystemTime)>, Error> {
+        let now = SystemTime::now();
+        let mut files = vec![];
+
+        for entry in fs::read_dir(&self.config.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+
+            if !metadata.is_file() || is_recording_to(&entry.path()) {
+                continue;
+            }
+
+            let modified = metadata.modified()?;
+            let age = now.duration_since(modified).unwrap_or_default();
+
+            if age >= self.config.min_age {
+                files.push((entry.path(), modified));
+            }
+        }
+
+        files.sort_by_key(|(_, modified)| *modified);
+
+        Ok(files)
+    }
+
+    /// Uploads the file at `path` and returns its object key
+    pub async fn upload(&self, path: &Path, modified: SystemTime) -> Result<String, Error> {
+        let file_name = path
+            .file_name()
+            .context("Not a file")?
+            .to_string_lossy()
+            .to_string();
+        let key = get_object_key(&self.config.prefix, &file_name, modified.into());
+
+        let bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Couldn't read {path:?}"))?;
+
+        self.store
+            .put(&ObjectPath::from(key.as_str()), PutPayload::from(bytes))
+            .await
+            .with_context(|| format!("Couldn't upload {path:?} to {key}"))?;
+
+        if self.config.delete_after_upload {
+            tokio::fs::remove_file(path).await?;
+        }
+
+        Ok(key)
+    }
+
+    /// Uploads every completed file, returns how many were uploaded
+    pub async fn archive_completed(&self) -> Result<usize, Error> {
+        let mut uploaded = 0;
+
+        for (path, modified) in self.get_completed_files()? {
+            match self.upload(&path, modified).await {
+                Ok(key) => {
+                    info!("Archived {path:?} to {key}");
+                    uploaded += 1;
+                }
+                Err(err) => error!("Couldn't archive {path:?}: {err:?}"),
+            }
+        }
+
+        Ok(uploaded)
+    }
+}
+
+/// Archives the completed files every `scan_interval`. Runs until the returned handle is aborted.
+/// Without `delete_after_upload`, files are uploaded again on every scan.
+pub fn start_archive_task(archiver: Archiver) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut scan_interval = interval(archiver.get_config().scan_interval);
+
+        info!("archive_task: Starting...");
+
+        loop {
+            scan_interval.tick().await;
+
+            if let Err(err) = archiver.archive_completed().await {
+                error!("archive_task: Error while scanning: {err:?}");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use crate::recorder::{Record, RecordFormat, RecordWriter};
+
+    use super::{get_object_key, ArchiveConfig, ArchiveStore, Archiver};
+
+    #[test]
+    fn keys_are_grouped_by_date() {
+        let modified = Utc.with_ymd_and_hms(2024, 6, 10, 6, 13, 20).unwrap();
+
+        assert_eq!(
+            get_object_key("/recordings/", "ETH_books_20240610T000000.jsonl", modified),
+            "recordings/2024-06-10/ETH_books_20240610T000000.jsonl"
+        );
+        assert_eq!(
+            get_object_key("", "a.jsonl", modified),
+            "2024-06-10/a.jsonl"
+        );
+    }
+
+    #[tokio::test]
+    async fn uploads_and_removes_completed_files() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("hl_archive_{}", std::process::id()));
+        let dir = root.join("recordings");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("ETH.jsonl"), "{}\n")?;
+        let modified: DateTime<Utc> = fs::metadata(dir.join("ETH.jsonl"))?.modified()?.into();
+
+        let mut config = ArchiveConfig::new(
+            &dir,
+            ArchiveStore::Local {
+                dir: root.join("store"),
+            },
+        );
+        config.min_age = Duration::ZERO;
+
+        let archiver = Archiver::new(config)?;
+        assert_eq!(archiver.archive_completed().await?, 1);
+        assert!(!dir.join("ETH.jsonl").exists());
+
+        let date = modified.format("%Y-%m-%d");
+        assert!(root
+            .join(format!("store/recordings/{date}/ETH.jsonl"))
+            .exists());
+
+        fs::remove_dir_all(&root)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn files_still_recorded_to_are_skipped() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("hl_archive_open_{}", std::process::id()));
+        let dir = root.join("recordings");
+        fs::create_dir_all(&dir)?;
+
+        let mut writer =
+            RecordWriter::<u64>::create(&dir.join("ETH.jsonl"), RecordFormat::JsonLines)?;
+        writer.write(&Record { time: 1, data: 1 })?;
+        writer.flush()?;
+
+        let mut config = ArchiveConfig::new(
+            &dir,
+            ArchiveStore::Local {
+                dir: root.join("store"),
+            },
+        );
+        config.min_age = Duration::ZERO;
+        let archiver = Archiver::new(config)?;
+
+        assert_eq!(archiver.archive_completed().await?, 0);
+        assert!(dir.join("ETH.jsonl").exists());
+
+        writer.finish()?;
+        assert_eq!(archiver.archive_completed().await?, 1);
+
+        fs::remove_dir_all(&root)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use anyhow::{bail, Error};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{interval, sleep},
+};
+use tracing::{error, info};
+
+use crate::{
+    events::{emit, ConnectionEvents, FeedEventKind},
+    prices::build_info_http_client,
+    trades::{Trade, TradesStream},
+    types::{NameToOrderbookMap, Orderbook},
+};
+
+#[derive(Clone, Debug)]
+pub struct ClickHouseConfig {
+    /// The HTTP interface, e.g. `http://localhost:8123`
+    pub url: String,
+    pub database: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Needs the columns of [`Trade`]
+    pub trades_table: String,
+    /// Needs the columns of [`BookRow`]
+    pub books_table: String,
+    /// Flushes early once this many rows are buffered
+    pub max_batch: usize,
+    pub flush_interval: Duration,
+}
+
+impl ClickHouseConfig {
+    pub fn new(url: &str, database: &str) -> Self {
+        ClickHouseConfig {
+            url: url.to_string(),
+            database: database.to_string(),
+            user: None,
+            password: None,
+            trades_table: "trades".to_string(),
+            books_table: "books".to_string(),
+            max_batch: 10_000,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+
+    pub fn with_credentials(mut self, user: &str, password: &str) -> Self {
+        self.user = Some(user.to_string());
+        self.password = Some(password.to_string());
+        self
+    }
+}
+
+/// A book snapshot as a ClickHouse row, the levels as parallel arrays best first, e.g. for
+/// `CREATE TABLE books (time UInt64, coin LowCardinality(String), bid_px Array(Float64),
+/// bid_sz Array(Float64), ask_px Array(Float64), ask_sz Array(Float64)) ENGINE = MergeTree
+/// ORDER BY (coin, time)`.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct BookRow {
+    pub time: u64,
+    pub coin: String,
+    pub bid_px: Vec<f64>,
+    pub bid_sz: Vec<f64>,
+    pub ask_px: Vec<f64>,
+    pub ask_sz: Vec<f64>,
+}
+
+impl BookRow {
+    /// The first `levels` levels of each side of `book`
+    pub fn from_book(book: &Orderbook, levels: usize) -> Self {
+        let bids = book.bids.iter().take(levels);
+        let asks = book.asks.iter().take(levels);
+
+        BookRow {
+            time: book.time,
+            coin: book.coin.clone(),
+            bid_px: bids.clone().map(|level| level.price).collect(),
+            bid_sz: bids.map(|level| level.size).collect(),
+            ask_px: asks.clone().map(|level| level.price).collect(),
+            ask_sz: asks.map(|level| level.size).collect(),
+        }
+    }
+}
+
+/// Inserts rows through ClickHouse's HTTP interface as `JSONEachRow`.
+#[derive(Clone)]
+pub struct ClickHouseWriter {
+    client: Client,
+    config: ClickHouseConfig,
+}
+
+impl ClickHouseWriter {
+    pub fn new(config: ClickHouseConfig) -> Result<Self, Error> {
+        Ok(ClickHouseWriter {
+            client: build_info_http_client()?,
+            config,
+        })
+    }
+
+    pub fn get_config(&self) -> &ClickHouseConfig {
+        &self.config
+    }
+
+    pub async fn insert<T: Serialize>(&self, table: &str, rows: &[T]) -> Result<(), Error> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut body = Vec::new();
+        for row in rows {
+            serde_json::to_writer(&mut body, row)?;
+            body.push(b'\n');
+        }
+
+        let query = format!(
+            "INSERT INTO {}.{table} FORMAT JSONEachRow",
+            self.config.database
+        );
+
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .query(&[("query", query)])
+            .body(body);
+
+        if let Some(user) = &self.config.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.config.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            bail!(
+                "ClickHouse rejected {} rows for {table} with {}: {}",
+                rows.len(),
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Inserts `rows` into `table` and clears them. They're dropped if the insert fails, so an outage
+/// of the database doesn't grow the batch unbounded.
+async fn flush_rows<T: Serialize>(writer: &ClickHouseWriter, table: &str, rows: &mut Vec<T>) {
+    if let Err(err) = writer.insert(table, rows).await {
+        error!("clickhouse: Dropping {} rows: {err:?}", rows.len());
+    }
+
+    rows.clear();
+}
+
+/// Streams the trades of `coins` into the trades table, resubscribing on errors. Runs until the
+/// returned handle is aborted.
+pub fn start_clickhouse_trades_task(
+    writer: ClickHouseWriter,
+    coins: Vec<String>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let table = writer.get_config().trades_table.clone();
+        let max_batch = writer.get_config().max_batch;
+        let mut flush_interval = interval(writer.get_config().flush_interval);
+        let mut rows: Vec<Trade> = vec![];
+
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("clickhouse_trades_task: Starting...");
+
+            let mut trades_stream = match TradesStream::new(&coins).await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Error while getting TradesStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("clickhouse_trades_task");
+
+            let err = loop {
+                tokio::select! {
+                    trades = trades_stream.get_next_trades() => match trades {
+                        Ok(Some(trades)) => rows.extend(trades),
+                        Ok(None) => continue,
+                        Err(err) => break err,
+                    },
+                    _ = flush_interval.tick() => flush_rows(&writer, &table, &mut rows).await,
+                }
+
+                if rows.len() >= max_batch {
+                    flush_rows(&writer, &table, &mut rows).await;
+                }
+            };
+
+            error!("clickhouse_trades_task: Error: {err:?}");
+            emit(
+                "clickhouse_trades_task",
+                FeedEventKind::SubscriptionDropped {
+                    reason: format!("{err:?}"),
+                },
+            );
+            info!("clickhouse_trades_task: Resetting...");
+
+            let _ = trades_stream.unsub().await;
+            sleep(Duration::from_secs(5)).await;
+        }
+    })
+}
+
+/// Inserts the first `levels` levels of every book of `receiver` each `sample_interval`, until
+/// every sender is dropped.
+pub fn start_clickhouse_books_task(
+    writer: ClickHouseWriter,
+    receiver: watch::Receiver<NameToOrderbookMap>,
+    levels: usize,
+    sample_interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let table = writer.get_config().books_table.clone();
+        let max_batch = writer.get_config().max_batch;
+        let mut sample_interval = interval(sample_interval);
+        let mut flush_interval = interval(writer.get_config().flush_interval);
+        let mut rows: Vec<BookRow> = vec![];
+        let mut last_times = std::collections::HashMap::new();
+
+        info!("clickhouse_books_task: Starting...");
+
+        loop {
+            tokio::select! {
+                _ = sample_interval.tick() => {
+                    // Books that didn't change since the last sample aren't written again
+                    for (coin, book) in receiver.borrow().iter() {
+                        if last_times.insert(coin.clone(), book.time) != Some(book.time) {
+                            rows.push(BookRow::from_book(book, levels));
+                        }
+                    }
+
+                    if rows.len() >= max_batch {
+                        flush_rows(&writer, &table, &mut rows).await;
+                    }
+                }
+                _ = flush_interval.tick() => flush_rows(&writer, &table, &mut rows).await,
+            }
+
+            if receiver.has_changed().is_err() {
+                flush_rows(&writer, &table, &mut rows).await;
+                info!("clickhouse_books_task: Feed closed, stopping...");
+                return;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{BookLevel, Orderbook};
+
+    use super::BookRow;
+
+    #[test]
+    fn book_rows_keep_the_top_levels() {
+        let level = |price: f64, size: f64| BookLevel {
+            price,
+            size,
+            orders: 1,
+        };
+        let book = Orderbook {
+            coin: "ETH".to_string(),
+            time: 1,
+            bids: vec![level(99.0, 1.0), level(98.0, 2.0)],
+            asks: vec![level(101.0, 3.0)],
+        };
+
+        let row = BookRow::from_book(&book, 1);
+
+        assert_eq!(row.bid_px, vec![99.0]);
+        assert_eq!(row.bid_sz, vec![1.0]);
+        assert_eq!(row.ask_px, vec![101.0]);
+        assert_eq!(row.ask_sz, vec![3.0]);
+    }
+}
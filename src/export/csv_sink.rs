@@ -0,0 +1,396 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fs::{self, File},
+    hash::{BuildHasher, Hash},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{bail, Error};
+use chrono::Utc;
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{interval, Instant},
+};
+use tracing::{error, info};
+
+use crate::types::{Orderbook, Price};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsvLayout {
+    /// One file per coin with a row per sample
+    PerCoin,
+    /// One file with a column per coin and field. The columns are the configured coins, or every
+    /// coin seen so far without a coin filter, in which case a new coin starts a new file with
+    /// its columns added.
+    Wide,
+}
+
+#[derive(Clone, Debug)]
+pub struct CsvSinkConfig {
+    pub dir: PathBuf,
+    /// File names are `{prefix}_{coin or "wide"}_{file opening time, to the ms}.csv`
+    pub prefix: String,
+    pub layout: CsvLayout,
+    /// Only these coins are written, all of them if `None`
+    pub coins: Option<Vec<String>>,
+    /// How often a row is sampled from the feed
+    pub sample_interval: Duration,
+    pub flush_interval: Duration,
+    /// How often new files are started
+    pub rotate_every: Duration,
+}
+
+impl Default for CsvSinkConfig {
+    fn default() -> Self {
+        CsvSinkConfig {
+            dir: PathBuf::from("."),
+            prefix: "prices".to_string(),
+            layout: CsvLayout::Wide,
+            coins: None,
+            sample_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(10),
+            rotate_every: Duration::from_secs(60 * 60 * 24),
+        }
+    }
+}
+
+/// A value of one of the shared maps that can be written as CSV columns.
+pub trait CsvRecord {
+    fn columns() -> &'static [&'static str];
+
+    /// One value per column, empty strings for missing values
+    fn values(&self) -> Vec<String>;
+}
+
+impl CsvRecord for Price {
+    fn columns() -> &'static [&'static str] {
+        &["price"]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![self.get_value().to_string()]
+    }
+}
+
+/// Books are written as their BBO
+impl CsvRecord for Orderbook {
+    fn columns() -> &'static [&'static str] {
+        &["bid_px", "bid_sz", "ask_px", "ask_sz"]
+    }
+
+    fn values(&self) -> Vec<String> {
+        let bbo = self.get_bbo();
+        let level = |level: Option<f64>| level.map(|v| v.to_string()).unwrap_or_default();
+
+        vec![
+            level(bbo.bid.as_ref().map(|bid| bid.price)),
+            level(bbo.bid.as_ref().map(|bid| bid.size)),
+            level(bbo.ask.as_ref().map(|ask| ask.price)),
+            level(bbo.ask.as_ref().map(|ask| ask.size)),
+        ]
+    }
+}
+
+struct CsvFiles {
+    writers: HashMap<String, BufWriter<File>>,
+    /// Coins of the wide file, in column order
+    wide_coins: Vec<String>,
+    opened_at: Instant,
+}
+
+fn open_csv(config: &CsvSinkConfig, name: &str, header: &str) -> Result<BufWriter<File>, Error> {
+    let file_name = format!(
+        "{}_{}_{}.csv",
+        config.prefix,
+        name.replace('/', "-"),
+        Utc::now().format("%Y%m%dT%H%M%S%3f")
+    );
+
+    let mut writer = BufWriter::new(File::create(config.dir.join(file_name))?);
+    writeln!(writer, "{header}")?;
+
+    Ok(writer)
+}
+
+fn is_selected(config: &CsvSinkConfig, coin: &str) -> bool {
+    match &config.coins {
+        Some(coins) => coins.iter().any(|c| c == coin),
+        None => true,
+    }
+}
+
+/// Name of a map key, the maps being keyed by `String` or [`crate::types::Symbol`]
+fn get_coin<K: Borrow<str>>(coin: &K) -> &str {
+    coin.borrow()
+}
+
+/// Columns of the wide file for `map`, from the config when it lists the coins
+fn get_wide_coins<K: Borrow<str>, T, S>(
+    config: &CsvSinkConfig,
+    files: &CsvFiles,
+    map: &HashMap<K, T, S>,
+) -> Vec<String> {
+    let mut coins = match &config.coins {
+        Some(coins) => coins.clone(),
+        None => files
+            .wide_coins
+            .iter()
+            .cloned()
+            .chain(map.keys().map(|coin| get_coin(coin).to_string()))
+            .collect(),
+    };
+    coins.sort();
+    coins.dedup();
+
+    coins
+}
+
+/// The values of `value`, erroring instead of writing a misaligned row if they don't match the
+/// columns.
+fn get_values<T: CsvRecord>(value: &T) -> Result<Vec<String>, Error> {
+    let values = value.values();
+
+    if values.len() != T::columns().len() {
+        bail!(
+            "Got {} values for the {} columns {:?}",
+            values.len(),
+            T::columns().len(),
+            T::columns()
+        );
+    }
+
+    Ok(values)
+}
+
+fn write_sample<K: Borrow<str> + Eq + Hash, T: CsvRecord, S: BuildHasher>(
+    config: &CsvSinkConfig,
+    files: &mut CsvFiles,
+    map: &HashMap<K, T, S>,
+) -> Result<(), Error> {
+    let time = Utc::now().timestamp_millis();
+
+    match config.layout {
+        CsvLayout::PerCoin => {
+            for (coin, value) in map
+                .iter()
+                .map(|(coin, value)| (get_coin(coin), value))
+                .filter(|(coin, _)| is_selected(config, coin))
+            {
+                if !files.writers.contains_key(coin) {
+                    let header = format!("time,{}", T::columns().join(","));
+                    let writer = open_csv(config, coin, &header)?;
+                    files.writers.insert(coin.to_string(), writer);
+                }
+
+                if let Some(writer) = files.writers.get_mut(coin) {
+                    writeln!(writer, "{time},{}", get_values(value)?.join(","))?;
+                }
+            }
+        }
+        CsvLayout::Wide => {
+            let coins = get_wide_coins(config, files, map);
+
+            if files.writers.is_empty() || coins != files.wide_coins {
+                if let Some(mut writer) = files.writers.remove("wide") {
+                    writer.flush()?;
+                }
+
+                let header = std::iter::once("time".to_string())
+                    .chain(coins.iter().flat_map(|coin| {
+                        T::columns()
+                            .iter()
+                            .map(move |column| format!("{coin}_{column}"))
+                    }))
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                let writer = open_csv(config, "wide", &header)?;
+                files.writers.insert("wide".to_string(), writer);
+                files.wide_coins = coins;
+            }
+
+            let mut row = vec![time.to_string()];
+            for coin in files.wide_coins.iter() {
+                match map.get(coin.as_str()) {
+                    Some(value) => row.extend(get_values(value)?),
+                    None => row.extend(vec![String::new(); T::columns().len()]),
+                }
+            }
+            let row = row.join(",");
+
+            if let Some(writer) = files.writers.get_mut("wide") {
+                writeln!(writer, "{row}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn flush_all(files: &mut CsvFiles) -> Result<(), Error> {
+    for writer in files.writers.values_mut() {
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Samples `receiver` into CSV files until every sender is dropped.
+pub fn start_csv_sink_task<K, T, S>(
+    receiver: watch::Receiver<HashMap<K, T, S>>,
+    config: CsvSinkConfig,
+) -> anyhow::Result<JoinHandle<()>>
+where
+    K: Borrow<str> + Eq + Hash + Send + Sync + 'static,
+    T: CsvRecord + Send + Sync + 'static,
+    S: BuildHasher + Send + Sync + 'static,
+{
+    fs::create_dir_all(&config.dir)?;
+
+    Ok(tokio::spawn(async move {
+        let mut files = CsvFiles {
+            writers: HashMap::new(),
+            wide_coins: vec![],
+            opened_at: Instant::now(),
+        };
+
+        let mut sample_interval = interval(config.sample_interval);
+        let mut flush_interval = interval(config.flush_interval);
+
+        info!("csv_sink_task: Writing to {:?}", config.dir);
+
+        loop {
+            tokio::select! {
+                _ = sample_interval.tick() => {
+                    if files.opened_at.elapsed() >= config.rotate_every {
+                        if let Err(err) = flush_all(&mut files) {
+                            error!("csv_sink_task: Error while flushing: {err:?}");
+                        }
+
+                        files.writers.clear();
+                        files.opened_at = Instant::now();
+                    }
+
+                    let res = write_sample(&config, &mut files, &receiver.borrow());
+                    if let Err(err) = res {
+                        error!("csv_sink_task: Error while writing: {err:?}");
+                    }
+                }
+                _ = flush_interval.tick() => {
+                    if let Err(err) = flush_all(&mut files) {
+                        error!("csv_sink_task: Error while flushing: {err:?}");
+                    }
+                }
+            }
+
+            if receiver.has_changed().is_err() {
+                let _ = flush_all(&mut files);
+                info!("csv_sink_task: Feed closed, stopping...");
+                return;
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, time::Duration};
+
+    use tokio::time::Instant;
+
+    use super::{write_sample, CsvFiles, CsvLayout, CsvRecord, CsvSinkConfig};
+
+    struct Mid(f64);
+
+    impl CsvRecord for Mid {
+        fn columns() -> &'static [&'static str] {
+            &["mid"]
+        }
+
+        fn values(&self) -> Vec<String> {
+            vec![self.0.to_string()]
+        }
+    }
+
+    struct Broken;
+
+    impl CsvRecord for Broken {
+        fn columns() -> &'static [&'static str] {
+            &["bid", "ask"]
+        }
+
+        fn values(&self) -> Vec<String> {
+            vec!["1.0".to_string()]
+        }
+    }
+
+    fn new_files() -> CsvFiles {
+        CsvFiles {
+            writers: HashMap::new(),
+            wide_coins: vec![],
+            opened_at: Instant::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn wide_columns_follow_the_coins() {
+        let dir = std::env::temp_dir().join(format!("hl_csv_sink_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let config = CsvSinkConfig {
+            dir: dir.clone(),
+            layout: CsvLayout::Wide,
+            ..CsvSinkConfig::default()
+        };
+        let mut files = new_files();
+
+        let eth = HashMap::from([("ETH".to_string(), Mid(2000.0))]);
+        write_sample(&config, &mut files, &eth).unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        let both = HashMap::from([
+            ("ETH".to_string(), Mid(2001.0)),
+            ("BTC".to_string(), Mid(60000.0)),
+        ]);
+        write_sample(&config, &mut files, &both).unwrap();
+        write_sample(&config, &mut files, &eth).unwrap();
+        files.writers.clear();
+
+        let mut paths: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        let contents: Vec<String> = paths
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+
+        assert_eq!(contents.len(), 2);
+        assert!(contents[0].starts_with("time,ETH_mid\n"));
+
+        let lines: Vec<&str> = contents[1].lines().collect();
+        assert_eq!(lines[0], "time,BTC_mid,ETH_mid");
+        assert!(lines[1].ends_with(",60000,2001"));
+        // BTC went missing, its column stays in place
+        assert!(lines[2].ends_with(",,2000"));
+
+        let fixed = CsvSinkConfig {
+            coins: Some(vec!["SOL".to_string(), "ETH".to_string()]),
+            ..config.clone()
+        };
+        assert_eq!(
+            super::get_wide_coins(&fixed, &new_files(), &both),
+            ["ETH", "SOL"]
+        );
+
+        let broken = HashMap::from([("ETH".to_string(), Broken)]);
+        assert!(write_sample(&config, &mut new_files(), &broken).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Error;
+use polars::{df, frame::DataFrame};
+
+use crate::{
+    candles::{Candle, CandleBuffer},
+    history::PriceHistory,
+};
+
+impl PriceHistory {
+    /// Long format frame with the columns `coin`, `time` and `price`.
+    pub fn to_dataframe(&self) -> Result<DataFrame, Error> {
+        let mut coins: Vec<&str> = vec![];
+        let mut times: Vec<i64> = vec![];
+        let mut prices: Vec<f64> = vec![];
+
+        for (coin, points) in self.iter() {
+            for point in points {
+                coins.push(coin.as_str());
+                times.push(point.time);
+                prices.push(point.price);
+            }
+        }
+
+        Ok(df!(
+            "coin" => coins,
+            "time" => times,
+            "price" => prices,
+        )?)
+    }
+}
+
+impl CandleBuffer {
+    /// Same columns as [`CandleBuffer::to_record_batch`].
+    pub fn to_dataframe(&self) -> Result<DataFrame, Error> {
+        let candles: Vec<&Candle> = self.iter().flat_map(|(_, candles)| candles).collect();
+
+        Ok(df!(
+            "coin" => candles.iter().map(|c| c.coin.as_str()).collect::<Vec<_>>(),
+            "interval" => candles.iter().map(|c| c.interval.as_str()).collect::<Vec<_>>(),
+            "open_time" => candles.iter().map(|c| c.open_time).collect::<Vec<_>>(),
+            "close_time" => candles.iter().map(|c| c.close_time).collect::<Vec<_>>(),
+            "open" => candles.iter().map(|c| c.open).collect::<Vec<_>>(),
+            "high" => candles.iter().map(|c| c.high).collect::<Vec<_>>(),
+            "low" => candles.iter().map(|c| c.low).collect::<Vec<_>>(),
+            "close" => candles.iter().map(|c| c.close).collect::<Vec<_>>(),
+            "volume" => candles.iter().map(|c| c.volume).collect::<Vec<_>>(),
+            "trades" => candles.iter().map(|c| c.trades).collect::<Vec<_>>(),
+        )?)
+    }
+}
@@ -0,0 +1,159 @@
+use std::{fs, path::Path};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use serde_json::json;
+
+use crate::types::Meta;
+#[cfg(feature = "live")]
+use crate::{
+    price_data::{perps::PerpsMeta, spot::SpotMeta},
+    prices::{build_info_http_client, post_info},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetaFormat {
+    Json,
+    Csv,
+}
+
+/// One perp or spot pair of the universe, flattened for diffing between days.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MetaRow {
+    /// "perp" or "spot"
+    pub kind: String,
+    /// Name used by the API, e.g. "ETH" or "@107"
+    pub name: String,
+    /// "TOKEN1/TOKEN2" for spot pairs, the name for perps
+    pub display_name: String,
+    /// Asset id used by the exchange endpoint
+    pub asset: u32,
+    pub sz_decimals: u16,
+    pub max_leverage: u16,
+    pub only_isolated: bool,
+    pub is_delisted: bool,
+}
+
+impl From<&Meta> for MetaRow {
+    fn from(meta: &Meta) -> Self {
+        let (kind, display_name, is_delisted) = match meta {
+            Meta::Spot { quote, base, .. } => {
+                ("spot", format!("{}/{}", quote.name, base.name), false)
+            }
+            Meta::Perp {
+                name, is_delisted, ..
+            } => ("perp", name.clone(), is_delisted.unwrap_or(false)),
+        };
+
+        MetaRow {
+            kind: kind.to_string(),
+            name: meta.get_name().clone(),
+            display_name,
+            asset: meta.get_asset_index(),
+            sz_decimals: meta.get_sz_decimals(),
+            max_leverage: meta.get_max_leverage(),
+            only_isolated: meta.is_only_isolated(),
+            is_delisted,
+        }
+    }
+}
+
+const CSV_HEADER: &str =
+    "kind,name,display_name,asset,sz_decimals,max_leverage,only_isolated,is_delisted";
+
+/// Rows of `metas` sorted by asset id, so exports of the same universe are identical.
+pub fn get_meta_rows(metas: &[Meta]) -> Vec<MetaRow> {
+    let mut rows: Vec<MetaRow> = metas.iter().map(MetaRow::from).collect();
+    rows.sort_by_key(|row| row.asset);
+
+    rows
+}
+
+pub fn format_meta(metas: &[Meta], format: MetaFormat) -> Result<String, Error> {
+    let rows = get_meta_rows(metas);
+
+    Ok(match format {
+        MetaFormat::Json => serde_json::to_string_pretty(&rows)?,
+        MetaFormat::Csv => {
+            let mut csv = format!("{CSV_HEADER}\n");
+
+            for row in rows {
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{},{},{}\n",
+                    row.kind,
+                    row.name,
+                    row.display_name,
+                    row.asset,
+                    row.sz_decimals,
+                    row.max_leverage,
+                    row.only_isolated,
+                    row.is_delisted
+                ));
+            }
+
+            csv
+        }
+    })
+}
+
+pub fn write_meta(path: impl AsRef<Path>, metas: &[Meta], format: MetaFormat) -> Result<(), Error> {
+    fs::write(path, format_meta(metas, format)?)?;
+
+    Ok(())
+}
+
+/// Fetches the perps and spot meta and writes the merged universe to `path`.
+#[cfg(feature = "live")]
+pub async fn export_meta(path: impl AsRef<Path>, format: MetaFormat) -> Result<(), Error> {
+    let client = build_info_http_client()?;
+
+    let (perps_meta, spot_meta) = tokio::try_join!(
+        post_info::<PerpsMeta>(&client, json!({ "type": "meta" })),
+        post_info::<SpotMeta>(&client, json!({ "type": "spotMeta" })),
+    )?;
+
+    let mut metas = perps_meta.get_metas();
+    metas.extend(spot_meta.get_metas());
+
+    write_meta(path, &metas, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Meta, SpotAssetMeta};
+
+    use super::{format_meta, MetaFormat};
+
+    #[test]
+    fn formats_csv_sorted_by_asset() {
+        let token = |name: &str| SpotAssetMeta {
+            name: name.to_string(),
+            ..Default::default()
+        };
+
+        let metas = [
+            Meta::Spot {
+                name: "@1".to_string(),
+                index: 1,
+                quote: token("PURR"),
+                base: token("USDC"),
+            },
+            Meta::Perp {
+                name: "ETH".to_string(),
+                index: 1,
+                sz_decimals: 4,
+                max_leverage: 25,
+                only_isolated: None,
+                is_delisted: Some(true),
+            },
+        ];
+
+        let csv = format_meta(&metas, MetaFormat::Csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "perp,ETH,ETH,1,4,25,false,true");
+        assert_eq!(lines[2], "spot,@1,PURR/USDC,10001,0,1,false,false");
+    }
+}
@@ -0,0 +1,17 @@
+#[cfg(feature = "polars")]
+mod dataframe;
+#[cfg(feature = "arrow")]
+mod record_batch;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod meta;
+#[cfg(feature = "live")]
+pub mod csv_sink;
+#[cfg(feature = "live")]
+pub mod webhook;
+#[cfg(feature = "live")]
+pub mod tsdb;
+#[cfg(feature = "live")]
+pub mod clickhouse;
+#[cfg(feature = "archive")]
+pub mod archive;
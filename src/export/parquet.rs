@@ -0,0 +1,248 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    mem,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use ::parquet::arrow::{
+    arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder},
+    ArrowWriter,
+};
+use anyhow::{anyhow, Error};
+use arrow::{
+    array::{Array, ArrayRef, Int64Array, StringArray},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::recorder::{Record, RecordSerializer};
+
+/// Rows buffered before they're written as a row group
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 10_000;
+
+fn get_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("time", DataType::Int64, false),
+        Field::new("data", DataType::Utf8, false),
+    ]))
+}
+
+/// The bytes the Parquet writer produced that weren't handed to the recording's file yet
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes recordings as Parquet files with the columns `time` and `data`, the latter holding the
+/// JSON of each record's data so every recorded type fits the same schema.
+///
+/// Records are written as a row group every `row_group_size` records and whenever the recording
+/// is flushed, so at most one row group is held in memory. The footer indexing the row groups is
+/// only written once the file is finished, so a file cut short by a crash can't be read back;
+/// rotate long recordings (see [`crate::recorder::RecorderConfig::with_rotation`]) to bound what
+/// that loses.
+pub struct ParquetSerializer {
+    row_group_size: usize,
+    times: Vec<i64>,
+    data: Vec<String>,
+    buffer: SharedBuffer,
+    parquet_writer: Option<ArrowWriter<SharedBuffer>>,
+}
+
+impl Default for ParquetSerializer {
+    fn default() -> Self {
+        ParquetSerializer {
+            row_group_size: DEFAULT_ROW_GROUP_SIZE,
+            times: vec![],
+            data: vec![],
+            buffer: SharedBuffer::default(),
+            parquet_writer: None,
+        }
+    }
+}
+
+impl ParquetSerializer {
+    pub fn new() -> Self {
+        ParquetSerializer::default()
+    }
+
+    pub fn with_row_group_size(mut self, row_group_size: usize) -> Self {
+        self.row_group_size = row_group_size.max(1);
+        self
+    }
+
+    /// Writes the buffered records as a row group and hands what the Parquet writer produced so
+    /// far to `writer`.
+    fn write_row_group(&mut self, writer: &mut (dyn Write + Send)) -> Result<(), Error> {
+        if !self.times.is_empty() {
+            let batch = RecordBatch::try_new(
+                get_schema(),
+                vec![
+                    Arc::new(Int64Array::from(mem::take(&mut self.times))) as ArrayRef,
+                    Arc::new(StringArray::from(mem::take(&mut self.data))),
+                ],
+            )?;
+
+            if self.parquet_writer.is_none() {
+                self.parquet_writer = Some(ArrowWriter::try_new(
+                    self.buffer.clone(),
+                    get_schema(),
+                    None,
+                )?);
+            }
+
+            if let Some(parquet_writer) = &mut self.parquet_writer {
+                parquet_writer.write(&batch)?;
+                parquet_writer.flush()?;
+            }
+        }
+
+        self.drain(writer)
+    }
+
+    fn drain(&self, writer: &mut (dyn Write + Send)) -> Result<(), Error> {
+        let bytes = mem::take(&mut *self.buffer.0.lock().unwrap());
+        writer.write_all(&bytes)?;
+
+        Ok(())
+    }
+}
+
+impl<T: Serialize> RecordSerializer<T> for ParquetSerializer {
+    fn write(&mut self, writer: &mut (dyn Write + Send), record: &Record<T>) -> Result<(), Error> {
+        self.data.push(serde_json::to_string(&record.data)?);
+        self.times.push(record.time);
+
+        if self.times.len() >= self.row_group_size {
+            self.write_row_group(writer)?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self, writer: &mut (dyn Write + Send)) -> Result<(), Error> {
+        self.write_row_group(writer)
+    }
+
+    fn finish(&mut self, writer: &mut (dyn Write + Send)) -> Result<(), Error> {
+        self.write_row_group(writer)?;
+
+        // A file without records still gets the schema and footer
+        let parquet_writer = match self.parquet_writer.take() {
+            Some(parquet_writer) => parquet_writer,
+            None => ArrowWriter::try_new(self.buffer.clone(), get_schema(), None)?,
+        };
+        parquet_writer.close()?;
+
+        self.drain(writer)
+    }
+}
+
+/// Reads back the records of a file written by a [`ParquetSerializer`], see
+/// [`crate::recorder::RecordReader::open_parquet`].
+pub(crate) struct ParquetRecords {
+    batches: ParquetRecordBatchReader,
+    batch: Option<(Int64Array, StringArray)>,
+    row: usize,
+}
+
+impl ParquetRecords {
+    pub(crate) fn open(path: &Path) -> Result<Self, Error> {
+        Ok(ParquetRecords {
+            batches: ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?,
+            batch: None,
+            row: 0,
+        })
+    }
+
+    pub(crate) fn read_next<T: DeserializeOwned>(&mut self) -> Result<Option<Record<T>>, Error> {
+        loop {
+            if let Some((times, data)) = &self.batch {
+                if self.row < times.len() {
+                    let row = self.row;
+                    self.row += 1;
+
+                    return Ok(Some(Record {
+                        time: times.value(row),
+                        data: serde_json::from_str(data.value(row))?,
+                    }));
+                }
+            }
+
+            let batch = match self.batches.next() {
+                Some(batch) => batch?,
+                None => return Ok(None),
+            };
+
+            let times = batch
+                .column_by_name("time")
+                .and_then(|column| column.as_any().downcast_ref::<Int64Array>())
+                .ok_or_else(|| anyhow!("No int64 time column"))?;
+            let data = batch
+                .column_by_name("data")
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| anyhow!("No string data column"))?;
+
+            self.batch = Some((times.clone(), data.clone()));
+            self.row = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use anyhow::Error;
+
+    use crate::recorder::{Record, RecordReader, RecordWriter};
+
+    use super::ParquetSerializer;
+
+    #[test]
+    fn records_round_trip_through_row_groups() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("hl_parquet_{}.parquet", std::process::id()));
+        let records: Vec<Record<Vec<String>>> = (0..5)
+            .map(|time| Record {
+                time,
+                data: vec![format!("ETH {time}")],
+            })
+            .collect();
+
+        let mut writer =
+            RecordWriter::with_serializer(&path, ParquetSerializer::new().with_row_group_size(2))?;
+        for record in &records[..3] {
+            writer.write(record)?;
+        }
+        writer.flush()?;
+        for record in &records[3..] {
+            writer.write(record)?;
+        }
+        writer.finish()?;
+
+        // Two full row groups and the third record, written on the flush
+        let metadata = ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?;
+        assert_eq!(metadata.metadata().num_row_groups(), 3);
+
+        let read: Vec<Record<Vec<String>>> =
+            RecordReader::open_parquet(&path)?.collect::<Result<_, _>>()?;
+        assert_eq!(read, records);
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use arrow::{
+    array::{ArrayRef, Float64Array, Int64Array, StringArray, UInt64Array},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+
+use crate::{
+    candles::{Candle, CandleBuffer},
+    history::PriceHistory,
+};
+
+impl PriceHistory {
+    /// One row per recorded point with the columns `coin`, `time` and `price`.
+    pub fn to_record_batch(&self) -> Result<RecordBatch, Error> {
+        let mut coins = vec![];
+        let mut times = vec![];
+        let mut prices = vec![];
+
+        for (coin, points) in self.iter() {
+            for point in points {
+                coins.push(coin.as_str());
+                times.push(point.time);
+                prices.push(point.price);
+            }
+        }
+
+        let schema = Schema::new(vec![
+            Field::new("coin", DataType::Utf8, false),
+            Field::new("time", DataType::Int64, false),
+            Field::new("price", DataType::Float64, false),
+        ]);
+
+        Ok(RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from(coins)) as ArrayRef,
+                Arc::new(Int64Array::from(times)),
+                Arc::new(Float64Array::from(prices)),
+            ],
+        )?)
+    }
+}
+
+impl CandleBuffer {
+    /// One row per candle with the columns `coin`, `interval`, `open_time`, `close_time`, `open`,
+    /// `high`, `low`, `close`, `volume` and `trades`.
+    pub fn to_record_batch(&self) -> Result<RecordBatch, Error> {
+        let candles: Vec<&Candle> = self.iter().flat_map(|(_, candles)| candles).collect();
+
+        let schema = Schema::new(vec![
+            Field::new("coin", DataType::Utf8, false),
+            Field::new("interval", DataType::Utf8, false),
+            Field::new("open_time", DataType::UInt64, false),
+            Field::new("close_time", DataType::UInt64, false),
+            Field::new("open", DataType::Float64, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float64, false),
+            Field::new("close", DataType::Float64, false),
+            Field::new("volume", DataType::Float64, false),
+            Field::new("trades", DataType::UInt64, false),
+        ]);
+
+        Ok(RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    candles.iter().map(|c| c.coin.as_str()),
+                )) as ArrayRef,
+                Arc::new(StringArray::from_iter_values(
+                    candles.iter().map(|c| c.interval.as_str()),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    candles.iter().map(|c| c.open_time),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    candles.iter().map(|c| c.close_time),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    candles.iter().map(|c| c.open),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    candles.iter().map(|c| c.high),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    candles.iter().map(|c| c.low),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    candles.iter().map(|c| c.close),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    candles.iter().map(|c| c.volume),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    candles.iter().map(|c| c.trades),
+                )),
+            ],
+        )?)
+    }
+}
@@ -0,0 +1,412 @@
+use std::{borrow::Borrow, collections::HashMap, fmt::Write, hash::BuildHasher, time::Duration};
+
+use anyhow::{bail, Error};
+use chrono::Utc;
+use reqwest::Client;
+use tokio::{sync::watch, task::JoinHandle, time::interval};
+use tracing::{error, info, warn};
+
+use crate::{price_data::perps::PerpQuote, prices::build_info_http_client, types::Price};
+
+/// One time-series sample, e.g. `funding,coin=ETH value=0.0000125 1718000000000`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub measurement: &'static str,
+    pub coin: String,
+    pub fields: Vec<(&'static str, f64)>,
+    /// Unix milliseconds
+    pub time: i64,
+}
+
+impl Point {
+    /// The point in InfluxDB line protocol with millisecond precision. `tags` are added after the
+    /// coin tag.
+    pub fn to_line_protocol(&self, tags: &[(String, String)]) -> String {
+        let mut line = escape_line_protocol(self.measurement, false);
+        let _ = write!(line, ",coin={}", escape_line_protocol(&self.coin, true));
+
+        for (key, value) in tags {
+            let _ = write!(
+                line,
+                ",{}={}",
+                escape_line_protocol(key, true),
+                escape_line_protocol(value, true)
+            );
+        }
+
+        let fields = self
+            .fields
+            .iter()
+            .filter(|(_, value)| value.is_finite())
+            .map(|(key, value)| format!("{}={value}", escape_line_protocol(key, true)))
+            .collect::<Vec<String>>()
+            .join(",");
+
+        let _ = write!(line, " {fields} {}", self.time);
+
+        line
+    }
+
+    /// Whether any field is finite. Points without one can't be written, line protocol needs at
+    /// least one field.
+    pub fn has_fields(&self) -> bool {
+        self.fields.iter().any(|(_, value)| value.is_finite())
+    }
+}
+
+/// Commas and spaces are escaped everywhere, equals signs only in tag keys and values.
+fn escape_line_protocol(s: &str, is_tag: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if c == ',' || c == ' ' || (is_tag && c == '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// A value of one of the shared maps that can be written as time-series points.
+pub trait TsdbRecord {
+    fn points(&self, coin: &str, time: i64) -> Vec<Point>;
+}
+
+impl TsdbRecord for Price {
+    fn points(&self, coin: &str, time: i64) -> Vec<Point> {
+        vec![Point {
+            measurement: "price",
+            coin: coin.to_string(),
+            fields: vec![("value", self.get_value())],
+            time,
+        }]
+    }
+}
+
+/// A price point, plus funding and open interest points once the coin's context is known
+impl TsdbRecord for PerpQuote {
+    fn points(&self, coin: &str, time: i64) -> Vec<Point> {
+        let mut points = self.price.points(coin, time);
+
+        if let Some(ctx) = &self.ctx {
+            points.push(Point {
+                measurement: "funding",
+                coin: coin.to_string(),
+                fields: vec![("rate", ctx.funding), ("premium", ctx.premium)],
+                time,
+            });
+            points.push(Point {
+                measurement: "open_interest",
+                coin: coin.to_string(),
+                fields: vec![
+                    ("value", ctx.open_interest),
+                    ("notional", ctx.open_interest * ctx.mark_px),
+                ],
+                time,
+            });
+        }
+
+        points
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum TsdbTarget {
+    /// The v2 write endpoint, e.g.
+    /// `http://localhost:8086/api/v2/write?org=my-org&bucket=hyperliquid&precision=ms`. The
+    /// precision has to be `ms`.
+    Influx { url: String, token: Option<String> },
+    /// A postgres connection string, e.g. `host=localhost user=postgres dbname=market`. Points go
+    /// to `table`, which needs the columns
+    /// `(time timestamptz, measurement text, coin text, tags jsonb, field text, value double precision)`
+    /// and is best made a hypertable on `time`.
+    #[cfg(feature = "timescale")]
+    Timescale { config: String, table: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct TsdbConfig {
+    pub target: TsdbTarget,
+    /// Added to every point, e.g. `("host", "capture-1")`
+    pub tags: Vec<(String, String)>,
+    /// Only these coins are written, all of them if `None`
+    pub coins: Option<Vec<String>>,
+    /// How often points are sampled from the feed
+    pub sample_interval: Duration,
+    pub flush_interval: Duration,
+    /// Flushes early once this many points are buffered
+    pub max_batch: usize,
+}
+
+impl TsdbConfig {
+    pub fn new(target: TsdbTarget) -> Self {
+        TsdbConfig {
+            target,
+            tags: vec![],
+            coins: None,
+            sample_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(10),
+            max_batch: 5_000,
+        }
+    }
+
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+enum TsdbClient {
+    Influx(Client),
+    #[cfg(feature = "timescale")]
+    Timescale(tokio_postgres::Client),
+}
+
+/// Batches points and writes them to InfluxDB or TimescaleDB.
+pub struct TsdbWriter {
+    client: TsdbClient,
+    config: TsdbConfig,
+    buffer: Vec<Point>,
+}
+
+impl TsdbWriter {
+    pub async fn connect(config: TsdbConfig) -> Result<Self, Error> {
+        let client = match &config.target {
+            TsdbTarget::Influx { .. } => TsdbClient::Influx(build_info_http_client()?),
+            #[cfg(feature = "timescale")]
+            TsdbTarget::Timescale { config, .. } => {
+                let (client, connection) =
+                    tokio_postgres::connect(config, tokio_postgres::NoTls).await?;
+
+                tokio::spawn(async move {
+                    if let Err(err) = connection.await {
+                        error!("Timescale connection closed: {err:?}");
+                    }
+                });
+
+                TsdbClient::Timescale(client)
+            }
+        };
+
+        Ok(TsdbWriter {
+            client,
+            config,
+            buffer: vec![],
+        })
+    }
+
+    pub fn get_buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Buffers `points`, flushing once `max_batch` is reached. Points without a finite field are
+    /// skipped.
+    pub async fn write(&mut self, points: Vec<Point>) -> Result<(), Error> {
+        self.buffer
+            .extend(points.into_iter().filter(|point| point.has_fields()));
+
+        if self.buffer.len() >= self.config.max_batch {
+            self.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the buffered points. They're dropped if the write fails, so an outage of the
+    /// database doesn't grow the buffer unbounded.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let points = std::mem::take(&mut self.buffer);
+
+        match (&self.client, &self.config.target) {
+            (TsdbClient::Influx(client), TsdbTarget::Influx { url, token }) => {
+                let body = points
+                    .iter()
+                    .map(|point| point.to_line_protocol(&self.config.tags))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                let mut request = client.post(url).body(body);
+                if let Some(token) = token {
+                    request = request.header("Authorization", format!("Token {token}"));
+                }
+
+                let response = request.send().await?;
+                if !response.status().is_success() {
+                    bail!(
+                        "Influx rejected {} points with {}: {}",
+                        points.len(),
+                        response.status(),
+                        response.text().await.unwrap_or_default()
+                    );
+                }
+            }
+            #[cfg(feature = "timescale")]
+            (TsdbClient::Timescale(client), TsdbTarget::Timescale { table, .. }) => {
+                insert_timescale(client, table, &self.config.tags, &points).await?;
+            }
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("The client is always built from the target"),
+        }
+
+        Ok(())
+    }
+}
+
+/// Inserts `points` in one statement, a row per finite field. The values are bound as arrays and
+/// unnested, so no coin or tag ends up in the SQL text.
+#[cfg(feature = "timescale")]
+async fn insert_timescale(
+    client: &tokio_postgres::Client,
+    table: &str,
+    tags: &[(String, String)],
+    points: &[Point],
+) -> Result<(), Error> {
+    let tags: serde_json::Map<String, serde_json::Value> = tags
+        .iter()
+        .map(|(key, value)| (key.clone(), serde_json::Value::String(value.clone())))
+        .collect();
+    let tags = serde_json::Value::Object(tags).to_string();
+
+    let mut times: Vec<i64> = vec![];
+    let mut measurements: Vec<&str> = vec![];
+    let mut coins: Vec<&str> = vec![];
+    let mut fields: Vec<&str> = vec![];
+    let mut values: Vec<f64> = vec![];
+
+    for point in points {
+        for (field, value) in point.fields.iter().filter(|(_, value)| value.is_finite()) {
+            times.push(point.time);
+            measurements.push(point.measurement);
+            coins.push(&point.coin);
+            fields.push(*field);
+            values.push(*value);
+        }
+    }
+
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    // The table is configured, not data, and can't be a parameter
+    let statement = format!(
+        "INSERT INTO {table} (time, measurement, coin, tags, field, value) \
+         SELECT to_timestamp(r.time / 1000.0), r.measurement, r.coin, $1::text::jsonb, \
+         r.field, r.value \
+         FROM UNNEST($2::bigint[], $3::text[], $4::text[], $5::text[], $6::float8[]) \
+         AS r(time, measurement, coin, field, value)"
+    );
+
+    client
+        .execute(
+            &statement,
+            &[&tags, &times, &measurements, &coins, &fields, &values],
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn is_selected(config: &TsdbConfig, coin: &str) -> bool {
+    match &config.coins {
+        Some(coins) => coins.iter().any(|c| c == coin),
+        None => true,
+    }
+}
+
+/// Samples `receiver` into the configured database until every sender is dropped, e.g. the
+/// receiver of [`crate::prices::start_perp_quote_task`] for prices, funding and open interest.
+pub async fn start_tsdb_writer_task<K, T, S>(
+    receiver: watch::Receiver<HashMap<K, T, S>>,
+    config: TsdbConfig,
+) -> anyhow::Result<JoinHandle<()>>
+where
+    K: Borrow<str> + Send + Sync + 'static,
+    T: TsdbRecord + Send + Sync + 'static,
+    S: BuildHasher + Send + Sync + 'static,
+{
+    let mut writer = TsdbWriter::connect(config.clone()).await?;
+
+    Ok(tokio::spawn(async move {
+        let mut sample_interval = interval(config.sample_interval);
+        let mut flush_interval = interval(config.flush_interval);
+
+        info!("tsdb_writer_task: Starting...");
+
+        loop {
+            tokio::select! {
+                _ = sample_interval.tick() => {
+                    let time = Utc::now().timestamp_millis();
+                    let points: Vec<Point> = receiver
+                        .borrow()
+                        .iter()
+                        .map(|(coin, value)| (Borrow::<str>::borrow(coin), value))
+                        .filter(|(coin, _)| is_selected(&config, coin))
+                        .flat_map(|(coin, value)| value.points(coin, time))
+                        .collect();
+
+                    if let Err(err) = writer.write(points).await {
+                        warn!("tsdb_writer_task: Error while writing: {err:?}");
+                    }
+                }
+                _ = flush_interval.tick() => {
+                    if let Err(err) = writer.flush().await {
+                        warn!("tsdb_writer_task: Error while flushing: {err:?}");
+                    }
+                }
+            }
+
+            if receiver.has_changed().is_err() {
+                if let Err(err) = writer.flush().await {
+                    error!("tsdb_writer_task: Error while flushing: {err:?}");
+                }
+
+                info!("tsdb_writer_task: Feed closed, stopping...");
+                return;
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Point;
+
+    #[test]
+    fn points_without_a_finite_field_are_skipped() {
+        let point = |value| Point {
+            measurement: "price",
+            coin: "ETH".to_string(),
+            fields: vec![("value", value)],
+            time: 1_718_000_000_000,
+        };
+
+        assert!(point(2000.0).has_fields());
+        assert!(!point(f64::NAN).has_fields());
+        assert!(!Point {
+            fields: vec![],
+            ..point(2000.0)
+        }
+        .has_fields());
+    }
+
+    #[test]
+    fn writes_escaped_line_protocol() {
+        let point = Point {
+            measurement: "funding",
+            coin: "PURR/USDC".to_string(),
+            fields: vec![("rate", 0.0000125), ("premium", f64::NAN)],
+            time: 1_718_000_000_000,
+        };
+        let tags = vec![("host".to_string(), "capture 1".to_string())];
+
+        assert_eq!(
+            point.to_line_protocol(&tags),
+            "funding,coin=PURR/USDC,host=capture\\ 1 rate=0.0000125 1718000000000"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use anyhow::{bail, Error};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client,
+};
+use serde::Serialize;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc::UnboundedReceiver},
+    task::JoinHandle,
+    time::sleep,
+};
+use tracing::{error, info, warn};
+
+use crate::{events::subscribe_feed_events, prices::build_info_http_client};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Including the first try
+    pub max_attempts: u32,
+    /// Doubled after every failed attempt, up to `max_backoff`
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after the `attempt`th (1 based) failed attempt
+    pub fn get_backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Sent with every request, e.g. an `Authorization` header
+    pub headers: Vec<(String, String)>,
+    pub retry: RetryPolicy,
+}
+
+impl WebhookConfig {
+    pub fn new(url: &str) -> Self {
+        WebhookConfig {
+            url: url.to_string(),
+            headers: vec![],
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// POSTs events serialized as JSON to a webhook. Server errors, rate limits and connection errors
+/// are retried according to the config's [`RetryPolicy`], other client errors aren't.
+pub struct WebhookSink {
+    client: Client,
+    config: WebhookConfig,
+    headers: HeaderMap,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> Result<Self, Error> {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in config.headers.iter() {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+
+        Ok(WebhookSink {
+            client: build_info_http_client()?,
+            config,
+            headers,
+        })
+    }
+
+    pub fn get_config(&self) -> &WebhookConfig {
+        &self.config
+    }
+
+    pub async fn send<T: Serialize>(&self, event: &T) -> Result<(), Error> {
+        let retry = &self.config.retry;
+        let mut attempt = 1;
+
+        loop {
+            let res = self
+                .client
+                .post(&self.config.url)
+                .headers(self.headers.clone())
+                .json(event)
+                .send()
+                .await;
+
+            let err = match res {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+
+                    if status.is_client_error() && status.as_u16() != 429 {
+                        bail!("Webhook rejected the event with {status}");
+                    }
+
+                    anyhow::anyhow!("Webhook returned {status}")
+                }
+                Err(err) => err.into(),
+            };
+
+            if attempt >= retry.max_attempts {
+                return Err(err.context(format!("Giving up after {attempt} attempts")));
+            }
+
+            warn!("Webhook attempt {attempt} failed, retrying: {err:?}");
+            sleep(retry.get_backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Sends every event of `events` to `sink` until the channel closes. Events that still fail
+/// after the retries are logged and dropped.
+pub fn start_webhook_task<T>(sink: WebhookSink, mut events: UnboundedReceiver<T>) -> JoinHandle<()>
+where
+    T: Serialize + std::fmt::Debug + Send + 'static,
+{
+    tokio::spawn(async move {
+        info!("webhook_task: Starting...");
+
+        while let Some(event) = events.recv().await {
+            if let Err(err) = sink.send(&event).await {
+                error!("webhook_task: Couldn't deliver {event:?}: {err:?}");
+            }
+        }
+
+        info!("webhook_task: Event channel closed, stopping...");
+    })
+}
+
+/// Sends every feed lifecycle event (connects, reconnects, drops, gaps and stale feeds) to
+/// `sink`.
+pub fn start_feed_event_webhook_task(sink: WebhookSink) -> JoinHandle<()> {
+    let mut events = subscribe_feed_events();
+
+    tokio::spawn(async move {
+        info!("feed_event_webhook_task: Starting...");
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("feed_event_webhook_task: Skipped {skipped} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            if let Err(err) = sink.send(&event).await {
+                error!("feed_event_webhook_task: Couldn't deliver {event:?}: {err:?}");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn backoff_doubles_up_to_the_max() {
+        let retry = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+
+        assert_eq!(retry.get_backoff(1), Duration::from_millis(100));
+        assert_eq!(retry.get_backoff(2), Duration::from_millis(200));
+        assert_eq!(retry.get_backoff(3), Duration::from_millis(400));
+        assert_eq!(retry.get_backoff(4), Duration::from_millis(500));
+        assert_eq!(retry.get_backoff(40), Duration::from_millis(500));
+    }
+}
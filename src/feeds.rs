@@ -0,0 +1,315 @@
+use std::{collections::HashMap, future::Future};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    prices::{start_perps_sender_task, start_spot_sender_task},
+    types::{Meta, NameToPriceMap, Price, Symbol},
+};
+
+pub const HYPERLIQUID_VENUE: &str = "hyperliquid";
+
+/// A live per-coin price map. Every feed is keyed by Hyperliquid coin names so feeds from
+/// different venues can be compared directly.
+pub trait PriceFeed {
+    /// Short name of the venue, used to label spreads
+    fn get_venue(&self) -> &str;
+
+    fn get_receiver(&self) -> watch::Receiver<NameToPriceMap>;
+}
+
+/// The crate's own AllMids based feeds.
+pub struct HyperliquidPriceFeed {
+    receiver: watch::Receiver<NameToPriceMap>,
+}
+
+impl HyperliquidPriceFeed {
+    pub async fn perps() -> Result<Self, Error> {
+        Ok(HyperliquidPriceFeed {
+            receiver: start_perps_sender_task().await?,
+        })
+    }
+
+    pub async fn spot() -> Result<Self, Error> {
+        Ok(HyperliquidPriceFeed {
+            receiver: start_spot_sender_task().await?,
+        })
+    }
+}
+
+impl PriceFeed for HyperliquidPriceFeed {
+    fn get_venue(&self) -> &str {
+        HYPERLIQUID_VENUE
+    }
+
+    fn get_receiver(&self) -> watch::Receiver<NameToPriceMap> {
+        self.receiver.clone()
+    }
+}
+
+/// Implemented by users to plug another venue's mids (e.g. a Binance or Bybit websocket) into an
+/// [`ExternalPriceFeed`].
+pub trait ExternalMidSource: Send + 'static {
+    fn get_venue(&self) -> &str;
+
+    /// Resolves with the next batch of mids, keyed by Hyperliquid coin name (normalized like a
+    /// [`Symbol`], so "eth" is fine too). Coins missing from a batch keep their previous price.
+    /// Errors are logged and retried after 5 secs.
+    fn get_next_mids(&mut self)
+        -> impl Future<Output = Result<HashMap<String, f64>, Error>> + Send;
+}
+
+/// Turns an [`ExternalMidSource`] into a [`PriceFeed`]. Mids are wrapped with the Hyperliquid
+/// meta of the coin so they are rounded the same way, coins without a meta are ignored.
+pub struct ExternalPriceFeed {
+    venue: String,
+    receiver: watch::Receiver<NameToPriceMap>,
+}
+
+impl ExternalPriceFeed {
+    pub fn start<S: ExternalMidSource>(mut source: S, metas: HashMap<Symbol, Meta>) -> Self {
+        let venue = source.get_venue().to_string();
+        let (price_sender, price_recv) = watch::channel(NameToPriceMap::default());
+
+        let task_venue = venue.clone();
+        tokio::spawn(async move {
+            info!("external_feed_task({task_venue}): Starting...");
+
+            loop {
+                let mids = match source.get_next_mids().await {
+                    Ok(mids) => mids,
+                    Err(err) => {
+                        error!("external_feed_task({task_venue}): Error: {err:?}");
+                        error!("Sleeping for 5 secs and retrying...");
+                        sleep(std::time::Duration::from_secs(5)).await;
+                        continue;
+                    }
+                };
+
+                price_sender.send_modify(|map| {
+                    for (coin, mid) in mids {
+                        let Ok(coin) = Symbol::new(&coin) else {
+                            continue;
+                        };
+
+                        if let Some(meta) = metas.get(&coin) {
+                            map.insert(coin, Price::from_meta(mid, meta));
+                        }
+                    }
+                });
+
+                if price_sender.is_closed() {
+                    info!("external_feed_task({task_venue}): All receivers dropped, stopping...");
+                    return;
+                }
+            }
+        });
+
+        ExternalPriceFeed {
+            venue,
+            receiver: price_recv,
+        }
+    }
+}
+
+impl PriceFeed for ExternalPriceFeed {
+    fn get_venue(&self) -> &str {
+        &self.venue
+    }
+
+    fn get_receiver(&self) -> watch::Receiver<NameToPriceMap> {
+        self.receiver.clone()
+    }
+}
+
+/// Metas of every coin in a Hyperliquid price map, to pass to [`ExternalPriceFeed::start`].
+pub fn get_name_to_meta_map(prices: &NameToPriceMap) -> HashMap<Symbol, Meta> {
+    prices
+        .iter()
+        .filter(|(_, price)| !matches!(price, Price::None))
+        .map(|(coin, price)| (coin.clone(), price.get_meta().clone()))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CrossVenueSpread {
+    pub coin: String,
+    pub base_venue: String,
+    pub other_venue: String,
+    pub base_price: f64,
+    pub other_price: f64,
+    /// other - base
+    pub spread: f64,
+    pub spread_bps: f64,
+}
+
+/// Spread of every coin priced (above 0.0) on both venues.
+pub fn get_cross_venue_spreads(
+    base_venue: &str,
+    base: &NameToPriceMap,
+    other_venue: &str,
+    other: &NameToPriceMap,
+) -> HashMap<String, CrossVenueSpread> {
+    base.iter()
+        .filter_map(|(coin, base_price)| {
+            let base_price = base_price.get_value();
+            let other_price = other.get(coin)?.get_value();
+
+            if base_price <= 0.0 || other_price <= 0.0 {
+                return None;
+            }
+
+            let spread = other_price - base_price;
+
+            Some((
+                coin.to_string(),
+                CrossVenueSpread {
+                    coin: coin.to_string(),
+                    base_venue: base_venue.to_string(),
+                    other_venue: other_venue.to_string(),
+                    base_price,
+                    other_price,
+                    spread,
+                    spread_bps: spread / base_price * 10_000.0,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Recomputes the spreads between two feeds every time either of them changes.
+pub async fn start_cross_venue_spread_task(
+    base: &impl PriceFeed,
+    other: &impl PriceFeed,
+) -> anyhow::Result<watch::Receiver<HashMap<String, CrossVenueSpread>>> {
+    let base_venue = base.get_venue().to_string();
+    let other_venue = other.get_venue().to_string();
+    let mut base_receiver = base.get_receiver();
+    let mut other_receiver = other.get_receiver();
+
+    let (spread_sender, spread_recv) = watch::channel(HashMap::<String, CrossVenueSpread>::new());
+
+    tokio::spawn(async move {
+        info!("cross_venue_spread_task: Starting...");
+
+        loop {
+            let changed = tokio::select! {
+                res = base_receiver.changed() => res,
+                res = other_receiver.changed() => res,
+            };
+
+            if changed.is_err() {
+                info!("cross_venue_spread_task: Price channel closed, stopping...");
+                return;
+            }
+
+            let spreads = get_cross_venue_spreads(
+                &base_venue,
+                &base_receiver.borrow_and_update(),
+                &other_venue,
+                &other_receiver.borrow_and_update(),
+            );
+
+            if spread_sender.send(spreads).is_err() {
+                info!("cross_venue_spread_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(spread_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Error;
+
+    use crate::types::{Meta, NameToPriceMap, Price, Symbol};
+
+    use super::{get_cross_venue_spreads, ExternalMidSource, ExternalPriceFeed, PriceFeed};
+
+    fn perp(coin: &str) -> Meta {
+        Meta::Perp {
+            name: coin.to_string(),
+            index: 0,
+            sz_decimals: 4,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        }
+    }
+
+    struct Batches(Vec<HashMap<String, f64>>);
+
+    impl ExternalMidSource for Batches {
+        fn get_venue(&self) -> &str {
+            "test_venue"
+        }
+
+        async fn get_next_mids(&mut self) -> Result<HashMap<String, f64>, Error> {
+            match self.0.pop() {
+                Some(mids) => Ok(mids),
+                None => std::future::pending().await,
+            }
+        }
+    }
+
+    #[test]
+    fn spreads_of_coins_priced_on_both_venues() {
+        let base: NameToPriceMap = [
+            (
+                Symbol::from_api("ETH"),
+                Price::new_perp(2000.0, perp("ETH")),
+            ),
+            (
+                Symbol::from_api("BTC"),
+                Price::new_perp(60000.0, perp("BTC")),
+            ),
+            (Symbol::from_api("SOL"), Price::new_perp(150.0, perp("SOL"))),
+        ]
+        .into_iter()
+        .collect();
+        let other: NameToPriceMap = [
+            (
+                Symbol::from_api("ETH"),
+                Price::new_perp(2002.0, perp("ETH")),
+            ),
+            (Symbol::from_api("BTC"), Price::new_perp(0.0, perp("BTC"))),
+        ]
+        .into_iter()
+        .collect();
+
+        let spreads = get_cross_venue_spreads("hyperliquid", &base, "other", &other);
+
+        assert_eq!(spreads.len(), 1);
+        let eth = &spreads["ETH"];
+        assert_eq!(eth.spread, 2.0);
+        assert!((eth.spread_bps - 10.0).abs() < 1e-9);
+        assert_eq!(eth.other_venue, "other");
+    }
+
+    #[tokio::test]
+    async fn external_feed_keeps_coins_with_a_meta() {
+        let source = Batches(vec![
+            HashMap::from([("ETH".to_string(), 2001.0)]),
+            HashMap::from([("eth".to_string(), 2000.0), ("DOGE".to_string(), 0.1)]),
+        ]);
+        let metas = HashMap::from([(Symbol::from_api("ETH"), perp("ETH"))]);
+
+        let feed = ExternalPriceFeed::start(source, metas);
+        assert_eq!(feed.get_venue(), "test_venue");
+
+        let mut receiver = feed.get_receiver();
+        let map = receiver
+            .wait_for(|map| map.get("ETH").map(|price| price.get_value()) == Some(2001.0))
+            .await
+            .unwrap();
+
+        assert_eq!(map.len(), 1);
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::Price;
+
+/// Builder fees are expressed in tenths of a basis point
+pub const BUILDER_FEE_UNIT: f64 = 0.000_01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    /// 14 day weighted volume in USD needed to reach the tier
+    pub min_volume: f64,
+    pub taker_rate: f64,
+    pub maker_rate: f64,
+}
+
+const fn tier(min_volume: f64, taker_rate: f64, maker_rate: f64) -> FeeTier {
+    FeeTier {
+        min_volume,
+        taker_rate,
+        maker_rate,
+    }
+}
+
+pub const PERPS_FEE_TIERS: [FeeTier; 7] = [
+    tier(0.0, 0.000_45, 0.000_15),
+    tier(5_000_000.0, 0.000_40, 0.000_12),
+    tier(25_000_000.0, 0.000_35, 0.000_08),
+    tier(100_000_000.0, 0.000_30, 0.000_04),
+    tier(500_000_000.0, 0.000_28, 0.0),
+    tier(2_000_000_000.0, 0.000_26, 0.0),
+    tier(7_000_000_000.0, 0.000_24, 0.0),
+];
+
+pub const SPOT_FEE_TIERS: [FeeTier; 7] = [
+    tier(0.0, 0.000_70, 0.000_40),
+    tier(5_000_000.0, 0.000_60, 0.000_30),
+    tier(25_000_000.0, 0.000_50, 0.000_20),
+    tier(100_000_000.0, 0.000_40, 0.000_10),
+    tier(500_000_000.0, 0.000_35, 0.0),
+    tier(2_000_000_000.0, 0.000_30, 0.0),
+    tier(7_000_000_000.0, 0.000_25, 0.0),
+];
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct TradeCost {
+    /// Size in units of the asset, rounded to its sz_decimals
+    pub size: f64,
+    pub notional: f64,
+    pub exchange_fee: f64,
+    pub builder_fee: f64,
+    pub total_fee: f64,
+}
+
+/// Fee rates applied to an order, as fractions of the notional.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub taker_rate: f64,
+    pub maker_rate: f64,
+    pub builder_fee_rate: f64,
+}
+
+impl FeeSchedule {
+    pub fn from_tier(tier: &FeeTier) -> Self {
+        FeeSchedule {
+            taker_rate: tier.taker_rate,
+            maker_rate: tier.maker_rate,
+            builder_fee_rate: 0.0,
+        }
+    }
+
+    /// Schedule of the highest tier reached with `volume` (14 day USD volume).
+    pub fn from_volume(volume: f64, is_spot: bool) -> Self {
+        let tiers = if is_spot {
+            &SPOT_FEE_TIERS
+        } else {
+            &PERPS_FEE_TIERS
+        };
+
+        let tier = tiers
+            .iter()
+            .rev()
+            .find(|tier| volume >= tier.min_volume)
+            .unwrap_or(&tiers[0]);
+
+        Self::from_tier(tier)
+    }
+
+    /// Adds a builder fee of `fee` tenths of a basis point, as set on the order's builder info.
+    pub fn with_builder_fee(mut self, fee: u32) -> Self {
+        self.builder_fee_rate = fee as f64 * BUILDER_FEE_UNIT;
+        self
+    }
+
+    pub fn get_exchange_rate(&self, is_maker: bool) -> f64 {
+        if is_maker {
+            self.maker_rate
+        } else {
+            self.taker_rate
+        }
+    }
+
+    /// Exchange rate plus builder fee
+    pub fn get_rate(&self, is_maker: bool) -> f64 {
+        self.get_exchange_rate(is_maker) + self.builder_fee_rate
+    }
+
+    /// Cost of trading `size` USDC worth of the asset at its current price.
+    pub fn estimate(&self, price: &Price, size: f64, is_maker: bool) -> TradeCost {
+        if let Price::None = price {
+            return TradeCost::default();
+        }
+
+        let size = price.get_asset_denom_size(size);
+        let notional = size * price.get_value();
+
+        let exchange_fee = notional * self.get_exchange_rate(is_maker);
+        let builder_fee = notional * self.builder_fee_rate;
+
+        TradeCost {
+            size,
+            notional,
+            exchange_fee,
+            builder_fee,
+            total_fee: exchange_fee + builder_fee,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Meta, Price};
+
+    use super::FeeSchedule;
+
+    #[test]
+    fn taker_fee_with_builder_fee() {
+        let price = Price::new_perp(
+            2000.0,
+            Meta::Perp {
+                name: "ETH".to_string(),
+                index: 1,
+                sz_decimals: 4,
+                max_leverage: 50,
+                only_isolated: None,
+                is_delisted: None,
+            },
+        );
+
+        let schedule = FeeSchedule::from_volume(30_000_000.0, false).with_builder_fee(10);
+        assert_eq!(schedule.taker_rate, 0.000_35);
+
+        let cost = schedule.estimate(&price, 10_000.0, false);
+        assert_eq!(cost.size, 5.0);
+        assert!((cost.exchange_fee - 3.5).abs() < 1e-9);
+        assert!((cost.builder_fee - 1.0).abs() < 1e-9);
+        assert!((cost.total_fee - 4.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,106 @@
+//! Minimal C ABI around the price feeds, see `include/hl_utils.h`.
+//!
+//! Build a shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+
+use std::{
+    ffi::{c_char, c_int, CStr},
+    ptr,
+};
+
+use tokio::{runtime::Runtime, sync::watch};
+use tracing::error;
+
+use crate::{
+    prices::{start_perps_sender_task, start_spot_sender_task},
+    types::NameToPriceMap,
+};
+
+pub const HL_FEED_PERPS: c_int = 0;
+pub const HL_FEED_SPOT: c_int = 1;
+
+pub const HL_OK: c_int = 0;
+pub const HL_ERR_INVALID_ARGUMENT: c_int = -1;
+pub const HL_ERR_NOT_FOUND: c_int = -2;
+
+/// Opaque handle owning the runtime the feed runs on.
+pub struct HlPriceFeed {
+    runtime: Runtime,
+    receiver: watch::Receiver<NameToPriceMap>,
+}
+
+/// Starts a perps (`HL_FEED_PERPS`) or spot (`HL_FEED_SPOT`) price feed on its own runtime.
+/// Returns null on failure. The handle must be released with `hl_price_feed_stop`.
+#[no_mangle]
+pub extern "C" fn hl_price_feed_start(kind: c_int) -> *mut HlPriceFeed {
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(err) => {
+            error!("ffi: Couldn't build the runtime: {err:?}");
+            return ptr::null_mut();
+        }
+    };
+
+    let receiver = match kind {
+        HL_FEED_PERPS => runtime.block_on(start_perps_sender_task()),
+        HL_FEED_SPOT => runtime.block_on(start_spot_sender_task()),
+        _ => return ptr::null_mut(),
+    };
+
+    match receiver {
+        Ok(receiver) => Box::into_raw(Box::new(HlPriceFeed { runtime, receiver })),
+        Err(err) => {
+            error!("ffi: Couldn't start the feed: {err:?}");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Writes the latest price of `coin` to `out_price`. Returns `HL_ERR_NOT_FOUND` until the coin
+/// has been received at least once.
+///
+/// # Safety
+///
+/// `feed` must come from `hl_price_feed_start` and not have been stopped, `coin` must be a valid
+/// NUL terminated string and `out_price` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn hl_price_feed_get_price(
+    feed: *const HlPriceFeed,
+    coin: *const c_char,
+    out_price: *mut f64,
+) -> c_int {
+    if feed.is_null() || coin.is_null() || out_price.is_null() {
+        return HL_ERR_INVALID_ARGUMENT;
+    }
+
+    let coin = match CStr::from_ptr(coin).to_str() {
+        Ok(c) => c,
+        Err(_) => return HL_ERR_INVALID_ARGUMENT,
+    };
+
+    match (*feed).receiver.borrow().get(coin) {
+        Some(price) if price.get_value() > 0.0 => {
+            *out_price = price.get_value();
+            HL_OK
+        }
+        _ => HL_ERR_NOT_FOUND,
+    }
+}
+
+/// Stops the feed and frees the handle. Passing null is a no-op.
+///
+/// # Safety
+///
+/// `feed` must come from `hl_price_feed_start` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn hl_price_feed_stop(feed: *mut HlPriceFeed) {
+    if feed.is_null() {
+        return;
+    }
+
+    let feed = Box::from_raw(feed);
+    feed.runtime.shutdown_background();
+}
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use alloy::primitives::Address;
+use anyhow::{Context, Error};
+use hyperliquid_rust_sdk::{Message, Subscription, TradeInfo};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tracing::error;
+
+use crate::{
+    account::get_account_address,
+    counters::{Counter, FeedCounter, FeedCounters},
+    latency::record_exchange_time,
+    pagination::get_all_pages,
+    prices::post_info,
+    subscription::SubscriptionGuard,
+    transport::{LiveTransport, Transport},
+};
+
+/// Most fills the `userFillsByTime` info request returns at once
+pub const USER_FILLS_PAGE_SIZE: usize = 2000;
+
+/// Only this many of the most recent fills of a user can be fetched
+pub const MAX_USER_FILLS_HISTORY: usize = 10000;
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Fill {
+    pub coin: String,
+    pub is_buy: bool,
+    pub price: f64,
+    pub size: f64,
+    pub fee: f64,
+    pub closed_pnl: f64,
+    pub time: u64,
+    pub tid: u64,
+    pub oid: u64,
+}
+
+impl Fill {
+    /// Size with the sign of the fill's direction, positive for buys.
+    pub fn get_signed_size(&self) -> f64 {
+        if self.is_buy {
+            self.size
+        } else {
+            -self.size
+        }
+    }
+}
+
+impl TryFrom<TradeInfo> for Fill {
+    type Error = Error;
+
+    fn try_from(trade: TradeInfo) -> Result<Self, Self::Error> {
+        Ok(Fill {
+            is_buy: trade.side == "B",
+            price: trade.px.parse::<f64>().context("Couldn't parse fill px")?,
+            size: trade.sz.parse::<f64>().context("Couldn't parse fill sz")?,
+            fee: trade
+                .fee
+                .parse::<f64>()
+                .context("Couldn't parse fill fee")?,
+            closed_pnl: trade
+                .closed_pnl
+                .parse::<f64>()
+                .context("Couldn't parse fill closed_pnl")?,
+            time: trade.time,
+            tid: trade.tid,
+            oid: trade.oid,
+            coin: trade.coin,
+        })
+    }
+}
+
+/// Fills of `user` between `start_time` and `end_time` (ms since epoch, now if `None`), oldest
+/// first. Requests more pages until the range is covered, see [`get_all_pages`]. The API only
+/// serves the
+/// [`MAX_USER_FILLS_HISTORY`] most recent fills, older ones are silently missing.
+pub async fn get_user_fills_by_time(
+    client: &Client,
+    user: Address,
+    start_time: u64,
+    end_time: Option<u64>,
+) -> Result<Vec<Fill>, Error> {
+    // Fills of one order share a time, so a page can end between them
+    let trades = get_all_pages(
+        start_time,
+        USER_FILLS_PAGE_SIZE,
+        |cursor| {
+            let mut request =
+                json!({ "type": "userFillsByTime", "user": user, "startTime": cursor });
+            if let Some(end_time) = end_time {
+                request["endTime"] = json!(end_time);
+            }
+
+            post_info::<Vec<TradeInfo>>(client, request)
+        },
+        |trade| trade.time,
+        |trade| trade.tid,
+    )
+    .await?;
+
+    let mut fills: Vec<Fill> = trades
+        .into_iter()
+        .filter_map(|trade| match Fill::try_from(trade) {
+            Ok(fill) => Some(fill),
+            Err(err) => {
+                error!("Skipping malformed fill: {err:?}");
+                None
+            }
+        })
+        .collect();
+
+    fills.sort_by_key(|fill| (fill.time, fill.tid));
+
+    Ok(fills)
+}
+
+/// A batch of fills from the user fills subscription. The first message after subscribing is a
+/// snapshot of recent fills that have already been accounted for elsewhere.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct FillsUpdate {
+    pub is_snapshot: bool,
+    pub fills: Vec<Fill>,
+}
+
+pub struct UserFillsStream {
+    subscriptions: SubscriptionGuard,
+    fills_receiver: UnboundedReceiver<Message>,
+    counter: FeedCounter,
+}
+
+impl UserFillsStream {
+    /// Fills of `user` through a new mainnet connection.
+    pub async fn new(user: Address) -> Result<Self, Error> {
+        UserFillsStream::with_vault(user, None).await
+    }
+
+    /// Fills of `user`, or of `vault_address` if set, through a new mainnet connection.
+    pub async fn with_vault(user: Address, vault_address: Option<Address>) -> Result<Self, Error> {
+        UserFillsStream::with_transport(Arc::new(LiveTransport::new().await?), user, vault_address)
+            .await
+    }
+
+    pub async fn with_transport(
+        transport: Arc<dyn Transport>,
+        user: Address,
+        vault_address: Option<Address>,
+    ) -> Result<Self, Error> {
+        let user = get_account_address(user, vault_address);
+        let mut subscriptions = SubscriptionGuard::with_transport(transport);
+
+        let (sender, receiver) = unbounded_channel();
+        subscriptions
+            .subscribe(Subscription::UserFills { user }, sender)
+            .await?;
+
+        Ok(UserFillsStream {
+            subscriptions,
+            fills_receiver: receiver,
+            counter: FeedCounter::new("fills"),
+        })
+    }
+
+    /// Counts on `counter` rather than on counters starting from zero.
+    pub fn with_counter(mut self, counter: FeedCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    pub fn get_counters(&self) -> FeedCounters {
+        self.counter.get_counters()
+    }
+
+    pub async fn get_next_fills(&mut self) -> anyhow::Result<Option<FillsUpdate>> {
+        let msg = self.fills_receiver.recv().await;
+        if msg.is_some() {
+            self.counter.count(Counter::Received);
+        }
+
+        match msg {
+            Some(msg) => match msg {
+                Message::NoData => {
+                    error!("Couldn't recieve fills data");
+                    Err(anyhow::anyhow!("No data found"))
+                }
+                Message::HyperliquidError(err) => {
+                    error!("Hyperliquid error while getting fills data: {err:?}");
+                    Err(anyhow::anyhow!("Hyperliquid error found"))
+                }
+                Message::UserFills(user_fills) => {
+                    let is_snapshot = user_fills.data.is_snapshot.unwrap_or(false);
+                    let received = user_fills.data.fills.len();
+                    let fills: Vec<Fill> = user_fills
+                        .data
+                        .fills
+                        .into_iter()
+                        .filter_map(|trade| match Fill::try_from(trade) {
+                            Ok(fill) => Some(fill),
+                            Err(err) => {
+                                error!("Skipping malformed fill: {err:?}");
+                                None
+                            }
+                        })
+                        .collect();
+                    self.counter.add(
+                        Counter::DeserializeFailures,
+                        (received - fills.len()) as u64,
+                    );
+
+                    // The snapshot holds past fills, only live ones say how late the feed is
+                    if !is_snapshot {
+                        if let Some(time) = fills.iter().map(|fill| fill.time).max() {
+                            record_exchange_time("fills", time);
+                        }
+                    }
+
+                    Ok(Some(FillsUpdate { is_snapshot, fills }))
+                }
+                s => {
+                    error!("Got something else: {s:?}");
+                    self.counter.count(Counter::Dropped);
+                    Ok(None)
+                }
+            },
+            None => Err(anyhow::anyhow!("Fills channel closed")),
+        }
+    }
+
+    pub async fn unsub(&mut self) -> anyhow::Result<()> {
+        self.subscriptions.unsubscribe_all().await
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{Context, Error};
+use futures::future::BoxFuture;
+use hyperliquid_rust_sdk::{Message, Subscription};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tracing::{error, info};
+
+use crate::transport::{FakeTransport, Transport};
+
+/// Wraps a transport and, while capturing is enabled, writes the raw payloads going through it to
+/// `dir`: info responses to `<request type>.json` (latest one wins) and AllMids and L2Book
+/// messages to `<channel>.jsonl`, one message per line.
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    dir: PathBuf,
+    is_capturing: Arc<AtomicBool>,
+}
+
+impl RecordingTransport {
+    /// Starts with capturing enabled.
+    pub fn new(inner: Arc<dyn Transport>, dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Couldn't create the fixture dir {dir:?}"))?;
+
+        Ok(RecordingTransport {
+            inner,
+            dir,
+            is_capturing: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    pub fn set_capturing(&self, is_capturing: bool) {
+        self.is_capturing.store(is_capturing, Ordering::SeqCst);
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.is_capturing.load(Ordering::SeqCst)
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn post_info(&self, request: Value) -> BoxFuture<'_, Result<Value, Error>> {
+        Box::pin(async move {
+            let request_type = request["type"].as_str().unwrap_or("unknown").to_string();
+            let response = self.inner.post_info(request).await?;
+
+            if self.is_capturing() {
+                let path = self.dir.join(format!("{request_type}.json"));
+                if let Err(err) = fs::write(&path, serde_json::to_vec_pretty(&response)?) {
+                    error!("Couldn't write fixture {path:?}: {err:?}");
+                }
+            }
+
+            Ok(response)
+        })
+    }
+
+    fn subscribe(
+        &self,
+        subscription: Subscription,
+        sender: UnboundedSender<Message>,
+    ) -> BoxFuture<'_, Result<u32, Error>> {
+        let (recording_sender, mut recording_receiver) = unbounded_channel::<Message>();
+        let dir = self.dir.clone();
+        let is_capturing = self.is_capturing.clone();
+
+        // Ends once the inner transport drops its sender on unsubscribe
+        tokio::spawn(async move {
+            while let Some(message) = recording_receiver.recv().await {
+                if is_capturing.load(Ordering::SeqCst) {
+                    if let Some((channel, value)) = get_message_fixture(&message) {
+                        if let Err(err) = append_line(&dir.join(format!("{channel}.jsonl")), &value)
+                        {
+                            error!("Couldn't record a {channel} message: {err:?}");
+                        }
+                    }
+                }
+
+                if sender.send(message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        self.inner.subscribe(subscription, recording_sender)
+    }
+
+    fn unsubscribe(&self, sub_id: u32) -> BoxFuture<'_, Result<(), Error>> {
+        self.inner.unsubscribe(sub_id)
+    }
+}
+
+/// The SDK's messages can't be serialized, so the captured ones are rebuilt in the wire format.
+fn get_message_fixture(message: &Message) -> Option<(&'static str, Value)> {
+    match message {
+        Message::AllMids(all_mids) => Some((
+            "allMids",
+            json!({ "channel": "allMids", "data": { "mids": all_mids.data.mids } }),
+        )),
+        Message::L2Book(l2_book) => {
+            let levels: Vec<Vec<Value>> = l2_book
+                .data
+                .levels
+                .iter()
+                .map(|side| {
+                    side.iter()
+                        .map(|level| json!({ "px": level.px, "sz": level.sz, "n": level.n }))
+                        .collect()
+                })
+                .collect();
+
+            Some((
+                "l2Book",
+                json!({
+                    "channel": "l2Book",
+                    "data": {
+                        "coin": l2_book.data.coin,
+                        "time": l2_book.data.time,
+                        "levels": levels,
+                    }
+                }),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn append_line(path: &Path, value: &Value) -> Result<(), Error> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(value)?)?;
+
+    Ok(())
+}
+
+/// Deserializes a captured info response, e.g. `load_fixture::<PerpsMeta>("fixtures/meta.json")`.
+pub fn load_fixture<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, Error> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_context(|| format!("Couldn't read the fixture {path:?}"))?;
+
+    serde_json::from_slice(&bytes).with_context(|| format!("Couldn't deserialize {path:?}"))
+}
+
+/// Deserializes captured websocket messages, in the order they were received.
+pub fn load_message_fixtures(path: impl AsRef<Path>) -> Result<Vec<Message>, Error> {
+    let path = path.as_ref();
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Couldn't read the fixture {path:?}"))?;
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Couldn't deserialize line {} of {path:?}", i + 1))
+        })
+        .collect()
+}
+
+impl FakeTransport {
+    /// Answers info requests with every `<request type>.json` in `dir`. Messages are replayed
+    /// with [`load_message_fixtures`] and [`FakeTransport::push`].
+    pub fn from_fixtures(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let transport = FakeTransport::new();
+
+        for entry in fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+
+            let request_type = match (path.file_stem(), path.extension()) {
+                (Some(stem), Some(ext)) if ext == "json" => stem.to_string_lossy().to_string(),
+                _ => continue,
+            };
+
+            info!("Loading fixture {path:?}");
+            transport.set_response(&request_type, load_fixture(&path)?);
+        }
+
+        Ok(transport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use serde_json::json;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use crate::{
+        price_data::perps::PerpsMeta,
+        transport::{FakeTransport, Transport},
+    };
+
+    use super::{load_fixture, load_message_fixtures, RecordingTransport};
+
+    #[tokio::test]
+    async fn captured_payloads_replay() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("hl_fixtures_{}", std::process::id()));
+
+        let fake = Arc::new(FakeTransport::new());
+        fake.set_response(
+            "meta",
+            json!({ "universe": [{ "name": "ETH", "szDecimals": 4, "maxLeverage": 25 }] }),
+        );
+
+        let recording = RecordingTransport::new(fake.clone(), &dir)?;
+        recording.post_info(json!({ "type": "meta" })).await?;
+
+        let (sender, mut receiver) = unbounded_channel();
+        recording
+            .subscribe(hyperliquid_rust_sdk::Subscription::AllMids, sender)
+            .await?;
+        fake.push(serde_json::from_value(json!({
+            "channel": "allMids",
+            "data": { "mids": { "ETH": "2000.5" } }
+        }))?);
+        receiver.recv().await.unwrap();
+
+        let meta: PerpsMeta = load_fixture(dir.join("meta.json"))?;
+        assert_eq!(meta.get_coin_names(), vec!["ETH".to_string()]);
+        assert_eq!(load_message_fixtures(dir.join("allMids.jsonl"))?.len(), 1);
+
+        let replay = FakeTransport::from_fixtures(&dir)?;
+        assert!(replay.post_info(json!({ "type": "meta" })).await.is_ok());
+
+        fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    events::{emit, ConnectionEvents, FeedEventKind},
+    trades::{Trade, TradesStream},
+};
+
+pub type CoinToOrderFlowMap = HashMap<String, OrderFlowImbalance>;
+
+/// Taker volume over a window, in units of the coin.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct OrderFlowImbalance {
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub trades: u64,
+}
+
+impl OrderFlowImbalance {
+    /// Taker buy volume minus taker sell volume
+    pub fn get_imbalance(&self) -> f64 {
+        self.buy_volume - self.sell_volume
+    }
+
+    /// Imbalance over total volume, between -1.0 and 1.0. 0.0 when nothing traded.
+    pub fn get_imbalance_ratio(&self) -> f64 {
+        let total = self.buy_volume + self.sell_volume;
+
+        if total > 0.0 {
+            self.get_imbalance() / total
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Keeps the trades of the last `window` per coin.
+#[derive(Clone, Debug)]
+pub struct OrderFlowTracker {
+    window_ms: u64,
+    trades: HashMap<String, VecDeque<Trade>>,
+}
+
+impl OrderFlowTracker {
+    pub fn new(window: Duration) -> Self {
+        OrderFlowTracker {
+            window_ms: window.as_millis() as u64,
+            trades: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, trade: Trade) {
+        let now = trade.time;
+        self.trades
+            .entry(trade.coin.clone())
+            .or_default()
+            .push_back(trade);
+        self.evict(now);
+    }
+
+    /// Drops every trade older than the window relative to `now` (ms since epoch).
+    pub fn evict(&mut self, now: u64) {
+        let cutoff = now.saturating_sub(self.window_ms);
+
+        for trades in self.trades.values_mut() {
+            while trades.front().is_some_and(|trade| trade.time < cutoff) {
+                trades.pop_front();
+            }
+        }
+    }
+
+    pub fn get_imbalance(&self, coin: &str) -> OrderFlowImbalance {
+        let mut flow = OrderFlowImbalance::default();
+
+        for trade in self.trades.get(coin).into_iter().flatten() {
+            if trade.is_buy {
+                flow.buy_volume += trade.size;
+            } else {
+                flow.sell_volume += trade.size;
+            }
+            flow.trades += 1;
+        }
+
+        flow
+    }
+
+    pub fn get_imbalance_map(&self) -> CoinToOrderFlowMap {
+        self.trades
+            .keys()
+            .map(|coin| (coin.clone(), self.get_imbalance(coin)))
+            .collect()
+    }
+}
+
+/// Publishes the order flow imbalance of `coins` over the last `window`. The map is republished
+/// on every batch of trades and every second so that quiet coins decay.
+pub async fn start_order_flow_task(
+    coins: Vec<String>,
+    window: Duration,
+) -> anyhow::Result<watch::Receiver<CoinToOrderFlowMap>> {
+    let (flow_sender, flow_recv) = watch::channel(CoinToOrderFlowMap::new());
+
+    tokio::spawn(async move {
+        let f_s = flow_sender;
+        let mut tracker = OrderFlowTracker::new(window);
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("order_flow_task: Starting...");
+
+            let mut trades_stream = match TradesStream::new(&coins).await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Error while getting TradesStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("order_flow_task");
+
+            let err = loop {
+                tokio::select! {
+                    trades = trades_stream.get_next_trades() => match trades {
+                        Ok(Some(trades)) => trades.into_iter().for_each(|trade| tracker.push(trade)),
+                        Ok(None) => continue,
+                        Err(err) => break err,
+                    },
+                    _ = interval.tick() => {
+                        tracker.evict(chrono::Utc::now().timestamp_millis() as u64);
+                    }
+                }
+
+                if f_s.send(tracker.get_imbalance_map()).is_err() {
+                    info!("order_flow_task: All receivers dropped, stopping...");
+                    let _ = trades_stream.unsub().await;
+                    return;
+                }
+            };
+
+            error!("order_flow_task: Error: {err:?}");
+            emit(
+                "order_flow_task",
+                FeedEventKind::SubscriptionDropped {
+                    reason: format!("{err:?}"),
+                },
+            );
+            info!("order_flow_task: Resetting...");
+
+            let _ = trades_stream.unsub().await;
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(flow_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::trades::Trade;
+
+    use super::OrderFlowTracker;
+
+    fn trade(is_buy: bool, size: f64, time: u64) -> Trade {
+        Trade {
+            coin: "BTC".to_string(),
+            is_buy,
+            price: 100.0,
+            size,
+            time,
+            tid: time,
+        }
+    }
+
+    #[test]
+    fn imbalance_over_window() {
+        let mut tracker = OrderFlowTracker::new(Duration::from_secs(10));
+
+        tracker.push(trade(true, 3.0, 0));
+        tracker.push(trade(false, 1.0, 5_000));
+        assert_eq!(tracker.get_imbalance("BTC").get_imbalance(), 2.0);
+        assert_eq!(tracker.get_imbalance("BTC").get_imbalance_ratio(), 0.5);
+
+        // The first buy falls out of the window
+        tracker.push(trade(false, 1.0, 12_000));
+        let flow = tracker.get_imbalance("BTC");
+        assert_eq!(flow.get_imbalance(), -2.0);
+        assert_eq!(flow.trades, 2);
+    }
+}
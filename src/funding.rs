@@ -0,0 +1,349 @@
+use std::{collections::HashMap, time::Duration};
+
+#[cfg(feature = "live")]
+use alloy::primitives::Address;
+#[cfg(feature = "live")]
+use anyhow::Error;
+#[cfg(feature = "live")]
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use serde_json::json;
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+#[cfg(feature = "live")]
+use crate::{pagination::get_all_pages, price_data::perps::NameToCtxMap, prices::post_info};
+use crate::{
+    price_data::perps::{parse_string_to_float, PerpsAssetCtx},
+    types::NameToPriceMap,
+};
+
+/// Funding is paid every hour on Hyperliquid
+pub const FUNDING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Hourly funding rate per perp coin
+pub type CoinToFundingRateMap = HashMap<String, f64>;
+
+/// The next funding settlement strictly after `now`, both in ms since epoch
+pub fn get_next_funding_time(now: i64) -> i64 {
+    let interval_ms = FUNDING_INTERVAL.as_millis() as i64;
+
+    now - now.rem_euclid(interval_ms) + interval_ms
+}
+
+/// Time left until the next funding settlement after `now` (ms since epoch)
+pub fn get_time_to_next_funding(now: i64) -> Duration {
+    Duration::from_millis((get_next_funding_time(now) - now) as u64)
+}
+
+pub fn get_funding_rate_map(ctxs: &HashMap<String, PerpsAssetCtx>) -> CoinToFundingRateMap {
+    ctxs.iter()
+        .map(|(coin, ctx)| (coin.clone(), ctx.funding))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FundingPayment {
+    /// Funding time in ms since epoch
+    pub time: i64,
+    pub payment: f64,
+    pub cumulative: f64,
+}
+
+/// Funding expected on a position if the current rate and price hold. Payments are in USD and
+/// positive when received.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FundingEstimate {
+    pub coin: String,
+    /// Negative for shorts
+    pub size: f64,
+    pub price: f64,
+    pub funding_rate: f64,
+    pub payment_per_interval: f64,
+    pub intervals: u32,
+    pub cumulative_payment: f64,
+}
+
+impl FundingEstimate {
+    /// One payment per funding time after `now` (ms since epoch) within the horizon.
+    pub fn get_payments(&self, now: i64) -> Vec<FundingPayment> {
+        let interval_ms = FUNDING_INTERVAL.as_millis() as i64;
+        let next_funding = get_next_funding_time(now);
+
+        (0..self.intervals)
+            .map(|i| FundingPayment {
+                time: next_funding + i as i64 * interval_ms,
+                payment: self.payment_per_interval,
+                cumulative: self.payment_per_interval * (i + 1) as f64,
+            })
+            .collect()
+    }
+}
+
+/// Estimates the funding of a `size` position in `coin` over `horizon`, rounded down to whole
+/// funding intervals. `None` if the coin has no funding rate or no price.
+pub fn estimate_funding(
+    coin: &str,
+    size: f64,
+    is_long: bool,
+    horizon: Duration,
+    funding_rates: &CoinToFundingRateMap,
+    prices: &NameToPriceMap,
+) -> Option<FundingEstimate> {
+    let funding_rate = *funding_rates.get(coin)?;
+    let price = prices
+        .get(coin)
+        .map(|price| price.get_value())
+        .filter(|price| *price > 0.0)?;
+
+    let size = if is_long { size.abs() } else { -size.abs() };
+    // Longs pay shorts when the rate is positive
+    let payment_per_interval = -size * price * funding_rate;
+    let intervals = (horizon.as_secs() / FUNDING_INTERVAL.as_secs()) as u32;
+
+    Some(FundingEstimate {
+        coin: coin.to_string(),
+        size,
+        price,
+        funding_rate,
+        payment_per_interval,
+        intervals,
+        cumulative_payment: payment_per_interval * intervals as f64,
+    })
+}
+
+/// The next funding settlement of a position and what it would pay at the current rate and price.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NextFunding {
+    /// ms since epoch
+    pub time: i64,
+    pub time_left: Duration,
+    /// USD, positive when received
+    pub payment: f64,
+}
+
+/// The next funding of a `size` position in `coin` after `now` (ms since epoch), e.g. to decide
+/// whether to close before it. `None` if the coin has no funding rate or no price.
+pub fn get_next_funding(
+    coin: &str,
+    size: f64,
+    is_long: bool,
+    now: i64,
+    funding_rates: &CoinToFundingRateMap,
+    prices: &NameToPriceMap,
+) -> Option<NextFunding> {
+    let estimate = estimate_funding(coin, size, is_long, FUNDING_INTERVAL, funding_rates, prices)?;
+
+    Some(NextFunding {
+        time: get_next_funding_time(now),
+        time_left: get_time_to_next_funding(now),
+        payment: estimate.payment_per_interval,
+    })
+}
+
+/// Most records the `userFunding` info request returns at once
+pub const USER_FUNDING_PAGE_SIZE: usize = 500;
+
+/// A funding payment of a position, as returned by the `userFunding` info request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserFunding {
+    /// ms since epoch
+    pub time: i64,
+    pub hash: String,
+    pub coin: String,
+    /// USD paid, positive when received
+    pub usdc: f64,
+    /// Signed position size at the time, negative for shorts
+    pub size: f64,
+    pub funding_rate: f64,
+    /// Set for positions too small to be charged every hour, in which case the payment covers
+    /// that many hours
+    pub n_samples: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FundingDelta {
+    coin: String,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    usdc: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    szi: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    funding_rate: f64,
+    n_samples: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FundingUpdate {
+    time: i64,
+    hash: String,
+    delta: FundingDelta,
+}
+
+impl From<FundingUpdate> for UserFunding {
+    fn from(update: FundingUpdate) -> Self {
+        UserFunding {
+            time: update.time,
+            hash: update.hash,
+            coin: update.delta.coin,
+            usdc: update.delta.usdc,
+            size: update.delta.szi,
+            funding_rate: update.delta.funding_rate,
+            n_samples: update.delta.n_samples,
+        }
+    }
+}
+
+/// Net funding per coin over `payments`, to separate it from price PnL.
+pub fn get_funding_by_coin(payments: &[UserFunding]) -> HashMap<String, f64> {
+    let mut funding: HashMap<String, f64> = HashMap::new();
+
+    for payment in payments {
+        *funding.entry(payment.coin.clone()).or_default() += payment.usdc;
+    }
+
+    funding
+}
+
+/// Every funding payment of `user` between `start_time` and `end_time` (ms since epoch, now if
+/// `None`), oldest first. Requests more pages until the range is covered, see
+/// [`get_all_pages`].
+#[cfg(feature = "live")]
+pub async fn get_user_funding(
+    client: &Client,
+    user: Address,
+    start_time: i64,
+    end_time: Option<i64>,
+) -> Result<Vec<UserFunding>, Error> {
+    // Every position is paid at the same time, so a page can end in the middle of a settlement
+    let updates = get_all_pages(
+        start_time,
+        USER_FUNDING_PAGE_SIZE,
+        |cursor| {
+            let mut request = json!({ "type": "userFunding", "user": user, "startTime": cursor });
+            if let Some(end_time) = end_time {
+                request["endTime"] = json!(end_time);
+            }
+
+            post_info::<Vec<FundingUpdate>>(client, request)
+        },
+        |update| update.time,
+        |update| (update.time, update.delta.coin.clone()),
+    )
+    .await?;
+
+    Ok(updates.into_iter().map(UserFunding::from).collect())
+}
+
+/// Publishes the funding rate of every perp every time the asset contexts of `ctx_receiver`
+/// (see [`crate::prices::start_asset_ctx_task`]) change.
+#[cfg(feature = "live")]
+pub async fn start_funding_rate_task(
+    mut ctx_receiver: watch::Receiver<NameToCtxMap>,
+) -> anyhow::Result<watch::Receiver<CoinToFundingRateMap>> {
+    let (rate_sender, rate_recv) = watch::channel(CoinToFundingRateMap::new());
+
+    // The contexts already published are used right away
+    ctx_receiver.mark_changed();
+
+    tokio::spawn(async move {
+        info!("funding_rate_task: Starting...");
+
+        loop {
+            if ctx_receiver.changed().await.is_err() {
+                info!("funding_rate_task: Asset context channel closed, stopping...");
+                return;
+            }
+
+            let rates = get_funding_rate_map(&ctx_receiver.borrow_and_update());
+
+            if rate_sender.send(rates).is_err() {
+                info!("funding_rate_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(rate_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use crate::types::{Meta, NameToPriceMap, Price, Symbol};
+
+    use super::{
+        estimate_funding, get_funding_by_coin, get_next_funding, get_time_to_next_funding,
+        FundingUpdate, UserFunding,
+    };
+
+    #[test]
+    fn short_receives_positive_funding() {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 4,
+            max_leverage: 50,
+            only_isolated: None,
+            is_delisted: None,
+        };
+        let prices: NameToPriceMap = [(Symbol::from_api("ETH"), Price::new_perp(2000.0, meta))]
+            .into_iter()
+            .collect();
+        let rates = HashMap::from([("ETH".to_string(), 0.0001)]);
+
+        let estimate = estimate_funding(
+            "ETH",
+            2.0,
+            false,
+            Duration::from_secs(24 * 60 * 60),
+            &rates,
+            &prices,
+        )
+        .unwrap();
+
+        assert!((estimate.payment_per_interval - 0.4).abs() < 1e-9);
+        assert_eq!(estimate.intervals, 24);
+        assert!((estimate.cumulative_payment - 9.6).abs() < 1e-9);
+
+        let payments = estimate.get_payments(1_800_000);
+        assert_eq!(payments[0].time, 3_600_000);
+        assert_eq!(payments.len(), 24);
+
+        let next = get_next_funding("ETH", 2.0, true, 3_000_000, &rates, &prices).unwrap();
+        assert_eq!(next.time, 3_600_000);
+        assert_eq!(next.time_left, Duration::from_secs(600));
+        assert!((next.payment + 0.4).abs() < 1e-9);
+
+        // Right on a settlement, the next one is a full interval away
+        assert_eq!(
+            get_time_to_next_funding(3_600_000),
+            Duration::from_secs(3600)
+        );
+    }
+
+    #[test]
+    fn parses_user_funding() {
+        let updates: Vec<FundingUpdate> = serde_json::from_str(
+            r#"[
+                {"time": 1681923600000, "hash": "0x00", "delta": {"type": "funding", "coin": "ETH", "usdc": "-3.625312", "szi": "49.1477", "fundingRate": "0.0000417", "nSamples": null}},
+                {"time": 1681927200000, "hash": "0x00", "delta": {"type": "funding", "coin": "ETH", "usdc": "1.5", "szi": "49.1477", "fundingRate": "-0.0000172", "nSamples": null}},
+                {"time": 1681927200000, "hash": "0x00", "delta": {"type": "funding", "coin": "BTC", "usdc": "2.0", "szi": "-0.5", "fundingRate": "0.00001", "nSamples": 8}}
+            ]"#,
+        )
+        .unwrap();
+
+        let payments: Vec<UserFunding> = updates.into_iter().map(UserFunding::from).collect();
+        assert_eq!(payments[0].size, 49.1477);
+        assert_eq!(payments[2].n_samples, Some(8));
+
+        let funding = get_funding_by_coin(&payments);
+        assert!((funding["ETH"] + 2.125312).abs() < 1e-9);
+        assert_eq!(funding["BTC"], 2.0);
+    }
+}
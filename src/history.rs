@@ -0,0 +1,302 @@
+use std::collections::{HashMap, VecDeque};
+
+#[cfg(feature = "live")]
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+use crate::types::{NameToPriceMap, Orderbook};
+
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct PricePoint {
+    /// Unix milliseconds
+    pub time: i64,
+    pub price: f64,
+}
+
+/// Fixed capacity per coin ring buffers of prices, oldest first. A capacity of 0 keeps nothing.
+#[derive(Clone, Debug, Default)]
+pub struct PriceHistory {
+    capacity: usize,
+    map: HashMap<String, VecDeque<PricePoint>>,
+}
+
+impl PriceHistory {
+    pub fn new(capacity: usize) -> Self {
+        PriceHistory {
+            capacity,
+            map: HashMap::new(),
+        }
+    }
+
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&mut self, coin: &str, point: PricePoint) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let points = self
+            .map
+            .entry(coin.to_string())
+            .or_insert_with(|| VecDeque::with_capacity(self.capacity));
+
+        if points.len() == self.capacity {
+            points.pop_front();
+        }
+
+        points.push_back(point);
+    }
+
+    /// Records every non-zero price in the map at `time`.
+    pub fn record(&mut self, prices: &NameToPriceMap, time: i64) {
+        for (coin, price) in prices.iter() {
+            let price = price.get_value();
+
+            if price > 0.0 {
+                self.push(coin, PricePoint { time, price });
+            }
+        }
+    }
+
+    pub fn get(&self, coin: &str) -> Option<&VecDeque<PricePoint>> {
+        self.map.get(coin)
+    }
+
+    pub fn get_prices(&self, coin: &str) -> Vec<f64> {
+        self.get(coin)
+            .map(|points| points.iter().map(|point| point.price).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_coins(&self) -> Vec<&String> {
+        self.map.keys().collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &VecDeque<PricePoint>)> {
+        self.map.iter()
+    }
+}
+
+/// Liquidity of one book at one time
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct DepthPoint {
+    /// Unix milliseconds
+    pub time: i64,
+    pub spread_bps: f64,
+    /// USD notional (bid, ask) within each of the history's bands, in the same order
+    pub depth: Vec<(f64, f64)>,
+}
+
+impl DepthPoint {
+    /// `None` if the book is empty on either side
+    pub fn from_book(book: &Orderbook, bands_bps: &[f64], time: i64) -> Option<Self> {
+        let spread_bps = book.get_spread()? / book.get_mid()? * 10_000.0;
+
+        Some(DepthPoint {
+            time,
+            spread_bps,
+            depth: bands_bps
+                .iter()
+                .map(|bps| book.get_depth_within_bps(*bps))
+                .collect(),
+        })
+    }
+
+    /// Bid plus ask notional within the `band`th band
+    pub fn get_total_depth(&self, band: usize) -> Option<f64> {
+        self.depth.get(band).map(|(bid, ask)| bid + ask)
+    }
+}
+
+/// Fixed capacity per coin ring buffers of spread and depth within `bands_bps` of the mid,
+/// oldest first. Much lighter than recording full books when only the liquidity regime matters.
+/// A capacity of 0 keeps nothing.
+#[derive(Clone, Debug, Default)]
+pub struct DepthHistory {
+    capacity: usize,
+    bands_bps: Vec<f64>,
+    map: HashMap<String, VecDeque<DepthPoint>>,
+}
+
+impl DepthHistory {
+    pub fn new(capacity: usize, bands_bps: Vec<f64>) -> Self {
+        DepthHistory {
+            capacity,
+            bands_bps,
+            map: HashMap::new(),
+        }
+    }
+
+    pub fn get_bands_bps(&self) -> &[f64] {
+        &self.bands_bps
+    }
+
+    /// Samples `book`, skipped if it's empty on either side.
+    pub fn record(&mut self, book: &Orderbook, time: i64) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let point = match DepthPoint::from_book(book, &self.bands_bps, time) {
+            Some(point) => point,
+            None => return,
+        };
+
+        let points = self
+            .map
+            .entry(book.coin.clone())
+            .or_insert_with(|| VecDeque::with_capacity(self.capacity));
+
+        if points.len() == self.capacity {
+            points.pop_front();
+        }
+
+        points.push_back(point);
+    }
+
+    pub fn get(&self, coin: &str) -> Option<&VecDeque<DepthPoint>> {
+        self.map.get(coin)
+    }
+
+    /// The last `count` points of `coin`, oldest first
+    pub fn get_recent(&self, coin: &str, count: usize) -> Vec<DepthPoint> {
+        self.get(coin)
+            .map(|points| {
+                points
+                    .iter()
+                    .skip(points.len().saturating_sub(count))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_spreads_bps(&self, coin: &str) -> Vec<f64> {
+        self.get(coin)
+            .map(|points| points.iter().map(|point| point.spread_bps).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_coins(&self) -> Vec<&String> {
+        self.map.keys().collect()
+    }
+}
+
+/// Records every update of `price_receiver` into a [`PriceHistory`] keeping the last `capacity`
+/// points per coin.
+#[cfg(feature = "live")]
+pub async fn start_price_history_task(
+    mut price_receiver: watch::Receiver<NameToPriceMap>,
+    capacity: usize,
+) -> anyhow::Result<watch::Receiver<PriceHistory>> {
+    let (history_sender, history_recv) = watch::channel(PriceHistory::new(capacity));
+
+    tokio::spawn(async move {
+        while price_receiver.changed().await.is_ok() {
+            let time = Utc::now().timestamp_millis();
+
+            history_sender.send_modify(|history| {
+                history.record(&price_receiver.borrow_and_update(), time);
+            });
+
+            if history_sender.is_closed() {
+                info!("price_history_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+
+        info!("price_history_task: Price channel closed, stopping...");
+    });
+
+    Ok(history_recv)
+}
+
+/// Samples the books of `coins` (every coin if empty) into a [`DepthHistory`] every `interval`,
+/// keeping the last `capacity` points per coin.
+#[cfg(feature = "live")]
+pub async fn start_depth_history_task(
+    book_receiver: watch::Receiver<crate::types::NameToOrderbookMap>,
+    coins: Vec<String>,
+    bands_bps: Vec<f64>,
+    interval: std::time::Duration,
+    capacity: usize,
+) -> anyhow::Result<watch::Receiver<DepthHistory>> {
+    let (history_sender, history_recv) = watch::channel(DepthHistory::new(capacity, bands_bps));
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+
+        info!("depth_history_task: Starting...");
+
+        loop {
+            interval.tick().await;
+
+            let time = Utc::now().timestamp_millis();
+
+            history_sender.send_modify(|history| {
+                for (coin, book) in book_receiver.borrow().iter() {
+                    if coins.is_empty() || coins.iter().any(|c| c == coin.as_str()) {
+                        history.record(book, time);
+                    }
+                }
+            });
+
+            if history_sender.is_closed() {
+                info!("depth_history_task: All receivers dropped, stopping...");
+                return;
+            }
+
+            if book_receiver.has_changed().is_err() {
+                info!("depth_history_task: Book channel closed, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(history_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{BookLevel, Orderbook};
+
+    use super::DepthHistory;
+
+    #[test]
+    fn keeps_the_last_depth_points() {
+        let level = |price: f64, size: f64| BookLevel {
+            price,
+            size,
+            orders: 1,
+        };
+        let book = Orderbook {
+            coin: "ETH".to_string(),
+            time: 0,
+            bids: vec![level(99.9, 1.0), level(99.0, 1.0)],
+            asks: vec![level(100.1, 2.0)],
+        };
+
+        let mut history = DepthHistory::new(2, vec![5.0, 200.0]);
+        for time in 0..3 {
+            history.record(&book, time);
+        }
+        history.record(&Orderbook::default(), 3);
+
+        let mut empty = DepthHistory::new(0, vec![5.0]);
+        empty.record(&book, 0);
+        assert!(empty.get("ETH").is_none());
+
+        let points = history.get_recent("ETH", 5);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].time, 1);
+        assert!((points[1].spread_bps - 20.0).abs() < 1e-9);
+        assert_eq!(points[1].depth[0], (0.0, 0.0));
+        assert!((points[1].get_total_depth(1).unwrap() - (99.9 + 99.0 + 200.2)).abs() < 1e-9);
+    }
+}
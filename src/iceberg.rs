@@ -0,0 +1,336 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        watch,
+    },
+    time::sleep,
+};
+use tracing::{error, info};
+
+use crate::{
+    events::{emit, ConnectionEvents, FeedEventKind},
+    trades::{Trade, TradesStream},
+    types::{NameToOrderbookMap, Orderbook},
+};
+
+/// A level that refilled after being traded against at least `min_refills` times.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IcebergEvent {
+    pub coin: String,
+    pub is_bid: bool,
+    pub price: f64,
+    /// Book time of the refill that triggered the event, ms since epoch
+    pub time: u64,
+    pub refills: u32,
+    /// Volume traded at the level since it was first seen
+    pub traded_volume: f64,
+    /// Size that reappeared after trades, summed over the refills
+    pub refilled_volume: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IcebergConfig {
+    pub min_refills: u32,
+    /// Only the first `levels` levels of each side are watched
+    pub levels: usize,
+    /// Refills smaller than this share of the traded size are ignored as noise
+    pub min_refill_ratio: f64,
+}
+
+impl Default for IcebergConfig {
+    fn default() -> Self {
+        IcebergConfig {
+            min_refills: 3,
+            levels: 10,
+            min_refill_ratio: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct LevelState {
+    size: f64,
+    /// Traded since the last book
+    pending_traded: f64,
+    traded_volume: f64,
+    refills: u32,
+    refilled_volume: f64,
+}
+
+/// Flags levels that keep refilling after being consumed by comparing consecutive books with the
+/// trades in between.
+///
+/// Trades are matched to the first book at or after their time, whichever of the two arrives
+/// first. Trades older than the last book of their coin came too late to be matched and are
+/// skipped.
+///
+/// Purely a heuristic: new orders joining a level right after a trade look like a refill too. A
+/// level's history is dropped once it leaves the watched levels.
+#[derive(Clone, Debug)]
+pub struct IcebergDetector {
+    config: IcebergConfig,
+    /// By (coin, is_bid, price bits)
+    levels: HashMap<(String, bool, u64), LevelState>,
+    /// Trades newer than the last book of their coin, by coin
+    pending_trades: HashMap<String, Vec<Trade>>,
+    /// Time of the last book of every coin
+    book_times: HashMap<String, u64>,
+}
+
+impl IcebergDetector {
+    pub fn new(config: IcebergConfig) -> Self {
+        IcebergDetector {
+            config,
+            levels: HashMap::new(),
+            pending_trades: HashMap::new(),
+            book_times: HashMap::new(),
+        }
+    }
+
+    pub fn on_trade(&mut self, trade: &Trade) {
+        if self
+            .book_times
+            .get(&trade.coin)
+            .is_some_and(|book_time| trade.time <= *book_time)
+        {
+            return;
+        }
+
+        self.pending_trades
+            .entry(trade.coin.clone())
+            .or_default()
+            .push(trade.clone());
+    }
+
+    /// Adds the pending trades of `book`'s coin up to its time to the levels they hit.
+    fn apply_trades(&mut self, book: &Orderbook) {
+        let Some(trades) = self.pending_trades.get_mut(&book.coin) else {
+            return;
+        };
+
+        let (matched, pending): (Vec<Trade>, Vec<Trade>) = std::mem::take(trades)
+            .into_iter()
+            .partition(|trade| trade.time <= book.time);
+        *trades = pending;
+
+        for trade in matched {
+            // A taker buy lifts resting asks and the other way around
+            let key = (trade.coin, !trade.is_buy, trade.price.to_bits());
+
+            if let Some(level) = self.levels.get_mut(&key) {
+                level.pending_traded += trade.size;
+                level.traded_volume += trade.size;
+            }
+        }
+    }
+
+    /// Returns an event for every watched level that just refilled for at least the
+    /// `min_refills`th time.
+    pub fn on_book(&mut self, book: &Orderbook) -> Vec<IcebergEvent> {
+        self.apply_trades(book);
+        self.book_times.insert(book.coin.clone(), book.time);
+
+        let mut events = vec![];
+        let mut seen = vec![];
+
+        for (is_bid, side) in [(true, &book.bids), (false, &book.asks)] {
+            for book_level in side.iter().take(self.config.levels) {
+                let key = (book.coin.clone(), is_bid, book_level.price.to_bits());
+                let level = self.levels.entry(key.clone()).or_default();
+
+                if level.pending_traded > 0.0 {
+                    let expected = (level.size - level.pending_traded).max(0.0);
+                    let refill = book_level.size - expected;
+
+                    if refill >= level.pending_traded * self.config.min_refill_ratio {
+                        level.refills += 1;
+                        level.refilled_volume += refill;
+
+                        if level.refills >= self.config.min_refills {
+                            events.push(IcebergEvent {
+                                coin: book.coin.clone(),
+                                is_bid,
+                                price: book_level.price,
+                                time: book.time,
+                                refills: level.refills,
+                                traded_volume: level.traded_volume,
+                                refilled_volume: level.refilled_volume,
+                            });
+                        }
+                    }
+                }
+
+                level.size = book_level.size;
+                level.pending_traded = 0.0;
+                seen.push(key);
+            }
+        }
+
+        self.levels
+            .retain(|key, _| key.0 != book.coin || seen.contains(key));
+
+        events
+    }
+}
+
+/// Sends an [`IcebergEvent`] for every likely iceberg on the books of `book_receiver`, using the
+/// trades of `coins`. Stops once the receiver is dropped or the book channel closes.
+pub async fn start_iceberg_task(
+    mut book_receiver: watch::Receiver<NameToOrderbookMap>,
+    coins: Vec<String>,
+    config: IcebergConfig,
+) -> anyhow::Result<UnboundedReceiver<IcebergEvent>> {
+    let (event_sender, event_recv) = unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut detector = IcebergDetector::new(config);
+        let mut book_times: HashMap<String, u64> = HashMap::new();
+
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("iceberg_task: Starting...");
+
+            let mut trades_stream = match TradesStream::new(&coins).await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Error while getting TradesStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("iceberg_task");
+
+            let err = loop {
+                let events = tokio::select! {
+                    trades = trades_stream.get_next_trades() => match trades {
+                        Ok(Some(trades)) => {
+                            trades.iter().for_each(|trade| detector.on_trade(trade));
+                            continue;
+                        }
+                        Ok(None) => continue,
+                        Err(err) => break err,
+                    },
+                    changed = book_receiver.changed() => {
+                        if changed.is_err() {
+                            info!("iceberg_task: Book channel closed, stopping...");
+                            let _ = trades_stream.unsub().await;
+                            return;
+                        }
+
+                        book_receiver
+                            .borrow_and_update()
+                            .values()
+                            .filter(|book| coins.contains(&book.coin))
+                            .filter(|book| book_times.insert(book.coin.clone(), book.time) != Some(book.time))
+                            .flat_map(|book| detector.on_book(book))
+                            .collect::<Vec<IcebergEvent>>()
+                    }
+                };
+
+                if events
+                    .into_iter()
+                    .any(|event| event_sender.send(event).is_err())
+                {
+                    info!("iceberg_task: Event receiver dropped, stopping...");
+                    let _ = trades_stream.unsub().await;
+                    return;
+                }
+            };
+
+            error!("iceberg_task: Error: {err:?}");
+            emit(
+                "iceberg_task",
+                FeedEventKind::SubscriptionDropped {
+                    reason: format!("{err:?}"),
+                },
+            );
+            info!("iceberg_task: Resetting...");
+
+            let _ = trades_stream.unsub().await;
+            sleep(Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(event_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        trades::Trade,
+        types::{BookLevel, Orderbook},
+    };
+
+    use super::{IcebergConfig, IcebergDetector};
+
+    fn book(time: u64, ask_size: f64) -> Orderbook {
+        Orderbook {
+            coin: "ETH".to_string(),
+            time,
+            bids: vec![],
+            asks: vec![BookLevel {
+                price: 101.0,
+                size: ask_size,
+                orders: 1,
+            }],
+        }
+    }
+
+    fn buy(size: f64, time: u64) -> Trade {
+        Trade {
+            coin: "ETH".to_string(),
+            is_buy: true,
+            price: 101.0,
+            size,
+            time,
+            tid: time,
+        }
+    }
+
+    #[test]
+    fn flags_refilling_levels() {
+        let mut detector = IcebergDetector::new(IcebergConfig {
+            min_refills: 2,
+            ..Default::default()
+        });
+
+        assert!(detector.on_book(&book(0, 1.0)).is_empty());
+
+        // Fully taken, shows 1.0 again
+        detector.on_trade(&buy(1.0, 1));
+        assert!(detector.on_book(&book(2, 1.0)).is_empty());
+
+        detector.on_trade(&buy(1.0, 3));
+        let events = detector.on_book(&book(4, 1.0));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].refills, 2);
+        assert_eq!(events[0].traded_volume, 2.0);
+
+        // Consumed without refilling
+        detector.on_trade(&buy(0.5, 5));
+        assert!(detector.on_book(&book(6, 0.5)).is_empty());
+    }
+
+    #[test]
+    fn trades_are_matched_to_books_by_time() {
+        let mut detector = IcebergDetector::new(IcebergConfig {
+            min_refills: 1,
+            ..Default::default()
+        });
+
+        assert!(detector.on_book(&book(0, 1.0)).is_empty());
+
+        // Arrives before the book at 2 but happened after it, so it belongs to the book at 4
+        detector.on_trade(&buy(1.0, 3));
+        assert!(detector.on_book(&book(2, 1.0)).is_empty());
+        assert_eq!(detector.on_book(&book(4, 1.0)).len(), 1);
+
+        // Already reflected by the book at 4
+        detector.on_trade(&buy(1.0, 4));
+        assert!(detector.on_book(&book(6, 1.0)).is_empty());
+    }
+}
@@ -0,0 +1,692 @@
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "live")]
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+use crate::candles::Candle;
+#[cfg(feature = "live")]
+use crate::{candles::CandleBuffer, types::NameToPriceMap};
+
+pub type CoinToIndicatorValuesMap = HashMap<String, IndicatorValues>;
+
+/// Exponential moving average seeded with the first value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        Ema {
+            alpha: 2.0 / (period.max(1) as f64 + 1.0),
+            value: None,
+        }
+    }
+
+    pub fn get_value(&self) -> Option<f64> {
+        self.value
+    }
+
+    /// The value `update` would return, without changing the average
+    pub fn peek(&self, value: f64) -> f64 {
+        match self.value {
+            Some(prev) => prev + self.alpha * (value - prev),
+            None => value,
+        }
+    }
+
+    pub fn update(&mut self, value: f64) -> f64 {
+        let ema = self.peek(value);
+
+        self.value = Some(ema);
+        ema
+    }
+}
+
+/// Wilder's RSI over `period` changes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rsi {
+    period: usize,
+    prev: Option<f64>,
+    changes: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Rsi {
+            period: period.max(1),
+            prev: None,
+            changes: 0,
+            avg_gain: 0.0,
+            avg_loss: 0.0,
+        }
+    }
+
+    fn get_rsi(&self, changes: usize, avg_gain: f64, avg_loss: f64) -> Option<f64> {
+        if changes < self.period {
+            return None;
+        }
+
+        if avg_loss == 0.0 {
+            return Some(if avg_gain == 0.0 { 50.0 } else { 100.0 });
+        }
+
+        Some(100.0 - 100.0 / (1.0 + avg_gain / avg_loss))
+    }
+
+    pub fn get_value(&self) -> Option<f64> {
+        self.get_rsi(self.changes, self.avg_gain, self.avg_loss)
+    }
+
+    /// Changes and average gain and loss after `close`, `None` for the first close
+    fn step(&self, close: f64) -> Option<(usize, f64, f64)> {
+        let change = close - self.prev?;
+        let (gain, loss) = (change.max(0.0), (-change).max(0.0));
+        let changes = self.changes + 1;
+
+        // Simple average over the first period, smoothed after
+        let weight = changes.min(self.period) as f64;
+
+        Some((
+            changes,
+            self.avg_gain + (gain - self.avg_gain) / weight,
+            self.avg_loss + (loss - self.avg_loss) / weight,
+        ))
+    }
+
+    /// The value `update` would return, without changing the state
+    pub fn peek(&self, close: f64) -> Option<f64> {
+        let (changes, avg_gain, avg_loss) = self.step(close)?;
+
+        self.get_rsi(changes, avg_gain, avg_loss)
+    }
+
+    /// `None` until `period` changes have been seen.
+    pub fn update(&mut self, close: f64) -> Option<f64> {
+        let step = self.step(close);
+
+        self.prev = Some(close);
+        (self.changes, self.avg_gain, self.avg_loss) = step?;
+
+        self.get_value()
+    }
+}
+
+/// Wilder's average true range over `period` bars
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Atr {
+    period: usize,
+    prev_close: Option<f64>,
+    bars: usize,
+    value: f64,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Atr {
+            period: period.max(1),
+            prev_close: None,
+            bars: 0,
+            value: 0.0,
+        }
+    }
+
+    pub fn get_value(&self) -> Option<f64> {
+        (self.bars >= self.period).then_some(self.value)
+    }
+
+    /// Bars and average after the bar
+    fn step(&self, high: f64, low: f64) -> (usize, f64) {
+        let true_range = match self.prev_close {
+            Some(prev) => (high - low)
+                .max((high - prev).abs())
+                .max((low - prev).abs()),
+            None => high - low,
+        };
+        let bars = self.bars + 1;
+
+        (
+            bars,
+            self.value + (true_range - self.value) / bars.min(self.period) as f64,
+        )
+    }
+
+    /// The value `update` would return, without changing the state
+    pub fn peek(&self, high: f64, low: f64) -> Option<f64> {
+        let (bars, value) = self.step(high, low);
+
+        (bars >= self.period).then_some(value)
+    }
+
+    /// `None` until `period` bars have been seen.
+    pub fn update(&mut self, high: f64, low: f64, close: f64) -> Option<f64> {
+        (self.bars, self.value) = self.step(high, low);
+        self.prev_close = Some(close);
+
+        self.get_value()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct BollingerValue {
+    pub middle: f64,
+    pub upper: f64,
+    pub lower: f64,
+}
+
+impl BollingerValue {
+    /// Where `price` sits between the bands, 0.0 on the lower and 1.0 on the upper one
+    pub fn get_percent_b(&self, price: f64) -> Option<f64> {
+        let width = self.upper - self.lower;
+        (width > 0.0).then_some((price - self.lower) / width)
+    }
+}
+
+/// Simple moving average of the last `period` values with bands `k` standard deviations away,
+/// kept with running sums instead of a pass over the window.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BollingerBands {
+    period: usize,
+    k: f64,
+    window: VecDeque<f64>,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl BollingerBands {
+    pub fn new(period: usize, k: f64) -> Self {
+        BollingerBands {
+            period: period.max(1),
+            k,
+            window: VecDeque::with_capacity(period),
+            sum: 0.0,
+            sum_sq: 0.0,
+        }
+    }
+
+    fn get_bands(&self, len: usize, sum: f64, sum_sq: f64) -> Option<BollingerValue> {
+        if len < self.period {
+            return None;
+        }
+
+        let len = len as f64;
+        let mean = sum / len;
+        // Population variance, clamped against rounding in the running sums
+        let std_dev = (sum_sq / len - mean * mean).max(0.0).sqrt();
+
+        Some(BollingerValue {
+            middle: mean,
+            upper: mean + self.k * std_dev,
+            lower: mean - self.k * std_dev,
+        })
+    }
+
+    pub fn get_value(&self) -> Option<BollingerValue> {
+        self.get_bands(self.window.len(), self.sum, self.sum_sq)
+    }
+
+    /// The value `update` would return, without changing the window
+    pub fn peek(&self, value: f64) -> Option<BollingerValue> {
+        let (mut len, mut sum, mut sum_sq) = (self.window.len(), self.sum, self.sum_sq);
+
+        if len == self.period {
+            if let Some(oldest) = self.window.front() {
+                len -= 1;
+                sum -= oldest;
+                sum_sq -= oldest * oldest;
+            }
+        }
+
+        self.get_bands(len + 1, sum + value, sum_sq + value * value)
+    }
+
+    /// `None` until `period` values have been seen.
+    pub fn update(&mut self, value: f64) -> Option<BollingerValue> {
+        if self.window.len() == self.period {
+            if let Some(oldest) = self.window.pop_front() {
+                self.sum -= oldest;
+                self.sum_sq -= oldest * oldest;
+            }
+        }
+
+        self.window.push_back(value);
+        self.sum += value;
+        self.sum_sq += value * value;
+
+        self.get_value()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct MacdValue {
+    pub macd: f64,
+    pub signal: f64,
+    pub histogram: f64,
+}
+
+/// Fast EMA minus slow EMA, with an EMA of that as the signal line
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Macd {
+    slow_period: usize,
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+    values: usize,
+}
+
+impl Macd {
+    pub fn new(fast_period: usize, slow_period: usize, signal_period: usize) -> Self {
+        Macd {
+            slow_period,
+            fast: Ema::new(fast_period),
+            slow: Ema::new(slow_period),
+            signal: Ema::new(signal_period),
+            values: 0,
+        }
+    }
+
+    pub fn get_value(&self) -> Option<MacdValue> {
+        if self.values < self.slow_period {
+            return None;
+        }
+
+        let macd = self.fast.get_value()? - self.slow.get_value()?;
+        let signal = self.signal.get_value()?;
+
+        Some(MacdValue {
+            macd,
+            signal,
+            histogram: macd - signal,
+        })
+    }
+
+    /// The value `update` would return, without changing the averages
+    pub fn peek(&self, value: f64) -> Option<MacdValue> {
+        if self.values + 1 < self.slow_period {
+            return None;
+        }
+
+        let macd = self.fast.peek(value) - self.slow.peek(value);
+        let signal = self.signal.peek(macd);
+
+        Some(MacdValue {
+            macd,
+            signal,
+            histogram: macd - signal,
+        })
+    }
+
+    /// `None` until `slow_period` values have been seen, the slow EMA being too close to its
+    /// seed before that.
+    pub fn update(&mut self, value: f64) -> Option<MacdValue> {
+        let macd = self.fast.update(value) - self.slow.update(value);
+        self.values += 1;
+
+        if self.values >= self.slow_period {
+            self.signal.update(macd);
+        }
+
+        self.get_value()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndicatorConfig {
+    pub rsi_period: usize,
+    pub atr_period: usize,
+    pub bollinger_period: usize,
+    pub bollinger_k: f64,
+    pub macd_fast: usize,
+    pub macd_slow: usize,
+    pub macd_signal: usize,
+}
+
+impl Default for IndicatorConfig {
+    fn default() -> Self {
+        IndicatorConfig {
+            rsi_period: 14,
+            atr_period: 14,
+            bollinger_period: 20,
+            bollinger_k: 2.0,
+            macd_fast: 12,
+            macd_slow: 26,
+            macd_signal: 9,
+        }
+    }
+}
+
+/// Latest values of a coin's indicators, `None` while warming up
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct IndicatorValues {
+    pub time: u64,
+    pub rsi: Option<f64>,
+    pub atr: Option<f64>,
+    pub bollinger: Option<BollingerValue>,
+    pub macd: Option<MacdValue>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Indicators {
+    rsi: Rsi,
+    atr: Atr,
+    bollinger: BollingerBands,
+    macd: Macd,
+}
+
+impl Indicators {
+    fn new(config: &IndicatorConfig) -> Self {
+        Indicators {
+            rsi: Rsi::new(config.rsi_period),
+            atr: Atr::new(config.atr_period),
+            bollinger: BollingerBands::new(config.bollinger_period, config.bollinger_k),
+            macd: Macd::new(config.macd_fast, config.macd_slow, config.macd_signal),
+        }
+    }
+
+    fn update(&mut self, time: u64, high: f64, low: f64, close: f64) -> IndicatorValues {
+        IndicatorValues {
+            time,
+            rsi: self.rsi.update(close),
+            atr: self.atr.update(high, low, close),
+            bollinger: self.bollinger.update(close),
+            macd: self.macd.update(close),
+        }
+    }
+
+    /// The values `update` would return, without committing the bar
+    fn peek(&self, time: u64, high: f64, low: f64, close: f64) -> IndicatorValues {
+        IndicatorValues {
+            time,
+            rsi: self.rsi.peek(close),
+            atr: self.atr.peek(high, low),
+            bollinger: self.bollinger.peek(close),
+            macd: self.macd.peek(close),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CoinIndicators {
+    /// Fed with every closed bar
+    closed: Indicators,
+    /// The candle still open, only folded into `closed` once the next one starts
+    open: Option<Candle>,
+    values: IndicatorValues,
+}
+
+/// Per coin RSI, ATR, Bollinger Bands and MACD, updated in constant time per bar instead of
+/// being recomputed over the whole history.
+///
+/// Candles can be pushed repeatedly while they're open, as the candle stream does: the values
+/// then include the open candle, which is only committed to the indicators once a candle with a
+/// later open time arrives. Prices pushed with [`IndicatorEngine::push_price`] are each a closed
+/// bar, with no range, so the ATR is the average absolute change.
+#[derive(Clone, Debug, Default)]
+pub struct IndicatorEngine {
+    config: IndicatorConfig,
+    map: HashMap<String, CoinIndicators>,
+}
+
+impl IndicatorEngine {
+    pub fn new(config: IndicatorConfig) -> Self {
+        IndicatorEngine {
+            config,
+            map: HashMap::new(),
+        }
+    }
+
+    fn get_coin(&mut self, coin: &str) -> &mut CoinIndicators {
+        let config = &self.config;
+
+        self.map
+            .entry(coin.to_string())
+            .or_insert_with(|| CoinIndicators {
+                closed: Indicators::new(config),
+                open: None,
+                values: IndicatorValues::default(),
+            })
+    }
+
+    /// Candles older than the open one are ignored.
+    pub fn push_candle(&mut self, candle: &Candle) -> &IndicatorValues {
+        let coin = self.get_coin(&candle.coin);
+
+        match &coin.open {
+            Some(open) if candle.open_time < open.open_time => return &coin.values,
+            Some(open) if candle.open_time > open.open_time => {
+                coin.closed
+                    .update(open.close_time, open.high, open.low, open.close);
+            }
+            _ => (),
+        }
+
+        // Previewed, so the next update of the open candle starts from the closed bars
+        coin.values = coin
+            .closed
+            .peek(candle.close_time, candle.high, candle.low, candle.close);
+        coin.open = Some(candle.clone());
+
+        &coin.values
+    }
+
+    pub fn push_price(&mut self, coin: &str, time: u64, price: f64) -> &IndicatorValues {
+        let coin = self.get_coin(coin);
+
+        coin.values = coin.closed.update(time, price, price, price);
+        &coin.values
+    }
+
+    pub fn get_values(&self, coin: &str) -> Option<&IndicatorValues> {
+        self.map.get(coin).map(|coin| &coin.values)
+    }
+
+    pub fn get_values_map(&self) -> CoinToIndicatorValuesMap {
+        self.map
+            .iter()
+            .map(|(coin, indicators)| (coin.clone(), indicators.values.clone()))
+            .collect()
+    }
+}
+
+/// Feeds the candles of `candle_receiver` (see [`crate::candle_stream::start_candle_task`]) to an
+/// [`IndicatorEngine`], only pushing the candles that changed since the last update.
+#[cfg(feature = "live")]
+pub async fn start_candle_indicator_task(
+    mut candle_receiver: watch::Receiver<CandleBuffer>,
+    config: IndicatorConfig,
+) -> anyhow::Result<watch::Receiver<CoinToIndicatorValuesMap>> {
+    let (values_sender, values_recv) = watch::channel(CoinToIndicatorValuesMap::new());
+
+    tokio::spawn(async move {
+        let mut engine = IndicatorEngine::new(config);
+        let mut last_candles: HashMap<String, Candle> = HashMap::new();
+
+        info!("candle_indicator_task: Starting...");
+
+        while candle_receiver.changed().await.is_ok() {
+            for (coin, candles) in candle_receiver.borrow_and_update().iter() {
+                let last = last_candles.get(coin);
+
+                let new_candles: Vec<&Candle> = candles
+                    .iter()
+                    .filter(|candle| match last {
+                        Some(last) => {
+                            candle.open_time > last.open_time
+                                || (candle.open_time == last.open_time && *candle != last)
+                        }
+                        None => true,
+                    })
+                    .collect();
+
+                for candle in new_candles {
+                    engine.push_candle(candle);
+                    last_candles.insert(coin.clone(), candle.clone());
+                }
+            }
+
+            if values_sender.send(engine.get_values_map()).is_err() {
+                info!("candle_indicator_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+
+        info!("candle_indicator_task: Candle channel closed, stopping...");
+    });
+
+    Ok(values_recv)
+}
+
+/// Samples `price_receiver` every `interval` and feeds the prices to an [`IndicatorEngine`], each
+/// sample being a bar, so the configured periods count intervals rather than price updates.
+#[cfg(feature = "live")]
+pub async fn start_price_indicator_task(
+    price_receiver: watch::Receiver<NameToPriceMap>,
+    config: IndicatorConfig,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<CoinToIndicatorValuesMap>> {
+    let (values_sender, values_recv) = watch::channel(CoinToIndicatorValuesMap::new());
+
+    tokio::spawn(async move {
+        let mut engine = IndicatorEngine::new(config);
+        let mut interval = tokio::time::interval(interval);
+
+        info!("price_indicator_task: Starting...");
+
+        loop {
+            interval.tick().await;
+
+            if price_receiver.has_changed().is_err() {
+                info!("price_indicator_task: Price channel closed, stopping...");
+                return;
+            }
+
+            let time = chrono::Utc::now().timestamp_millis() as u64;
+
+            for (coin, price) in price_receiver.borrow().iter() {
+                let price = price.get_value();
+
+                if price > 0.0 {
+                    engine.push_price(coin, time, price);
+                }
+            }
+
+            if values_sender.send(engine.get_values_map()).is_err() {
+                info!("price_indicator_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(values_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::candles::Candle;
+
+    use super::{Atr, BollingerBands, IndicatorConfig, IndicatorEngine, Macd, Rsi};
+
+    #[test]
+    fn rsi_and_atr_warm_up() {
+        let mut rsi = Rsi::new(2);
+        assert_eq!(rsi.update(10.0), None);
+        assert_eq!(rsi.update(12.0), None);
+        // Gains average 1.0 and losses 0.5 over the first two changes
+        assert!((rsi.update(11.0).unwrap() - 100.0 * 2.0 / 3.0).abs() < 1e-9);
+
+        let mut atr = Atr::new(2);
+        assert_eq!(atr.update(11.0, 9.0, 10.0), None);
+        // True range reaches back to the previous close
+        assert_eq!(atr.update(14.0, 12.0, 13.0), Some(3.0));
+    }
+
+    #[test]
+    fn peeks_match_updates() {
+        let closes = [10.0, 12.0, 11.0, 13.0, 12.5, 14.0, 13.0, 15.0];
+        let mut rsi = Rsi::new(3);
+        let mut atr = Atr::new(3);
+        let mut bands = BollingerBands::new(3, 2.0);
+        let mut macd = Macd::new(2, 4, 2);
+
+        for close in closes {
+            let (high, low) = (close + 1.0, close - 1.0);
+
+            assert_eq!(rsi.peek(close), rsi.clone().update(close));
+            assert_eq!(atr.peek(high, low), atr.clone().update(high, low, close));
+            assert_eq!(bands.peek(close), bands.clone().update(close));
+            assert_eq!(macd.peek(close), macd.clone().update(close));
+
+            rsi.update(close);
+            atr.update(high, low, close);
+            bands.update(close);
+            macd.update(close);
+        }
+    }
+
+    #[test]
+    fn bollinger_bands_roll_over_the_window() {
+        let mut bands = BollingerBands::new(3, 2.0);
+        bands.update(100.0);
+        bands.update(1.0);
+        bands.update(2.0);
+
+        let value = bands.update(3.0).unwrap();
+        assert!((value.middle - 2.0).abs() < 1e-9);
+        assert!((value.upper - (2.0 + 2.0 * (2.0_f64 / 3.0).sqrt())).abs() < 1e-9);
+        assert!((value.get_percent_b(2.0).unwrap() - 0.5).abs() < 1e-9);
+
+        let mut macd = Macd::new(2, 3, 2);
+        assert_eq!(macd.update(1.0), None);
+        assert_eq!(macd.update(1.0), None);
+        assert_eq!(macd.update(1.0).unwrap().macd, 0.0);
+    }
+
+    #[test]
+    fn open_candle_is_only_committed_once_closed() {
+        let candle = |open_time: u64, close: f64| Candle {
+            coin: "ETH".to_string(),
+            interval: "1m".to_string(),
+            open_time,
+            close_time: open_time + 59_999,
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+            trades: 1,
+        };
+
+        let config = IndicatorConfig {
+            rsi_period: 1,
+            ..IndicatorConfig::default()
+        };
+
+        let mut engine = IndicatorEngine::new(config.clone());
+        engine.push_candle(&candle(0, 10.0));
+        // Updates of the open candle replace each other
+        engine.push_candle(&candle(60_000, 5.0));
+        engine.push_candle(&candle(60_000, 12.0));
+        assert_eq!(engine.get_values("ETH").unwrap().rsi, Some(100.0));
+
+        let mut closed = IndicatorEngine::new(config);
+        closed.push_price("ETH", 0, 10.0);
+        closed.push_price("ETH", 60_000, 12.0);
+        assert_eq!(
+            engine.get_values("ETH").unwrap().rsi,
+            closed.get_values("ETH").unwrap().rsi
+        );
+
+        // Stale candles are ignored
+        engine.push_candle(&candle(0, 1.0));
+        engine.push_candle(&candle(120_000, 6.0));
+        assert_eq!(engine.get_values("ETH").unwrap().rsi, Some(0.0));
+    }
+}
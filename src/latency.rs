@@ -0,0 +1,320 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{bail, Error};
+use chrono::Utc;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    endpoints::get_info_endpoints,
+    price_data::perps::PerpsMetaAndAssetCtxs,
+    prices::{build_info_http_client, post_info},
+    ratelimit::{get_info_weight, get_rest_rate_limiter},
+    volume::{get_liquid_coins, get_volume_map},
+};
+
+/// Messages per feed the min and max latency are taken over
+pub const LATENCY_WINDOW: usize = 256;
+
+/// Weight of the newest message in [`FeedLatency::mean_ms`]
+const LATENCY_SMOOTHING: f64 = 0.1;
+
+/// Requests per clock skew measurement, the fastest one is kept
+const CLOCK_SKEW_SAMPLES: usize = 5;
+
+/// How far behind the exchange a feed's messages arrive, from the exchange timestamps of the
+/// websocket messages that carry one (books, trades and fills). The raw numbers are local receive
+/// time minus exchange time, so they include the clock skew, see [`FeedLatency::get_latency_ms`].
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct FeedLatency {
+    pub feed: String,
+    pub samples: u64,
+    /// Of the latest message, in ms
+    pub last_ms: i64,
+    /// Smoothed over the last few messages, in ms
+    pub mean_ms: f64,
+    /// Over the last [`LATENCY_WINDOW`] messages, in ms
+    pub min_ms: i64,
+    /// Over the last [`LATENCY_WINDOW`] messages, in ms
+    pub max_ms: i64,
+    /// Local time the latest message was received, ms since epoch
+    pub last_received: i64,
+}
+
+impl FeedLatency {
+    /// Mean latency with the clock skew taken out
+    pub fn get_latency_ms(&self, clock_skew: &ClockSkew) -> f64 {
+        self.mean_ms + clock_skew.skew_ms
+    }
+
+    /// How much later than the fastest recent message the latest one arrived. Doesn't depend on
+    /// the clock skew, so it's meaningful before it's measured.
+    pub fn get_excess_ms(&self) -> i64 {
+        self.last_ms - self.min_ms
+    }
+}
+
+#[derive(Debug, Default)]
+struct LatencyWindow {
+    latency: FeedLatency,
+    window: VecDeque<i64>,
+}
+
+impl LatencyWindow {
+    fn record(&mut self, latency_ms: i64, received: i64) {
+        if self.window.len() == LATENCY_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(latency_ms);
+
+        let latency = &mut self.latency;
+        latency.mean_ms = if latency.samples == 0 {
+            latency_ms as f64
+        } else {
+            latency.mean_ms + LATENCY_SMOOTHING * (latency_ms as f64 - latency.mean_ms)
+        };
+        latency.samples += 1;
+        latency.last_ms = latency_ms;
+        latency.min_ms = self.window.iter().copied().min().unwrap_or(latency_ms);
+        latency.max_ms = self.window.iter().copied().max().unwrap_or(latency_ms);
+        latency.last_received = received;
+    }
+}
+
+fn get_windows() -> &'static Mutex<HashMap<String, LatencyWindow>> {
+    static WINDOWS: OnceLock<Mutex<HashMap<String, LatencyWindow>>> = OnceLock::new();
+
+    WINDOWS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Records a message of `feed` stamped `exchange_time` (ms since epoch) by the exchange, received
+/// now.
+pub(crate) fn record_exchange_time(feed: &str, exchange_time: u64) {
+    let received = Utc::now().timestamp_millis();
+    let mut windows = get_windows().lock().unwrap();
+
+    let window = windows
+        .entry(feed.to_string())
+        .or_insert_with(|| LatencyWindow {
+            latency: FeedLatency {
+                feed: feed.to_string(),
+                ..FeedLatency::default()
+            },
+            window: VecDeque::with_capacity(LATENCY_WINDOW),
+        });
+
+    window.record(received - exchange_time as i64, received);
+}
+
+pub fn get_feed_latency(feed: &str) -> Option<FeedLatency> {
+    get_windows()
+        .lock()
+        .unwrap()
+        .get(feed)
+        .map(|window| window.latency.clone())
+}
+
+/// Every feed that received a timestamped message, sorted by name
+pub fn get_feed_latencies() -> Vec<FeedLatency> {
+    let mut latencies: Vec<FeedLatency> = get_windows()
+        .lock()
+        .unwrap()
+        .values()
+        .map(|window| window.latency.clone())
+        .collect();
+    latencies.sort_by(|a, b| a.feed.cmp(&b.feed));
+
+    latencies
+}
+
+/// Offset of the exchange clock from the local one
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct ClockSkew {
+    /// Exchange time minus local time in ms, positive if the local clock is behind
+    pub skew_ms: f64,
+    /// Round trip of the request it was measured with, the skew is only known within half of it
+    pub rtt_ms: f64,
+    /// ms since epoch
+    pub measured_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BookTime {
+    time: u64,
+}
+
+fn now_ms() -> f64 {
+    Utc::now().timestamp_micros() as f64 / 1000.0
+}
+
+/// The perp that traded the most over the last 24h. Its book changes every few blocks, so the
+/// book's time is close to the time it was served, see [`measure_clock_skew`].
+pub async fn get_most_active_coin(client: &Client) -> Result<String, Error> {
+    let meta_and_ctxs: PerpsMetaAndAssetCtxs =
+        post_info(client, json!({ "type": "metaAndAssetCtxs" })).await?;
+    let volumes = get_volume_map(&meta_and_ctxs.get_name_to_ctx_map());
+
+    match get_liquid_coins(&volumes, 0.0).into_iter().next() {
+        Some(coin) => Ok(coin),
+        None => bail!("No perp to measure the clock skew with"),
+    }
+}
+
+/// Estimates the clock skew from the time of `coin`'s book, assuming it was stamped halfway
+/// through the request. Keeps the fastest of a few requests, which is the least skewed by a slow
+/// leg. Goes straight to the endpoints, as an answer from the circuit breaker's cache would carry
+/// an old time.
+///
+/// The book's time is its last change rather than the time it was served, so a quiet book reads
+/// as a skew too low by however long it's been still. Pass an active coin, e.g. from
+/// [`get_most_active_coin`].
+pub async fn measure_clock_skew(client: &Client, coin: &str) -> Result<ClockSkew, Error> {
+    let request = json!({ "type": "l2Book", "coin": coin });
+    let mut best: Option<ClockSkew> = None;
+
+    for _ in 0..CLOCK_SKEW_SAMPLES {
+        get_rest_rate_limiter()
+            .acquire(get_info_weight("l2Book"))
+            .await;
+
+        let sent = now_ms();
+        let bytes = get_info_endpoints().post_info(client, &request).await?;
+        let received = now_ms();
+
+        let book: BookTime = serde_json::from_slice(&bytes)?;
+        let skew = ClockSkew {
+            skew_ms: book.time as f64 - (sent + received) / 2.0,
+            rtt_ms: received - sent,
+            measured_at: received as i64,
+        };
+
+        if best.is_none_or(|best| skew.rtt_ms < best.rtt_ms) {
+            best = Some(skew);
+        }
+    }
+
+    match best {
+        Some(skew) => Ok(skew),
+        None => bail!("No clock skew sample"),
+    }
+}
+
+/// Clock skew and per feed latencies
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct LatencyReport {
+    /// `None` until first measured
+    pub clock_skew: Option<ClockSkew>,
+    pub feeds: Vec<FeedLatency>,
+}
+
+impl LatencyReport {
+    /// Mean latency of `feed` with the skew taken out, `None` until both are known
+    pub fn get_latency_ms(&self, feed: &str) -> Option<f64> {
+        let clock_skew = self.clock_skew.as_ref()?;
+
+        self.feeds
+            .iter()
+            .find(|latency| latency.feed == feed)
+            .map(|latency| latency.get_latency_ms(clock_skew))
+    }
+}
+
+/// Measures the clock skew against the book of the most active coin every `interval` and
+/// publishes it with the latencies of the feeds. The coin is picked again on every reset.
+pub async fn start_latency_task(
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<LatencyReport>> {
+    let (report_sender, report_recv) = watch::channel(LatencyReport::default());
+
+    tokio::spawn(async move {
+        loop {
+            info!("latency_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let coin = match get_most_active_coin(&client).await {
+                Ok(coin) => coin,
+                Err(err) => {
+                    error!("latency_task: Error while picking the coin: {err:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            let mut interval = tokio::time::interval(interval);
+
+            info!("latency_task: Measuring the clock skew with {coin}");
+
+            let err = loop {
+                interval.tick().await;
+
+                let clock_skew = match measure_clock_skew(&client, &coin).await {
+                    Ok(clock_skew) => clock_skew,
+                    Err(err) => break err,
+                };
+
+                let report = LatencyReport {
+                    clock_skew: Some(clock_skew),
+                    feeds: get_feed_latencies(),
+                };
+
+                if report_sender.send(report).is_err() {
+                    info!("latency_task: All receivers dropped, stopping...");
+                    return;
+                }
+            };
+
+            error!("latency_task: Error: {err:?}");
+            info!("latency_task: Resetting...");
+
+            sleep(Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(report_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClockSkew, LatencyWindow, LATENCY_WINDOW};
+
+    #[test]
+    fn window_tracks_recent_extremes() {
+        let mut window = LatencyWindow::default();
+
+        window.record(500, 0);
+        for _ in 0..LATENCY_WINDOW {
+            window.record(100, 0);
+        }
+        window.record(130, 0);
+
+        let latency = &window.latency;
+        assert_eq!(latency.samples, LATENCY_WINDOW as u64 + 2);
+        // The 500 fell out of the window
+        assert_eq!((latency.min_ms, latency.max_ms), (100, 130));
+        assert_eq!(latency.get_excess_ms(), 30);
+
+        // Local clock 40ms ahead of the exchange, so the raw numbers are 40ms too high
+        let clock_skew = ClockSkew {
+            skew_ms: -40.0,
+            rtt_ms: 10.0,
+            measured_at: 0,
+        };
+        assert!(latency.get_latency_ms(&clock_skew) < latency.mean_ms);
+    }
+}
@@ -1,4 +1,89 @@
+#[cfg(feature = "live")]
 pub mod telemetry;
+#[cfg(feature = "live")]
 pub mod prices;
+#[cfg(feature = "live")]
+pub mod breaker;
+#[cfg(feature = "live")]
+pub mod endpoints;
+#[cfg(feature = "live")]
+pub mod ratelimit;
+#[cfg(feature = "live")]
+pub mod latency;
+#[cfg(feature = "live")]
+pub mod counters;
 pub mod types;
 pub mod price_data;
+#[cfg(feature = "live")]
+pub mod orderbook;
+#[cfg(feature = "live")]
+pub mod scanner;
+#[cfg(feature = "live")]
+pub mod portfolio;
+#[cfg(feature = "live")]
+pub mod fills;
+#[cfg(feature = "live")]
+pub mod pnl;
+#[cfg(feature = "live")]
+pub mod exec;
+#[cfg(feature = "live")]
+pub mod account;
+#[cfg(feature = "live")]
+pub mod subaccounts;
+#[cfg(feature = "live")]
+pub mod staking;
+pub mod snapshot;
+pub mod candles;
+pub mod export;
+pub mod history;
+pub mod pagination;
+pub mod recorder;
+pub mod sources;
+#[cfg(feature = "live")]
+pub mod subscription;
+#[cfg(feature = "live")]
+pub mod trades;
+#[cfg(feature = "live")]
+pub mod flow;
+#[cfg(feature = "live")]
+pub mod tape;
+#[cfg(feature = "live")]
+pub mod queue;
+#[cfg(feature = "live")]
+pub mod iceberg;
+#[cfg(feature = "live")]
+pub mod feeds;
+pub mod funding;
+pub mod volume;
+pub mod margin;
+pub mod fees;
+#[cfg(feature = "live")]
+pub mod pipeline;
+#[cfg(feature = "live")]
+pub mod candle_stream;
+pub mod analytics;
+pub mod indicators;
+#[cfg(feature = "live")]
+pub mod service;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "live")]
+pub mod manager;
+#[cfg(feature = "live")]
+pub mod events;
+#[cfg(feature = "live")]
+pub mod callbacks;
+#[cfg(feature = "live")]
+pub mod streams;
+#[cfg(feature = "live")]
+pub mod transport;
+#[cfg(feature = "live")]
+pub mod websocket;
+#[cfg(feature = "live")]
+pub mod fixtures;
+#[cfg(feature = "notify")]
+pub mod notify;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "ffi")]
+pub mod ffi;
@@ -0,0 +1,638 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Error};
+use hyperliquid_rust_sdk::{Message, Subscription};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    oneshot, watch,
+};
+use tokio::time::{sleep, timeout, Instant};
+use tracing::{error, info, warn};
+
+use crate::{
+    candles::{Candle, CandleBuffer},
+    counters::{Counter, FeedCounter, FeedCounters},
+    events::{emit, ConnectionEvents, FeedEventKind},
+    subscription::SubscriptionGuard,
+    transport::Transport,
+    types::{NameToOrderbookMap, Orderbook},
+};
+
+/// A per-coin websocket feed that a [`FeedManager`] can subscribe coins to one at a time.
+pub trait CoinFeed: Send + 'static {
+    type State: Default + Send + Sync + 'static;
+
+    /// Used in logs
+    fn get_name(&self) -> &str;
+
+    fn get_subscription(&self, coin: &str) -> Subscription;
+
+    /// Coin a message of this feed is about, `None` for anything else.
+    fn get_coin<'a>(&self, message: &'a Message) -> Option<&'a str>;
+
+    /// Applies a message of a subscribed coin to the published state.
+    fn apply(&mut self, state: &mut Self::State, message: Message) -> Result<(), Error>;
+
+    /// Drops everything about `coin` from the state once it's removed.
+    fn remove(&mut self, state: &mut Self::State, coin: &str);
+}
+
+/// L2 books, published as a [`NameToOrderbookMap`]
+pub struct BookFeed;
+
+impl CoinFeed for BookFeed {
+    type State = NameToOrderbookMap;
+
+    fn get_name(&self) -> &str {
+        "book_manager"
+    }
+
+    fn get_subscription(&self, coin: &str) -> Subscription {
+        Subscription::L2Book {
+            coin: coin.to_string(),
+        }
+    }
+
+    fn get_coin<'a>(&self, message: &'a Message) -> Option<&'a str> {
+        match message {
+            Message::L2Book(book) => Some(&book.data.coin),
+            _ => None,
+        }
+    }
+
+    fn apply(&mut self, state: &mut Self::State, message: Message) -> Result<(), Error> {
+        if let Message::L2Book(book) = message {
+            let book = Orderbook::from(book.data);
+            state.insert(book.get_symbol(), book);
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, state: &mut Self::State, coin: &str) {
+        state.remove(coin);
+    }
+}
+
+/// Candles of one interval, published as a [`CandleBuffer`]
+pub struct CandleFeed {
+    pub interval: String,
+    pub capacity: usize,
+}
+
+impl CoinFeed for CandleFeed {
+    type State = CandleBuffer;
+
+    fn get_name(&self) -> &str {
+        "candle_manager"
+    }
+
+    fn get_subscription(&self, coin: &str) -> Subscription {
+        Subscription::Candle {
+            coin: coin.to_string(),
+            interval: self.interval.clone(),
+        }
+    }
+
+    fn get_coin<'a>(&self, message: &'a Message) -> Option<&'a str> {
+        match message {
+            Message::Candle(candle) => Some(&candle.data.coin),
+            _ => None,
+        }
+    }
+
+    fn apply(&mut self, state: &mut Self::State, message: Message) -> Result<(), Error> {
+        if let Message::Candle(candle) = message {
+            // The buffer starts without a capacity as the state has to be `Default`
+            if state.get_capacity() == 0 {
+                *state = CandleBuffer::new(self.capacity);
+            }
+
+            state.push(Candle::try_from(candle.data)?);
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, state: &mut Self::State, coin: &str) {
+        state.remove(coin);
+    }
+}
+
+enum Command {
+    Add(String, oneshot::Sender<Result<(), Error>>),
+    Remove(String, oneshot::Sender<Result<(), Error>>),
+    GetCoins(oneshot::Sender<Vec<String>>),
+}
+
+/// Runs a [`CoinFeed`] on a single websocket connection and lets coins be added or removed while
+/// it runs. Subscription ids are tracked per coin, so adding or removing a coin leaves the other
+/// subscriptions alone. On connection errors every current coin is resubscribed.
+pub struct FeedManager<F: CoinFeed> {
+    counter: FeedCounter,
+    commands: UnboundedSender<Command>,
+    receiver: watch::Receiver<F::State>,
+}
+
+pub type BookManager = FeedManager<BookFeed>;
+pub type CandleManager = FeedManager<CandleFeed>;
+
+impl<F: CoinFeed> FeedManager<F> {
+    /// Subscribes through a new mainnet connection, and a new one after every connection error.
+    pub fn start(feed: F, coins: Vec<String>) -> Self {
+        FeedManager::start_inner(feed, coins, None)
+    }
+
+    /// Subscribes through `transport`, reused after connection errors.
+    pub fn start_with_transport(
+        feed: F,
+        coins: Vec<String>,
+        transport: Arc<dyn Transport>,
+    ) -> Self {
+        FeedManager::start_inner(feed, coins, Some(transport))
+    }
+
+    fn start_inner(feed: F, coins: Vec<String>, transport: Option<Arc<dyn Transport>>) -> Self {
+        let (command_sender, command_receiver) = unbounded_channel();
+        let (state_sender, state_receiver) = watch::channel(F::State::default());
+        let counter = FeedCounter::new(feed.get_name());
+
+        tokio::spawn(run_feed(
+            feed,
+            coins,
+            transport,
+            command_receiver,
+            state_sender,
+            counter.clone(),
+        ));
+
+        FeedManager {
+            counter,
+            commands: command_sender,
+            receiver: state_receiver,
+        }
+    }
+
+    pub fn get_receiver(&self) -> watch::Receiver<F::State> {
+        self.receiver.clone()
+    }
+
+    /// Message counts of this manager's feed, over every connection it made
+    pub fn get_counters(&self) -> FeedCounters {
+        self.counter.get_counters()
+    }
+
+    /// Subscribes `coin`, a no-op if it's already subscribed.
+    pub async fn add_coin(&self, coin: &str) -> Result<(), Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(Command::Add(coin.to_string(), reply))?;
+
+        response.await.context("Feed manager task stopped")?
+    }
+
+    /// Unsubscribes `coin` and drops it from the published state.
+    pub async fn remove_coin(&self, coin: &str) -> Result<(), Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(Command::Remove(coin.to_string(), reply))?;
+
+        response.await.context("Feed manager task stopped")?
+    }
+
+    pub async fn get_coins(&self) -> Result<Vec<String>, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(Command::GetCoins(reply))?;
+
+        response.await.context("Feed manager task stopped")
+    }
+
+    fn send(&self, command: Command) -> Result<(), Error> {
+        self.commands
+            .send(command)
+            .map_err(|_| anyhow!("Feed manager task stopped"))
+    }
+}
+
+/// How long [`LazyFeedManager::get_book`] waits for the first book of a newly subscribed coin
+pub const FIRST_MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A [`FeedManager`] that starts empty, subscribes a coin the first time it's accessed and
+/// unsubscribes it once it hasn't been accessed for `idle_timeout`.
+pub struct LazyFeedManager<F: CoinFeed> {
+    manager: Arc<FeedManager<F>>,
+    last_access: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+pub type LazyBookManager = LazyFeedManager<BookFeed>;
+pub type LazyCandleManager = LazyFeedManager<CandleFeed>;
+
+impl<F: CoinFeed> LazyFeedManager<F> {
+    pub fn start(feed: F, idle_timeout: Duration) -> Self {
+        LazyFeedManager::with_manager(FeedManager::start(feed, vec![]), idle_timeout)
+    }
+
+    /// Subscribes through `transport`, see [`FeedManager::start_with_transport`].
+    pub fn start_with_transport(
+        feed: F,
+        idle_timeout: Duration,
+        transport: Arc<dyn Transport>,
+    ) -> Self {
+        LazyFeedManager::with_manager(
+            FeedManager::start_with_transport(feed, vec![], transport),
+            idle_timeout,
+        )
+    }
+
+    fn with_manager(manager: FeedManager<F>, idle_timeout: Duration) -> Self {
+        let manager = Arc::new(manager);
+        let last_access = Arc::new(Mutex::new(HashMap::new()));
+
+        tokio::spawn(run_idle_sweeper(
+            Arc::downgrade(&manager),
+            last_access.clone(),
+            idle_timeout,
+        ));
+
+        LazyFeedManager {
+            manager,
+            last_access,
+        }
+    }
+
+    pub fn get_counters(&self) -> FeedCounters {
+        self.manager.get_counters()
+    }
+
+    /// Subscribes `coin` if needed and marks it as used. The returned receiver holds every
+    /// coin in use, not just `coin`.
+    pub async fn access(&self, coin: &str) -> Result<watch::Receiver<F::State>, Error> {
+        let is_new = self
+            .last_access
+            .lock()
+            .map_err(|_| anyhow!("Access map poisoned"))?
+            .insert(coin.to_string(), Instant::now())
+            .is_none();
+
+        if is_new {
+            if let Err(err) = self.manager.add_coin(coin).await {
+                if let Ok(mut last_access) = self.last_access.lock() {
+                    last_access.remove(coin);
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(self.manager.get_receiver())
+    }
+
+    pub async fn get_coins(&self) -> Result<Vec<String>, Error> {
+        self.manager.get_coins().await
+    }
+}
+
+impl LazyFeedManager<BookFeed> {
+    /// Latest book of `coin`, subscribing it first if needed. `None` if no book arrived within
+    /// [`FIRST_MESSAGE_TIMEOUT`].
+    pub async fn get_book(&self, coin: &str) -> Result<Option<Orderbook>, Error> {
+        let mut receiver = self.access(coin).await?;
+
+        let book = timeout(
+            FIRST_MESSAGE_TIMEOUT,
+            receiver.wait_for(|books| books.contains_key(coin)),
+        )
+        .await;
+
+        match book {
+            Ok(books) => Ok(books?.get(coin).cloned()),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl LazyFeedManager<CandleFeed> {
+    /// Candles of `coin` received so far, subscribing it first if needed.
+    pub async fn get_candles(&self, coin: &str) -> Result<Vec<Candle>, Error> {
+        let receiver = self.access(coin).await?;
+        let candles = receiver.borrow();
+
+        Ok(candles
+            .get(coin)
+            .map(|candles| candles.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+async fn run_idle_sweeper<F: CoinFeed>(
+    manager: Weak<FeedManager<F>>,
+    last_access: Arc<Mutex<HashMap<String, Instant>>>,
+    idle_timeout: Duration,
+) {
+    let mut interval = tokio::time::interval((idle_timeout / 2).max(Duration::from_secs(1)));
+
+    loop {
+        interval.tick().await;
+
+        let manager = match manager.upgrade() {
+            Some(manager) => manager,
+            None => {
+                info!("idle_sweeper: Manager dropped, stopping...");
+                return;
+            }
+        };
+
+        let idle: Vec<String> = match last_access.lock() {
+            Ok(mut last_access) => {
+                let idle: Vec<String> = last_access
+                    .iter()
+                    .filter(|(_, accessed)| accessed.elapsed() >= idle_timeout)
+                    .map(|(coin, _)| coin.clone())
+                    .collect();
+
+                idle.iter().for_each(|coin| {
+                    last_access.remove(coin);
+                });
+
+                idle
+            }
+            Err(_) => {
+                error!("idle_sweeper: Access map poisoned, stopping...");
+                return;
+            }
+        };
+
+        for coin in idle {
+            info!("idle_sweeper: {coin} idle for {idle_timeout:?}, unsubscribing...");
+
+            if let Err(err) = manager.remove_coin(&coin).await {
+                warn!("idle_sweeper: Couldn't remove {coin}: {err:?}");
+            }
+
+            // Accessed again while it was being removed
+            let accessed = last_access
+                .lock()
+                .map(|last_access| last_access.contains_key(&coin))
+                .unwrap_or(false);
+
+            if accessed {
+                if let Err(err) = manager.add_coin(&coin).await {
+                    warn!("idle_sweeper: Couldn't resubscribe {coin}: {err:?}");
+                }
+            }
+        }
+    }
+}
+
+struct Connection {
+    subscriptions: SubscriptionGuard,
+    sender: UnboundedSender<Message>,
+    receiver: UnboundedReceiver<Message>,
+    sub_ids: HashMap<String, u32>,
+}
+
+impl Connection {
+    async fn new<F: CoinFeed>(
+        feed: &F,
+        coins: &[String],
+        transport: Option<&Arc<dyn Transport>>,
+    ) -> Result<Self, Error> {
+        let (sender, receiver) = unbounded_channel();
+
+        let subscriptions = match transport {
+            Some(transport) => SubscriptionGuard::with_transport(transport.clone()),
+            None => SubscriptionGuard::new().await?,
+        };
+
+        let mut connection = Connection {
+            subscriptions,
+            sender,
+            receiver,
+            sub_ids: HashMap::new(),
+        };
+
+        for coin in coins {
+            connection.add(feed, coin).await?;
+        }
+
+        Ok(connection)
+    }
+
+    async fn add<F: CoinFeed>(&mut self, feed: &F, coin: &str) -> Result<(), Error> {
+        if self.sub_ids.contains_key(coin) {
+            return Ok(());
+        }
+
+        let sub_id = self
+            .subscriptions
+            .subscribe(feed.get_subscription(coin), self.sender.clone())
+            .await
+            .with_context(|| format!("Couldn't subscribe to {coin}"))?;
+
+        self.sub_ids.insert(coin.to_string(), sub_id);
+
+        Ok(())
+    }
+
+    async fn remove(&mut self, coin: &str) -> Result<(), Error> {
+        match self.sub_ids.remove(coin) {
+            Some(sub_id) => self.subscriptions.unsubscribe(sub_id).await,
+            None => Ok(()),
+        }
+    }
+}
+
+async fn run_feed<F: CoinFeed>(
+    mut feed: F,
+    mut coins: Vec<String>,
+    transport: Option<Arc<dyn Transport>>,
+    mut commands: UnboundedReceiver<Command>,
+    state_sender: watch::Sender<F::State>,
+    counter: FeedCounter,
+) {
+    let name = feed.get_name().to_string();
+
+    let mut events = ConnectionEvents::default();
+    loop {
+        info!("{name}: Starting with {coins:?}...");
+
+        let mut connection = match Connection::new(&feed, &coins, transport.as_ref()).await {
+            Ok(c) => c,
+            Err(e) => {
+                error!("{name}: Error while connecting: {e:?}");
+                error!("Sleeping for 5 secs and restarting...");
+                sleep(std::time::Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        events.connected(&name);
+
+        let err = loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(Command::Add(coin, reply)) => {
+                        let res = connection.add(&feed, &coin).await;
+                        if res.is_ok() && !coins.contains(&coin) {
+                            coins.push(coin);
+                        }
+                        let _ = reply.send(res);
+                    }
+                    Some(Command::Remove(coin, reply)) => {
+                        coins.retain(|c| *c != coin);
+                        state_sender.send_modify(|state| feed.remove(state, &coin));
+                        let _ = reply.send(connection.remove(&coin).await);
+                    }
+                    Some(Command::GetCoins(reply)) => {
+                        let _ = reply.send(coins.clone());
+                    }
+                    None => {
+                        info!("{name}: Manager dropped, stopping...");
+                        return;
+                    }
+                },
+                message = connection.receiver.recv() => match message {
+                    Some(Message::NoData) => break anyhow!("No data found"),
+                    Some(Message::HyperliquidError(err)) => break anyhow!("Hyperliquid error: {err:?}"),
+                    Some(message) => {
+                        counter.count(Counter::Received);
+
+                        // Messages of a coin that was just removed can still be in flight
+                        let is_subscribed = feed
+                            .get_coin(&message)
+                            .is_some_and(|coin| coins.iter().any(|c| c == coin));
+
+                        if is_subscribed {
+                            let mut res = Ok(());
+                            state_sender.send_modify(|state| res = feed.apply(state, message));
+                            counter.count(Counter::Sends);
+
+                            if let Err(err) = res {
+                                error!("{name}: Skipping message: {err:?}");
+                                counter.count(Counter::DeserializeFailures);
+                            }
+                        } else {
+                            counter.count(Counter::Dropped);
+                        }
+                    }
+                    None => break anyhow!("Channel closed"),
+                },
+            }
+
+            if state_sender.is_closed() {
+                info!("{name}: All receivers dropped, stopping...");
+                return;
+            }
+        };
+
+        error!("{name}: Error: {err:?}");
+        emit(
+            &name,
+            FeedEventKind::SubscriptionDropped {
+                reason: format!("{err:?}"),
+            },
+        );
+        info!("{name}: Resetting...");
+
+        let _ = connection.subscriptions.unsubscribe_all().await;
+        sleep(std::time::Duration::from_secs(5)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use hyperliquid_rust_sdk::Message;
+    use serde_json::json;
+
+    use crate::transport::FakeTransport;
+
+    use super::{BookFeed, BookManager, LazyBookManager};
+
+    fn book(coin: &str, time: u64) -> Message {
+        serde_json::from_value(json!({
+            "channel": "l2Book",
+            "data": {
+                "coin": coin,
+                "time": time,
+                "levels": [
+                    [{ "px": "99.0", "sz": "1.0", "n": 1 }],
+                    [{ "px": "101.0", "sz": "1.0", "n": 1 }]
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn get_subscribed_coins(transport: &FakeTransport) -> Vec<String> {
+        let mut coins: Vec<String> = transport
+            .get_subscriptions()
+            .iter()
+            .map(|subscription| subscription["coin"].as_str().unwrap().to_string())
+            .collect();
+        coins.sort();
+
+        coins
+    }
+
+    #[tokio::test]
+    async fn coins_are_added_and_removed_while_running() -> anyhow::Result<()> {
+        let transport = Arc::new(FakeTransport::new());
+        let manager =
+            BookManager::start_with_transport(BookFeed, vec!["ETH".to_string()], transport.clone());
+        let mut receiver = manager.get_receiver();
+
+        manager.add_coin("SOL").await?;
+        // Already subscribed, so nothing changes
+        manager.add_coin("SOL").await?;
+        assert_eq!(get_subscribed_coins(&transport), ["ETH", "SOL"]);
+
+        transport.push(book("ETH", 1));
+        transport.push(book("SOL", 2));
+        receiver
+            .wait_for(|books| books.contains_key("ETH") && books.contains_key("SOL"))
+            .await?;
+
+        manager.remove_coin("ETH").await?;
+        assert_eq!(get_subscribed_coins(&transport), ["SOL"]);
+        assert_eq!(manager.get_coins().await?, ["SOL"]);
+        assert!(!receiver.borrow().contains_key("ETH"));
+
+        // The SOL subscription was left alone and still publishes
+        transport.push(book("SOL", 3));
+        receiver
+            .wait_for(|books| books.get("SOL").is_some_and(|book| book.time == 3))
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lazy_coins_are_subscribed_on_access_and_dropped_when_idle() -> anyhow::Result<()> {
+        let transport = Arc::new(FakeTransport::new());
+        let manager = LazyBookManager::start_with_transport(
+            BookFeed,
+            Duration::from_secs(10),
+            transport.clone(),
+        );
+        assert!(transport.get_subscriptions().is_empty());
+
+        manager.access("ETH").await?;
+        assert_eq!(get_subscribed_coins(&transport), ["ETH"]);
+
+        tokio::time::sleep(Duration::from_secs(7)).await;
+        manager.access("SOL").await?;
+        tokio::time::sleep(Duration::from_secs(6)).await;
+
+        // ETH was idle for 10s at the sweep at 10s, SOL only for 3s
+        assert_eq!(get_subscribed_coins(&transport), ["SOL"]);
+        assert_eq!(manager.get_coins().await?, ["SOL"]);
+
+        // No book arrives for a coin nobody publishes
+        assert_eq!(manager.get_book("BTC").await?, None);
+        assert_eq!(get_subscribed_coins(&transport), ["BTC", "SOL"]);
+
+        Ok(())
+    }
+}
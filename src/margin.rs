@@ -0,0 +1,104 @@
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::types::Meta;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MarginRequirement {
+    pub notional: f64,
+    pub leverage: u16,
+    pub is_isolated: bool,
+    /// Margin needed to open the position
+    pub initial_margin: f64,
+    /// Margin below which the position gets liquidated
+    pub maintenance_margin: f64,
+}
+
+/// Maintenance margin is half of the initial margin at max leverage.
+pub fn get_maintenance_margin_rate(meta: &Meta) -> f64 {
+    1.0 / (2.0 * meta.get_max_leverage().max(1) as f64)
+}
+
+/// Margin needed for a perp order of `notional` USD at `leverage`. Fails for spot metas, for a
+/// leverage of 0 or above the coin's max, and for cross margin on isolated-only coins.
+pub fn get_margin_requirement(
+    meta: &Meta,
+    notional: f64,
+    leverage: u16,
+    is_isolated: bool,
+) -> Result<MarginRequirement, Error> {
+    if !meta.is_perp() {
+        bail!(
+            "{} is not a perp, spot orders aren't margined",
+            meta.get_name()
+        );
+    }
+
+    if leverage == 0 || leverage > meta.get_max_leverage() {
+        bail!(
+            "Leverage {leverage} is outside 1..={} for {}",
+            meta.get_max_leverage(),
+            meta.get_name()
+        );
+    }
+
+    if meta.is_only_isolated() && !is_isolated {
+        bail!(
+            "{} can only be traded with isolated margin",
+            meta.get_name()
+        );
+    }
+
+    let notional = notional.abs();
+
+    Ok(MarginRequirement {
+        notional,
+        leverage,
+        is_isolated,
+        initial_margin: notional / leverage as f64,
+        maintenance_margin: notional * get_maintenance_margin_rate(meta),
+    })
+}
+
+/// Whether `available_margin` covers the initial margin of the order.
+pub fn can_afford(
+    meta: &Meta,
+    notional: f64,
+    leverage: u16,
+    is_isolated: bool,
+    available_margin: f64,
+) -> Result<bool, Error> {
+    Ok(
+        get_margin_requirement(meta, notional, leverage, is_isolated)?.initial_margin
+            <= available_margin,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Meta;
+
+    use super::get_margin_requirement;
+
+    fn meta(only_isolated: Option<bool>) -> Meta {
+        Meta::Perp {
+            name: "BTC".to_string(),
+            index: 0,
+            sz_decimals: 5,
+            max_leverage: 40,
+            only_isolated,
+            is_delisted: None,
+        }
+    }
+
+    #[test]
+    fn initial_and_maintenance_margin() {
+        let req = get_margin_requirement(&meta(None), 10_000.0, 10, false).unwrap();
+        assert_eq!(req.initial_margin, 1_000.0);
+        assert_eq!(req.maintenance_margin, 125.0);
+
+        assert!(get_margin_requirement(&meta(None), 10_000.0, 41, false).is_err());
+        assert!(get_margin_requirement(&meta(Some(true)), 10_000.0, 10, false).is_err());
+        assert!(get_margin_requirement(&meta(Some(true)), 10_000.0, 10, true).is_ok());
+    }
+}
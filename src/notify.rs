@@ -0,0 +1,303 @@
+use std::future::Future;
+
+use anyhow::{bail, Error};
+use chrono::Utc;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc::UnboundedReceiver},
+    task::JoinHandle,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    events::{subscribe_feed_events, FeedEvent, FeedEventKind},
+    prices::build_info_http_client,
+};
+
+pub const DEFAULT_ALERT_TEMPLATE: &str = "[{kind}] {coin} {message}";
+
+/// Something worth pinging a human about, e.g. a basis, funding or price trigger.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    /// ms since epoch
+    pub time: i64,
+    /// Short label of the trigger, e.g. "funding" or "basis"
+    pub kind: String,
+    pub coin: Option<String>,
+    pub message: String,
+    /// The value that triggered the alert, if any
+    pub value: Option<f64>,
+}
+
+impl Alert {
+    pub fn new(kind: &str, coin: Option<&str>, message: &str, value: Option<f64>) -> Self {
+        Alert {
+            time: Utc::now().timestamp_millis(),
+            kind: kind.to_string(),
+            coin: coin.map(str::to_string),
+            message: message.to_string(),
+            value,
+        }
+    }
+
+    /// Fills in `{kind}`, `{coin}`, `{message}`, `{value}` and `{time}` (RFC 3339). Missing
+    /// coins and values are left empty.
+    pub fn render(&self, template: &str) -> String {
+        let time = chrono::DateTime::from_timestamp_millis(self.time)
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default();
+
+        template
+            .replace("{kind}", &self.kind)
+            .replace("{coin}", self.coin.as_deref().unwrap_or(""))
+            .replace("{message}", &self.message)
+            .replace(
+                "{value}",
+                &self.value.map(|v| v.to_string()).unwrap_or_default(),
+            )
+            .replace("{time}", &time)
+    }
+}
+
+impl From<&FeedEvent> for Alert {
+    fn from(event: &FeedEvent) -> Self {
+        let (kind, message, value) = match &event.kind {
+            FeedEventKind::Connected => ("connected", "Connected".to_string(), None),
+            FeedEventKind::Reconnected { attempt } => (
+                "reconnected",
+                format!("Reconnected, attempt {attempt}"),
+                Some(*attempt as f64),
+            ),
+            FeedEventKind::SubscriptionDropped { reason } => (
+                "subscription_dropped",
+                format!("Subscription dropped: {reason}"),
+                None,
+            ),
+            FeedEventKind::PriceGap { gap_ms } => (
+                "price_gap",
+                format!("No price update for {gap_ms}ms"),
+                Some(*gap_ms as f64),
+            ),
+            FeedEventKind::Stale { silent_ms } => (
+                "stale",
+                format!("No message for {silent_ms}ms"),
+                Some(*silent_ms as f64),
+            ),
+            FeedEventKind::InvalidBook { reason, .. } => (
+                "invalid_book",
+                format!("Resyncing after an invalid book: {reason}"),
+                None,
+            ),
+            FeedEventKind::CircuitOpened { failures } => (
+                "circuit_opened",
+                format!("Circuit opened after {failures} consecutive failures"),
+                Some(*failures as f64),
+            ),
+            FeedEventKind::CircuitClosed => ("circuit_closed", "Circuit closed".to_string(), None),
+        };
+
+        let coin = match &event.kind {
+            FeedEventKind::InvalidBook { coin, .. } => Some(coin.clone()),
+            _ => None,
+        };
+
+        Alert {
+            time: event.time,
+            kind: kind.to_string(),
+            coin,
+            message: format!("{}: {message}", event.feed),
+            value,
+        }
+    }
+}
+
+/// Delivers alerts somewhere a human will see them.
+pub trait Notifier: Send + Sync + 'static {
+    fn get_name(&self) -> &str;
+
+    fn notify(&self, alert: &Alert) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+/// Posts to a Discord channel through an incoming webhook.
+pub struct DiscordNotifier {
+    client: Client,
+    webhook_url: String,
+    template: String,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: &str) -> Result<Self, Error> {
+        Ok(DiscordNotifier {
+            client: build_info_http_client()?,
+            webhook_url: webhook_url.to_string(),
+            template: DEFAULT_ALERT_TEMPLATE.to_string(),
+        })
+    }
+
+    /// See [`Alert::render`] for the placeholders.
+    pub fn with_template(mut self, template: &str) -> Self {
+        self.template = template.to_string();
+        self
+    }
+}
+
+impl Notifier for DiscordNotifier {
+    fn get_name(&self) -> &str {
+        "discord"
+    }
+
+    fn notify(&self, alert: &Alert) -> impl Future<Output = Result<(), Error>> + Send {
+        let body = json!({ "content": alert.render(&self.template) });
+
+        async move {
+            let response = self
+                .client
+                .post(&self.webhook_url)
+                .json(&body)
+                .send()
+                .await
+                // The URL holds the webhook's token
+                .map_err(|err| err.without_url())?;
+
+            if !response.status().is_success() {
+                bail!("Discord webhook returned {}", response.status());
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Sends messages to a Telegram chat through a bot.
+pub struct TelegramNotifier {
+    client: Client,
+    bot_token: String,
+    chat_id: String,
+    template: String,
+}
+
+impl TelegramNotifier {
+    pub fn new(bot_token: &str, chat_id: &str) -> Result<Self, Error> {
+        Ok(TelegramNotifier {
+            client: build_info_http_client()?,
+            bot_token: bot_token.to_string(),
+            chat_id: chat_id.to_string(),
+            template: DEFAULT_ALERT_TEMPLATE.to_string(),
+        })
+    }
+
+    /// See [`Alert::render`] for the placeholders.
+    pub fn with_template(mut self, template: &str) -> Self {
+        self.template = template.to_string();
+        self
+    }
+}
+
+impl Notifier for TelegramNotifier {
+    fn get_name(&self) -> &str {
+        "telegram"
+    }
+
+    fn notify(&self, alert: &Alert) -> impl Future<Output = Result<(), Error>> + Send {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+        let body = json!({ "chat_id": self.chat_id, "text": alert.render(&self.template) });
+
+        async move {
+            let response = self
+                .client
+                .post(url)
+                .json(&body)
+                .send()
+                .await
+                // The URL holds the bot token
+                .map_err(|err| err.without_url())?;
+
+            if !response.status().is_success() {
+                bail!("Telegram returned {}", response.status());
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Delivers every alert sent to `alerts` until the channel closes. Failed deliveries are logged
+/// and dropped, so a notifier outage never backs up the alert source.
+pub fn start_notifier_task<N: Notifier>(
+    notifier: N,
+    mut alerts: UnboundedReceiver<Alert>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let name = notifier.get_name().to_string();
+        info!("notifier_task({name}): Starting...");
+
+        while let Some(alert) = alerts.recv().await {
+            if let Err(err) = notifier.notify(&alert).await {
+                error!("notifier_task({name}): Couldn't deliver {alert:?}: {err:?}");
+            }
+        }
+
+        info!("notifier_task({name}): Alert channel closed, stopping...");
+    })
+}
+
+/// Delivers the feed lifecycle events for which `filter` holds, e.g. only
+/// `SubscriptionDropped` and `Stale`.
+pub fn start_feed_event_notifier_task<N, F>(notifier: N, filter: F) -> JoinHandle<()>
+where
+    N: Notifier,
+    F: Fn(&FeedEvent) -> bool + Send + 'static,
+{
+    let mut events = subscribe_feed_events();
+
+    tokio::spawn(async move {
+        let name = notifier.get_name().to_string();
+        info!("feed_event_notifier_task({name}): Starting...");
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("feed_event_notifier_task({name}): Skipped {skipped} events");
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+
+            if !filter(&event) {
+                continue;
+            }
+
+            if let Err(err) = notifier.notify(&Alert::from(&event)).await {
+                error!("feed_event_notifier_task({name}): Couldn't deliver {event:?}: {err:?}");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Alert, DEFAULT_ALERT_TEMPLATE};
+
+    #[test]
+    fn renders_templates() {
+        let alert = Alert {
+            time: 0,
+            kind: "funding".to_string(),
+            coin: Some("ETH".to_string()),
+            message: "Funding above 0.01%".to_string(),
+            value: Some(0.0002),
+        };
+
+        assert_eq!(
+            alert.render(DEFAULT_ALERT_TEMPLATE),
+            "[funding] ETH Funding above 0.01%"
+        );
+        assert_eq!(
+            alert.render("{coin}={value} at {time}"),
+            "ETH=0.0002 at 1970-01-01T00:00:00+00:00"
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{bail, Context, Error};
+use hyperliquid_rust_sdk::{Message, Subscription};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, WeakUnboundedSender},
+        watch,
+    },
+    task::JoinHandle,
+    time::sleep,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    counters::{Counter, FeedCounter, FeedCounters},
+    events::{emit, ConnectionEvents, FeedEventKind},
+    latency::record_exchange_time,
+    pipeline::Pipeline,
+    prices::spawn_update_time_task,
+    subscription::{Heartbeat, SubscriptionGuard},
+    transport::{LiveTransport, Transport},
+    types::{CoinToMidMap, NameToOrderbookMap, NameToUpdateTimeMap, Orderbook},
+};
+
+/// Books are only pushed when they change, so quiet coins get more slack than AllMids.
+pub const ORDERBOOK_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct OrderbookStream {
+    subscriptions: SubscriptionGuard,
+    book_receiver: UnboundedReceiver<Message>,
+    /// Weak so the channel still closes when the transport drops its senders
+    book_sender: WeakUnboundedSender<Message>,
+    /// Subscription of every coin, to resync one coin without touching the others
+    sub_ids: HashMap<String, u32>,
+    heartbeat: Heartbeat,
+    /// Time of the last book of every coin, to catch out of order updates
+    last_times: HashMap<String, u64>,
+    counter: FeedCounter,
+}
+
+impl OrderbookStream {
+    /// Subscribes through a new mainnet connection.
+    pub async fn new(coins: &[String]) -> Result<Self, Error> {
+        OrderbookStream::with_transport(Arc::new(LiveTransport::new().await?), coins).await
+    }
+
+    pub async fn with_transport(
+        transport: Arc<dyn Transport>,
+        coins: &[String],
+    ) -> Result<Self, Error> {
+        let mut subscriptions = SubscriptionGuard::with_transport(transport);
+
+        let (sender, receiver) = unbounded_channel();
+        let mut sub_ids = HashMap::new();
+
+        for coin in coins {
+            let sub_id = subscriptions
+                .subscribe(Subscription::L2Book { coin: coin.clone() }, sender.clone())
+                .await
+                .with_context(|| format!("Couldn't subscribe to the L2 book of {coin}"))?;
+            sub_ids.insert(coin.clone(), sub_id);
+        }
+
+        Ok(OrderbookStream {
+            subscriptions,
+            book_receiver: receiver,
+            book_sender: sender.downgrade(),
+            sub_ids,
+            heartbeat: Heartbeat::new(ORDERBOOK_HEARTBEAT_TIMEOUT).with_feed("orderbook"),
+            last_times: HashMap::new(),
+            counter: FeedCounter::new("orderbook"),
+        })
+    }
+
+    /// Counts on `counter` instead of counters of its own, e.g. to keep counting across the
+    /// streams of a sender task.
+    pub fn with_counter(mut self, counter: FeedCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    pub fn get_counters(&self) -> FeedCounters {
+        self.counter.get_counters()
+    }
+
+    /// Crossed, locked or out of order books aren't returned. Their coin is resubscribed to
+    /// start over from a fresh snapshot, while the other coins keep streaming.
+    pub async fn get_next_book(&mut self) -> anyhow::Result<Option<Orderbook>> {
+        let msg = self.heartbeat.recv(&mut self.book_receiver).await?;
+        if msg.is_some() {
+            self.counter.count(Counter::Received);
+        }
+
+        match msg {
+            Some(msg) => match msg {
+                Message::NoData => {
+                    error!("Couldn't recieve book data");
+                    Err(anyhow::anyhow!("No data found"))
+                }
+                Message::HyperliquidError(err) => {
+                    error!("Hyperliquid error while getting book data: {err:?}");
+                    Err(anyhow::anyhow!("Hyperliquid error found"))
+                }
+                Message::L2Book(l2_book) => {
+                    let book = Orderbook::from(l2_book.data);
+                    record_exchange_time("orderbook", book.time);
+                    if let Err(err) = self.check_book(&book) {
+                        self.counter.count(Counter::Dropped);
+                        warn!("Resyncing the book of {}: {err:?}", book.coin);
+                        self.resync(&book.coin).await?;
+                        return Ok(None);
+                    }
+
+                    Ok(Some(book))
+                }
+                s => {
+                    error!("Got something else: {s:?}");
+                    self.counter.count(Counter::Dropped);
+                    Ok(None)
+                }
+            },
+            None => Err(anyhow::anyhow!("Book channel closed")),
+        }
+    }
+
+    fn check_book(&mut self, book: &Orderbook) -> Result<(), Error> {
+        let last_time = self.last_times.insert(book.coin.clone(), book.time);
+
+        let reason = if book.is_crossed() {
+            format!(
+                "Crossed book, bid {:?} ask {:?}",
+                book.best_bid().map(|level| level.price),
+                book.best_ask().map(|level| level.price)
+            )
+        } else if let Some(last_time) = last_time.filter(|last_time| book.time < *last_time) {
+            format!("Out of order book at {} after {last_time}", book.time)
+        } else {
+            return Ok(());
+        };
+
+        emit(
+            "orderbook",
+            FeedEventKind::InvalidBook {
+                coin: book.coin.clone(),
+                reason: reason.clone(),
+            },
+        );
+        bail!("{}: {reason}", book.coin)
+    }
+
+    /// Resubscribes to the book of `coin` alone.
+    async fn resync(&mut self, coin: &str) -> Result<(), Error> {
+        self.last_times.remove(coin);
+
+        if let Some(sub_id) = self.sub_ids.remove(coin) {
+            self.subscriptions.unsubscribe(sub_id).await?;
+        }
+
+        let sender = self.book_sender.upgrade().context("Book channel closed")?;
+        let sub_id = self
+            .subscriptions
+            .subscribe(
+                Subscription::L2Book {
+                    coin: coin.to_string(),
+                },
+                sender,
+            )
+            .await
+            .with_context(|| format!("Couldn't resubscribe to the L2 book of {coin}"))?;
+        self.sub_ids.insert(coin.to_string(), sub_id);
+
+        Ok(())
+    }
+
+    /// How long `get_next_book` waits for a message before treating the connection as dead.
+    pub fn set_heartbeat_timeout(&mut self, timeout: Duration) {
+        self.heartbeat.set_timeout(timeout);
+    }
+
+    pub async fn start_sending(
+        &mut self,
+        sender: watch::Sender<NameToOrderbookMap>,
+    ) -> Result<(), Error> {
+        loop {
+            if let Some(book) = self.get_next_book().await? {
+                sender.send_modify(|map| {
+                    map.insert(book.get_symbol(), book);
+                });
+                self.counter.count(Counter::Sends);
+            }
+
+            if sender.is_closed() {
+                return Ok(());
+            }
+        }
+    }
+
+    pub async fn unsub(&mut self) -> anyhow::Result<()> {
+        self.subscriptions.unsubscribe_all().await
+    }
+}
+
+pub async fn start_orderbook_sender_task(
+    coins: Vec<String>,
+) -> anyhow::Result<watch::Receiver<NameToOrderbookMap>> {
+    Ok(spawn_orderbook_sender_task(coins, FeedCounter::new("orderbook")).0)
+}
+
+/// [`start_orderbook_sender_task`] with the handle of the task, for owners that stop it, counting
+/// the messages of every stream on `counter`.
+pub(crate) fn spawn_orderbook_sender_task(
+    coins: Vec<String>,
+    counter: FeedCounter,
+) -> (watch::Receiver<NameToOrderbookMap>, JoinHandle<()>) {
+    let (book_sender, book_recv) = watch::channel(NameToOrderbookMap::default());
+
+    let task = tokio::spawn(async move {
+        let b_s = book_sender;
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("orderbook_sender_task: Starting...");
+
+            let mut new_books = match OrderbookStream::new(&coins).await {
+                Ok(b) => b.with_counter(counter.clone()),
+                Err(e) => {
+                    error!("Error while getting OrderbookStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("orderbook_sender_task");
+
+            match new_books.start_sending(b_s.clone()).await {
+                Ok(it) => it,
+                Err(err) => {
+                    error!("orderbook_sender_task: Error: {err:?}");
+                    emit(
+                        "orderbook_sender_task",
+                        FeedEventKind::SubscriptionDropped {
+                            reason: format!("{err:?}"),
+                        },
+                    );
+                }
+            };
+
+            let _ = new_books.unsub().await;
+
+            if b_s.is_closed() {
+                info!("orderbook_sender_task: All receivers dropped, stopping...");
+                return;
+            }
+
+            info!("orderbook_sender_task: Resetting...");
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    (book_recv, task)
+}
+
+/// Publishes when each coin of `book_receiver` last got a book. Every book message is a fresh
+/// snapshot of one coin, so unlike prices a coin is stamped on every book, moved or not.
+pub async fn start_book_update_time_task(
+    book_receiver: watch::Receiver<NameToOrderbookMap>,
+) -> anyhow::Result<watch::Receiver<NameToUpdateTimeMap>> {
+    Ok(spawn_book_update_time_task(book_receiver).0)
+}
+
+/// [`start_book_update_time_task`] with the handle of the task, for owners that stop it.
+pub(crate) fn spawn_book_update_time_task(
+    book_receiver: watch::Receiver<NameToOrderbookMap>,
+) -> (watch::Receiver<NameToUpdateTimeMap>, JoinHandle<()>) {
+    spawn_update_time_task("book_update_time_task", book_receiver, |previous, book| {
+        previous.time != book.time
+    })
+}
+
+/// Publishes [`Orderbook::get_depth_weighted_mid`] over the first `levels` levels for every coin in
+/// `book_receiver`, as a quoting reference that reacts faster than the AllMids mid. The mids are
+/// flagged stale when no book arrives for `stale_after`, and can be chained into further
+/// [`Pipeline`] stages.
+pub async fn start_depth_weighted_mid_task(
+    book_receiver: watch::Receiver<NameToOrderbookMap>,
+    levels: usize,
+    stale_after: Duration,
+) -> anyhow::Result<Pipeline<CoinToMidMap>> {
+    Ok(
+        Pipeline::new(book_receiver, stale_after).map("depth_weighted_mid_task", move |books| {
+            books
+                .iter()
+                .filter_map(|(coin, book)| {
+                    Some((coin.to_string(), book.get_depth_weighted_mid(levels)?))
+                })
+                .collect()
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hyperliquid_rust_sdk::Message;
+    use serde_json::json;
+
+    use crate::transport::FakeTransport;
+
+    use super::OrderbookStream;
+
+    fn book_between(coin: &str, time: u64, bid: &str, ask: &str) -> Message {
+        serde_json::from_value(json!({
+            "channel": "l2Book",
+            "data": {
+                "coin": coin,
+                "time": time,
+                "levels": [
+                    [{ "px": bid, "sz": "1.0", "n": 1 }],
+                    [{ "px": ask, "sz": "1.0", "n": 1 }]
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    fn book(coin: &str, time: u64) -> Message {
+        book_between(coin, time, "99.0", "101.0")
+    }
+
+    #[tokio::test]
+    async fn books_arrive_through_the_transport() -> anyhow::Result<()> {
+        let transport = Arc::new(FakeTransport::new());
+        let mut stream =
+            OrderbookStream::with_transport(transport.clone(), &["ETH".to_string()]).await?;
+        assert_eq!(
+            transport.get_subscriptions(),
+            [json!({ "type": "l2Book", "coin": "ETH" })]
+        );
+
+        transport.push(book("ETH", 1));
+        let book = stream.get_next_book().await?.unwrap();
+        assert_eq!((book.coin.as_str(), book.time), ("ETH", 1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_the_coin_of_an_invalid_book_is_resubscribed() -> anyhow::Result<()> {
+        let transport = Arc::new(FakeTransport::new());
+        let coins = ["ETH".to_string(), "SOL".to_string()];
+        let mut stream = OrderbookStream::with_transport(transport.clone(), &coins).await?;
+        let sub_ids = stream.subscriptions.get_sub_ids().clone();
+
+        transport.push(book("ETH", 5));
+        stream.get_next_book().await?.unwrap();
+
+        transport.push(book_between("ETH", 6, "102.0", "101.0"));
+        assert_eq!(stream.get_next_book().await?, None);
+        assert_eq!(transport.get_subscriptions().len(), 2);
+        assert_eq!(stream.sub_ids["SOL"], sub_ids[1]);
+        assert_ne!(stream.sub_ids["ETH"], sub_ids[0]);
+
+        // The fresh snapshot may be older than the dropped book
+        transport.push(book("ETH", 4));
+        assert_eq!(stream.get_next_book().await?.unwrap().time, 4);
+
+        transport.push(book("SOL", 7));
+        assert_eq!(stream.get_next_book().await?.unwrap().coin, "SOL");
+
+        Ok(())
+    }
+}
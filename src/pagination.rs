@@ -0,0 +1,100 @@
+use std::{collections::HashSet, future::Future, hash::Hash};
+
+use anyhow::Error;
+
+/// Pages through a time ranged info request (`userFunding`, `userFillsByTime`...) until the range
+/// is covered, oldest records first as the API returns them.
+///
+/// `get_page` requests the page starting at a time. Records sharing a time (fills of one order,
+/// the payments of one funding settlement) can be split across pages, so every page after the
+/// first starts at the latest time seen so far and records whose `get_key` was already seen are
+/// skipped. Stops at a page shorter than `page_size` or one without anything new.
+pub async fn get_all_pages<T, C, K, F, Fut>(
+    start_time: C,
+    page_size: usize,
+    mut get_page: F,
+    get_time: impl Fn(&T) -> C,
+    get_key: impl Fn(&T) -> K,
+) -> Result<Vec<T>, Error>
+where
+    C: Copy + Ord,
+    K: Eq + Hash,
+    F: FnMut(C) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, Error>>,
+{
+    let mut records: Vec<T> = vec![];
+    let mut seen: HashSet<K> = HashSet::new();
+    let mut cursor = start_time;
+
+    loop {
+        let page = get_page(cursor).await?;
+        let page_len = page.len();
+        let before = records.len();
+
+        for record in page {
+            if seen.insert(get_key(&record)) {
+                records.push(record);
+            }
+        }
+
+        if page_len < page_size || records.len() == before {
+            break;
+        }
+
+        cursor = records.iter().map(&get_time).max().unwrap_or(cursor);
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::get_all_pages;
+
+    #[tokio::test]
+    async fn records_split_across_pages_are_kept_once() {
+        // (time, id), pages of 3 that end between records sharing a time
+        let records = [(10, 1), (20, 2), (20, 3), (30, 4), (30, 5), (40, 6)];
+        let requested = Mutex::new(vec![]);
+
+        let pages = get_all_pages(
+            0,
+            3,
+            |cursor: u64| {
+                requested.lock().unwrap().push(cursor);
+                let page: Vec<(u64, u64)> = records
+                    .iter()
+                    .filter(|(time, _)| *time >= cursor)
+                    .take(3)
+                    .copied()
+                    .collect();
+
+                async move { Ok(page) }
+            },
+            |(time, _)| *time,
+            |(_, id)| *id,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(pages, records);
+        assert_eq!(*requested.lock().unwrap(), vec![0, 20, 30, 40]);
+    }
+
+    #[tokio::test]
+    async fn a_full_page_of_one_time_stops() {
+        let pages = get_all_pages(
+            0,
+            2,
+            |_| async { Ok(vec![(5_i64, 1), (5, 2)]) },
+            |(time, _)| *time,
+            |(_, id)| *id,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(pages, vec![(5, 1), (5, 2)]);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::VecDeque,
+    panic::{catch_unwind, AssertUnwindSafe},
+    time::Duration,
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::timeout};
+use tracing::{error, info, warn};
+
+/// A value published by a pipeline stage. `value` is `None` until the first update.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Signal<T> {
+    pub value: Option<T>,
+    /// When the source value this was derived from was published, ms since epoch
+    pub time: i64,
+    /// Set when the source hasn't updated for the pipeline's `stale_after`
+    pub is_stale: bool,
+}
+
+impl<T> Default for Signal<T> {
+    fn default() -> Self {
+        Signal {
+            value: None,
+            time: 0,
+            is_stale: false,
+        }
+    }
+}
+
+/// A derived feed built from a watch channel. Every stage runs in its own task with the same
+/// handling: a stage stops once its input or all of its receivers are gone, a panic in a
+/// transformation skips that update instead of killing the stage, and the output is flagged stale
+/// when nothing arrives for `stale_after`.
+///
+/// ```ignore
+/// let btc = Pipeline::new(price_receiver, Duration::from_secs(5))
+///     .map("btc_mid", |prices| prices.get("BTC").map(|p| p.get_value()))
+///     .filter("btc_some", |mid| mid.is_some())
+///     .rolling("btc_avg", 20, |mids| mids.iter().flatten().sum::<f64>() / mids.len() as f64);
+/// ```
+pub struct Pipeline<T> {
+    receiver: watch::Receiver<Signal<T>>,
+    stale_after: Duration,
+}
+
+impl<T: Clone + Send + Sync + 'static> Pipeline<T> {
+    pub fn new(source: watch::Receiver<T>, stale_after: Duration) -> Self {
+        let receiver = spawn_stage(
+            "pipeline_source".to_string(),
+            source,
+            stale_after,
+            |_| false,
+            |value| {
+                Some(Signal {
+                    value: Some(value.clone()),
+                    time: Utc::now().timestamp_millis(),
+                    is_stale: false,
+                })
+            },
+        );
+
+        Pipeline {
+            receiver,
+            stale_after,
+        }
+    }
+
+    pub fn map<U, F>(&self, name: &str, mut f: F) -> Pipeline<U>
+    where
+        U: Clone + Send + Sync + 'static,
+        F: FnMut(&T) -> U + Send + 'static,
+    {
+        self.derive(name, move |value| Some(f(value)))
+    }
+
+    /// Only forwards values for which `predicate` holds.
+    pub fn filter<F>(&self, name: &str, mut predicate: F) -> Pipeline<T>
+    where
+        F: FnMut(&T) -> bool + Send + 'static,
+    {
+        self.derive(name, move |value| predicate(value).then(|| value.clone()))
+    }
+
+    /// Applies `f` to the last `window` values, oldest first, every time a new one arrives.
+    pub fn rolling<U, F>(&self, name: &str, window: usize, mut f: F) -> Pipeline<U>
+    where
+        U: Clone + Send + Sync + 'static,
+        F: FnMut(&VecDeque<T>) -> U + Send + 'static,
+    {
+        let mut values = VecDeque::with_capacity(window);
+
+        self.derive(name, move |value| {
+            if values.len() == window.max(1) {
+                values.pop_front();
+            }
+            values.push_back(value.clone());
+
+            Some(f(&values))
+        })
+    }
+
+    pub fn get_receiver(&self) -> watch::Receiver<Signal<T>> {
+        self.receiver.clone()
+    }
+
+    pub fn get_stale_after(&self) -> Duration {
+        self.stale_after
+    }
+
+    fn derive<U, F>(&self, name: &str, mut f: F) -> Pipeline<U>
+    where
+        U: Clone + Send + Sync + 'static,
+        F: FnMut(&T) -> Option<U> + Send + 'static,
+    {
+        let receiver = spawn_stage(
+            name.to_string(),
+            self.receiver.clone(),
+            self.stale_after,
+            |signal: &Signal<T>| signal.is_stale,
+            move |signal: &Signal<T>| {
+                let value = f(signal.value.as_ref()?)?;
+
+                Some(Signal {
+                    value: Some(value),
+                    time: signal.time,
+                    is_stale: false,
+                })
+            },
+        );
+
+        Pipeline {
+            receiver,
+            stale_after: self.stale_after,
+        }
+    }
+}
+
+/// Runs `step` on every input update. Inputs for which `is_stale` holds only flag the output
+/// stale, so stale markers don't get fed to the transformations as new values.
+fn spawn_stage<I, U, S, F>(
+    name: String,
+    mut input: watch::Receiver<I>,
+    stale_after: Duration,
+    is_stale: S,
+    mut step: F,
+) -> watch::Receiver<Signal<U>>
+where
+    I: Clone + Send + Sync + 'static,
+    U: Clone + Send + Sync + 'static,
+    S: Fn(&I) -> bool + Send + 'static,
+    F: FnMut(&I) -> Option<Signal<U>> + Send + 'static,
+{
+    let (sender, receiver) = watch::channel(Signal::default());
+
+    let mark_stale = |sender: &watch::Sender<Signal<U>>| {
+        sender.send_if_modified(|signal| {
+            if signal.is_stale || signal.value.is_none() {
+                return false;
+            }
+
+            signal.is_stale = true;
+            true
+        })
+    };
+
+    tokio::spawn(async move {
+        info!("{name}: Starting...");
+
+        loop {
+            match timeout(stale_after, input.changed()).await {
+                Ok(Ok(())) => {
+                    let value = input.borrow_and_update().clone();
+
+                    if is_stale(&value) {
+                        mark_stale(&sender);
+                        continue;
+                    }
+
+                    match catch_unwind(AssertUnwindSafe(|| step(&value))) {
+                        Ok(Some(signal)) => {
+                            let _ = sender.send(signal);
+                        }
+                        Ok(None) => (),
+                        Err(_) => error!("{name}: Transformation panicked, skipping update"),
+                    }
+                }
+                Ok(Err(_)) => {
+                    info!("{name}: Input channel closed, stopping...");
+                    return;
+                }
+                Err(_) => {
+                    if mark_stale(&sender) {
+                        warn!("{name}: No update for {stale_after:?}, marking stale");
+                    }
+                }
+            }
+
+            if sender.is_closed() {
+                info!("{name}: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::watch;
+
+    use super::Pipeline;
+
+    #[tokio::test]
+    async fn map_filter_and_rolling() {
+        let (sender, receiver) = watch::channel(0.0_f64);
+
+        let pipeline = Pipeline::new(receiver, Duration::from_millis(100))
+            .map("double", |value| value * 2.0)
+            .filter("positive", |value| *value > 0.0)
+            .rolling("sum", 2, |values| values.iter().sum::<f64>());
+        let mut output = pipeline.get_receiver();
+
+        for value in [1.0, 2.0, 3.0] {
+            sender.send(value).unwrap();
+            output
+                .wait_for(|signal| signal.value == Some((value - 1.0).max(0.0) * 2.0 + value * 2.0))
+                .await
+                .unwrap();
+        }
+
+        let signal = output
+            .wait_for(|signal| signal.is_stale)
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(signal.value, Some(10.0));
+    }
+}
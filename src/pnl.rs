@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+
+use alloy::primitives::Address;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    account::get_account_address,
+    events::{emit, ConnectionEvents, FeedEventKind},
+    fills::{get_user_fills_by_time, Fill, UserFillsStream},
+    prices::build_info_http_client,
+    types::NameToPriceMap,
+};
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct CoinPnl {
+    /// Signed position size, negative when short
+    pub position: f64,
+    /// 0.0 when flat
+    pub avg_entry_price: f64,
+    /// Realized PnL before fees
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub fees: f64,
+    pub last_price: f64,
+}
+
+impl CoinPnl {
+    pub fn apply_fill(&mut self, fill: &Fill) {
+        let qty = fill.get_signed_size();
+
+        if self.position == 0.0 || self.position.signum() == qty.signum() {
+            let new_position = self.position + qty;
+            self.avg_entry_price = (self.position.abs() * self.avg_entry_price
+                + qty.abs() * fill.price)
+                / new_position.abs();
+            self.position = new_position;
+        } else {
+            let closed = qty.abs().min(self.position.abs());
+            self.realized_pnl +=
+                closed * (fill.price - self.avg_entry_price) * self.position.signum();
+
+            let new_position = self.position + qty;
+
+            if new_position == 0.0 {
+                self.avg_entry_price = 0.0;
+            } else if new_position.signum() != self.position.signum() {
+                // Flipped sides, the remainder was opened at the fill price
+                self.avg_entry_price = fill.price;
+            }
+
+            self.position = new_position;
+        }
+
+        self.fees += fill.fee;
+        self.mark_to_market(fill.price);
+    }
+
+    pub fn mark_to_market(&mut self, price: f64) {
+        self.last_price = price;
+        self.unrealized_pnl = (price - self.avg_entry_price) * self.position;
+    }
+
+    /// Realized plus unrealized PnL, net of fees
+    pub fn get_net_pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl - self.fees
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct PnlTracker {
+    pub coins: HashMap<String, CoinPnl>,
+}
+
+impl PnlTracker {
+    pub fn apply_fill(&mut self, fill: &Fill) {
+        self.coins
+            .entry(fill.coin.clone())
+            .or_default()
+            .apply_fill(fill);
+    }
+
+    pub fn mark_to_market(&mut self, prices: &NameToPriceMap) {
+        for (coin, pnl) in self.coins.iter_mut() {
+            if let Some(price) = prices.get(coin.as_str()) {
+                if price.get_value() > 0.0 {
+                    pnl.mark_to_market(price.get_value());
+                }
+            }
+        }
+    }
+
+    pub fn get_coin(&self, coin: &str) -> Option<&CoinPnl> {
+        self.coins.get(coin)
+    }
+
+    pub fn get_total_realized_pnl(&self) -> f64 {
+        self.coins.values().map(|pnl| pnl.realized_pnl).sum()
+    }
+
+    pub fn get_total_unrealized_pnl(&self) -> f64 {
+        self.coins.values().map(|pnl| pnl.unrealized_pnl).sum()
+    }
+
+    pub fn get_total_fees(&self) -> f64 {
+        self.coins.values().map(|pnl| pnl.fees).sum()
+    }
+
+    pub fn get_total_net_pnl(&self) -> f64 {
+        self.coins.values().map(CoinPnl::get_net_pnl).sum()
+    }
+}
+
+/// Remembers the latest fill time applied and the fills at that time, so fills seen again by a
+/// backfill overlapping the live stream are only applied once. Fills arrive in time order, so
+/// anything older than the latest time was already applied.
+#[derive(Clone, Debug, Default)]
+struct FillCursor {
+    last_time: u64,
+    tids: HashSet<u64>,
+}
+
+impl FillCursor {
+    fn is_new(&mut self, fill: &Fill) -> bool {
+        if fill.time < self.last_time {
+            return false;
+        }
+
+        if fill.time > self.last_time {
+            self.last_time = fill.time;
+            self.tids.clear();
+        }
+
+        self.tids.insert(fill.tid)
+    }
+}
+
+/// Tracks PnL for `user` (or `vault_address` if set). With `backfill_from` (ms since epoch) the
+/// fills since then are fetched first, so the tracker starts from the positions built up before
+/// the task, and the live fills stream takes over from there. Without it, tracking starts with
+/// the task.
+///
+/// The fills snapshot sent on (re)subscribe is skipped, and a restart backfills the fills missed
+/// while the stream was down, applying every fill once.
+pub async fn start_pnl_tracker_task(
+    user: Address,
+    vault_address: Option<Address>,
+    backfill_from: Option<u64>,
+    mut price_receiver: watch::Receiver<NameToPriceMap>,
+) -> anyhow::Result<watch::Receiver<PnlTracker>> {
+    let (pnl_sender, pnl_recv) = watch::channel(PnlTracker::default());
+    let account = get_account_address(user, vault_address);
+    let started_at = Utc::now().timestamp_millis() as u64;
+
+    tokio::spawn(async move {
+        let p_s = pnl_sender;
+        let mut cursor = FillCursor::default();
+        let mut backfill_from = backfill_from;
+
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("pnl_tracker_task: Starting...");
+
+            // Subscribed before the backfill, so no fill falls between the two
+            let mut fills_stream = match UserFillsStream::with_vault(user, vault_address).await {
+                Ok(f) => f,
+                Err(e) => {
+                    error!("Error while getting UserFillsStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("pnl_tracker_task");
+
+            if let Some(since) = backfill_from {
+                let backfill = match build_info_http_client() {
+                    Ok(client) => get_user_fills_by_time(&client, account, since, None).await,
+                    Err(err) => Err(err),
+                };
+
+                match backfill {
+                    Ok(fills) => {
+                        info!("pnl_tracker_task: Backfilled {} fills", fills.len());
+
+                        let prices = price_receiver.borrow().clone();
+                        p_s.send_modify(|tracker| {
+                            fills
+                                .iter()
+                                .filter(|fill| cursor.is_new(fill))
+                                .for_each(|fill| tracker.apply_fill(fill));
+                            tracker.mark_to_market(&prices);
+                        });
+                    }
+                    Err(e) => {
+                        error!("Error while backfilling fills: {e:?}");
+                        error!("Sleeping for 5 secs and restarting...");
+                        let _ = fills_stream.unsub().await;
+                        sleep(std::time::Duration::from_secs(5)).await;
+                        continue;
+                    }
+                }
+            }
+
+            let err = loop {
+                tokio::select! {
+                    update = fills_stream.get_next_fills() => match update {
+                        Ok(Some(update)) if !update.is_snapshot => {
+                            let prices = price_receiver.borrow().clone();
+                            p_s.send_modify(|tracker| {
+                                update
+                                    .fills
+                                    .iter()
+                                    .filter(|fill| cursor.is_new(fill))
+                                    .for_each(|fill| tracker.apply_fill(fill));
+                                tracker.mark_to_market(&prices);
+                            });
+                        }
+                        Ok(_) => (),
+                        Err(err) => break err,
+                    },
+                    changed = price_receiver.changed() => {
+                        if changed.is_err() {
+                            info!("pnl_tracker_task: Price channel closed, stopping...");
+                            let _ = fills_stream.unsub().await;
+                            return;
+                        }
+
+                        let prices = price_receiver.borrow_and_update().clone();
+                        p_s.send_modify(|tracker| tracker.mark_to_market(&prices));
+                    }
+                }
+
+                if p_s.is_closed() {
+                    info!("pnl_tracker_task: All receivers dropped, stopping...");
+                    let _ = fills_stream.unsub().await;
+                    return;
+                }
+            };
+
+            error!("pnl_tracker_task: Error: {err:?}");
+            emit(
+                "pnl_tracker_task",
+                FeedEventKind::SubscriptionDropped {
+                    reason: format!("{err:?}"),
+                },
+            );
+            info!("pnl_tracker_task: Resetting...");
+
+            // Picks up from the last fill applied
+            backfill_from = Some(cursor.last_time.max(backfill_from.unwrap_or(started_at)));
+
+            let _ = fills_stream.unsub().await;
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(pnl_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        fills::Fill,
+        types::{Meta, NameToPriceMap, Price, Symbol},
+    };
+
+    use super::{CoinPnl, FillCursor, PnlTracker};
+
+    fn fill(is_buy: bool, price: f64, size: f64) -> Fill {
+        Fill {
+            coin: "ETH".to_string(),
+            is_buy,
+            price,
+            size,
+            fee: 1.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn average_entry_and_realized_pnl() {
+        let mut pnl = CoinPnl::default();
+
+        pnl.apply_fill(&fill(true, 100.0, 1.0));
+        pnl.apply_fill(&fill(true, 200.0, 1.0));
+        assert_eq!(pnl.position, 2.0);
+        assert_eq!(pnl.avg_entry_price, 150.0);
+
+        pnl.apply_fill(&fill(false, 250.0, 1.0));
+        assert_eq!(pnl.position, 1.0);
+        assert_eq!(pnl.realized_pnl, 100.0);
+        assert_eq!(pnl.unrealized_pnl, 100.0);
+
+        // Flip to short
+        pnl.apply_fill(&fill(false, 100.0, 3.0));
+        assert_eq!(pnl.position, -2.0);
+        assert_eq!(pnl.avg_entry_price, 100.0);
+        assert_eq!(pnl.realized_pnl, 50.0);
+        assert_eq!(pnl.fees, 4.0);
+    }
+
+    #[test]
+    fn cursor_skips_fills_already_applied() {
+        let at = |time: u64, tid: u64| Fill {
+            time,
+            tid,
+            ..Default::default()
+        };
+        let mut cursor = FillCursor::default();
+
+        // Backfill
+        assert!(cursor.is_new(&at(10, 1)));
+        assert!(cursor.is_new(&at(20, 2)));
+
+        // Live stream overlapping it
+        assert!(!cursor.is_new(&at(10, 1)));
+        assert!(!cursor.is_new(&at(20, 2)));
+        assert!(cursor.is_new(&at(20, 3)));
+        assert!(cursor.is_new(&at(30, 4)));
+        assert!(!cursor.is_new(&at(20, 5)));
+    }
+
+    #[test]
+    fn tracker_marks_every_coin_to_market() {
+        let mut tracker = PnlTracker::default();
+
+        tracker.apply_fill(&fill(true, 100.0, 2.0));
+        tracker.apply_fill(&Fill {
+            coin: "BTC".to_string(),
+            ..fill(false, 50.0, 1.0)
+        });
+
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 2,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        };
+        let prices: NameToPriceMap = [(Symbol::from_api("ETH"), Price::new_perp(110.0, meta))]
+            .into_iter()
+            .collect();
+
+        tracker.mark_to_market(&prices);
+
+        assert_eq!(tracker.get_coin("ETH").unwrap().unrealized_pnl, 20.0);
+        // No price for BTC, it stays marked at its fill
+        assert_eq!(tracker.get_coin("BTC").unwrap().last_price, 50.0);
+        assert_eq!(tracker.get_total_unrealized_pnl(), 20.0);
+        assert_eq!(tracker.get_total_fees(), 2.0);
+        assert_eq!(tracker.get_total_net_pnl(), 18.0);
+    }
+}
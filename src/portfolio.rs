@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::info;
+
+use crate::types::{Meta, NameToPriceMap, Price};
+
+pub const USDC: &str = "USDC";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Holding {
+    /// A token balance, `token` being the token name (e.g. "PURR"), not the pair name
+    Spot { token: String, amount: f64 },
+    /// A perp position, `size` is negative for shorts
+    Perp {
+        coin: String,
+        size: f64,
+        entry_price: Option<f64>,
+    },
+}
+
+impl Holding {
+    pub fn get_name(&self) -> &String {
+        match self {
+            Holding::Spot { token, .. } => token,
+            Holding::Perp { coin, .. } => coin,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HoldingValuation {
+    pub holding: Holding,
+    pub price: f64,
+    /// Token value for spot, signed notional for perps
+    pub usd_value: f64,
+    /// Always 0.0 for spot and for perps without an entry price
+    pub unrealized_pnl: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct PortfolioValuation {
+    pub holdings: Vec<HoldingValuation>,
+    pub spot_usd: f64,
+    pub perps_notional_usd: f64,
+    pub unrealized_pnl: f64,
+    /// Spot value plus unrealized perps PnL
+    pub total_usd: f64,
+    /// Holdings that couldn't be priced and are left out of the totals
+    pub missing: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Portfolio {
+    holdings: Vec<Holding>,
+}
+
+impl Portfolio {
+    pub fn new(holdings: Vec<Holding>) -> Self {
+        Portfolio { holdings }
+    }
+
+    pub fn add_holding(&mut self, holding: Holding) {
+        self.holdings.push(holding);
+    }
+
+    pub fn get_holdings(&self) -> &Vec<Holding> {
+        &self.holdings
+    }
+
+    pub fn value(
+        &self,
+        spot_prices: &NameToPriceMap,
+        perps_prices: &NameToPriceMap,
+    ) -> PortfolioValuation {
+        let token_prices = get_token_to_usd_price_map(spot_prices);
+        let mut valuation = PortfolioValuation::default();
+
+        for holding in self.holdings.iter() {
+            let price = match holding {
+                Holding::Spot { token, .. } => token_prices.get(token).copied(),
+                Holding::Perp { coin, .. } => perps_prices
+                    .get(coin.as_str())
+                    .map(|price| price.get_value())
+                    .filter(|price| *price > 0.0),
+            };
+
+            let price = match price {
+                Some(price) => price,
+                None => {
+                    valuation.missing.push(holding.get_name().clone());
+                    continue;
+                }
+            };
+
+            let (usd_value, unrealized_pnl) = match holding {
+                Holding::Spot { amount, .. } => {
+                    valuation.spot_usd += amount * price;
+                    (amount * price, 0.0)
+                }
+                Holding::Perp {
+                    size, entry_price, ..
+                } => {
+                    let pnl = entry_price.map_or(0.0, |entry| (price - entry) * size);
+
+                    valuation.perps_notional_usd += (size * price).abs();
+                    valuation.unrealized_pnl += pnl;
+                    (size * price, pnl)
+                }
+            };
+
+            valuation.holdings.push(HoldingValuation {
+                holding: holding.clone(),
+                price,
+                usd_value,
+                unrealized_pnl,
+            });
+        }
+
+        valuation.total_usd = valuation.spot_usd + valuation.unrealized_pnl;
+
+        valuation
+    }
+
+    /// Revalues the portfolio every time either price channel changes.
+    pub async fn start_valuation_task(
+        self,
+        mut spot_receiver: watch::Receiver<NameToPriceMap>,
+        mut perps_receiver: watch::Receiver<NameToPriceMap>,
+    ) -> anyhow::Result<watch::Receiver<PortfolioValuation>> {
+        let initial = self.value(&spot_receiver.borrow(), &perps_receiver.borrow());
+        let (valuation_sender, valuation_recv) = watch::channel(initial);
+
+        tokio::spawn(async move {
+            loop {
+                let changed = tokio::select! {
+                    res = spot_receiver.changed() => res,
+                    res = perps_receiver.changed() => res,
+                };
+
+                if changed.is_err() {
+                    info!("portfolio_valuation_task: Price channel closed, stopping...");
+                    return;
+                }
+
+                let valuation = self.value(
+                    &spot_receiver.borrow_and_update(),
+                    &perps_receiver.borrow_and_update(),
+                );
+
+                if valuation_sender.send(valuation).is_err() {
+                    info!("portfolio_valuation_task: All receivers dropped, stopping...");
+                    return;
+                }
+            }
+        });
+
+        Ok(valuation_recv)
+    }
+}
+
+/// USD price of every spot token quoted against USDC. USDC itself is always 1.0.
+pub fn get_token_to_usd_price_map(spot_prices: &NameToPriceMap) -> HashMap<String, f64> {
+    let mut token_prices: HashMap<String, f64> = spot_prices
+        .values()
+        .filter_map(|price| match price {
+            // The first token of a pair is stored as `quote` and the second as `base`
+            Price::Spot {
+                price,
+                meta: Meta::Spot { quote, base, .. },
+            } if base.name == USDC && *price > 0.0 => Some((quote.name.clone(), *price)),
+            _ => None,
+        })
+        .collect();
+
+    token_prices.insert(USDC.to_string(), 1.0);
+
+    token_prices
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Meta, NameToPriceMap, Price, SpotAssetMeta, Symbol};
+
+    use super::{Holding, Portfolio};
+
+    fn spot_prices() -> NameToPriceMap {
+        let token = |name: &str, index| SpotAssetMeta {
+            sz_decimals: 2,
+            wei_decimals: 8,
+            name: name.to_string(),
+            index,
+        };
+
+        let meta = Meta::Spot {
+            name: "@1".to_string(),
+            index: 1,
+            quote: token("PURR", 1),
+            base: token("USDC", 0),
+        };
+
+        [(Symbol::from_api("@1"), Price::new_spot(0.2, meta))]
+            .into_iter()
+            .collect()
+    }
+
+    fn perps_prices(eth: f64) -> NameToPriceMap {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 4,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        };
+
+        [(Symbol::from_api("ETH"), Price::new_perp(eth, meta))]
+            .into_iter()
+            .collect()
+    }
+
+    fn portfolio() -> Portfolio {
+        Portfolio::new(vec![
+            Holding::Spot {
+                token: "USDC".to_string(),
+                amount: 1000.0,
+            },
+            Holding::Spot {
+                token: "PURR".to_string(),
+                amount: 500.0,
+            },
+            Holding::Perp {
+                coin: "ETH".to_string(),
+                size: -2.0,
+                entry_price: Some(2100.0),
+            },
+            Holding::Perp {
+                coin: "BTC".to_string(),
+                size: 1.0,
+                entry_price: None,
+            },
+        ])
+    }
+
+    #[test]
+    fn values_spot_and_perps() {
+        let valuation = portfolio().value(&spot_prices(), &perps_prices(2000.0));
+
+        assert_eq!(valuation.spot_usd, 1100.0);
+        assert_eq!(valuation.perps_notional_usd, 4000.0);
+        // Short 2 from 2100 to 2000
+        assert_eq!(valuation.unrealized_pnl, 200.0);
+        assert_eq!(valuation.total_usd, 1300.0);
+        assert_eq!(valuation.holdings.len(), 3);
+        assert_eq!(valuation.holdings[2].usd_value, -4000.0);
+        assert_eq!(valuation.missing, vec!["BTC".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn revalues_on_price_changes() -> anyhow::Result<()> {
+        let (_spot_sender, spot_receiver) = tokio::sync::watch::channel(spot_prices());
+        let (perps_sender, perps_receiver) = tokio::sync::watch::channel(perps_prices(2000.0));
+
+        let mut valuations = portfolio()
+            .start_valuation_task(spot_receiver, perps_receiver)
+            .await?;
+        assert_eq!(valuations.borrow().unrealized_pnl, 200.0);
+
+        perps_sender.send(perps_prices(2200.0))?;
+        valuations.changed().await?;
+        assert_eq!(valuations.borrow().unrealized_pnl, -200.0);
+
+        Ok(())
+    }
+}
@@ -1,2 +1,3 @@
 pub mod spot;
 pub mod perps;
+pub mod symbols;
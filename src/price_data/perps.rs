@@ -5,8 +5,9 @@ use serde::{
     de::{self, Visitor},
     Deserialize, Deserializer, Serialize,
 };
+use tracing::warn;
 
-use crate::types::{Meta, NameToPriceMap, Price};
+use crate::types::{Meta, NameToPriceMap, Price, Symbol};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PerpsMeta {
@@ -14,48 +15,77 @@ pub struct PerpsMeta {
 }
 
 impl PerpsMeta {
-    pub fn get_perps_prices_data(self, prices: HashMap<String, f64>) -> PerpsPriceData {
-        let universe = &self.universe;
+    pub fn get_coin_names(&self) -> Vec<String> {
+        self.universe.iter().map(|uni| uni.name.clone()).collect()
+    }
 
-        let name_to_universe_map: HashMap<&str, &UniverseData> = self
-            .universe
+    /// Metas of every coin in the universe, whether it has a price or not
+    pub fn get_metas(&self) -> Vec<Meta> {
+        self.universe
             .iter()
-            .map(|uni| (uni.name.as_str(), uni))
-            .collect();
-
-        let mut result: HashMap<String, Price> = HashMap::new();
-
-        for i in 0..universe.len() {
-            let universe_data = name_to_universe_map[universe[i].name.as_str()];
-
-            result.insert(
-                universe[i].name.clone(),
-                Price::new_perp(
-                    prices[&universe_data.name],
-                    Meta::Perp {
-                        name: universe[i].name.clone(),
-                        sz_decimals: universe_data.sz_decimals,
-                        max_leverage: universe[i].max_leverage,
-                        only_isolated: universe[i].only_isolated,
-                        is_delisted: universe[i].is_delisted,
-                    },
-                ),
-            );
+            .enumerate()
+            .map(|(i, uni)| Meta::Perp {
+                name: uni.name.clone(),
+                index: i as u16,
+                sz_decimals: uni.sz_decimals,
+                max_leverage: uni.max_leverage,
+                only_isolated: uni.only_isolated,
+                is_delisted: uni.is_delisted,
+            })
+            .collect()
+    }
+
+    pub fn get_perps_prices_data(self, prices: HashMap<String, f64>) -> PerpsPriceData {
+        let mut result = NameToPriceMap::default();
+
+        for meta in self.get_metas() {
+            let price = match prices.get(meta.get_name()) {
+                Some(price) => *price,
+                None => {
+                    warn!("No price for {}, leaving it out", meta.get_name());
+                    continue;
+                }
+            };
+
+            result.insert(meta.get_symbol(), Price::new_perp(price, meta));
         }
 
-        PerpsPriceData { map: result }
+        PerpsPriceData {
+            meta: self,
+            map: result,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct PerpsPriceData {
+    meta: PerpsMeta,
     pub map: NameToPriceMap,
 }
 
 impl PerpsPriceData {
+    /// Coins missing from `price_map` keep their previous price. Coins that weren't priced yet
+    /// are added as soon as they show up.
     pub fn update(&mut self, price_map: HashMap<String, f64>) {
         for (name, price) in self.map.iter_mut() {
-            price.update_price(price_map[name])
+            if let Some(new_price) = price_map.get(name.as_str()) {
+                price.update_price(*new_price)
+            }
+        }
+
+        if self.map.len() == self.meta.universe.len() {
+            return;
+        }
+
+        for meta in self.meta.get_metas() {
+            if self.map.contains_key(meta.get_name().as_str()) {
+                continue;
+            }
+
+            if let Some(new_price) = price_map.get(meta.get_name()) {
+                self.map
+                    .insert(meta.get_symbol(), Price::new_perp(*new_price, meta));
+            }
         }
     }
 }
@@ -70,9 +100,28 @@ struct UniverseData {
     pub is_delisted: Option<bool>,
 }
 
+pub type NameToCtxMap = HashMap<String, PerpsAssetCtx>;
+
+/// Response of the `metaAndAssetCtxs` info request. The contexts are in the same order as the
+/// universe.
 #[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PerpsMetaAndAssetCtxs(pub PerpsMeta, pub Vec<PerpsAssetCtx>);
+
+impl PerpsMetaAndAssetCtxs {
+    pub fn get_name_to_ctx_map(&self) -> NameToCtxMap {
+        self.0
+            .universe
+            .iter()
+            .zip(self.1.iter())
+            .map(|(uni, ctx)| (uni.name.clone(), ctx.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
 #[serde(rename_all = "camelCase")]
-struct PairPriceData {
+pub struct PerpsAssetCtx {
+    /// Hourly funding rate
     #[serde(deserialize_with = "parse_string_to_float")]
     pub funding: f64,
 
@@ -100,7 +149,93 @@ struct PairPriceData {
     pub impact_pxs: Option<Vec<String>>,
 }
 
-fn parse_string_to_float<'de, D>(deserializer: D) -> Result<f64, D::Error>
+pub type NameToPerpQuoteMap = HashMap<String, PerpQuote>;
+
+/// A live perps mid joined with the last polled asset context of the coin. The mark, oracle,
+/// funding and open interest are only as fresh as `ctx_time`, the mid is not. `ctx` is `None`
+/// until the first contexts were fetched, or for coins listed after that.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct PerpQuote {
+    pub price: Price,
+    pub ctx: Option<PerpsAssetCtx>,
+    /// When `ctx` was fetched, ms since epoch
+    pub ctx_time: Option<i64>,
+}
+
+impl PerpQuote {
+    pub fn get_mid(&self) -> f64 {
+        self.price.get_value()
+    }
+
+    pub fn get_mark_px(&self) -> Option<f64> {
+        Some(self.ctx.as_ref()?.mark_px)
+    }
+
+    pub fn get_oracle_px(&self) -> Option<f64> {
+        Some(self.ctx.as_ref()?.oracle_px)
+    }
+
+    /// Hourly funding rate
+    pub fn get_funding(&self) -> Option<f64> {
+        Some(self.ctx.as_ref()?.funding)
+    }
+
+    /// Open interest in units of the coin
+    pub fn get_open_interest(&self) -> Option<f64> {
+        Some(self.ctx.as_ref()?.open_interest)
+    }
+
+    /// Relative distance of the mark from the oracle (0.01 == 1%)
+    pub fn get_mark_oracle_basis(&self) -> Option<f64> {
+        let ctx = self.ctx.as_ref()?;
+
+        if ctx.oracle_px == 0.0 {
+            return None;
+        }
+
+        Some((ctx.mark_px - ctx.oracle_px) / ctx.oracle_px)
+    }
+
+    /// Largest distance of the mid or mark from the oracle, in basis points of the oracle
+    pub fn get_oracle_deviation_bps(&self) -> Option<f64> {
+        let ctx = self.ctx.as_ref()?;
+
+        if ctx.oracle_px <= 0.0 {
+            return None;
+        }
+
+        let mid_deviation = (self.get_mid() - ctx.oracle_px).abs();
+        let mark_deviation = (ctx.mark_px - ctx.oracle_px).abs();
+
+        Some(mid_deviation.max(mark_deviation) / ctx.oracle_px * 10_000.0)
+    }
+}
+
+/// Joins every price in `prices` with its context in `ctxs`, fetched at `ctx_time`.
+pub fn get_perp_quote_map(
+    prices: &NameToPriceMap,
+    ctxs: &NameToCtxMap,
+    ctx_time: Option<i64>,
+) -> NameToPerpQuoteMap {
+    prices
+        .iter()
+        .map(|(name, price)| {
+            (
+                name.to_string(),
+                PerpQuote {
+                    price: price.clone(),
+                    ctx: ctxs.get(name.as_str()).cloned(),
+                    ctx_time: ctxs
+                        .contains_key(name.as_str())
+                        .then_some(ctx_time)
+                        .flatten(),
+                },
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn parse_string_to_float<'de, D>(deserializer: D) -> Result<f64, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -141,3 +276,74 @@ where
     // Accepts either a valid string or null (handled as unit).
     deserializer.deserialize_any(StringToFloatVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Meta, NameToPriceMap, Price, Symbol};
+
+    use super::{get_perp_quote_map, NameToCtxMap, PerpsAssetCtx, PerpsMeta};
+
+    #[test]
+    fn coins_priced_late_are_added() {
+        let meta: PerpsMeta = serde_json::from_str(
+            r#"{"universe": [
+                {"name": "BTC", "szDecimals": 5, "maxLeverage": 40},
+                {"name": "ETH", "szDecimals": 4, "maxLeverage": 25}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut data = meta.get_perps_prices_data([("BTC".to_string(), 60000.0)].into());
+        assert!(!data.map.contains_key("ETH"));
+
+        data.update([("ETH".to_string(), 2000.0), ("@1".to_string(), 1.0)].into());
+
+        assert_eq!(data.map["BTC"].get_value(), 60000.0);
+        assert_eq!(data.map["ETH"].get_value(), 2000.0);
+        assert!(matches!(
+            data.map["ETH"].get_meta(),
+            Meta::Perp { index: 1, .. }
+        ));
+        assert_eq!(data.map.len(), 2);
+    }
+
+    #[test]
+    fn quotes_join_prices_with_ctxs() {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 4,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        };
+        let prices: NameToPriceMap = [
+            (
+                Symbol::from_api("ETH"),
+                Price::new_perp(2000.0, meta.clone()),
+            ),
+            (Symbol::from_api("NEW"), Price::new_perp(1.0, meta)),
+        ]
+        .into_iter()
+        .collect();
+        let ctxs = NameToCtxMap::from([(
+            "ETH".to_string(),
+            PerpsAssetCtx {
+                mark_px: 2002.0,
+                oracle_px: 2000.0,
+                funding: 0.0001,
+                ..Default::default()
+            },
+        )]);
+
+        let quotes = get_perp_quote_map(&prices, &ctxs, Some(1_000));
+
+        let eth = &quotes["ETH"];
+        assert_eq!(eth.get_mid(), 2000.0);
+        assert_eq!(eth.get_mark_px(), Some(2002.0));
+        assert!((eth.get_mark_oracle_basis().unwrap() - 0.001).abs() < 1e-12);
+        assert_eq!(eth.ctx_time, Some(1_000));
+        assert_eq!(quotes["NEW"].get_funding(), None);
+        assert_eq!(quotes["NEW"].ctx_time, None);
+    }
+}
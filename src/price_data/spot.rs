@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 
-use ethers::types::H128;
+use anyhow::{anyhow, bail, Error};
 use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-use crate::types::{Meta, NameToPriceMap, Price, SpotAssetMeta};
+use crate::types::{Meta, NameToPriceMap, Price, SpotAssetMeta, Symbol};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SpotMeta {
@@ -28,7 +28,8 @@ struct UniverseTokensData {
     pub sz_decimals: u16,
     pub wei_decimals: u16,
     pub index: u16,
-    pub token_id: H128,
+    /// Hex encoded 16 byte id
+    pub token_id: String,
     pub is_canonical: bool,
 }
 
@@ -40,177 +41,202 @@ impl SpotMeta {
             .collect()
     }
 
-    pub fn get_spot_price_data(self, prices: HashMap<String, f64>) -> SpotPriceData {
-        let res: NameToPriceMap = self
-            .universe
+    /// Maps every pair name in the universe (e.g. "@107") to its (first, second) token names.
+    pub fn get_pair_name_to_tokens_map(&self) -> HashMap<String, (String, String)> {
+        let index_to_name: HashMap<u16, String> = self.get_index_to_name_map();
+
+        self.universe
             .iter()
-            .map(|uni| {
-                let price = prices[&uni.name];
-
-                let quote_spot_context: SpotAssetMeta = self
-                    .tokens
-                    .iter()
-                    .find_map(|token| {
-                        if token.index == uni.tokens[0] {
-                            Some(SpotAssetMeta {
-                                sz_decimals: token.sz_decimals,
-                                wei_decimals: token.wei_decimals,
-                                index: token.index,
-                                name: token.name.clone(),
-                            })
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap();
-
-                let base_spot_context: SpotAssetMeta = self
-                    .tokens
-                    .iter()
-                    .find_map(|token| {
-                        if token.index == uni.tokens[1] {
-                            Some(SpotAssetMeta {
-                                sz_decimals: token.sz_decimals,
-                                wei_decimals: token.wei_decimals,
-                                index: token.index,
-                                name: token.name.clone(),
-                            })
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap();
-
-                (
+            .filter_map(|uni| {
+                Some((
                     uni.name.clone(),
-                    Price::new_spot(
-                        price,
-                        Meta::Spot {
-                            name: uni.name.clone(),
-                            quote: quote_spot_context,
-                            base: base_spot_context,
-                        },
+                    (
+                        index_to_name.get(&uni.tokens[0])?.clone(),
+                        index_to_name.get(&uni.tokens[1])?.clone(),
                     ),
-                )
+                ))
+            })
+            .collect()
+    }
+
+    /// Maps "TOKEN1/TOKEN2" to the pair name in the universe (e.g. "@107").
+    pub fn get_pair_to_name_map(&self) -> HashMap<String, String> {
+        self.get_pair_name_to_tokens_map()
+            .into_iter()
+            .map(|(name, (token_1_name, token_2_name))| {
+                (format!("{}/{}", token_1_name, token_2_name), name)
+            })
+            .collect()
+    }
+
+    fn get_spot_asset_meta(&self, token_index: u16) -> Option<SpotAssetMeta> {
+        self.tokens.iter().find_map(|token| {
+            if token.index == token_index {
+                Some(SpotAssetMeta {
+                    sz_decimals: token.sz_decimals,
+                    wei_decimals: token.wei_decimals,
+                    index: token.index,
+                    name: token.name.clone(),
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Maps token indices to their metas, e.g. to convert the balances of
+    /// `spotClearinghouseState`.
+    pub fn get_token_metas(&self) -> HashMap<u16, SpotAssetMeta> {
+        self.tokens
+            .iter()
+            .filter_map(|token| Some((token.index, self.get_spot_asset_meta(token.index)?)))
+            .collect()
+    }
+
+    fn get_spot_meta(&self, uni: &UniverseData) -> Result<Meta, Error> {
+        let quote_spot_context: SpotAssetMeta = self
+            .get_spot_asset_meta(uni.tokens[0])
+            .ok_or_else(|| anyhow!("Unknown token {} in pair {}", uni.tokens[0], uni.name))?;
+
+        let base_spot_context: SpotAssetMeta = self
+            .get_spot_asset_meta(uni.tokens[1])
+            .ok_or_else(|| anyhow!("Unknown token {} in pair {}", uni.tokens[1], uni.name))?;
+
+        Ok(Meta::Spot {
+            name: uni.name.clone(),
+            index: uni.index,
+            quote: quote_spot_context,
+            base: base_spot_context,
+        })
+    }
+
+    fn get_spot_price(&self, uni: &UniverseData, price: f64) -> Result<Price, Error> {
+        Ok(Price::new_spot(price, self.get_spot_meta(uni)?))
+    }
+
+    /// Metas of every pair in the universe, whether it has a price or not. Pairs with unknown
+    /// tokens are left out.
+    pub fn get_metas(&self) -> Vec<Meta> {
+        self.universe
+            .iter()
+            .filter_map(|uni| match self.get_spot_meta(uni) {
+                Ok(meta) => Some(meta),
+                Err(err) => {
+                    warn!("Skipping pair {}: {err:?}", uni.name);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Pairs without a price in `prices` are left out and backfilled by
+    /// [`SpotPriceData::update`] once a price shows up. Fails if no pair could be priced at all.
+    pub fn get_spot_price_data(self, prices: HashMap<String, f64>) -> Result<SpotPriceData, Error> {
+        let res: NameToPriceMap = self
+            .universe
+            .iter()
+            .filter_map(|uni| {
+                let price = match prices.get(&uni.name) {
+                    Some(price) => *price,
+                    None => {
+                        warn!("No price for the pair {} yet, leaving it out", uni.name);
+                        return None;
+                    }
+                };
+
+                match self.get_spot_price(uni, price) {
+                    Ok(price) => Some((Symbol::from_api(&uni.name), price)),
+                    Err(err) => {
+                        warn!("Skipping pair {}: {err:?}", uni.name);
+                        None
+                    }
+                }
             })
             .collect();
 
-        SpotPriceData {
+        if res.is_empty() && !self.universe.is_empty() {
+            bail!("None of the {} spot pairs had a price", self.universe.len());
+        }
+
+        Ok(SpotPriceData {
+            pair_to_name: self.get_pair_to_name_map(),
             meta: self,
             map: res,
-        }
+        })
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct SpotPriceData {
     meta: SpotMeta,
+    /// "TOKEN1/TOKEN2" to the pair name used as key in `map`, built once from the meta
+    pair_to_name: HashMap<String, String>,
     pub map: NameToPriceMap,
 }
 
 impl SpotPriceData {
     pub fn get_pair_to_raw_price_map(&self) -> HashMap<String, f64> {
-        let index_to_name: HashMap<u16, String> = self.meta.get_index_to_name_map();
-
-        self.meta
-            .universe
+        self.pair_to_name
             .iter()
-            .map(|val| {
-                let token_1_name = if let Some(name) = index_to_name.get(val.tokens.get(0).unwrap())
-                {
-                    name
-                } else {
-                    ""
-                };
-                let token_2_name = if let Some(name) = index_to_name.get(val.tokens.get(1).unwrap())
-                {
-                    name
-                } else {
-                    ""
-                };
-
-                let price = if let Some(price) = self.map.get(&val.name) {
+            .map(|(pair, name)| {
+                let price = if let Some(price) = self.map.get(name.as_str()) {
                     price.get_value()
                 } else {
-                    warn!(
-                        "There was an issue getting the price for the pair {}/{}",
-                        token_1_name, token_2_name
-                    );
+                    warn!("There was an issue getting the price for the pair {}", pair);
                     0.0
                 };
 
-                (
-                    format!("{}/{}", token_1_name, token_2_name).to_string(),
-                    price,
-                )
+                (pair.clone(), price)
             })
             .collect()
     }
 
     pub fn get_pair_to_name_map(&self) -> HashMap<String, String> {
-        let index_to_name: HashMap<u16, String> = self.meta.get_index_to_name_map();
-
-        self.meta
-            .universe
-            .iter()
-            .map(|val| {
-                let token_1_name = if let Some(name) = index_to_name.get(val.tokens.get(0).unwrap())
-                {
-                    name
-                } else {
-                    ""
-                };
-                let token_2_name = if let Some(name) = index_to_name.get(val.tokens.get(1).unwrap())
-                {
-                    name
-                } else {
-                    ""
-                };
-
-                (
-                    format!("{}/{}", token_1_name, token_2_name).to_string(),
-                    val.name.clone(),
-                )
-            })
-            .collect()
+        self.pair_to_name.clone()
     }
 
+    /// Pairs missing from `price_map` keep their previous price. Pairs that weren't priced yet
+    /// are added as soon as they show up.
     pub fn update(&mut self, price_map: HashMap<String, f64>) {
         for (name, price) in self.map.iter_mut() {
-            price.update_price(price_map[name])
+            if let Some(new_price) = price_map.get(name.as_str()) {
+                price.update_price(*new_price)
+            }
         }
-    }
 
-    pub fn get_price_from_pair(&self, pair: String) -> f64 {
-        let index_to_name: HashMap<u16, String> = self.meta.get_index_to_name_map();
-
-        self.meta
-            .universe
-            .iter()
-            .filter(|val| {
-                let token_1_name = if let Some(name) = index_to_name.get(val.tokens.get(0).unwrap())
-                {
-                    name
-                } else {
-                    ""
-                };
-                let token_2_name = if let Some(name) = index_to_name.get(val.tokens.get(1).unwrap())
-                {
-                    name
-                } else {
-                    ""
-                };
+        if self.map.len() == self.meta.universe.len() {
+            return;
+        }
 
-                format!("{}/{}", token_1_name, token_2_name) == pair
-            })
-            .map(|val| {
-                if let Some(price) = self.map.get(&val.name) {
-                    price.get_value()
-                } else {
-                    0.0
+        for uni in self.meta.universe.iter() {
+            if self.map.contains_key(uni.name.as_str()) {
+                continue;
+            }
+
+            if let Some(new_price) = price_map.get(&uni.name) {
+                match self.meta.get_spot_price(uni, *new_price) {
+                    Ok(price) => {
+                        self.map.insert(Symbol::from_api(&uni.name), price);
+                    }
+                    Err(err) => warn!("Skipping pair {}: {err:?}", uni.name),
                 }
-            })
-            .next()
-            .unwrap()
+            }
+        }
+    }
+
+    /// Resolves a "TOKEN1/TOKEN2" pair to the symbol it's keyed by in `map` (e.g. "@107").
+    pub fn get_symbol_from_pair(&self, pair: &Symbol) -> Option<Symbol> {
+        self.pair_to_name
+            .get(pair.as_str())
+            .map(|name| Symbol::from_api(name))
+    }
+
+    /// Price of a pair given as "TOKEN1/TOKEN2", `None` if the pair doesn't exist or hasn't been
+    /// priced yet.
+    pub fn get_price_from_pair(&self, pair: &str) -> Option<f64> {
+        self.pair_to_name
+            .get(pair)
+            .and_then(|name| self.map.get(name.as_str()))
+            .map(|price| price.get_value())
     }
 }
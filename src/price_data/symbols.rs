@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::price_data::{perps::PerpsMeta, spot::SpotMeta};
+
+/// Spot tokens that are named differently from their perp, as (spot token, perp coin).
+pub const DEFAULT_ALIASES: [(&str, &str); 3] = [("UBTC", "BTC"), ("UETH", "ETH"), ("USOL", "SOL")];
+
+/// Lookups between perp coins and the spot pairs trading the same asset, keyed the same way as
+/// the perps and spot `NameToPriceMap`s.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolMap {
+    perp_to_spot: HashMap<String, Vec<String>>,
+    spot_to_perp: HashMap<String, String>,
+}
+
+impl SymbolMap {
+    pub fn new(perps_meta: &PerpsMeta, spot_meta: &SpotMeta) -> Self {
+        Self::with_aliases(perps_meta, spot_meta, &DEFAULT_ALIASES)
+    }
+
+    pub fn with_aliases(
+        perps_meta: &PerpsMeta,
+        spot_meta: &SpotMeta,
+        aliases: &[(&str, &str)],
+    ) -> Self {
+        let coins = perps_meta.get_coin_names();
+        let aliases: HashMap<&str, &str> = aliases.iter().copied().collect();
+
+        let mut symbol_map = SymbolMap::default();
+
+        for (pair_name, (token, _)) in spot_meta.get_pair_name_to_tokens_map() {
+            let coin = aliases
+                .get(token.as_str())
+                .map(|coin| coin.to_string())
+                .unwrap_or(token);
+
+            if !coins.contains(&coin) {
+                continue;
+            }
+
+            symbol_map
+                .perp_to_spot
+                .entry(coin.clone())
+                .or_default()
+                .push(pair_name.clone());
+            symbol_map.spot_to_perp.insert(pair_name, coin);
+        }
+
+        // Keep lookups deterministic, the meta map iteration order isn't
+        symbol_map
+            .perp_to_spot
+            .values_mut()
+            .for_each(|pairs| pairs.sort());
+
+        symbol_map
+    }
+
+    /// Spot pairs trading the perp's asset, empty if there are none.
+    pub fn get_spot_pairs(&self, coin: &str) -> &[String] {
+        self.perp_to_spot
+            .get(coin)
+            .map(|pairs| pairs.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn get_perp(&self, spot_pair: &str) -> Option<&String> {
+        self.spot_to_perp.get(spot_pair)
+    }
+
+    pub fn get_perp_to_spot_map(&self) -> &HashMap<String, Vec<String>> {
+        &self.perp_to_spot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::price_data::{perps::PerpsMeta, spot::SpotMeta};
+
+    use super::SymbolMap;
+
+    fn token(name: &str, index: u16) -> serde_json::Value {
+        json!({
+            "name": name, "szDecimals": 2, "weiDecimals": 8, "index": index,
+            "tokenId": format!("0x{index:032x}"), "isCanonical": true
+        })
+    }
+
+    fn pair(name: &str, tokens: [u16; 2], index: u16) -> serde_json::Value {
+        json!({ "tokens": tokens, "name": name, "index": index, "isCanonical": false })
+    }
+
+    #[test]
+    fn maps_perps_to_spot_pairs_through_aliases() {
+        let perps_meta: PerpsMeta = serde_json::from_value(json!({
+            "universe": [
+                { "name": "ETH", "szDecimals": 4, "maxLeverage": 25 },
+                { "name": "HYPE", "szDecimals": 2, "maxLeverage": 10 },
+                { "name": "DOGE", "szDecimals": 0, "maxLeverage": 10 }
+            ]
+        }))
+        .unwrap();
+
+        let spot_meta: SpotMeta = serde_json::from_value(json!({
+            "universe": [
+                pair("@1", [1, 0], 1),
+                pair("@2", [2, 0], 2),
+                pair("@3", [2, 1], 3),
+                pair("@4", [3, 0], 4)
+            ],
+            "tokens": [token("USDC", 0), token("UETH", 1), token("HYPE", 2), token("PURR", 3)]
+        }))
+        .unwrap();
+
+        let symbol_map = SymbolMap::new(&perps_meta, &spot_meta);
+
+        assert_eq!(symbol_map.get_spot_pairs("ETH"), ["@1".to_string()]);
+        assert_eq!(
+            symbol_map.get_spot_pairs("HYPE"),
+            ["@2".to_string(), "@3".to_string()]
+        );
+        assert!(symbol_map.get_spot_pairs("DOGE").is_empty());
+
+        assert_eq!(symbol_map.get_perp("@1"), Some(&"ETH".to_string()));
+        // PURR has no perp
+        assert_eq!(symbol_map.get_perp("@4"), None);
+    }
+}
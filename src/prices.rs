@@ -1,78 +1,128 @@
-use std::{collections::HashMap, thread::sleep};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
-use anyhow::{Context, Error};
-use hyperliquid_rust_sdk::{BaseUrl, InfoClient, Message, Subscription};
+use anyhow::{bail, Context, Error};
+use chrono::Utc;
+use hyperliquid_rust_sdk::{BaseUrl, L2BookData, Message, Subscription};
 use reqwest::{
     header::{HeaderMap, HeaderValue, CONTENT_TYPE},
-    Client, Url,
+    Client, Proxy,
 };
-use serde_json::json;
-use tokio::sync::{
-    mpsc::{unbounded_channel, UnboundedReceiver},
-    watch,
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        watch,
+    },
+    task::JoinHandle,
+    time::{sleep, Instant},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{
+    breaker::{get_info_breaker, is_cacheable_info},
+    counters::{Counter, FeedCounter, FeedCounters},
+    endpoints::get_info_endpoints,
+    events::{emit, ConnectionEvents, FeedEventKind},
     price_data::{
-        perps::{PerpsMeta, PerpsPriceData},
+        perps::{
+            get_perp_quote_map, NameToCtxMap, NameToPerpQuoteMap, PerpsMeta, PerpsMetaAndAssetCtxs,
+            PerpsPriceData,
+        },
         spot::{SpotMeta, SpotPriceData},
+        symbols::SymbolMap,
+    },
+    ratelimit::{get_info_weight, get_rest_rate_limiter},
+    subscription::{Heartbeat, SubscriptionGuard},
+    transport::{request_info, LiveTransport, Transport},
+    types::{
+        set_pair_to_name_map, FastMap, NameToPriceMap, NameToUpdateTimeMap, Orderbook, Price,
+        Symbol,
     },
-    types::{NameToPriceMap, Price},
 };
 
+/// AllMids is pushed every block, so a few seconds without a message means the connection is dead.
+pub const PRICES_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Updates further apart than this emit a `PriceGap` feed event
+pub const PRICE_GAP_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// How long a `Prices` session runs before it unsubscribes and the sender task resubscribes.
+pub const DEFAULT_SESSION_DURATION: Duration = Duration::from_secs(20 * 60 * 60);
+
+/// A mid from the AllMids feed that couldn't be parsed and was left out of the price map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidParseFailure {
+    pub coin: String,
+    pub raw: String,
+}
+
 pub struct Prices {
-    client: Client,
-    info_client: InfoClient,
+    transport: Arc<dyn Transport>,
+    subscriptions: SubscriptionGuard,
     price_receiver: UnboundedReceiver<Message>,
-    sub_id: u32,
+    heartbeat: Heartbeat,
+    last_update: Option<Instant>,
+    session_duration: Duration,
+    parse_failure_count: u64,
+    last_parse_failures: Vec<MidParseFailure>,
+    counter: FeedCounter,
 }
 
 impl Prices {
     pub async fn new() -> Result<Self, Error> {
-        let mut info_client = InfoClient::new(None, Some(BaseUrl::Mainnet)).await.unwrap();
-
-        let (sender, receiver) = unbounded_channel();
-        let sub_id = info_client
-            .subscribe(Subscription::AllMids, sender.clone())
-            .await
-            .context("Couldn't get subscriptions id")?;
+        Prices::with_transport(Arc::new(LiveTransport::new().await?)).await
+    }
 
-        let mut headers = HeaderMap::new();
+    /// Same as [`Prices::new`] but with a dedicated HTTP client built from `config` instead of the
+    /// shared one.
+    pub async fn with_http_config(config: &HttpConfig) -> Result<Self, Error> {
+        Prices::with_transport(Arc::new(
+            LiveTransport::with_http_client(build_http_client(config)?).await?,
+        ))
+        .await
+    }
 
-        headers.append(
-            CONTENT_TYPE,
-            HeaderValue::from_str("application/json").unwrap(),
-        );
+    /// Sends both the info requests and the AllMids subscription through `transport`.
+    pub async fn with_transport(transport: Arc<dyn Transport>) -> Result<Self, Error> {
+        let mut subscriptions = SubscriptionGuard::with_transport(transport.clone());
 
-        let client = reqwest::ClientBuilder::new()
-            .default_headers(headers)
-            .build()?;
+        let (sender, receiver) = unbounded_channel();
+        subscriptions
+            .subscribe(Subscription::AllMids, sender.clone())
+            .await?;
 
         Ok(Prices {
-            client,
-            info_client,
+            transport,
+            subscriptions,
             price_receiver: receiver,
-            sub_id,
+            heartbeat: Heartbeat::new(PRICES_HEARTBEAT_TIMEOUT).with_feed("prices"),
+            last_update: None,
+            session_duration: DEFAULT_SESSION_DURATION,
+            parse_failure_count: 0,
+            last_parse_failures: vec![],
+            counter: FeedCounter::new("prices"),
         })
     }
 
-    pub async fn get_all_spot_meta(&self) -> Result<SpotMeta, Error> {
-        let data = json!({ "type": "spotMeta" });
-
-        let response = self
-            .client
-            .post(Url::parse("https://api-ui.hyperliquid.xyz/info")?)
-            .json(&data)
-            .send()
-            .await?;
-
-        let bytes = response.bytes().await?;
+    /// Counts on `counter` instead of counters of its own, e.g. to keep counting across
+    /// sessions.
+    pub fn with_counter(mut self, counter: FeedCounter) -> Self {
+        self.counter = counter;
+        self
+    }
 
-        // Deserializing this way seems to be more reliable
-        let response = serde_json::from_slice::<SpotMeta>(&bytes)?;
+    pub fn get_counters(&self) -> FeedCounters {
+        self.counter.get_counters()
+    }
 
-        Ok(response)
+    pub async fn get_all_spot_meta(&self) -> Result<SpotMeta, Error> {
+        request_info(&*self.transport, json!({ "type": "spotMeta" })).await
     }
 
     pub async fn start_sending(
@@ -81,17 +131,15 @@ impl Prices {
     ) -> Result<(), Error> {
         let mut spot_price_data = self.get_spot_price_data().await?;
 
-        let mut i = 0;
+        let session_start = Instant::now();
 
-        // Every 20 hours
-        while i < 100_000 {
+        while session_start.elapsed() < self.session_duration {
             spot_price_data.update(self.get_all_prices().await?);
             let name_to_price_map = spot_price_data.map.clone();
 
             sender.send(name_to_price_map)?;
-            sleep(std::time::Duration::from_millis(800));
-
-            i += 1;
+            self.counter.count(Counter::Sends);
+            sleep(std::time::Duration::from_millis(800)).await;
         }
 
         Ok(())
@@ -103,44 +151,50 @@ impl Prices {
     ) -> Result<(), Error> {
         let mut perps_price_data = self.get_perps_price_data().await?;
 
-        let mut i = 0;
+        let session_start = Instant::now();
 
-        // Every 20 hours
-        while i < 100_000 {
+        while session_start.elapsed() < self.session_duration {
             perps_price_data.update(self.get_all_prices().await?);
 
             let name_to_price_map = perps_price_data.map.clone();
 
             sender.send(name_to_price_map)?;
-            sleep(std::time::Duration::from_millis(800));
-
-            i += 1;
+            self.counter.count(Counter::Sends);
+            sleep(std::time::Duration::from_millis(800)).await;
         }
 
         Ok(())
     }
 
     pub async fn get_all_perps_meta(&self) -> Result<PerpsMeta, Error> {
-        let data = json!({ "type": "meta" });
-
-        let response = self
-            .client
-            .post(Url::parse("https://api-ui.hyperliquid.xyz/info")?)
-            //.post(Url::parse("https://api.hyperliquid-testnet.xyz/info")?)
-            .json(&data)
-            .send()
-            .await?;
+        request_info(&*self.transport, json!({ "type": "meta" })).await
+    }
 
-        let bytes = response.bytes().await?;
+    pub async fn get_symbol_map(&self) -> Result<SymbolMap, Error> {
+        let (perps_meta, spot_meta) =
+            tokio::try_join!(self.get_all_perps_meta(), self.get_all_spot_meta())?;
 
-        // Deserializing this way seems to be more reliable
-        let response = serde_json::from_slice::<PerpsMeta>(&bytes)?;
+        Ok(SymbolMap::new(&perps_meta, &spot_meta))
+    }
 
-        Ok(response)
+    pub async fn get_perps_meta_and_asset_ctxs(&self) -> Result<PerpsMetaAndAssetCtxs, Error> {
+        request_info(&*self.transport, json!({ "type": "metaAndAssetCtxs" })).await
+    }
+
+    pub async fn get_l2_book(&self, coin: &str) -> Result<Orderbook, Error> {
+        let data: L2BookData =
+            request_info(&*self.transport, json!({ "type": "l2Book", "coin": coin })).await?;
+
+        Ok(Orderbook::from(data))
     }
 
     pub async fn get_all_prices(&mut self) -> anyhow::Result<HashMap<String, f64>> {
-        let all_prices: HashMap<String, f64> = match self.price_receiver.recv().await {
+        let msg = self.heartbeat.recv(&mut self.price_receiver).await?;
+        if msg.is_some() {
+            self.counter.count(Counter::Received);
+        }
+
+        let all_prices: HashMap<String, f64> = match msg {
             Some(msg) => match msg {
                 Message::NoData => {
                     error!("Couldn't recieve price data");
@@ -150,14 +204,45 @@ impl Prices {
                     error!("Hyperliquid error while getting price data: {err:?}");
                     return Err(anyhow::anyhow!("Hyperliquid error found"));
                 }
-                Message::AllMids(all_mids) => all_mids
-                    .data
-                    .mids
-                    .into_iter()
-                    .map(|(k, v)| (k, v.parse::<f64>().unwrap_or(0.0_f64)))
-                    .collect(),
+                Message::AllMids(all_mids) => {
+                    if let Some(gap) = self.last_update.map(|last| last.elapsed()) {
+                        if gap > PRICE_GAP_THRESHOLD {
+                            emit(
+                                "prices",
+                                FeedEventKind::PriceGap {
+                                    gap_ms: gap.as_millis() as i64,
+                                },
+                            );
+                        }
+                    }
+                    self.last_update = Some(Instant::now());
+
+                    let mut failures = vec![];
+
+                    let prices = all_mids
+                        .data
+                        .mids
+                        .into_iter()
+                        .filter_map(|(coin, raw)| match raw.parse::<f64>() {
+                            Ok(price) if price.is_finite() && price > 0.0 => Some((coin, price)),
+                            _ => {
+                                warn!("Skipping unparseable mid for {coin}: {raw:?}");
+                                failures.push(MidParseFailure { coin, raw });
+                                None
+                            }
+                        })
+                        .collect();
+
+                    self.counter
+                        .add(Counter::DeserializeFailures, failures.len() as u64);
+                    self.parse_failure_count += failures.len() as u64;
+                    self.last_parse_failures = failures;
+
+                    prices
+                }
                 s => {
                     error!("Got something else: {s:?}");
+                    self.counter.count(Counter::Dropped);
                     HashMap::new()
                 }
             },
@@ -167,6 +252,27 @@ impl Prices {
         Ok(all_prices)
     }
 
+    /// How long `start_sending` and `start_sending_perps` run before returning so the session
+    /// can be rotated.
+    pub fn set_session_duration(&mut self, session_duration: Duration) {
+        self.session_duration = session_duration;
+    }
+
+    /// How long `get_all_prices` waits for a message before treating the connection as dead.
+    pub fn set_heartbeat_timeout(&mut self, timeout: Duration) {
+        self.heartbeat.set_timeout(timeout);
+    }
+
+    /// Number of mids skipped because they couldn't be parsed since this `Prices` was created.
+    pub fn get_parse_failure_count(&self) -> u64 {
+        self.parse_failure_count
+    }
+
+    /// Mids skipped in the last AllMids message.
+    pub fn get_last_parse_failures(&self) -> &Vec<MidParseFailure> {
+        &self.last_parse_failures
+    }
+
     pub async fn get_perps_price_data(&mut self) -> anyhow::Result<PerpsPriceData> {
         Ok(self
             .get_all_perps_meta()
@@ -175,94 +281,578 @@ impl Prices {
     }
 
     pub async fn get_spot_price_data(&mut self) -> anyhow::Result<SpotPriceData> {
-        Ok(self
-            .get_all_spot_meta()
-            .await?
-            .get_spot_price_data(self.get_all_prices().await?))
+        let spot_meta = self.get_all_spot_meta().await?;
+        set_pair_to_name_map(spot_meta.get_pair_to_name_map());
+
+        spot_meta.get_spot_price_data(self.get_all_prices().await?)
+    }
+
+    /// Fetches the spot meta, the perps meta and the first AllMids tick concurrently and builds
+    /// both price data from that one tick, for services that need both universes on start.
+    pub async fn init_all(&mut self) -> anyhow::Result<(SpotPriceData, PerpsPriceData)> {
+        let transport = self.transport.clone();
+
+        let (spot_meta, perps_meta, prices) = tokio::try_join!(
+            request_info::<SpotMeta>(&*transport, json!({ "type": "spotMeta" })),
+            request_info::<PerpsMeta>(&*transport, json!({ "type": "meta" })),
+            self.get_all_prices(),
+        )?;
+
+        set_pair_to_name_map(spot_meta.get_pair_to_name_map());
+
+        Ok((
+            spot_meta.get_spot_price_data(prices.clone())?,
+            perps_meta.get_perps_prices_data(prices),
+        ))
     }
 
     pub async fn unsub(&mut self) -> anyhow::Result<()> {
-        Ok(self.info_client.unsubscribe(self.sub_id).await?)
+        self.subscriptions.unsubscribe_all().await
+    }
+}
+
+/// Connection settings of the HTTP client used for info requests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpConfig {
+    /// Whole request, from connecting to reading the body
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// How long an idle pooled connection is kept around
+    pub pool_idle_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: usize,
+    pub tcp_keepalive: Option<Duration>,
+    /// Skips the HTTP/1.1 upgrade and talks HTTP/2 right away
+    pub http2_prior_knowledge: bool,
+    /// Routes every request through this proxy, e.g. "http://gateway:3128" or
+    /// "socks5h://127.0.0.1:1080". Without it the `HTTP_PROXY`/`HTTPS_PROXY` environment variables
+    /// are used, if set. Once set as the shared config, websockets go through it too, see
+    /// [`crate::websocket::get_ws_proxy`].
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            timeout: Some(Duration::from_secs(30)),
+            connect_timeout: Some(Duration::from_secs(10)),
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: 8,
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            http2_prior_knowledge: false,
+            proxy: None,
+        }
+    }
+}
+
+pub const MAINNET_API_URL: &str = "https://api-ui.hyperliquid.xyz";
+pub const TESTNET_API_URL: &str = "https://api.hyperliquid-testnet.xyz";
+
+static SHARED_HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
+static API_URL: OnceLock<String> = OnceLock::new();
+static SHARED_PROXY: OnceLock<Option<String>> = OnceLock::new();
+
+/// Sets the base URL every info request goes to, [`MAINNET_API_URL`] by default. Websocket
+/// subscriptions go to testnet when the URL is a testnet one and to mainnet otherwise. Fails once
+/// the URL was used, which happens on the first info request. Info requests fail over to the
+/// other official host and the mirrors, see [`crate::endpoints::get_api_urls`].
+pub fn set_api_url(url: &str) -> Result<(), Error> {
+    API_URL
+        .set(url.trim_end_matches('/').to_string())
+        .map_err(|_| anyhow::anyhow!("The API URL was already used"))
+}
+
+pub fn get_api_url() -> &'static str {
+    API_URL.get_or_init(|| MAINNET_API_URL.to_string())
+}
+
+/// The SDK's base URL matching [`get_api_url`], for its websocket and exchange clients.
+pub fn get_sdk_base_url() -> BaseUrl {
+    if get_api_url().contains("testnet") {
+        BaseUrl::Testnet
+    } else {
+        BaseUrl::Mainnet
+    }
+}
+
+/// Sets the config of the client every REST helper shares. Fails once the shared client was
+/// built, which happens on the first info request.
+pub fn set_shared_http_config(config: &HttpConfig) -> Result<(), Error> {
+    SHARED_HTTP_CLIENT
+        .set(build_http_client(config)?)
+        .map_err(|_| anyhow::anyhow!("The shared HTTP client was already built"))?;
+
+    SHARED_PROXY.get_or_init(|| config.proxy.clone());
+
+    Ok(())
+}
+
+/// The proxy of the config set with [`set_shared_http_config`]
+pub(crate) fn get_shared_proxy() -> Option<&'static str> {
+    SHARED_PROXY.get().and_then(|proxy| proxy.as_deref())
+}
+
+pub fn build_http_client(config: &HttpConfig) -> Result<Client, Error> {
+    let mut headers = HeaderMap::new();
+
+    headers.append(
+        CONTENT_TYPE,
+        HeaderValue::from_str("application/json").unwrap(),
+    );
+
+    let mut builder = reqwest::ClientBuilder::new()
+        .default_headers(headers)
+        .pool_idle_timeout(config.pool_idle_timeout)
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .tcp_keepalive(config.tcp_keepalive);
+
+    if let Some(timeout) = config.timeout {
+        builder = builder.timeout(timeout);
+    }
+
+    if let Some(connect_timeout) = config.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+
+    if config.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(Proxy::all(proxy).context("Invalid proxy URL")?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// The client shared by every REST helper, so meta refreshes reuse pooled connections. Cloning a
+/// `Client` only clones a handle to the same pool.
+pub(crate) fn build_info_http_client() -> Result<Client, Error> {
+    if let Some(client) = SHARED_HTTP_CLIENT.get() {
+        return Ok(client.clone());
+    }
+
+    let client = build_http_client(&HttpConfig::default())?;
+
+    // Another thread may have won the race, use whichever got stored
+    Ok(SHARED_HTTP_CLIENT.get_or_init(|| client).clone())
+}
+
+pub(crate) async fn post_info<T: DeserializeOwned>(
+    client: &Client,
+    data: Value,
+) -> Result<T, Error> {
+    let weight = get_info_weight(data["type"].as_str().unwrap_or_default());
+
+    // Fails over between the endpoints, and market data is answered from the cache while all of
+    // them are failing, see `EndpointPool` and `CircuitBreaker`
+    let key = is_cacheable_info(&data).then(|| data.to_string());
+
+    let bytes = get_info_breaker()
+        .call(key.as_deref(), || async {
+            get_rest_rate_limiter().acquire(weight).await;
+
+            get_info_endpoints().post_info(client, &data).await
+        })
+        .await?;
+
+    // Deserializing this way seems to be more reliable
+    let response = serde_json::from_slice::<T>(&bytes)?;
+
+    Ok(response)
+}
+
+/// Lets consumers of the `start_*_task` receivers wait for the first populated map instead of
+/// racing the empty map the channels are created with.
+pub trait AwaitReady {
+    /// Resolves once the map holds at least one entry. Errors if the task stopped before that.
+    fn await_ready(&mut self) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+impl<K: Send + Sync, V: Send + Sync, S: Send + Sync> AwaitReady
+    for watch::Receiver<HashMap<K, V, S>>
+{
+    fn await_ready(&mut self) -> impl Future<Output = Result<(), Error>> + Send {
+        async move {
+            self.wait_for(|map| !map.is_empty())
+                .await
+                .context("Sender task stopped before publishing any data")?;
+
+            Ok(())
+        }
     }
 }
 
 pub async fn start_perps_sender_task() -> anyhow::Result<watch::Receiver<NameToPriceMap>> {
+    start_perps_sender_task_with_session(DEFAULT_SESSION_DURATION).await
+}
+
+/// Same as [`start_perps_sender_task`] but rotates the websocket session every `session_duration`.
+pub async fn start_perps_sender_task_with_session(
+    session_duration: Duration,
+) -> anyhow::Result<watch::Receiver<NameToPriceMap>> {
+    Ok(spawn_perps_sender_task(session_duration, FeedCounter::new("perps_prices")).0)
+}
+
+/// [`start_perps_sender_task_with_session`] with the handle of the task, for owners that stop it,
+/// counting the messages of every session on `counter`.
+pub(crate) fn spawn_perps_sender_task(
+    session_duration: Duration,
+    counter: FeedCounter,
+) -> (watch::Receiver<NameToPriceMap>, JoinHandle<()>) {
     // TODO: Start returning an Arc<Mutex<watch::Receiver<..>>> so that you can create a new
     // connection efficiently from within the tokio task and update across all threads.
-    let (price_sender, price_recv) = watch::channel(HashMap::<String, Price>::new());
+    let (price_sender, price_recv) = watch::channel(NameToPriceMap::default());
 
-    tokio::spawn(async move {
+    let task = tokio::spawn(async move {
         let p_s = price_sender;
+        let mut events = ConnectionEvents::default();
         loop {
             info!("perps_sender_task: Starting...");
 
             let mut new_prices = match Prices::new().await {
-                Ok(p) => p,
+                Ok(p) => p.with_counter(counter.clone()),
                 Err(e) => {
                     error!("Error while getting Prices: {e:?}");
                     error!("Sleeping for 5 secs and restarting...");
-                    sleep(std::time::Duration::from_secs(5));
+                    sleep(std::time::Duration::from_secs(5)).await;
                     continue;
                 }
             };
+            new_prices.set_session_duration(session_duration);
+            events.connected("perps_sender_task");
 
             match new_prices.start_sending_perps(p_s.clone()).await {
-                Ok(it) => it,
+                Ok(()) => {
+                    info!("perps_sender_task: Session ended, rotating...");
+                    let _ = new_prices.unsub().await;
+                    continue;
+                }
                 Err(err) => {
                     error!("perps_sender_task: Error: {err:?}");
+                    emit(
+                        "perps_sender_task",
+                        FeedEventKind::SubscriptionDropped {
+                            reason: format!("{err:?}"),
+                        },
+                    );
                 }
             };
-            info!("perps_sender_task: Resetting...");
-
             let _ = new_prices.unsub().await;
-            sleep(std::time::Duration::from_secs(5));
+
+            if p_s.is_closed() {
+                info!("perps_sender_task: All receivers dropped, stopping...");
+                return;
+            }
+
+            info!("perps_sender_task: Resetting...");
+            sleep(std::time::Duration::from_secs(5)).await;
         }
     });
 
-    Ok(price_recv)
+    (price_recv, task)
 }
 
 pub async fn start_spot_sender_task() -> anyhow::Result<watch::Receiver<NameToPriceMap>> {
-    let (price_sender, price_recv) = watch::channel(HashMap::<String, Price>::new());
+    start_spot_sender_task_with_session(DEFAULT_SESSION_DURATION).await
+}
 
-    tokio::spawn(async move {
+/// Same as [`start_spot_sender_task`] but rotates the websocket session every `session_duration`.
+pub async fn start_spot_sender_task_with_session(
+    session_duration: Duration,
+) -> anyhow::Result<watch::Receiver<NameToPriceMap>> {
+    Ok(spawn_spot_sender_task(session_duration, FeedCounter::new("spot_prices")).0)
+}
+
+/// [`start_spot_sender_task_with_session`] with the handle of the task, for owners that stop it,
+/// counting the messages of every session on `counter`.
+pub(crate) fn spawn_spot_sender_task(
+    session_duration: Duration,
+    counter: FeedCounter,
+) -> (watch::Receiver<NameToPriceMap>, JoinHandle<()>) {
+    let (price_sender, price_recv) = watch::channel(NameToPriceMap::default());
+
+    let task = tokio::spawn(async move {
         let p_s = price_sender;
+        let mut events = ConnectionEvents::default();
         loop {
             info!("spot_sender_task: Starting...");
 
             let mut new_prices = match Prices::new().await {
-                Ok(p) => p,
+                Ok(p) => p.with_counter(counter.clone()),
                 Err(e) => {
                     error!("Error while getting Prices: {e:?}");
                     error!("Sleeping for 5 secs and restarting...");
-                    sleep(std::time::Duration::from_secs(5));
+                    sleep(std::time::Duration::from_secs(5)).await;
                     continue;
                 }
             };
+            new_prices.set_session_duration(session_duration);
+            events.connected("spot_sender_task");
 
             match new_prices.start_sending(p_s.clone()).await {
-                Ok(it) => it,
+                Ok(()) => {
+                    info!("spot_sender_task: Session ended, rotating...");
+                    let _ = new_prices.unsub().await;
+                    continue;
+                }
                 Err(err) => {
                     error!("spot_sender_task: Error: {err:?}");
+                    emit(
+                        "spot_sender_task",
+                        FeedEventKind::SubscriptionDropped {
+                            reason: format!("{err:?}"),
+                        },
+                    );
                 }
             };
+            let _ = new_prices.unsub().await;
+
+            if p_s.is_closed() {
+                info!("spot_sender_task: All receivers dropped, stopping...");
+                return;
+            }
+
             info!("spot_sender_task: Resetting...");
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
 
-            let _ = new_prices.unsub().await;
-            sleep(std::time::Duration::from_secs(5));
+    (price_recv, task)
+}
+
+/// Refetches the perps asset contexts (funding, open interest, mark and oracle prices) every
+/// `interval`. Failed fetches keep the previous map. The receiver is meant to be shared by every
+/// consumer of the contexts, e.g. [`crate::funding::start_funding_rate_task`] and
+/// [`crate::scanner::start_funding_scanner_task`], instead of each polling the endpoint.
+pub async fn start_asset_ctx_task(
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<NameToCtxMap>> {
+    Ok(spawn_asset_ctx_task(interval)?.0)
+}
+
+/// [`start_asset_ctx_task`] with the handle of the task, for owners that stop it.
+pub(crate) fn spawn_asset_ctx_task(
+    interval: Duration,
+) -> anyhow::Result<(watch::Receiver<NameToCtxMap>, JoinHandle<()>)> {
+    if interval.is_zero() {
+        bail!("The asset context interval can't be 0");
+    }
+
+    let client = build_info_http_client()?;
+    let (ctx_sender, ctx_recv) = watch::channel(NameToCtxMap::new());
+
+    let task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+
+        info!("asset_ctx_task: Starting...");
+
+        loop {
+            interval.tick().await;
+
+            let meta_and_ctxs: PerpsMetaAndAssetCtxs =
+                match post_info(&client, json!({ "type": "metaAndAssetCtxs" })).await {
+                    Ok(m) => m,
+                    Err(err) => {
+                        error!("asset_ctx_task: Error: {err:?}");
+                        continue;
+                    }
+                };
+
+            if ctx_sender
+                .send(meta_and_ctxs.get_name_to_ctx_map())
+                .is_err()
+            {
+                info!("asset_ctx_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok((ctx_recv, task))
+}
+
+/// Stamps every coin of `current` that `is_updated` compared to `previous` (or that's new) with
+/// `now` and drops the coins that left the map.
+pub fn update_times<T>(
+    times: &mut NameToUpdateTimeMap,
+    previous: &FastMap<Symbol, T>,
+    current: &FastMap<Symbol, T>,
+    now: i64,
+    is_updated: impl Fn(&T, &T) -> bool,
+) {
+    times.retain(|coin, _| current.contains_key(coin));
+
+    for (coin, value) in current {
+        let is_updated = previous
+            .get(coin)
+            .is_none_or(|previous| is_updated(previous, value));
+
+        if is_updated || !times.contains_key(coin) {
+            times.insert(coin.clone(), now);
+        }
+    }
+}
+
+/// [`update_times`] for prices, which count as updated when they move.
+pub fn update_price_times(
+    times: &mut NameToUpdateTimeMap,
+    previous: &NameToPriceMap,
+    current: &NameToPriceMap,
+    now: i64,
+) {
+    update_times(times, previous, current, now, has_price_moved);
+}
+
+fn has_price_moved(previous: &Price, price: &Price) -> bool {
+    previous.get_value() != price.get_value()
+}
+
+/// Coins of `times` that haven't updated for longer than `max_age`, sorted by name. For prices
+/// that means they haven't moved, so a healthy coin that's flat for longer than `max_age` is
+/// reported too; pick `max_age` above how long the coins watched normally sit still.
+pub fn get_stale_coins(times: &NameToUpdateTimeMap, now: i64, max_age: Duration) -> Vec<Symbol> {
+    let mut coins: Vec<Symbol> = times
+        .iter()
+        .filter(|(_, time)| now - **time > max_age.as_millis() as i64)
+        .map(|(coin, _)| coin.clone())
+        .collect();
+    coins.sort();
+
+    coins
+}
+
+/// Publishes when each coin of `price_receiver` (perps or spot) last moved, so a consumer can
+/// tell that one coin stopped updating while the feed as a whole is still alive. AllMids carries
+/// every coin in every message, so a coin counts as updated when its price changes rather than
+/// when it's sent, see [`get_stale_coins`].
+pub async fn start_price_update_time_task(
+    price_receiver: watch::Receiver<NameToPriceMap>,
+) -> anyhow::Result<watch::Receiver<NameToUpdateTimeMap>> {
+    Ok(spawn_price_update_time_task(price_receiver).0)
+}
+
+/// [`start_price_update_time_task`] with the handle of the task, for owners that stop it.
+pub(crate) fn spawn_price_update_time_task(
+    price_receiver: watch::Receiver<NameToPriceMap>,
+) -> (watch::Receiver<NameToUpdateTimeMap>, JoinHandle<()>) {
+    spawn_update_time_task("price_update_time_task", price_receiver, has_price_moved)
+}
+
+/// Stamps the coins of every update of `receiver` with [`update_times`].
+pub(crate) fn spawn_update_time_task<T: Clone + Send + Sync + 'static>(
+    name: &'static str,
+    mut receiver: watch::Receiver<FastMap<Symbol, T>>,
+    is_updated: fn(&T, &T) -> bool,
+) -> (watch::Receiver<NameToUpdateTimeMap>, JoinHandle<()>) {
+    let (time_sender, time_recv) = watch::channel(NameToUpdateTimeMap::default());
+
+    let task = tokio::spawn(async move {
+        let mut previous = FastMap::default();
+
+        info!("{name}: Starting...");
+
+        loop {
+            if receiver.changed().await.is_err() {
+                info!("{name}: Channel closed, stopping...");
+                return;
+            }
+
+            let current = receiver.borrow_and_update().clone();
+            let now = Utc::now().timestamp_millis();
+
+            time_sender
+                .send_modify(|times| update_times(times, &previous, &current, now, is_updated));
+            previous = current;
+
+            if time_sender.is_closed() {
+                info!("{name}: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    (time_recv, task)
+}
+
+/// Keeps every perps price joined with its latest asset context, so strategies get mark, oracle,
+/// funding and open interest alongside the mid. Republishes whenever either input changes. The
+/// contexts are polled by [`start_asset_ctx_task`], so they lag the mid by up to its interval,
+/// see [`crate::price_data::perps::PerpQuote::ctx_time`].
+pub async fn start_perp_quote_task(
+    price_receiver: watch::Receiver<NameToPriceMap>,
+    ctx_receiver: watch::Receiver<NameToCtxMap>,
+) -> anyhow::Result<watch::Receiver<NameToPerpQuoteMap>> {
+    Ok(spawn_perp_quote_task(price_receiver, ctx_receiver).0)
+}
+
+/// [`start_perp_quote_task`] with the handle of the task, for owners that stop it.
+pub(crate) fn spawn_perp_quote_task(
+    mut price_receiver: watch::Receiver<NameToPriceMap>,
+    mut ctx_receiver: watch::Receiver<NameToCtxMap>,
+) -> (watch::Receiver<NameToPerpQuoteMap>, JoinHandle<()>) {
+    let (quote_sender, quote_recv) = watch::channel(NameToPerpQuoteMap::new());
+
+    let task = tokio::spawn(async move {
+        // Contexts published before the task started are stamped with its start
+        let mut ctx_time =
+            (!ctx_receiver.borrow().is_empty()).then(|| Utc::now().timestamp_millis());
+
+        info!("perp_quote_task: Starting...");
+
+        loop {
+            tokio::select! {
+                res = price_receiver.changed() => if res.is_err() {
+                    info!("perp_quote_task: Price channel closed, stopping...");
+                    return;
+                },
+                res = ctx_receiver.changed() => match res {
+                    Ok(()) => ctx_time = Some(Utc::now().timestamp_millis()),
+                    Err(_) => {
+                        info!("perp_quote_task: Asset context channel closed, stopping...");
+                        return;
+                    }
+                },
+            }
+
+            let quotes = get_perp_quote_map(
+                &price_receiver.borrow_and_update(),
+                &ctx_receiver.borrow_and_update(),
+                ctx_time,
+            );
+
+            if quote_sender.send(quotes).is_err() {
+                info!("perp_quote_task: All receivers dropped, stopping...");
+                return;
+            }
         }
     });
 
-    Ok(price_recv)
+    (quote_recv, task)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Once;
+    use std::{
+        sync::{Arc, Once},
+        time::Duration,
+    };
 
+    use hyperliquid_rust_sdk::Message;
     use log::info;
+    use serde_json::json;
+    use tokio::{
+        sync::watch,
+        time::{sleep, Instant},
+    };
 
-    use crate::prices::{start_perps_sender_task, start_spot_sender_task};
+    use crate::{
+        prices::{
+            get_stale_coins, start_perps_sender_task, start_spot_sender_task, update_price_times,
+            update_times, AwaitReady, Prices,
+        },
+        transport::FakeTransport,
+        types::{FastMap, Meta, NameToPriceMap, NameToUpdateTimeMap, Price, Symbol},
+    };
 
     static INIT: Once = Once::new();
 
@@ -276,11 +866,12 @@ mod tests {
     async fn perps_prices_are_being_sent() -> anyhow::Result<()> {
         init_logger();
 
-        let receiver = start_perps_sender_task().await?;
+        let mut receiver = start_perps_sender_task().await?;
+        receiver.await_ready().await?;
 
         for _ in 0..100 {
             let prices = receiver.borrow().clone();
-            let price = prices.get(&"ETH".to_string()).unwrap();
+            let price = prices.get("ETH").unwrap();
             info!("{:?}", price);
         }
 
@@ -291,14 +882,165 @@ mod tests {
     async fn spot_prices_are_being_sent() -> anyhow::Result<()> {
         init_logger();
 
-        let receiver = start_spot_sender_task().await?;
+        let mut receiver = start_spot_sender_task().await?;
+        receiver.await_ready().await?;
 
         for _ in 0..100 {
             let prices = receiver.borrow().clone();
-            let price = prices.get(&"@2".to_string()).unwrap();
+            let price = prices.get("@2").unwrap();
             info!("{:?}", price);
         }
 
         Ok(())
     }
+
+    fn meta() -> serde_json::Value {
+        json!({ "universe": [{ "name": "ETH", "szDecimals": 4, "maxLeverage": 25 }] })
+    }
+
+    fn all_mids(btc: &str) -> Message {
+        serde_json::from_value(json!({
+            "channel": "allMids",
+            "data": { "mids": { "ETH": "2000.5", "BTC": btc } }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn perps_prices_from_fake_transport() -> anyhow::Result<()> {
+        let transport = Arc::new(FakeTransport::new());
+        transport.set_response("meta", meta());
+
+        let mut prices = Prices::with_transport(transport.clone()).await?;
+        assert_eq!(
+            transport.get_subscriptions(),
+            vec![json!({ "type": "allMids" })]
+        );
+
+        assert_eq!(transport.push(all_mids("oops")), 1);
+
+        let data = prices.get_perps_price_data().await?;
+        assert_eq!(data.map["ETH"].get_value(), 2000.5);
+        assert_eq!(prices.get_parse_failure_count(), 1);
+
+        prices.unsub().await?;
+        assert!(transport.get_subscriptions().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn init_all_builds_both_universes() -> anyhow::Result<()> {
+        let transport = Arc::new(FakeTransport::new());
+        transport.set_response("meta", meta());
+        transport.set_response(
+            "spotMeta",
+            json!({
+                "universe": [{ "tokens": [1, 0], "name": "@1", "index": 1, "isCanonical": false }],
+                "tokens": [
+                    {
+                        "name": "USDC", "szDecimals": 8, "weiDecimals": 8, "index": 0,
+                        "tokenId": "0x6d1e7cde53ba9467b783cb7c530ce054", "isCanonical": true
+                    },
+                    {
+                        "name": "PURR", "szDecimals": 0, "weiDecimals": 5, "index": 1,
+                        "tokenId": "0xc1fb593aeffbeb02f85e0308e9956a90", "isCanonical": true
+                    }
+                ]
+            }),
+        );
+
+        let mut prices = Prices::with_transport(transport.clone()).await?;
+        transport.push(serde_json::from_value(json!({
+            "channel": "allMids",
+            "data": { "mids": { "ETH": "2000.5", "@1": "0.2" } }
+        }))?);
+
+        let (spot, perps) = prices.init_all().await?;
+        assert_eq!(spot.get_price_from_pair("PURR/USDC"), Some(0.2));
+        assert_eq!(perps.map["ETH"].get_value(), 2000.5);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn session_ends_after_its_duration() -> anyhow::Result<()> {
+        let transport = Arc::new(FakeTransport::new());
+        transport.set_response("meta", meta());
+
+        let mut prices = Prices::with_transport(transport.clone()).await?;
+        prices.set_session_duration(Duration::from_secs(60));
+
+        let pusher = tokio::spawn({
+            let transport = transport.clone();
+            async move {
+                loop {
+                    transport.push(all_mids("60000.0"));
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        });
+
+        let (sender, receiver) = watch::channel(NameToPriceMap::default());
+        let start = Instant::now();
+
+        // Runs on the paused clock, so this takes no real time
+        prices.start_sending_perps(sender).await?;
+
+        assert!(start.elapsed() >= Duration::from_secs(60));
+        assert!(receiver.borrow().contains_key("ETH"));
+
+        pusher.abort();
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_moving_coins_are_restamped() {
+        let perp = |name: &str, price: f64| {
+            let meta = Meta::Perp {
+                name: name.to_string(),
+                index: 0,
+                sz_decimals: 2,
+                max_leverage: 20,
+                only_isolated: None,
+                is_delisted: None,
+            };
+
+            (Symbol::from_api(name), Price::new_perp(price, meta))
+        };
+
+        let first: NameToPriceMap = [perp("ETH", 2000.0), perp("BTC", 60000.0)]
+            .into_iter()
+            .collect();
+        let second: NameToPriceMap = [perp("ETH", 2001.0), perp("BTC", 60000.0)]
+            .into_iter()
+            .collect();
+        let third: NameToPriceMap = [perp("ETH", 2002.0)].into_iter().collect();
+
+        let mut times = NameToUpdateTimeMap::default();
+        update_price_times(&mut times, &NameToPriceMap::default(), &first, 1_000);
+        update_price_times(&mut times, &first, &second, 5_000);
+
+        assert_eq!(times["ETH"], 5_000);
+        assert_eq!(times["BTC"], 1_000);
+        assert_eq!(
+            get_stale_coins(&times, 6_000, Duration::from_secs(2)),
+            vec![Symbol::from_api("BTC")]
+        );
+
+        update_price_times(&mut times, &second, &third, 6_000);
+        assert!(!times.contains_key("BTC"));
+
+        // Book times, stamped on every new book even if its levels didn't move
+        let books = |time: u64| -> FastMap<Symbol, u64> {
+            [(Symbol::from_api("ETH"), time)].into_iter().collect()
+        };
+        let mut times = NameToUpdateTimeMap::default();
+        update_times(&mut times, &FastMap::default(), &books(1), 1_000, |a, b| {
+            a != b
+        });
+        update_times(&mut times, &books(1), &books(2), 5_000, |a, b| a != b);
+        assert_eq!(times["ETH"], 5_000);
+    }
 }
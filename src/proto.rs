@@ -0,0 +1,214 @@
+//! Protobuf messages for the core types, generated from `proto/market_data.proto` by the build
+//! script.
+
+use anyhow::{anyhow, Error};
+
+use crate::{candles, types};
+
+include!(concat!(env!("OUT_DIR"), "/hyperliquid_utils.rs"));
+
+fn to_u16(value: u32, field: &str) -> Result<u16, Error> {
+    u16::try_from(value).map_err(|_| anyhow!("{field} {value} doesn't fit in a u16"))
+}
+
+impl From<types::SpotAssetMeta> for SpotAssetMeta {
+    fn from(meta: types::SpotAssetMeta) -> Self {
+        SpotAssetMeta {
+            sz_decimals: meta.sz_decimals as u32,
+            wei_decimals: meta.wei_decimals as u32,
+            name: meta.name,
+            index: meta.index as u32,
+        }
+    }
+}
+
+impl TryFrom<SpotAssetMeta> for types::SpotAssetMeta {
+    type Error = Error;
+
+    fn try_from(meta: SpotAssetMeta) -> Result<Self, Self::Error> {
+        Ok(types::SpotAssetMeta {
+            sz_decimals: to_u16(meta.sz_decimals, "sz_decimals")?,
+            wei_decimals: to_u16(meta.wei_decimals, "wei_decimals")?,
+            name: meta.name,
+            index: to_u16(meta.index, "index")?,
+        })
+    }
+}
+
+impl From<types::Meta> for Meta {
+    fn from(meta: types::Meta) -> Self {
+        let kind = match meta {
+            types::Meta::Spot {
+                name,
+                index,
+                quote,
+                base,
+            } => meta::Kind::Spot(SpotMeta {
+                name,
+                index: index as u32,
+                quote: Some(quote.into()),
+                base: Some(base.into()),
+            }),
+            types::Meta::Perp {
+                name,
+                index,
+                sz_decimals,
+                max_leverage,
+                only_isolated,
+                is_delisted,
+            } => meta::Kind::Perp(PerpMeta {
+                name,
+                index: index as u32,
+                sz_decimals: sz_decimals as u32,
+                max_leverage: max_leverage as u32,
+                only_isolated,
+                is_delisted,
+            }),
+        };
+
+        Meta { kind: Some(kind) }
+    }
+}
+
+impl TryFrom<Meta> for types::Meta {
+    type Error = Error;
+
+    fn try_from(meta: Meta) -> Result<Self, Self::Error> {
+        match meta.kind.ok_or_else(|| anyhow!("Meta has no kind"))? {
+            meta::Kind::Spot(spot) => Ok(types::Meta::Spot {
+                name: spot.name,
+                index: to_u16(spot.index, "index")?,
+                quote: spot
+                    .quote
+                    .ok_or_else(|| anyhow!("Spot meta has no quote"))?
+                    .try_into()?,
+                base: spot
+                    .base
+                    .ok_or_else(|| anyhow!("Spot meta has no base"))?
+                    .try_into()?,
+            }),
+            meta::Kind::Perp(perp) => Ok(types::Meta::Perp {
+                name: perp.name,
+                index: to_u16(perp.index, "index")?,
+                sz_decimals: to_u16(perp.sz_decimals, "sz_decimals")?,
+                max_leverage: to_u16(perp.max_leverage, "max_leverage")?,
+                only_isolated: perp.only_isolated,
+                is_delisted: perp.is_delisted,
+            }),
+        }
+    }
+}
+
+impl From<types::Price> for Price {
+    fn from(price: types::Price) -> Self {
+        match price {
+            types::Price::None => Price {
+                price: 0.0,
+                meta: None,
+            },
+            types::Price::Spot { price, meta } | types::Price::Perp { price, meta } => Price {
+                price,
+                meta: Some(meta.into()),
+            },
+        }
+    }
+}
+
+impl TryFrom<Price> for types::Price {
+    type Error = Error;
+
+    /// The price is kept as is, it was already rounded when it was encoded.
+    fn try_from(price: Price) -> Result<Self, Self::Error> {
+        let meta: types::Meta = match price.meta {
+            Some(meta) => meta.try_into()?,
+            None => return Ok(types::Price::None),
+        };
+
+        Ok(match meta {
+            types::Meta::Spot { .. } => types::Price::Spot {
+                price: price.price,
+                meta,
+            },
+            types::Meta::Perp { .. } => types::Price::Perp {
+                price: price.price,
+                meta,
+            },
+        })
+    }
+}
+
+impl From<types::BookLevel> for BookLevel {
+    fn from(level: types::BookLevel) -> Self {
+        BookLevel {
+            price: level.price,
+            size: level.size,
+            orders: level.orders,
+        }
+    }
+}
+
+impl From<BookLevel> for types::BookLevel {
+    fn from(level: BookLevel) -> Self {
+        types::BookLevel {
+            price: level.price,
+            size: level.size,
+            orders: level.orders,
+        }
+    }
+}
+
+impl From<types::Orderbook> for Orderbook {
+    fn from(book: types::Orderbook) -> Self {
+        Orderbook {
+            coin: book.coin,
+            time: book.time,
+            bids: book.bids.into_iter().map(BookLevel::from).collect(),
+            asks: book.asks.into_iter().map(BookLevel::from).collect(),
+        }
+    }
+}
+
+impl From<Orderbook> for types::Orderbook {
+    fn from(book: Orderbook) -> Self {
+        types::Orderbook {
+            coin: book.coin,
+            time: book.time,
+            bids: book.bids.into_iter().map(types::BookLevel::from).collect(),
+            asks: book.asks.into_iter().map(types::BookLevel::from).collect(),
+        }
+    }
+}
+
+impl From<candles::Candle> for Candle {
+    fn from(candle: candles::Candle) -> Self {
+        Candle {
+            coin: candle.coin,
+            interval: candle.interval,
+            open_time: candle.open_time,
+            close_time: candle.close_time,
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: candle.volume,
+            trades: candle.trades,
+        }
+    }
+}
+
+impl From<Candle> for candles::Candle {
+    fn from(candle: Candle) -> Self {
+        candles::Candle {
+            coin: candle.coin,
+            interval: candle.interval,
+            open_time: candle.open_time,
+            close_time: candle.close_time,
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: candle.volume,
+            trades: candle.trades,
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{trades::Trade, types::Orderbook};
+
+/// A resting limit order whose place in its price level's queue is being estimated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrackedOrder {
+    pub oid: u64,
+    pub coin: String,
+    pub is_buy: bool,
+    pub price: f64,
+    pub size: f64,
+    /// ms since epoch
+    pub placed_at: u64,
+    /// Size resting at the order's price ahead of it
+    pub queue_ahead: f64,
+    pub filled: f64,
+}
+
+impl TrackedOrder {
+    pub fn get_remaining(&self) -> f64 {
+        (self.size - self.filled).max(0.0)
+    }
+
+    pub fn is_filled(&self) -> bool {
+        self.get_remaining() <= 0.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QueueEstimate {
+    pub oid: u64,
+    pub queue_ahead: f64,
+    pub remaining: f64,
+    /// Share of the level ahead of the order, 0.0 at the front
+    pub position: f64,
+    /// At the recent traded volume against the order's side, `None` without recent volume
+    pub expected_time_to_fill: Option<Duration>,
+}
+
+/// Estimates the queue position of resting orders from the streamed books and trades.
+///
+/// An order starts behind everything resting at its price in the last book (so track it before
+/// the book including it arrives). Trades at the order's price against its side move it up,
+/// and the level shrinking below the size ahead is taken as cancellations ahead of it. Trades
+/// through its price fill it.
+#[derive(Clone, Debug)]
+pub struct QueueTracker {
+    orders: HashMap<u64, TrackedOrder>,
+    books: HashMap<String, Orderbook>,
+    /// (time, size) of recent trades per (coin, resting side is buy)
+    volume: HashMap<(String, bool), VecDeque<(u64, f64)>>,
+    /// Span of trades the fill rate is computed over
+    window: Duration,
+}
+
+impl QueueTracker {
+    pub fn new(window: Duration) -> Self {
+        QueueTracker {
+            orders: HashMap::new(),
+            books: HashMap::new(),
+            volume: HashMap::new(),
+            window,
+        }
+    }
+
+    pub fn track(
+        &mut self,
+        oid: u64,
+        coin: &str,
+        is_buy: bool,
+        price: f64,
+        size: f64,
+        placed_at: u64,
+    ) {
+        let queue_ahead = self
+            .books
+            .get(coin)
+            .map(|book| get_level_size(book, is_buy, price))
+            .unwrap_or(0.0);
+
+        self.orders.insert(
+            oid,
+            TrackedOrder {
+                oid,
+                coin: coin.to_string(),
+                is_buy,
+                price,
+                size,
+                placed_at,
+                queue_ahead,
+                filled: 0.0,
+            },
+        );
+    }
+
+    /// Stops tracking an order, e.g. once it's canceled
+    pub fn untrack(&mut self, oid: u64) -> Option<TrackedOrder> {
+        self.orders.remove(&oid)
+    }
+
+    pub fn get_order(&self, oid: u64) -> Option<&TrackedOrder> {
+        self.orders.get(&oid)
+    }
+
+    pub fn on_book(&mut self, book: &Orderbook) {
+        for order in self
+            .orders
+            .values_mut()
+            .filter(|order| order.coin == book.coin)
+        {
+            // The level includes the order itself
+            let others =
+                (get_level_size(book, order.is_buy, order.price) - order.get_remaining()).max(0.0);
+            order.queue_ahead = order.queue_ahead.min(others);
+        }
+
+        self.books.insert(book.coin.clone(), book.clone());
+    }
+
+    pub fn on_trade(&mut self, trade: &Trade) {
+        // A taker buy hits resting sells and the other way around
+        let resting_is_buy = !trade.is_buy;
+
+        let volume = self
+            .volume
+            .entry((trade.coin.clone(), resting_is_buy))
+            .or_default();
+        volume.push_back((trade.time, trade.size));
+
+        let window_ms = self.window.as_millis() as u64;
+        while volume
+            .front()
+            .is_some_and(|(time, _)| time + window_ms < trade.time)
+        {
+            volume.pop_front();
+        }
+
+        for order in self.orders.values_mut().filter(|order| {
+            order.coin == trade.coin && order.is_buy == resting_is_buy && !order.is_filled()
+        }) {
+            let is_through = if order.is_buy {
+                trade.price < order.price
+            } else {
+                trade.price > order.price
+            };
+
+            if is_through {
+                order.queue_ahead = 0.0;
+                order.filled = order.size;
+            } else if trade.price == order.price {
+                let past_queue = trade.size - order.queue_ahead;
+                order.queue_ahead = (-past_queue).max(0.0);
+
+                if past_queue > 0.0 {
+                    order.filled = (order.filled + past_queue).min(order.size);
+                }
+            }
+        }
+    }
+
+    /// Size per second traded against `coin`'s `is_buy` side over the window
+    pub fn get_fill_rate(&self, coin: &str, is_buy: bool) -> Option<f64> {
+        let volume = self.volume.get(&(coin.to_string(), is_buy))?;
+        let total: f64 = volume.iter().map(|(_, size)| size).sum();
+
+        if total <= 0.0 || self.window.is_zero() {
+            return None;
+        }
+
+        Some(total / self.window.as_secs_f64())
+    }
+
+    pub fn get_estimate(&self, oid: u64) -> Option<QueueEstimate> {
+        let order = self.orders.get(&oid)?;
+        let remaining = order.get_remaining();
+
+        let level = order.queue_ahead + remaining;
+        let position = if level > 0.0 {
+            order.queue_ahead / level
+        } else {
+            0.0
+        };
+
+        let expected_time_to_fill = self
+            .get_fill_rate(&order.coin, order.is_buy)
+            .map(|rate| Duration::from_secs_f64(level / rate));
+
+        Some(QueueEstimate {
+            oid,
+            queue_ahead: order.queue_ahead,
+            remaining,
+            position,
+            expected_time_to_fill,
+        })
+    }
+
+    pub fn get_estimates(&self) -> Vec<QueueEstimate> {
+        self.orders
+            .keys()
+            .filter_map(|oid| self.get_estimate(*oid))
+            .collect()
+    }
+}
+
+/// Size resting at exactly `price` on the given side
+fn get_level_size(book: &Orderbook, is_buy: bool, price: f64) -> f64 {
+    let side = if is_buy { &book.bids } else { &book.asks };
+
+    side.iter()
+        .find(|level| level.price == price)
+        .map(|level| level.size)
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        trades::Trade,
+        types::{BookLevel, Orderbook},
+    };
+
+    use super::QueueTracker;
+
+    fn book(bid_size: f64) -> Orderbook {
+        Orderbook {
+            coin: "ETH".to_string(),
+            time: 0,
+            bids: vec![BookLevel {
+                price: 100.0,
+                size: bid_size,
+                orders: 3,
+            }],
+            asks: vec![],
+        }
+    }
+
+    fn sell(price: f64, size: f64, time: u64) -> Trade {
+        Trade {
+            coin: "ETH".to_string(),
+            is_buy: false,
+            price,
+            size,
+            time,
+            tid: time,
+        }
+    }
+
+    #[test]
+    fn moves_up_the_queue() {
+        let mut tracker = QueueTracker::new(Duration::from_secs(10));
+        tracker.on_book(&book(5.0));
+        tracker.track(1, "ETH", true, 100.0, 1.0, 0);
+        assert_eq!(tracker.get_estimate(1).unwrap().queue_ahead, 5.0);
+
+        // 2 traded ahead, then 1 canceled ahead
+        tracker.on_trade(&sell(100.0, 2.0, 1_000));
+        tracker.on_book(&book(3.0));
+        let estimate = tracker.get_estimate(1).unwrap();
+        assert_eq!(estimate.queue_ahead, 2.0);
+        assert!((estimate.position - 2.0 / 3.0).abs() < 1e-9);
+        // 2 traded over the 10s window
+        assert_eq!(
+            estimate.expected_time_to_fill,
+            Some(Duration::from_secs(15))
+        );
+
+        tracker.on_trade(&sell(100.0, 2.5, 2_000));
+        assert_eq!(tracker.get_order(1).unwrap().filled, 0.5);
+
+        tracker.on_trade(&sell(99.0, 0.1, 3_000));
+        assert!(tracker.get_order(1).unwrap().is_filled());
+    }
+}
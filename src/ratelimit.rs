@@ -0,0 +1,346 @@
+use std::{
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use alloy::primitives::Address;
+use anyhow::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{
+    sync::watch,
+    time::{sleep, Instant},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    price_data::perps::parse_string_to_float,
+    prices::{build_info_http_client, post_info},
+};
+
+/// REST weight an IP can spend per minute, across the info and exchange endpoints
+pub const REST_WEIGHT_PER_MINUTE: u32 = 1200;
+
+/// Actions an address can send once its allowance is used up
+pub const THROTTLED_ACTION_INTERVAL: Duration = Duration::from_secs(10);
+
+pub const USER_RATE_LIMIT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Weight of an info request of type `request_type`
+pub fn get_info_weight(request_type: &str) -> u32 {
+    match request_type {
+        "l2Book"
+        | "allMids"
+        | "clearinghouseState"
+        | "orderStatus"
+        | "spotClearinghouseState"
+        | "exchangeStatus" => 2,
+        "userRole" => 60,
+        _ => 20,
+    }
+}
+
+/// Weight of an exchange request carrying `orders` orders or cancels
+pub fn get_action_weight(orders: usize) -> u32 {
+    1 + (orders / 40) as u32
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    /// Tokens added per second
+    refill_rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.refilled_at = now;
+    }
+}
+
+/// Token bucket holding `capacity` weight, refilled over `period`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Starts full.
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        RateLimiter {
+            bucket: Mutex::new(Bucket {
+                capacity: capacity as f64,
+                refill_rate: capacity as f64 / period.as_secs_f64(),
+                tokens: capacity as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Weight that can be spent right away
+    pub fn get_available(&self) -> u32 {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+
+        bucket.tokens as u32
+    }
+
+    /// Changes the limit, keeping what's available if it still fits.
+    pub fn set_limit(&self, capacity: u32, period: Duration) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+
+        bucket.capacity = capacity as f64;
+        bucket.refill_rate = capacity as f64 / period.as_secs_f64();
+        bucket.tokens = bucket.tokens.min(bucket.capacity);
+    }
+
+    /// Spends `weight` if available, without waiting.
+    pub fn try_acquire(&self, weight: u32) -> bool {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+
+        if bucket.tokens < weight as f64 {
+            return false;
+        }
+
+        bucket.tokens -= weight as f64;
+        true
+    }
+
+    /// Waits until `weight` is available and spends it. Weights above the capacity wait for a
+    /// full bucket.
+    pub async fn acquire(&self, weight: u32) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill();
+
+                let weight = (weight as f64).min(bucket.capacity);
+
+                if bucket.tokens >= weight {
+                    bucket.tokens -= weight;
+                    return;
+                }
+
+                Duration::from_secs_f64((weight - bucket.tokens) / bucket.refill_rate)
+            };
+
+            sleep(wait).await;
+        }
+    }
+}
+
+/// The limiter every info request and the exchange requests of
+/// [`crate::exec::ExchangeUtils`] wait on, holding [`REST_WEIGHT_PER_MINUTE`]
+pub fn get_rest_rate_limiter() -> &'static RateLimiter {
+    static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+    LIMITER.get_or_init(|| RateLimiter::new(REST_WEIGHT_PER_MINUTE, Duration::from_secs(60)))
+}
+
+/// Address based action allowance, as returned by the `userRateLimit` info request. Every USD
+/// traded adds one request to the cap.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserRateLimit {
+    /// Cumulative traded volume in USD
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub cum_vlm: f64,
+    pub n_requests_used: u64,
+    pub n_requests_cap: u64,
+}
+
+impl UserRateLimit {
+    pub fn get_remaining(&self) -> u64 {
+        self.n_requests_cap.saturating_sub(self.n_requests_used)
+    }
+}
+
+pub async fn get_user_rate_limit(client: &Client, user: Address) -> Result<UserRateLimit, Error> {
+    post_info(client, json!({ "type": "userRateLimit", "user": user })).await
+}
+
+#[derive(Debug)]
+struct Allowance {
+    remaining: u64,
+    /// When the last action was let through while out of allowance
+    throttled_at: Option<Instant>,
+}
+
+/// What's left of an address' action allowance, counted down locally for every action and reset
+/// to the exchange's count by [`ActionBudget::update`]. Once used up, actions are paced to one
+/// per [`THROTTLED_ACTION_INTERVAL`] as the exchange does, instead of being rejected.
+#[derive(Debug)]
+pub struct ActionBudget {
+    allowance: Mutex<Allowance>,
+}
+
+impl ActionBudget {
+    pub fn new(remaining: u64) -> Self {
+        ActionBudget {
+            allowance: Mutex::new(Allowance {
+                remaining,
+                throttled_at: None,
+            }),
+        }
+    }
+
+    pub fn from_rate_limit(rate_limit: &UserRateLimit) -> Self {
+        ActionBudget::new(rate_limit.get_remaining())
+    }
+
+    pub fn get_remaining(&self) -> u64 {
+        self.allowance.lock().unwrap().remaining
+    }
+
+    pub fn update(&self, rate_limit: &UserRateLimit) {
+        let mut allowance = self.allowance.lock().unwrap();
+
+        allowance.remaining = rate_limit.get_remaining();
+        if allowance.remaining > 0 {
+            allowance.throttled_at = None;
+        }
+    }
+
+    /// Waits until `actions` (orders or cancels of a request) can be sent and counts them.
+    pub async fn acquire(&self, actions: u64) {
+        loop {
+            let wait = {
+                let mut allowance = self.allowance.lock().unwrap();
+
+                if allowance.remaining >= actions {
+                    allowance.remaining -= actions;
+                    return;
+                }
+
+                // Out of allowance the exchange lets a single request through every interval
+                let ready_at = allowance
+                    .throttled_at
+                    .map(|at| at + THROTTLED_ACTION_INTERVAL);
+
+                match ready_at {
+                    Some(ready_at) if ready_at > Instant::now() => ready_at - Instant::now(),
+                    _ => {
+                        allowance.remaining = 0;
+                        allowance.throttled_at = Some(Instant::now());
+                        return;
+                    }
+                }
+            };
+
+            sleep(wait).await;
+        }
+    }
+}
+
+/// Polls the rate limit of `user` every `interval` and keeps `budget` in sync with it.
+pub async fn start_user_rate_limit_task(
+    user: Address,
+    interval: Duration,
+    budget: Arc<ActionBudget>,
+) -> anyhow::Result<watch::Receiver<UserRateLimit>> {
+    let (limit_sender, limit_recv) = watch::channel(UserRateLimit::default());
+
+    tokio::spawn(async move {
+        loop {
+            info!("user_rate_limit_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let mut interval = tokio::time::interval(interval);
+
+            let err = loop {
+                interval.tick().await;
+
+                match get_user_rate_limit(&client, user).await {
+                    Ok(rate_limit) => {
+                        if rate_limit.get_remaining() == 0 {
+                            warn!(
+                                "user_rate_limit_task: Used all {} requests, throttled to one per {}s",
+                                rate_limit.n_requests_cap,
+                                THROTTLED_ACTION_INTERVAL.as_secs()
+                            );
+                        }
+
+                        budget.update(&rate_limit);
+
+                        if limit_sender.send(rate_limit).is_err() {
+                            info!("user_rate_limit_task: All receivers dropped, stopping...");
+                            return;
+                        }
+                    }
+                    Err(err) => break err,
+                }
+            };
+
+            error!("user_rate_limit_task: Error: {err:?}");
+            info!("user_rate_limit_task: Resetting...");
+
+            sleep(Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(limit_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{ActionBudget, RateLimiter, UserRateLimit};
+
+    #[tokio::test(start_paused = true)]
+    async fn limiter_waits_for_the_refill() {
+        let limiter = RateLimiter::new(1200, Duration::from_secs(60));
+
+        assert!(limiter.try_acquire(1190));
+        assert!(!limiter.try_acquire(20));
+
+        let start = Instant::now();
+        limiter.acquire(20).await;
+        // 10 missing at 20 per second
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn budget_is_throttled_once_used_up() {
+        let budget = ActionBudget::from_rate_limit(&UserRateLimit {
+            cum_vlm: 0.0,
+            n_requests_used: 9998,
+            n_requests_cap: 10000,
+        });
+
+        let start = Instant::now();
+        budget.acquire(2).await;
+        assert_eq!(budget.get_remaining(), 0);
+
+        budget.acquire(1).await;
+        budget.acquire(1).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+        budget.update(&UserRateLimit {
+            cum_vlm: 500.0,
+            n_requests_used: 10003,
+            n_requests_cap: 10500,
+        });
+        assert_eq!(budget.get_remaining(), 497);
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{bail, Error};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "live")]
+use tokio::{sync::watch, task::JoinHandle};
+#[cfg(feature = "live")]
+use tracing::{error, info};
+
+#[cfg(feature = "parquet")]
+use crate::export::parquet::ParquetRecords;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RecordFormat {
+    /// One JSON object per line
+    #[default]
+    JsonLines,
+    /// Back to back MessagePack values, around an order of magnitude smaller than JSON for tick
+    /// data
+    MessagePack,
+}
+
+/// Rotated files are named to the second, so a shorter interval would reopen the live file.
+pub const MIN_ROTATION_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug)]
+pub struct RecorderConfig {
+    pub path: PathBuf,
+    pub format: RecordFormat,
+    pub flush_interval: Duration,
+    /// Starts a new file this often. The files are named after `path` with the time they were
+    /// opened appended to the file stem, see [`get_rotated_path`].
+    pub rotate_every: Option<Duration>,
+}
+
+impl RecorderConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        RecorderConfig {
+            path: path.into(),
+            format: RecordFormat::default(),
+            flush_interval: Duration::from_secs(5),
+            rotate_every: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: RecordFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Intervals below [`MIN_ROTATION_INTERVAL`] are raised to it.
+    pub fn with_rotation(mut self, rotate_every: Duration) -> Self {
+        self.rotate_every = Some(rotate_every.max(MIN_ROTATION_INTERVAL));
+        self
+    }
+
+    /// The file to write to when opening one at `time`
+    fn get_path(&self, time: DateTime<Utc>) -> PathBuf {
+        match self.rotate_every {
+            Some(_) => get_rotated_path(&self.path, time),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// `path` with `time` appended to the file stem, e.g. `data/ETH_books.jsonl` becomes
+/// `data/ETH_books_20240610T061320.jsonl`.
+pub fn get_rotated_path(path: &Path, time: DateTime<Utc>) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut file_name = format!("{stem}_{}", time.format("%Y%m%dT%H%M%S"));
+
+    if let Some(ext) = path.extension() {
+        file_name = format!("{file_name}.{}", ext.to_string_lossy());
+    }
+
+    path.with_file_name(file_name)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record<T> {
+    /// Local receive time in unix milliseconds
+    pub time: i64,
+    pub data: T,
+}
+
+/// Encodes the records of a recording, implemented for [`RecordFormat`] and by users for their
+/// own formats, e.g. to compress or encrypt records before they hit the disk.
+pub trait RecordSerializer<T>: Send {
+    /// Appends `record` to the current file.
+    fn write(&mut self, writer: &mut (dyn Write + Send), record: &Record<T>) -> Result<(), Error>;
+
+    /// Called on every flush of the recording, for formats that buffer records to write what
+    /// they have so far.
+    fn flush(&mut self, _writer: &mut (dyn Write + Send)) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once before a file is closed, for formats that write a footer or buffer whole
+    /// files. On rotation the same serializer then goes on with the next file.
+    fn finish(&mut self, _writer: &mut (dyn Write + Send)) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// [`RecordFormat::JsonLines`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonLinesSerializer;
+
+impl<T: Serialize> RecordSerializer<T> for JsonLinesSerializer {
+    fn write(&mut self, writer: &mut (dyn Write + Send), record: &Record<T>) -> Result<(), Error> {
+        serde_json::to_writer(&mut *writer, record)?;
+        writer.write_all(b"\n")?;
+
+        Ok(())
+    }
+}
+
+/// [`RecordFormat::MessagePack`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MessagePackSerializer;
+
+impl<T: Serialize> RecordSerializer<T> for MessagePackSerializer {
+    fn write(&mut self, writer: &mut (dyn Write + Send), record: &Record<T>) -> Result<(), Error> {
+        Ok(rmp_serde::encode::write(writer, record)?)
+    }
+}
+
+impl<T: Serialize> RecordSerializer<T> for RecordFormat {
+    fn write(&mut self, writer: &mut (dyn Write + Send), record: &Record<T>) -> Result<(), Error> {
+        match self {
+            RecordFormat::JsonLines => JsonLinesSerializer.write(writer, record),
+            RecordFormat::MessagePack => MessagePackSerializer.write(writer, record),
+        }
+    }
+}
+
+fn get_open_paths() -> &'static Mutex<HashSet<PathBuf>> {
+    static OPEN_PATHS: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+    OPEN_PATHS.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// `path` made absolute, so paths given relative and ones listed from a directory compare equal
+fn get_canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether a [`RecordWriter`] of this process still writes to `path`. Formats that buffer whole
+/// files only write them when they're finished, so an open file can look untouched for a while.
+pub fn is_recording_to(path: &Path) -> bool {
+    get_open_paths()
+        .lock()
+        .unwrap()
+        .contains(&get_canonical_path(path))
+}
+
+pub struct RecordWriter<T> {
+    writer: BufWriter<File>,
+    serializer: Box<dyn RecordSerializer<T>>,
+    path: PathBuf,
+}
+
+impl<T: Serialize> RecordWriter<T> {
+    pub fn create(path: &Path, format: RecordFormat) -> Result<Self, Error> {
+        RecordWriter::with_serializer(path, format)
+    }
+}
+
+impl<T> RecordWriter<T> {
+    pub fn with_serializer(
+        path: &Path,
+        serializer: impl RecordSerializer<T> + 'static,
+    ) -> Result<Self, Error> {
+        let writer = BufWriter::new(File::create(path)?);
+        let path = get_canonical_path(path);
+        get_open_paths().lock().unwrap().insert(path.clone());
+
+        Ok(RecordWriter {
+            writer,
+            serializer: Box::new(serializer),
+            path,
+        })
+    }
+
+    pub fn write(&mut self, record: &Record<T>) -> Result<(), Error> {
+        self.serializer.write(&mut self.writer, record)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.serializer.flush(&mut self.writer)?;
+        Ok(self.writer.flush()?)
+    }
+
+    /// Completes the current file and goes on writing to `path`. The current file is kept if
+    /// `path` can't be created or is the current file, e.g. for a rotation within the same second.
+    pub fn rotate(&mut self, path: &Path) -> Result<(), Error> {
+        if get_canonical_path(path) == self.path {
+            bail!("Already writing to {path:?}");
+        }
+
+        let writer = BufWriter::new(File::create(path)?);
+
+        self.serializer.finish(&mut self.writer)?;
+        self.flush()?;
+        self.writer = writer;
+
+        let path = get_canonical_path(path);
+        let mut open_paths = get_open_paths().lock().unwrap();
+        open_paths.remove(&self.path);
+        open_paths.insert(path.clone());
+        self.path = path;
+
+        Ok(())
+    }
+
+    /// Lets the serializer complete the file and flushes it. Dropping the writer instead only
+    /// flushes what was written, which leaves formats with a footer unreadable.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.serializer.finish(&mut self.writer)?;
+        self.flush()
+    }
+}
+
+impl<T> Drop for RecordWriter<T> {
+    fn drop(&mut self) {
+        if let Ok(mut open_paths) = get_open_paths().lock() {
+            open_paths.remove(&self.path);
+        }
+    }
+}
+
+enum RecordSource {
+    File(BufReader<File>, RecordFormat),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetRecords),
+}
+
+/// Reads back the records written by a [`RecordWriter`] with one of the [`RecordFormat`]s, or
+/// with a [`crate::export::parquet::ParquetSerializer`], in order.
+pub struct RecordReader<T> {
+    source: RecordSource,
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> RecordReader<T> {
+    pub fn open(path: &Path, format: RecordFormat) -> Result<Self, Error> {
+        Ok(RecordReader {
+            source: RecordSource::File(BufReader::new(File::open(path)?), format),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Opens a finished file of a [`crate::export::parquet::ParquetSerializer`].
+    #[cfg(feature = "parquet")]
+    pub fn open_parquet(path: &Path) -> Result<Self, Error> {
+        Ok(RecordReader {
+            source: RecordSource::Parquet(ParquetRecords::open(path)?),
+            _marker: PhantomData,
+        })
+    }
+
+    fn read_next(&mut self) -> Result<Option<Record<T>>, Error> {
+        match &mut self.source {
+            RecordSource::File(reader, RecordFormat::JsonLines) => {
+                let mut line = String::new();
+
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 {
+                        return Ok(None);
+                    }
+
+                    if !line.trim().is_empty() {
+                        return Ok(Some(serde_json::from_str(&line)?));
+                    }
+                }
+            }
+            RecordSource::File(reader, RecordFormat::MessagePack) => {
+                if reader.fill_buf()?.is_empty() {
+                    return Ok(None);
+                }
+
+                Ok(Some(rmp_serde::decode::from_read(reader)?))
+            }
+            #[cfg(feature = "parquet")]
+            RecordSource::Parquet(records) => records.read_next(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for RecordReader<T> {
+    type Item = Result<Record<T>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_next().transpose()
+    }
+}
+
+/// Writes every update of `receiver` to `config.path` until the sender is dropped, starting a new
+/// file every `config.rotate_every` if set.
+#[cfg(feature = "live")]
+pub fn start_recorder_task<T>(
+    receiver: watch::Receiver<T>,
+    config: RecorderConfig,
+) -> anyhow::Result<JoinHandle<()>>
+where
+    T: Serialize + Clone + Send + Sync + 'static,
+{
+    let format = config.format;
+
+    start_recorder_task_with_serializer(receiver, config, format)
+}
+
+/// Same as [`start_recorder_task`] but encodes the records with `serializer` instead of
+/// `config.format`.
+#[cfg(feature = "live")]
+pub fn start_recorder_task_with_serializer<T, S>(
+    mut receiver: watch::Receiver<T>,
+    config: RecorderConfig,
+    serializer: S,
+) -> anyhow::Result<JoinHandle<()>>
+where
+    T: Clone + Send + Sync + 'static,
+    S: RecordSerializer<T> + 'static,
+{
+    let mut path = config.get_path(Utc::now());
+    let mut writer = RecordWriter::with_serializer(&path, serializer)?;
+
+    Ok(tokio::spawn(async move {
+        let mut flush_interval = tokio::time::interval(config.flush_interval);
+        let mut opened_at = tokio::time::Instant::now();
+
+        info!("recorder_task: Recording to {path:?}");
+
+        loop {
+            tokio::select! {
+                changed = receiver.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+
+                    if config.rotate_every.is_some_and(|every| opened_at.elapsed() >= every) {
+                        path = config.get_path(Utc::now());
+                        match writer.rotate(&path) {
+                            Ok(()) => {
+                                opened_at = tokio::time::Instant::now();
+                                info!("recorder_task: Rotated to {path:?}");
+                            }
+                            Err(err) => error!("recorder_task: Couldn't rotate to {path:?}: {err:?}"),
+                        }
+                    }
+
+                    let record = Record {
+                        time: Utc::now().timestamp_millis(),
+                        data: receiver.borrow_and_update().clone(),
+                    };
+
+                    if let Err(err) = writer.write(&record) {
+                        error!("recorder_task: Error while writing: {err:?}");
+                    }
+                }
+                _ = flush_interval.tick() => {
+                    if let Err(err) = writer.flush() {
+                        error!("recorder_task: Error while flushing: {err:?}");
+                    }
+                }
+            }
+        }
+
+        if let Err(err) = writer.finish() {
+            error!("recorder_task: Error while finishing the file: {err:?}");
+        }
+        info!("recorder_task: Feed closed, stopping...");
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Error;
+
+    use super::{
+        Record, RecordFormat, RecordReader, RecordSerializer, RecordWriter, RecorderConfig,
+        MIN_ROTATION_INTERVAL,
+    };
+
+    /// Counts the records and writes them as JSON lines, with a trailer once finished
+    #[derive(Default)]
+    struct CountingSerializer {
+        records: usize,
+    }
+
+    impl RecordSerializer<u64> for CountingSerializer {
+        fn write(
+            &mut self,
+            writer: &mut (dyn Write + Send),
+            record: &Record<u64>,
+        ) -> Result<(), Error> {
+            self.records += 1;
+            RecordFormat::JsonLines.write(writer, record)
+        }
+
+        fn finish(&mut self, writer: &mut (dyn Write + Send)) -> Result<(), Error> {
+            let trailer = Record {
+                time: 0,
+                data: self.records as u64,
+            };
+            self.records = 0;
+
+            RecordFormat::JsonLines.write(writer, &trailer)
+        }
+    }
+
+    #[test]
+    fn custom_serializer_finishes_every_file() -> Result<(), Error> {
+        let dir = std::env::temp_dir();
+        let first = dir.join(format!("hl_recorder_{}_a.jsonl", std::process::id()));
+        let second = dir.join(format!("hl_recorder_{}_b.jsonl", std::process::id()));
+
+        let mut writer = RecordWriter::with_serializer(&first, CountingSerializer::default())?;
+        writer.write(&Record { time: 1, data: 10 })?;
+        writer.write(&Record { time: 2, data: 20 })?;
+        writer.rotate(&second)?;
+        writer.write(&Record { time: 3, data: 30 })?;
+        writer.finish()?;
+
+        let read = |path| -> Result<Vec<u64>, Error> {
+            RecordReader::<u64>::open(path, RecordFormat::JsonLines)?
+                .map(|record| Ok(record?.data))
+                .collect()
+        };
+
+        assert_eq!(read(&first)?, vec![10, 20, 2]);
+        assert_eq!(read(&second)?, vec![30, 1]);
+
+        std::fs::remove_file(&first)?;
+        std::fs::remove_file(&second)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn rotating_to_the_live_file_keeps_it() -> Result<(), Error> {
+        let path =
+            std::env::temp_dir().join(format!("hl_recorder_{}_live.jsonl", std::process::id()));
+
+        let mut writer = RecordWriter::<u64>::create(&path, RecordFormat::JsonLines)?;
+        writer.write(&Record { time: 1, data: 10 })?;
+        assert!(writer.rotate(&path).is_err());
+        writer.write(&Record { time: 2, data: 20 })?;
+        writer.finish()?;
+
+        let read: Vec<u64> = RecordReader::<u64>::open(&path, RecordFormat::JsonLines)?
+            .map(|record| Ok::<_, Error>(record?.data))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(read, vec![10, 20]);
+
+        let config =
+            RecorderConfig::new(&path).with_rotation(std::time::Duration::from_millis(100));
+        assert_eq!(config.rotate_every, Some(MIN_ROTATION_INTERVAL));
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+}
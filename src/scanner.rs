@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::info;
+
+use crate::{
+    price_data::perps::{NameToCtxMap, PerpsAssetCtx},
+    types::NameToOrderbookMap,
+};
+
+const HOURS_PER_YEAR: f64 = 24.0 * 365.0;
+
+#[derive(Clone, Debug)]
+pub struct ScannerConfig {
+    /// Distance from the mid within which book depth is counted
+    pub depth_bps: f64,
+    /// Opportunities with less depth than this on the side that would be traded are dropped
+    pub min_depth_usd: f64,
+    pub max_results: usize,
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        ScannerConfig {
+            depth_bps: 50.0,
+            min_depth_usd: 10_000.0,
+            max_results: 20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CarrySide {
+    /// Funding is positive, shorts get paid
+    ShortPerp,
+    /// Funding is negative, longs get paid
+    LongPerp,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CarryOpportunity {
+    pub coin: String,
+    pub side: CarrySide,
+    /// Hourly funding rate as reported by the API
+    pub funding_rate: f64,
+    pub annualized_funding: f64,
+    /// (mark - oracle) / oracle
+    pub basis: f64,
+    /// USD depth on the side that would be traded to enter the perp leg
+    pub depth_usd: f64,
+    /// Annualized funding captured plus the basis that would be earned on convergence
+    pub score: f64,
+}
+
+/// Ranks every coin with a non-zero funding rate, best first.
+pub fn rank_carry_opportunities(
+    ctxs: &HashMap<String, PerpsAssetCtx>,
+    books: &NameToOrderbookMap,
+    config: &ScannerConfig,
+) -> Vec<CarryOpportunity> {
+    let mut opportunities: Vec<CarryOpportunity> = ctxs
+        .iter()
+        .filter(|(_, ctx)| ctx.funding != 0.0 && ctx.oracle_px > 0.0)
+        .filter_map(|(coin, ctx)| {
+            let side = if ctx.funding > 0.0 {
+                CarrySide::ShortPerp
+            } else {
+                CarrySide::LongPerp
+            };
+
+            let (bid_depth, ask_depth) = books
+                .get(coin.as_str())
+                .map(|book| book.get_depth_within_bps(config.depth_bps))
+                .unwrap_or((0.0, 0.0));
+
+            let depth_usd = match side {
+                CarrySide::ShortPerp => bid_depth,
+                CarrySide::LongPerp => ask_depth,
+            };
+
+            if depth_usd < config.min_depth_usd {
+                return None;
+            }
+
+            let basis = (ctx.mark_px - ctx.oracle_px) / ctx.oracle_px;
+            let annualized_funding = ctx.funding * HOURS_PER_YEAR;
+
+            // A rich perp converges down, which pays the short side and costs the long side
+            let basis_edge = match side {
+                CarrySide::ShortPerp => basis,
+                CarrySide::LongPerp => -basis,
+            };
+
+            Some(CarryOpportunity {
+                coin: coin.clone(),
+                side,
+                funding_rate: ctx.funding,
+                annualized_funding,
+                basis,
+                depth_usd,
+                score: annualized_funding.abs() + basis_edge,
+            })
+        })
+        .collect();
+
+    opportunities.sort_by(|a, b| b.score.total_cmp(&a.score));
+    opportunities.truncate(config.max_results);
+
+    opportunities
+}
+
+/// Ranks carry opportunities every time the asset contexts of `ctx_receiver` (see
+/// [`crate::prices::start_asset_ctx_task`]) change, using the books published by
+/// [`crate::orderbook::start_orderbook_sender_task`]. Coins without a book in `book_receiver` only
+/// show up if `min_depth_usd` is 0.
+pub async fn start_funding_scanner_task(
+    mut ctx_receiver: watch::Receiver<NameToCtxMap>,
+    book_receiver: watch::Receiver<NameToOrderbookMap>,
+    config: ScannerConfig,
+) -> anyhow::Result<watch::Receiver<Vec<CarryOpportunity>>> {
+    let (opportunity_sender, opportunity_recv) = watch::channel(Vec::<CarryOpportunity>::new());
+
+    // The contexts already published are used right away
+    ctx_receiver.mark_changed();
+
+    tokio::spawn(async move {
+        info!("funding_scanner_task: Starting...");
+
+        loop {
+            if ctx_receiver.changed().await.is_err() {
+                info!("funding_scanner_task: Asset context channel closed, stopping...");
+                return;
+            }
+
+            let opportunities = rank_carry_opportunities(
+                &ctx_receiver.borrow_and_update(),
+                &book_receiver.borrow(),
+                &config,
+            );
+
+            if opportunity_sender.send(opportunities).is_err() {
+                info!("funding_scanner_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(opportunity_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        price_data::perps::PerpsAssetCtx,
+        types::{BookLevel, NameToOrderbookMap, Orderbook, Symbol},
+    };
+
+    use super::{rank_carry_opportunities, CarrySide, ScannerConfig};
+
+    fn ctx(funding: f64, mark_px: f64, oracle_px: f64) -> PerpsAssetCtx {
+        PerpsAssetCtx {
+            funding,
+            mark_px,
+            oracle_px,
+            ..Default::default()
+        }
+    }
+
+    fn book(coin: &str, bid: f64, ask: f64, size: f64) -> Orderbook {
+        Orderbook {
+            coin: coin.to_string(),
+            time: 0,
+            bids: vec![BookLevel {
+                price: bid,
+                size,
+                orders: 1,
+            }],
+            asks: vec![BookLevel {
+                price: ask,
+                size,
+                orders: 1,
+            }],
+        }
+    }
+
+    #[test]
+    fn opportunities_are_ranked_and_filtered_by_depth() {
+        let ctxs = HashMap::from([
+            ("ETH".to_string(), ctx(0.0001, 3000.0, 3000.0)),
+            ("SOL".to_string(), ctx(-0.0003, 150.0, 150.0)),
+            ("DOGE".to_string(), ctx(0.001, 0.1, 0.1)),
+        ]);
+
+        let books: NameToOrderbookMap = [
+            (Symbol::from_api("ETH"), book("ETH", 2999.0, 3001.0, 100.0)),
+            (Symbol::from_api("SOL"), book("SOL", 149.9, 150.1, 1000.0)),
+            (Symbol::from_api("DOGE"), book("DOGE", 0.0999, 0.1001, 10.0)),
+        ]
+        .into_iter()
+        .collect();
+
+        let ranked = rank_carry_opportunities(&ctxs, &books, &ScannerConfig::default());
+
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].coin, "SOL");
+        assert_eq!(ranked[0].side, CarrySide::LongPerp);
+        assert_eq!(ranked[1].coin, "ETH");
+        assert_eq!(ranked[1].side, CarrySide::ShortPerp);
+    }
+}
@@ -0,0 +1,574 @@
+use std::{
+    collections::HashMap,
+    future::{pending, Future},
+    time::Duration,
+};
+
+use anyhow::Error;
+use chrono::Utc;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::watch::{self, error::RecvError},
+    task::JoinHandle,
+};
+use tracing::info;
+
+use crate::{
+    callbacks::{on_update, CallbackHandle},
+    candle_stream::spawn_candle_task,
+    candles::{Candle, CandleBuffer},
+    counters::{FeedCounter, FeedCounters},
+    exec::DeadManSwitch,
+    funding::{get_funding_rate_map, CoinToFundingRateMap},
+    orderbook::{spawn_book_update_time_task, spawn_orderbook_sender_task},
+    price_data::perps::{NameToCtxMap, NameToPerpQuoteMap, PerpQuote, PerpsAssetCtx},
+    prices::{
+        get_stale_coins, spawn_asset_ctx_task, spawn_perp_quote_task, spawn_perps_sender_task,
+        spawn_price_update_time_task, spawn_spot_sender_task, DEFAULT_SESSION_DURATION,
+    },
+    streams::watch_coin,
+    types::{
+        Bbo, CoinToOiValueMap, NameToOrderbookMap, NameToPriceMap, NameToUpdateTimeMap, Orderbook,
+        Price, Symbol,
+    },
+};
+
+#[derive(Clone, Debug)]
+pub struct MarketDataConfig {
+    pub perps_prices: bool,
+    pub spot_prices: bool,
+    /// Coins to keep L2 books for, none starts no orderbook task
+    pub book_coins: Vec<String>,
+    /// Coins to keep candles for, none starts no candle task
+    pub candle_coins: Vec<String>,
+    pub candle_interval: String,
+    /// Candles kept per coin
+    pub candle_capacity: usize,
+    /// How often asset contexts are refetched, `None` starts no asset context task
+    pub ctx_interval: Option<Duration>,
+}
+
+impl Default for MarketDataConfig {
+    fn default() -> Self {
+        MarketDataConfig {
+            perps_prices: true,
+            spot_prices: false,
+            book_coins: vec![],
+            candle_coins: vec![],
+            candle_interval: "1m".to_string(),
+            candle_capacity: 500,
+            ctx_interval: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// When each feed last published, ms since epoch. `None` until the first update.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct FeedTimes {
+    pub perps_prices: Option<i64>,
+    pub spot_prices: Option<i64>,
+    pub books: Option<i64>,
+    pub candles: Option<i64>,
+    pub ctxs: Option<i64>,
+}
+
+/// A feed's value in a [`MarketSnapshot`] with the time the feed last published it
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Timestamped<T> {
+    pub value: T,
+    pub time: Option<i64>,
+}
+
+/// Point in time view of every enabled feed, captured while holding all of them at once. Feeds
+/// that aren't enabled are empty with no time.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    /// When the snapshot was taken, ms since epoch
+    pub time: i64,
+    pub prices: Timestamped<NameToPriceMap>,
+    /// When each perps price last moved
+    pub price_times: NameToUpdateTimeMap,
+    pub spot_prices: Timestamped<NameToPriceMap>,
+    /// When each spot price last moved
+    pub spot_price_times: NameToUpdateTimeMap,
+    pub bbos: Timestamped<HashMap<Symbol, Bbo>>,
+    /// When each coin last got a book
+    pub book_times: NameToUpdateTimeMap,
+    pub funding: Timestamped<CoinToFundingRateMap>,
+    /// Open interest in USD, at the mark price
+    pub open_interest: Timestamped<CoinToOiValueMap>,
+}
+
+/// Owns the market data tasks enabled in its config behind one handle. Every getter returns
+/// `None` for a feed that isn't enabled or after [`MarketDataService::shutdown`].
+pub struct MarketDataService {
+    config: MarketDataConfig,
+    perps_prices: Option<watch::Receiver<NameToPriceMap>>,
+    spot_prices: Option<watch::Receiver<NameToPriceMap>>,
+    books: Option<watch::Receiver<NameToOrderbookMap>>,
+    candles: Option<watch::Receiver<CandleBuffer>>,
+    ctxs: Option<watch::Receiver<NameToCtxMap>>,
+    /// When each perps price last moved, when perps prices are enabled
+    price_times: Option<watch::Receiver<NameToUpdateTimeMap>>,
+    /// When each spot price last moved, when spot prices are enabled
+    spot_price_times: Option<watch::Receiver<NameToUpdateTimeMap>>,
+    /// When each coin last got a book, when books are enabled
+    book_times: Option<watch::Receiver<NameToUpdateTimeMap>>,
+    /// Perps prices joined with asset contexts, when both are enabled
+    quotes: Option<watch::Receiver<NameToPerpQuoteMap>>,
+    feed_times: watch::Receiver<FeedTimes>,
+    feed_times_task: JoinHandle<()>,
+    /// Every feed task, aborted by [`MarketDataService::shutdown`]
+    tasks: Vec<JoinHandle<()>>,
+    /// Of the websocket feed tasks started by this service
+    counters: Vec<FeedCounter>,
+    /// Cleared by [`MarketDataService::shutdown`]
+    dead_man_switch: Option<DeadManSwitch>,
+}
+
+impl MarketDataService {
+    pub async fn start(config: MarketDataConfig) -> Result<Self, Error> {
+        info!("market_data_service: Starting with {config:?}");
+
+        let mut tasks = vec![];
+        let mut counters = vec![];
+
+        let perps_prices = if config.perps_prices {
+            Some(keep_task(
+                &mut tasks,
+                spawn_perps_sender_task(
+                    DEFAULT_SESSION_DURATION,
+                    keep_counter(&mut counters, "perps_prices"),
+                ),
+            ))
+        } else {
+            None
+        };
+
+        let spot_prices = if config.spot_prices {
+            Some(keep_task(
+                &mut tasks,
+                spawn_spot_sender_task(
+                    DEFAULT_SESSION_DURATION,
+                    keep_counter(&mut counters, "spot_prices"),
+                ),
+            ))
+        } else {
+            None
+        };
+
+        let books = if config.book_coins.is_empty() {
+            None
+        } else {
+            Some(keep_task(
+                &mut tasks,
+                spawn_orderbook_sender_task(
+                    config.book_coins.clone(),
+                    keep_counter(&mut counters, "orderbook"),
+                ),
+            ))
+        };
+
+        let candles = if config.candle_coins.is_empty() {
+            None
+        } else {
+            Some(keep_task(
+                &mut tasks,
+                spawn_candle_task(
+                    config.candle_coins.clone(),
+                    config.candle_interval.clone(),
+                    config.candle_capacity,
+                    false,
+                    keep_counter(&mut counters, "candles"),
+                )?,
+            ))
+        };
+
+        let ctxs = match config.ctx_interval {
+            Some(interval) => Some(keep_task(&mut tasks, spawn_asset_ctx_task(interval)?)),
+            None => None,
+        };
+
+        let price_times = perps_prices
+            .clone()
+            .map(|perps_prices| keep_task(&mut tasks, spawn_price_update_time_task(perps_prices)));
+        let spot_price_times = spot_prices
+            .clone()
+            .map(|spot_prices| keep_task(&mut tasks, spawn_price_update_time_task(spot_prices)));
+        let book_times = books
+            .clone()
+            .map(|books| keep_task(&mut tasks, spawn_book_update_time_task(books)));
+
+        let quotes = match (&perps_prices, &ctxs) {
+            (Some(perps_prices), Some(ctxs)) => Some(keep_task(
+                &mut tasks,
+                spawn_perp_quote_task(perps_prices.clone(), ctxs.clone()),
+            )),
+            _ => None,
+        };
+
+        let (feed_times_sender, feed_times) = watch::channel(FeedTimes::default());
+        let feed_times_task = tokio::spawn(track_feed_times(
+            perps_prices.clone(),
+            spot_prices.clone(),
+            books.clone(),
+            candles.clone(),
+            ctxs.clone(),
+            feed_times_sender,
+        ));
+
+        Ok(MarketDataService {
+            config,
+            perps_prices,
+            spot_prices,
+            books,
+            candles,
+            ctxs,
+            price_times,
+            spot_price_times,
+            book_times,
+            quotes,
+            feed_times,
+            feed_times_task,
+            tasks,
+            counters,
+            dead_man_switch: None,
+        })
+    }
+
+    /// Ties `switch` to the service, so [`MarketDataService::shutdown`] also clears its deadline.
+    /// Dropping the service without shutting it down leaves the deadline to cancel the orders.
+    pub fn with_dead_man_switch(mut self, switch: DeadManSwitch) -> Self {
+        self.dead_man_switch = Some(switch);
+        self
+    }
+
+    pub fn get_config(&self) -> &MarketDataConfig {
+        &self.config
+    }
+
+    /// Perps prices
+    pub fn prices(&self) -> Option<watch::Receiver<NameToPriceMap>> {
+        self.perps_prices.clone()
+    }
+
+    pub fn spot_prices(&self) -> Option<watch::Receiver<NameToPriceMap>> {
+        self.spot_prices.clone()
+    }
+
+    pub fn books(&self) -> Option<watch::Receiver<NameToOrderbookMap>> {
+        self.books.clone()
+    }
+
+    pub fn candles(&self) -> Option<watch::Receiver<CandleBuffer>> {
+        self.candles.clone()
+    }
+
+    pub fn ctxs(&self) -> Option<watch::Receiver<NameToCtxMap>> {
+        self.ctxs.clone()
+    }
+
+    pub fn quotes(&self) -> Option<watch::Receiver<NameToPerpQuoteMap>> {
+        self.quotes.clone()
+    }
+
+    /// When each perps price last moved, see [`start_price_update_time_task`]
+    pub fn price_times(&self) -> Option<watch::Receiver<NameToUpdateTimeMap>> {
+        self.price_times.clone()
+    }
+
+    /// Latest perps price of `coin`
+    pub fn price(&self, coin: &str) -> Option<Price> {
+        self.perps_prices.as_ref()?.borrow().get(coin).cloned()
+    }
+
+    /// When the perps price of `coin` last moved, ms since epoch
+    pub fn price_time(&self, coin: &str) -> Option<i64> {
+        self.price_times.as_ref()?.borrow().get(coin).copied()
+    }
+
+    /// Perps coins whose price hasn't moved for longer than `max_age`, while the feed itself may
+    /// still be publishing. A flat coin is reported as well, see [`get_stale_coins`]. Empty if
+    /// perps prices aren't enabled.
+    pub fn get_stale_coins(&self, max_age: Duration) -> Vec<Symbol> {
+        get_stale(&self.price_times, max_age)
+    }
+
+    /// When each spot price last moved, see [`crate::prices::start_price_update_time_task`]
+    pub fn spot_price_times(&self) -> Option<watch::Receiver<NameToUpdateTimeMap>> {
+        self.spot_price_times.clone()
+    }
+
+    /// When the spot price of `coin` last moved, ms since epoch
+    pub fn spot_price_time(&self, coin: &str) -> Option<i64> {
+        self.spot_price_times.as_ref()?.borrow().get(coin).copied()
+    }
+
+    /// [`MarketDataService::get_stale_coins`] for spot prices
+    pub fn get_stale_spot_coins(&self, max_age: Duration) -> Vec<Symbol> {
+        get_stale(&self.spot_price_times, max_age)
+    }
+
+    pub fn book(&self, coin: &str) -> Option<Orderbook> {
+        self.books.as_ref()?.borrow().get(coin).cloned()
+    }
+
+    /// When each coin last got a book, see [`crate::orderbook::start_book_update_time_task`]
+    pub fn book_times(&self) -> Option<watch::Receiver<NameToUpdateTimeMap>> {
+        self.book_times.clone()
+    }
+
+    /// When the last book of `coin` arrived, ms since epoch
+    pub fn book_time(&self, coin: &str) -> Option<i64> {
+        self.book_times.as_ref()?.borrow().get(coin).copied()
+    }
+
+    /// Coins without a book for longer than `max_age`. Books are sent on every change, so unlike
+    /// prices a quiet but healthy coin still gets one regularly. Empty if books aren't enabled.
+    pub fn get_stale_books(&self, max_age: Duration) -> Vec<Symbol> {
+        get_stale(&self.book_times, max_age)
+    }
+
+    pub fn candle_history(&self, coin: &str) -> Option<Vec<Candle>> {
+        let candles = self.candles.as_ref()?.borrow();
+
+        Some(candles.get(coin)?.iter().cloned().collect())
+    }
+
+    pub fn ctx(&self, coin: &str) -> Option<PerpsAssetCtx> {
+        self.ctxs.as_ref()?.borrow().get(coin).cloned()
+    }
+
+    /// Perps price of `coin` with its mark, oracle, funding and open interest, the latter as of
+    /// the last asset context poll (every `ctx_interval`). Needs both perps prices and asset
+    /// contexts enabled.
+    pub fn quote(&self, coin: &str) -> Option<PerpQuote> {
+        self.quotes.as_ref()?.borrow().get(coin).cloned()
+    }
+
+    /// Perps prices of `coin` as they change, see [`watch_coin`]. `None` if perps prices aren't
+    /// enabled.
+    pub fn watch_coin(
+        &self,
+        coin: &str,
+        threshold: Option<f64>,
+    ) -> Option<impl Stream<Item = Price>> {
+        Some(watch_coin(self.perps_prices.clone()?, coin, threshold))
+    }
+
+    /// Calls `callback` every time the perps price of `coin` changes. `None` if perps prices
+    /// aren't enabled.
+    pub fn on_price_update<F, Fut>(&self, coin: &str, callback: F) -> Option<CallbackHandle>
+    where
+        F: FnMut(Price) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let coin = coin.to_string();
+
+        Some(on_update(
+            &format!("price_callback_{coin}"),
+            self.perps_prices.clone()?,
+            move |prices| prices.get(coin.as_str()).cloned(),
+            callback,
+        ))
+    }
+
+    /// Calls `callback` every time the book of `coin` changes. `None` if `coin` isn't in the
+    /// config's `book_coins`.
+    pub fn on_book_update<F, Fut>(&self, coin: &str, callback: F) -> Option<CallbackHandle>
+    where
+        F: FnMut(Orderbook) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if !self.config.book_coins.iter().any(|c| c == coin) {
+            return None;
+        }
+
+        let coin = coin.to_string();
+
+        Some(on_update(
+            &format!("book_callback_{coin}"),
+            self.books.clone()?,
+            move |books| books.get(coin.as_str()).cloned(),
+            callback,
+        ))
+    }
+
+    pub fn get_feed_times(&self) -> FeedTimes {
+        self.feed_times.borrow().clone()
+    }
+
+    /// Message counts of the websocket feeds enabled in the config, counted by this service's
+    /// tasks alone (`perps_prices`, `spot_prices`, `orderbook` and `candles`).
+    pub fn get_feed_counters(&self) -> Vec<FeedCounters> {
+        self.counters
+            .iter()
+            .map(|counter| counter.get_counters())
+            .collect()
+    }
+
+    /// Captures every enabled feed at once, so a strategy tick works on one coherent view instead
+    /// of borrowing each channel at a slightly different time.
+    pub fn snapshot(&self) -> MarketSnapshot {
+        let times = self.feed_times.borrow();
+        let perps_prices = self.perps_prices.as_ref().map(|r| r.borrow());
+        let spot_prices = self.spot_prices.as_ref().map(|r| r.borrow());
+        let books = self.books.as_ref().map(|r| r.borrow());
+        let ctxs = self.ctxs.as_ref().map(|r| r.borrow());
+        let price_times = self.price_times.as_ref().map(|r| r.borrow());
+        let spot_price_times = self.spot_price_times.as_ref().map(|r| r.borrow());
+        let book_times = self.book_times.as_ref().map(|r| r.borrow());
+        let time = Utc::now().timestamp_millis();
+
+        let ctxs_time = times.ctxs;
+
+        MarketSnapshot {
+            time,
+            prices: Timestamped {
+                value: perps_prices.map(|p| p.clone()).unwrap_or_default(),
+                time: times.perps_prices,
+            },
+            price_times: price_times.map(|t| t.clone()).unwrap_or_default(),
+            spot_prices: Timestamped {
+                value: spot_prices.map(|p| p.clone()).unwrap_or_default(),
+                time: times.spot_prices,
+            },
+            spot_price_times: spot_price_times.map(|t| t.clone()).unwrap_or_default(),
+            bbos: Timestamped {
+                value: books
+                    .map(|books| {
+                        books
+                            .iter()
+                            .map(|(coin, book)| (coin.clone(), book.get_bbo()))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                time: times.books,
+            },
+            book_times: book_times.map(|t| t.clone()).unwrap_or_default(),
+            funding: Timestamped {
+                value: ctxs
+                    .as_ref()
+                    .map(|ctxs| get_funding_rate_map(ctxs))
+                    .unwrap_or_default(),
+                time: ctxs_time,
+            },
+            open_interest: Timestamped {
+                value: ctxs
+                    .map(|ctxs| {
+                        ctxs.iter()
+                            .map(|(coin, ctx)| (coin.clone(), ctx.open_interest * ctx.mark_px))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                time: ctxs_time,
+            },
+        }
+    }
+
+    /// Aborts every feed task, which releases their subscriptions, and drops the service's
+    /// receivers. Receivers handed out by the getters see their channel closed.
+    ///
+    /// Then shuts down the dead man switch, if one is set, failing if its deadline couldn't be
+    /// cleared, in which case the exchange still cancels the open orders once it passes.
+    pub async fn shutdown(&mut self) -> Result<(), Error> {
+        info!("market_data_service: Shutting down...");
+
+        self.perps_prices = None;
+        self.spot_prices = None;
+        self.books = None;
+        self.candles = None;
+        self.ctxs = None;
+        self.price_times = None;
+        self.spot_price_times = None;
+        self.book_times = None;
+        self.quotes = None;
+        self.feed_times_task.abort();
+
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+
+        match self.dead_man_switch.take() {
+            Some(switch) => switch.shutdown().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MarketDataService {
+    fn drop(&mut self) {
+        // The tracker holds receivers of every feed, which would keep the tasks alive
+        self.feed_times_task.abort();
+    }
+}
+
+/// Keeps the handle of a spawned feed task and returns its receiver.
+fn keep_task<T>(tasks: &mut Vec<JoinHandle<()>>, (receiver, task): (T, JoinHandle<()>)) -> T {
+    tasks.push(task);
+    receiver
+}
+
+/// Coins of `times` that didn't update for longer than `max_age`, none if the feed isn't enabled.
+fn get_stale(
+    times: &Option<watch::Receiver<NameToUpdateTimeMap>>,
+    max_age: Duration,
+) -> Vec<Symbol> {
+    match times {
+        Some(times) => get_stale_coins(&times.borrow(), Utc::now().timestamp_millis(), max_age),
+        None => vec![],
+    }
+}
+
+/// Keeps a new counter of `feed` and returns a clone for its task to count on.
+fn keep_counter(counters: &mut Vec<FeedCounter>, feed: &str) -> FeedCounter {
+    let counter = FeedCounter::new(feed);
+    counters.push(counter.clone());
+    counter
+}
+
+/// Resolves when the feed changes, never if it isn't enabled.
+async fn changed<T>(receiver: &mut Option<watch::Receiver<T>>) -> Result<(), RecvError> {
+    match receiver {
+        Some(receiver) => receiver.changed().await,
+        None => pending().await,
+    }
+}
+
+async fn track_feed_times(
+    mut perps_prices: Option<watch::Receiver<NameToPriceMap>>,
+    mut spot_prices: Option<watch::Receiver<NameToPriceMap>>,
+    mut books: Option<watch::Receiver<NameToOrderbookMap>>,
+    mut candles: Option<watch::Receiver<CandleBuffer>>,
+    mut ctxs: Option<watch::Receiver<NameToCtxMap>>,
+    sender: watch::Sender<FeedTimes>,
+) {
+    let now = || Some(Utc::now().timestamp_millis());
+
+    loop {
+        // A closed feed stops being tracked
+        tokio::select! {
+            res = changed(&mut perps_prices) => match res {
+                Ok(()) => sender.send_modify(|times| times.perps_prices = now()),
+                Err(_) => perps_prices = None,
+            },
+            res = changed(&mut spot_prices) => match res {
+                Ok(()) => sender.send_modify(|times| times.spot_prices = now()),
+                Err(_) => spot_prices = None,
+            },
+            res = changed(&mut books) => match res {
+                Ok(()) => sender.send_modify(|times| times.books = now()),
+                Err(_) => books = None,
+            },
+            res = changed(&mut candles) => match res {
+                Ok(()) => sender.send_modify(|times| times.candles = now()),
+                Err(_) => candles = None,
+            },
+            res = changed(&mut ctxs) => match res {
+                Ok(()) => sender.send_modify(|times| times.ctxs = now()),
+                Err(_) => ctxs = None,
+            },
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Versioned JSON snapshots of the shared maps, meant to be sent between services.
+//!
+//! The schema only changes together with [`SNAPSHOT_SCHEMA_VERSION`]. Version 1:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "captured_at": 1718000000000,
+//!   "prices": [
+//!     { "coin": "ETH", "market": "perp", "price": 3500.1, "sz_decimals": 4, "max_decimals": 6 }
+//!   ]
+//! }
+//! ```
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "captured_at": 1718000000000,
+//!   "books": [
+//!     { "coin": "ETH", "time": 1717999999950, "bids": [[3500.0, 1.2, 3]], "asks": [[3500.2, 0.4, 1]] }
+//!   ]
+//! }
+//! ```
+//!
+//! `captured_at` and `time` are unix milliseconds, entries are sorted by coin and book levels are
+//! `[price, size, number of orders]`, best first.
+
+use anyhow::Error;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{BookLevel, NameToOrderbookMap, NameToPriceMap, Price};
+
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Market {
+    Spot,
+    Perp,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PriceSnapshotEntry {
+    pub coin: String,
+    pub market: Market,
+    pub price: f64,
+    pub sz_decimals: u16,
+    pub max_decimals: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PriceSnapshot {
+    pub version: u32,
+    pub captured_at: i64,
+    pub prices: Vec<PriceSnapshotEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderbookSnapshotEntry {
+    pub coin: String,
+    pub time: u64,
+    pub bids: Vec<(f64, f64, u64)>,
+    pub asks: Vec<(f64, f64, u64)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderbookSnapshot {
+    pub version: u32,
+    pub captured_at: i64,
+    pub books: Vec<OrderbookSnapshotEntry>,
+}
+
+pub trait ToJsonSnapshot {
+    type Snapshot: Serialize;
+
+    fn to_snapshot(&self) -> Self::Snapshot;
+
+    fn to_json_snapshot(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(&self.to_snapshot())?)
+    }
+}
+
+impl ToJsonSnapshot for NameToPriceMap {
+    type Snapshot = PriceSnapshot;
+
+    fn to_snapshot(&self) -> PriceSnapshot {
+        let mut prices: Vec<PriceSnapshotEntry> = self
+            .iter()
+            .filter_map(|(coin, price)| {
+                let market = match price {
+                    Price::Spot { .. } => Market::Spot,
+                    Price::Perp { .. } => Market::Perp,
+                    Price::None => return None,
+                };
+
+                Some(PriceSnapshotEntry {
+                    coin: coin.to_string(),
+                    market,
+                    price: price.get_value(),
+                    sz_decimals: price.get_meta().get_sz_decimals(),
+                    max_decimals: price.get_max_decimals(),
+                })
+            })
+            .collect();
+
+        prices.sort_by(|a, b| a.coin.cmp(&b.coin));
+
+        PriceSnapshot {
+            version: SNAPSHOT_SCHEMA_VERSION,
+            captured_at: Utc::now().timestamp_millis(),
+            prices,
+        }
+    }
+}
+
+impl ToJsonSnapshot for NameToOrderbookMap {
+    type Snapshot = OrderbookSnapshot;
+
+    fn to_snapshot(&self) -> OrderbookSnapshot {
+        let levels = |levels: &Vec<BookLevel>| {
+            levels
+                .iter()
+                .map(|level| (level.price, level.size, level.orders))
+                .collect()
+        };
+
+        let mut books: Vec<OrderbookSnapshotEntry> = self
+            .iter()
+            .map(|(coin, book)| OrderbookSnapshotEntry {
+                coin: coin.to_string(),
+                time: book.time,
+                bids: levels(&book.bids),
+                asks: levels(&book.asks),
+            })
+            .collect();
+
+        books.sort_by(|a, b| a.coin.cmp(&b.coin));
+
+        OrderbookSnapshot {
+            version: SNAPSHOT_SCHEMA_VERSION,
+            captured_at: Utc::now().timestamp_millis(),
+            books,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::types::{
+        BookLevel, Meta, NameToOrderbookMap, NameToPriceMap, Orderbook, Price, Symbol,
+    };
+
+    use super::ToJsonSnapshot;
+
+    /// The documented schema, without the capture time
+    fn parse(snapshot: String) -> Value {
+        let mut value: Value = serde_json::from_str(&snapshot).unwrap();
+        assert!(value["captured_at"].as_i64().unwrap() > 0);
+        value.as_object_mut().unwrap().remove("captured_at");
+
+        value
+    }
+
+    #[test]
+    fn price_snapshot_matches_schema_v1() {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 4,
+            max_leverage: 25,
+            only_isolated: None,
+            is_delisted: None,
+        };
+        let prices: NameToPriceMap = [
+            (Symbol::from_api("ETH"), Price::new_perp(3500.1, meta)),
+            (Symbol::from_api("NONE"), Price::None),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            parse(prices.to_json_snapshot().unwrap()),
+            json!({
+                "version": 1,
+                "prices": [
+                    { "coin": "ETH", "market": "perp", "price": 3500.1, "sz_decimals": 4, "max_decimals": 6 }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn orderbook_snapshot_matches_schema_v1() {
+        let level = |price, size, orders| BookLevel {
+            price,
+            size,
+            orders,
+        };
+        let books: NameToOrderbookMap = [("ETH", 1717999999950), ("BTC", 1717999999900)]
+            .into_iter()
+            .map(|(coin, time)| {
+                (
+                    Symbol::from_api(coin),
+                    Orderbook {
+                        coin: coin.to_string(),
+                        time,
+                        bids: vec![level(3500.0, 1.2, 3)],
+                        asks: vec![level(3500.2, 0.4, 1)],
+                    },
+                )
+            })
+            .collect();
+
+        let snapshot = parse(books.to_json_snapshot().unwrap());
+
+        assert_eq!(snapshot["version"], 1);
+        // Sorted by coin
+        assert_eq!(snapshot["books"][0]["coin"], "BTC");
+        assert_eq!(
+            snapshot["books"][1],
+            json!({ "coin": "ETH", "time": 1717999999950_u64, "bids": [[3500.0, 1.2, 3]], "asks": [[3500.2, 0.4, 1]] })
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    path::Path,
+};
+
+use anyhow::Error;
+#[cfg(feature = "live")]
+use chrono::Utc;
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+
+#[cfg(feature = "live")]
+use crate::{feeds::PriceFeed, orderbook::OrderbookStream};
+use crate::{
+    recorder::{RecordFormat, RecordReader},
+    types::{BookLevel, NameToOrderbookMap, NameToPriceMap, Orderbook},
+};
+
+/// Where a strategy gets its prices from, so the same code can run on the live feed, a recording
+/// or scripted prices in tests.
+pub trait PriceSource {
+    /// Waits for the next update. `false` once the source is exhausted, i.e. the end of a
+    /// recording or the live task having stopped.
+    fn get_next_update(&mut self) -> impl Future<Output = Result<bool, Error>> + Send;
+
+    /// Every price as of the latest update
+    fn get_snapshot(&self) -> NameToPriceMap;
+
+    /// When the latest update happened in unix milliseconds, the recording time when replaying
+    fn get_time(&self) -> i64;
+}
+
+/// A live price map from one of the `start_*_sender_task`s or a [`PriceFeed`]
+#[cfg(feature = "live")]
+pub struct LivePriceSource {
+    receiver: watch::Receiver<NameToPriceMap>,
+    time: i64,
+}
+
+#[cfg(feature = "live")]
+impl LivePriceSource {
+    pub fn new(receiver: watch::Receiver<NameToPriceMap>) -> Self {
+        LivePriceSource { receiver, time: 0 }
+    }
+
+    pub fn from_feed(feed: &impl PriceFeed) -> Self {
+        LivePriceSource::new(feed.get_receiver())
+    }
+}
+
+#[cfg(feature = "live")]
+impl PriceSource for LivePriceSource {
+    fn get_next_update(&mut self) -> impl Future<Output = Result<bool, Error>> + Send {
+        async move {
+            if self.receiver.changed().await.is_err() {
+                return Ok(false);
+            }
+
+            self.time = Utc::now().timestamp_millis();
+            Ok(true)
+        }
+    }
+
+    fn get_snapshot(&self) -> NameToPriceMap {
+        self.receiver.borrow().clone()
+    }
+
+    fn get_time(&self) -> i64 {
+        self.time
+    }
+}
+
+/// Replays a price map recording written by [`crate::recorder::start_recorder_task`], as fast as
+/// the updates are consumed.
+pub struct ReplayPriceSource {
+    reader: RecordReader<NameToPriceMap>,
+    prices: NameToPriceMap,
+    time: i64,
+}
+
+impl ReplayPriceSource {
+    pub fn open(path: &Path, format: RecordFormat) -> Result<Self, Error> {
+        Ok(ReplayPriceSource {
+            reader: RecordReader::open(path, format)?,
+            prices: NameToPriceMap::default(),
+            time: 0,
+        })
+    }
+}
+
+impl PriceSource for ReplayPriceSource {
+    fn get_next_update(&mut self) -> impl Future<Output = Result<bool, Error>> + Send {
+        let record = self.reader.next().transpose();
+
+        async move {
+            match record? {
+                Some(record) => {
+                    self.prices = record.data;
+                    self.time = record.time;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+
+    fn get_snapshot(&self) -> NameToPriceMap {
+        self.prices.clone()
+    }
+
+    fn get_time(&self) -> i64 {
+        self.time
+    }
+}
+
+/// Scripted updates for tests. Coins missing from an update keep their previous price.
+#[derive(Clone, Debug, Default)]
+pub struct MockPriceSource {
+    updates: VecDeque<(i64, NameToPriceMap)>,
+    prices: NameToPriceMap,
+    time: i64,
+}
+
+impl MockPriceSource {
+    pub fn new() -> Self {
+        MockPriceSource::default()
+    }
+
+    /// Queues an update at `time`, unix milliseconds.
+    pub fn push(&mut self, time: i64, prices: NameToPriceMap) {
+        self.updates.push_back((time, prices));
+    }
+
+    pub fn get_pending(&self) -> usize {
+        self.updates.len()
+    }
+}
+
+impl PriceSource for MockPriceSource {
+    fn get_next_update(&mut self) -> impl Future<Output = Result<bool, Error>> + Send {
+        async move {
+            let Some((time, prices)) = self.updates.pop_front() else {
+                return Ok(false);
+            };
+
+            self.prices.extend(prices);
+            self.time = time;
+            Ok(true)
+        }
+    }
+
+    fn get_snapshot(&self) -> NameToPriceMap {
+        self.prices.clone()
+    }
+
+    fn get_time(&self) -> i64 {
+        self.time
+    }
+}
+
+/// Where execution or backtest code gets its books from, live or simulated.
+pub trait OrderbookSource {
+    /// Waits for the next book of any of the source's coins. `None` once the source is
+    /// exhausted.
+    fn get_next_update(&mut self) -> impl Future<Output = Result<Option<Orderbook>, Error>> + Send;
+}
+
+#[cfg(feature = "live")]
+impl OrderbookSource for OrderbookStream {
+    fn get_next_update(&mut self) -> impl Future<Output = Result<Option<Orderbook>, Error>> + Send {
+        async move {
+            // `None` from the stream is a message that wasn't a book, it errors once closed
+            loop {
+                if let Some(book) = self.get_next_book().await? {
+                    return Ok(Some(book));
+                }
+            }
+        }
+    }
+}
+
+/// Replays a book map recording written by [`crate::recorder::start_recorder_task`], returning
+/// the books that changed in every record, oldest first.
+pub struct ReplayOrderbookSource {
+    reader: RecordReader<NameToOrderbookMap>,
+    pending: VecDeque<Orderbook>,
+    last_times: HashMap<String, u64>,
+}
+
+impl ReplayOrderbookSource {
+    pub fn open(path: &Path, format: RecordFormat) -> Result<Self, Error> {
+        Ok(ReplayOrderbookSource {
+            reader: RecordReader::open(path, format)?,
+            pending: VecDeque::new(),
+            last_times: HashMap::new(),
+        })
+    }
+
+    fn read_next(&mut self) -> Result<Option<Orderbook>, Error> {
+        while self.pending.is_empty() {
+            let Some(record) = self.reader.next().transpose()? else {
+                return Ok(None);
+            };
+
+            let mut books: Vec<Orderbook> = record
+                .data
+                .into_values()
+                .filter(|book| self.last_times.get(&book.coin) != Some(&book.time))
+                .collect();
+            books.sort_by_key(|book| book.time);
+
+            for book in books {
+                self.last_times.insert(book.coin.clone(), book.time);
+                self.pending.push_back(book);
+            }
+        }
+
+        Ok(self.pending.pop_front())
+    }
+}
+
+impl OrderbookSource for ReplayOrderbookSource {
+    fn get_next_update(&mut self) -> impl Future<Output = Result<Option<Orderbook>, Error>> + Send {
+        let book = self.read_next();
+
+        async move { book }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntheticBookConfig {
+    pub coin: String,
+    pub start_mid: f64,
+    pub spread_bps: f64,
+    /// Distance between consecutive levels of a side
+    pub level_step_bps: f64,
+    pub levels: usize,
+    /// Size of the best levels, each level further out holds one more of it
+    pub level_size: f64,
+    /// Largest move of the mid between two books, drawn uniformly in both directions
+    pub max_move_bps: f64,
+    pub interval_ms: u64,
+    /// Same seed, same books
+    pub seed: u64,
+    /// Stops after this many books if set
+    pub count: Option<usize>,
+}
+
+impl SyntheticBookConfig {
+    pub fn new(coin: &str, start_mid: f64) -> Self {
+        SyntheticBookConfig {
+            coin: coin.to_string(),
+            start_mid,
+            spread_bps: 2.0,
+            level_step_bps: 1.0,
+            levels: 20,
+            level_size: 1.0,
+            max_move_bps: 5.0,
+            interval_ms: 1000,
+            seed: 1,
+            count: None,
+        }
+    }
+}
+
+/// Random walk books for simulations, with evenly spaced levels around a mid that moves at most
+/// `max_move_bps` per book.
+#[derive(Clone, Debug)]
+pub struct SyntheticBookSource {
+    config: SyntheticBookConfig,
+    mid: f64,
+    time: u64,
+    generated: usize,
+    state: u64,
+}
+
+impl SyntheticBookSource {
+    pub fn new(config: SyntheticBookConfig) -> Self {
+        SyntheticBookSource {
+            mid: config.start_mid,
+            time: 0,
+            generated: 0,
+            // Xorshift gets stuck on 0
+            state: config.seed.max(1),
+            config,
+        }
+    }
+
+    /// Uniform in [-1.0, 1.0)
+    fn next_random(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        (self.state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+
+    fn next_book(&mut self) -> Option<Orderbook> {
+        if self
+            .config
+            .count
+            .is_some_and(|count| self.generated >= count)
+        {
+            return None;
+        }
+
+        if self.generated > 0 {
+            self.mid *= 1.0 + self.next_random() * self.config.max_move_bps / 10_000.0;
+            self.time += self.config.interval_ms;
+        }
+        self.generated += 1;
+
+        let config = &self.config;
+        let side = |sign: f64| -> Vec<BookLevel> {
+            (0..config.levels)
+                .map(|i| {
+                    let bps = config.spread_bps / 2.0 + i as f64 * config.level_step_bps;
+
+                    BookLevel {
+                        price: self.mid * (1.0 + sign * bps / 10_000.0),
+                        size: config.level_size * (i + 1) as f64,
+                        orders: 1,
+                    }
+                })
+                .collect()
+        };
+
+        Some(Orderbook {
+            coin: config.coin.clone(),
+            time: self.time,
+            bids: side(-1.0),
+            asks: side(1.0),
+        })
+    }
+}
+
+impl OrderbookSource for SyntheticBookSource {
+    fn get_next_update(&mut self) -> impl Future<Output = Result<Option<Orderbook>, Error>> + Send {
+        let book = self.next_book();
+
+        async move { Ok(book) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        recorder::{Record, RecordFormat, RecordWriter},
+        types::{Meta, NameToPriceMap, Price, Symbol},
+    };
+
+    use super::{
+        MockPriceSource, OrderbookSource, PriceSource, ReplayPriceSource, SyntheticBookConfig,
+        SyntheticBookSource,
+    };
+
+    fn prices(coins: &[(&str, f64)]) -> NameToPriceMap {
+        coins
+            .iter()
+            .enumerate()
+            .map(|(index, (coin, price))| {
+                let meta = Meta::Perp {
+                    name: coin.to_string(),
+                    index: index as u16,
+                    sz_decimals: 2,
+                    max_leverage: 20,
+                    only_isolated: None,
+                    is_delisted: None,
+                };
+
+                (Symbol::from_api(coin), Price::from_meta(*price, &meta))
+            })
+            .collect()
+    }
+
+    /// Generic over the source, as strategy code would be
+    async fn get_last_price<S: PriceSource>(source: &mut S, coin: &str) -> Option<f64> {
+        let mut last = None;
+
+        while source.get_next_update().await.ok()? {
+            last = source.get_snapshot().get(coin).map(Price::get_value);
+        }
+
+        last
+    }
+
+    #[tokio::test]
+    async fn mock_and_replay_sources() -> anyhow::Result<()> {
+        let mut mock = MockPriceSource::new();
+        mock.push(1000, prices(&[("ETH", 2000.0), ("BTC", 60000.0)]));
+        mock.push(2000, prices(&[("ETH", 2010.0)]));
+
+        assert!(mock.get_next_update().await?);
+        assert!(mock.get_next_update().await?);
+        assert_eq!(mock.get_time(), 2000);
+        // BTC kept its price from the first update
+        assert_eq!(mock.get_snapshot()["BTC"].get_value(), 60000.0);
+        assert!(!mock.get_next_update().await?);
+
+        let path = std::env::temp_dir().join(format!("hl_sources_{}.jsonl", std::process::id()));
+        let mut writer = RecordWriter::create(&path, RecordFormat::JsonLines)?;
+        for (time, price) in [(1000, 2000.0), (2000, 2020.0)] {
+            writer.write(&Record {
+                time,
+                data: prices(&[("ETH", price)]),
+            })?;
+        }
+        writer.flush()?;
+
+        let mut replay = ReplayPriceSource::open(&path, RecordFormat::JsonLines)?;
+        assert_eq!(get_last_price(&mut replay, "ETH").await, Some(2020.0));
+        assert_eq!(replay.get_time(), 2000);
+
+        std::fs::remove_file(&path)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn synthetic_books_are_reproducible() -> anyhow::Result<()> {
+        let config = SyntheticBookConfig {
+            levels: 3,
+            count: Some(50),
+            ..SyntheticBookConfig::new("ETH", 2000.0)
+        };
+
+        let mut source = SyntheticBookSource::new(config.clone());
+        let mut books = vec![];
+        while let Some(book) = source.get_next_update().await? {
+            books.push(book);
+        }
+
+        assert_eq!(books.len(), 50);
+        assert_eq!(books[0].get_mid(), Some(2000.0));
+        assert_eq!(books[49].time, 49_000);
+
+        for pair in books.windows(2) {
+            let (prev, next) = (pair[0].get_mid().unwrap(), pair[1].get_mid().unwrap());
+            assert!((next / prev - 1.0).abs() <= 5.0 / 10_000.0 + 1e-12);
+        }
+
+        let book = &books[10];
+        assert!(!book.is_crossed());
+        assert_eq!(book.asks.len(), 3);
+        assert_eq!(book.asks[2].size, 3.0);
+        assert!(
+            (book.get_spread().unwrap() / book.get_mid().unwrap() * 10_000.0 - 2.0).abs() < 1e-6
+        );
+
+        let mut again = SyntheticBookSource::new(config);
+        assert_eq!(again.get_next_update().await?.as_ref(), books.first());
+        assert_eq!(again.get_next_update().await?.as_ref(), books.get(1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use alloy::primitives::Address;
+use anyhow::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    price_data::perps::parse_string_to_float,
+    prices::{build_info_http_client, post_info},
+};
+
+/// HYPE staked with one validator, as returned by the `delegations` info request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delegation {
+    pub validator: Address,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub amount: f64,
+    /// ms since epoch, the delegation can't be undelegated before
+    pub locked_until_timestamp: u64,
+}
+
+/// Staking totals of a user, as returned by the `delegatorSummary` info request
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegatorSummary {
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub delegated: f64,
+    /// In the staking account but not delegated
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub undelegated: f64,
+    /// On the way back to the spot account
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub total_pending_withdrawal: f64,
+    pub n_pending_withdrawals: u32,
+}
+
+/// Rewards credited at `time`, as returned by the `delegatorRewards` info request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegatorReward {
+    /// ms since epoch
+    pub time: u64,
+    /// "delegation", or "commission" for validators
+    pub source: String,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub total_amount: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorStats {
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub uptime_fraction: f64,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub predicted_apr: f64,
+    pub n_samples: u64,
+}
+
+/// A validator as returned by the `validatorSummaries` info request
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorSummary {
+    pub validator: Address,
+    pub signer: Address,
+    pub name: String,
+    pub description: String,
+    pub n_recent_blocks: u64,
+    /// Total stake in the token's smallest unit (8 decimals)
+    pub stake: u64,
+    pub is_jailed: bool,
+    /// ms since epoch
+    pub unjailable_after: Option<u64>,
+    pub is_active: bool,
+    #[serde(deserialize_with = "parse_string_to_float")]
+    pub commission: f64,
+    /// Stats per period, e.g. ("day", ..), ("week", ..) and ("month", ..)
+    pub stats: Vec<(String, ValidatorStats)>,
+}
+
+impl ValidatorSummary {
+    pub fn get_stats(&self, period: &str) -> Option<&ValidatorStats> {
+        self.stats
+            .iter()
+            .find_map(|(p, stats)| (p == period).then_some(stats))
+    }
+
+    pub fn get_stake(&self) -> f64 {
+        self.stake as f64 / 1e8
+    }
+}
+
+pub async fn get_delegations(client: &Client, user: Address) -> Result<Vec<Delegation>, Error> {
+    post_info(client, json!({ "type": "delegations", "user": user })).await
+}
+
+pub async fn get_delegator_summary(
+    client: &Client,
+    user: Address,
+) -> Result<DelegatorSummary, Error> {
+    post_info(client, json!({ "type": "delegatorSummary", "user": user })).await
+}
+
+pub async fn get_delegator_rewards(
+    client: &Client,
+    user: Address,
+) -> Result<Vec<DelegatorReward>, Error> {
+    post_info(client, json!({ "type": "delegatorRewards", "user": user })).await
+}
+
+pub async fn get_validator_summaries(client: &Client) -> Result<Vec<ValidatorSummary>, Error> {
+    post_info(client, json!({ "type": "validatorSummaries" })).await
+}
+
+/// Everything staked by a user and what it earned
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct StakingState {
+    pub summary: DelegatorSummary,
+    pub delegations: Vec<Delegation>,
+    pub rewards: Vec<DelegatorReward>,
+}
+
+impl StakingState {
+    pub fn get_total_rewards(&self) -> f64 {
+        self.rewards.iter().map(|reward| reward.total_amount).sum()
+    }
+
+    /// Rewards credited at or after `since` (ms since epoch)
+    pub fn get_rewards_since(&self, since: u64) -> f64 {
+        self.rewards
+            .iter()
+            .filter(|reward| reward.time >= since)
+            .map(|reward| reward.total_amount)
+            .sum()
+    }
+
+    pub fn get_delegation(&self, validator: Address) -> Option<&Delegation> {
+        self.delegations
+            .iter()
+            .find(|delegation| delegation.validator == validator)
+    }
+}
+
+pub async fn get_staking_state(client: &Client, user: Address) -> Result<StakingState, Error> {
+    let (summary, delegations, rewards) = tokio::try_join!(
+        get_delegator_summary(client, user),
+        get_delegations(client, user),
+        get_delegator_rewards(client, user),
+    )?;
+
+    Ok(StakingState {
+        summary,
+        delegations,
+        rewards,
+    })
+}
+
+/// Polls the staking state of `user` every `interval`.
+pub async fn start_staking_task(
+    user: Address,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<StakingState>> {
+    let (state_sender, state_recv) = watch::channel(StakingState::default());
+
+    tokio::spawn(async move {
+        loop {
+            info!("staking_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let mut interval = tokio::time::interval(interval);
+
+            let err = loop {
+                interval.tick().await;
+
+                match get_staking_state(&client, user).await {
+                    Ok(state) => {
+                        if state_sender.send(state).is_err() {
+                            info!("staking_task: All receivers dropped, stopping...");
+                            return;
+                        }
+                    }
+                    Err(err) => break err,
+                }
+            };
+
+            error!("staking_task: Error: {err:?}");
+            info!("staking_task: Resetting...");
+
+            sleep(Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(state_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DelegatorReward, ValidatorSummary};
+
+    #[test]
+    fn parses_validator_summaries() {
+        let validators: Vec<ValidatorSummary> = serde_json::from_str(
+            r#"[{
+                "validator": "0x000000000056f99d36b6f2e0c51fd41496bbacb8",
+                "signer": "0x0000000000000000000000000000000000000001",
+                "name": "ValiDAO",
+                "description": "",
+                "nRecentBlocks": 8,
+                "stake": 1250000000000,
+                "isJailed": false,
+                "unjailableAfter": null,
+                "isActive": true,
+                "commission": "0.04",
+                "stats": [
+                    ["day", {"uptimeFraction": "1.0", "predictedApr": "0.0231", "nSamples": 1440}],
+                    ["week", {"uptimeFraction": "0.998", "predictedApr": "0.0229", "nSamples": 10080}]
+                ]
+            }]"#,
+        )
+        .unwrap();
+
+        let validator = &validators[0];
+        assert_eq!(validator.get_stake(), 12500.0);
+        assert_eq!(validator.commission, 0.04);
+        assert_eq!(validator.get_stats("week").unwrap().n_samples, 10080);
+        assert!(validator.get_stats("month").is_none());
+
+        let rewards: Vec<DelegatorReward> = serde_json::from_str(
+            r#"[{"time": 1736726400073, "source": "delegation", "totalAmount": "0.73117184"}]"#,
+        )
+        .unwrap();
+        assert_eq!(rewards[0].total_amount, 0.73117184);
+    }
+}
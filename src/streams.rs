@@ -0,0 +1,217 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+use futures::{stream, Stream, StreamExt};
+use tokio::sync::watch;
+
+use crate::{
+    candles::{Candle, CandleBuffer},
+    price_data::perps::{NameToCtxMap, PerpsAssetCtx},
+    types::{NameToOrderbookMap, NameToPriceMap, Orderbook, Price, Symbol},
+};
+
+/// Every (coin, price) of `receiver` that changed since the previous update, starting with the
+/// current prices. Ends once the price task stops.
+pub fn price_stream(
+    receiver: watch::Receiver<NameToPriceMap>,
+) -> impl Stream<Item = (Symbol, Price)> {
+    changes_stream(receiver, |prices| {
+        prices
+            .iter()
+            .map(|(coin, price)| (coin.clone(), price.clone()))
+            .collect()
+    })
+}
+
+/// Every book of `receiver` that changed since the previous update, starting with the current
+/// books.
+pub fn book_stream(receiver: watch::Receiver<NameToOrderbookMap>) -> impl Stream<Item = Orderbook> {
+    changes_stream(receiver, |books| {
+        books
+            .iter()
+            .map(|(coin, book)| (coin.clone(), book.clone()))
+            .collect()
+    })
+    .map(|(_, book)| book)
+}
+
+/// The latest candle of every coin whenever it changes, so an open candle shows up on every
+/// update until it closes.
+pub fn candle_stream(receiver: watch::Receiver<CandleBuffer>) -> impl Stream<Item = Candle> {
+    changes_stream(receiver, |buffer| {
+        buffer
+            .iter()
+            .filter_map(|(coin, candles)| Some((coin.clone(), candles.back()?.clone())))
+            .collect()
+    })
+    .map(|(_, candle)| candle)
+}
+
+pub fn ctx_stream(
+    receiver: watch::Receiver<NameToCtxMap>,
+) -> impl Stream<Item = (String, PerpsAssetCtx)> {
+    changes_stream(receiver, |ctxs| {
+        ctxs.iter()
+            .map(|(coin, ctx)| (coin.clone(), ctx.clone()))
+            .collect()
+    })
+}
+
+/// Prices of `coin` from `receiver`, starting with the current one. With a `threshold` (0.01 ==
+/// 1%) a price is only yielded once it moved more than that from the last yielded price, so
+/// updates to other coins in the shared map and small ticks don't wake the consumer.
+pub fn watch_coin(
+    receiver: watch::Receiver<NameToPriceMap>,
+    coin: &str,
+    threshold: Option<f64>,
+) -> impl Stream<Item = Price> {
+    let coin = coin.to_string();
+    let mut last: Option<f64> = None;
+
+    changes_stream(receiver, move |prices| {
+        prices
+            .get(coin.as_str())
+            .map(|price| vec![(coin.clone(), price.clone())])
+            .unwrap_or_default()
+    })
+    .filter_map(move |(_, price)| {
+        let is_move = match (last, threshold) {
+            (Some(last), Some(threshold)) => ((price.get_value() - last) / last).abs() > threshold,
+            _ => true,
+        };
+
+        if is_move {
+            last = Some(price.get_value());
+        }
+
+        async move { is_move.then_some(price) }
+    })
+}
+
+struct ChangesState<T, K, V, F> {
+    receiver: watch::Receiver<T>,
+    entries: F,
+    last: HashMap<K, V>,
+    pending: VecDeque<(K, V)>,
+    is_first: bool,
+}
+
+/// Turns a watch channel of per coin values into a stream of the (coin, value) pairs that
+/// differ from the previous update. Coins sharing an update are yielded in no particular order.
+fn changes_stream<T, K, V, F>(
+    receiver: watch::Receiver<T>,
+    entries: F,
+) -> impl Stream<Item = (K, V)>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + PartialEq,
+    F: Fn(&T) -> Vec<(K, V)>,
+{
+    let state = ChangesState {
+        receiver,
+        entries,
+        last: HashMap::new(),
+        pending: VecDeque::new(),
+        is_first: true,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.pending.pop_front() {
+                return Some((item, state));
+            }
+
+            if state.is_first {
+                state.is_first = false;
+            } else if state.receiver.changed().await.is_err() {
+                return None;
+            }
+
+            let entries = (state.entries)(&state.receiver.borrow_and_update());
+
+            for (coin, value) in entries {
+                if state.last.get(&coin) == Some(&value) {
+                    continue;
+                }
+
+                state.last.insert(coin.clone(), value.clone());
+                state.pending.push_back((coin, value));
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use futures::StreamExt;
+    use tokio::sync::watch;
+
+    use crate::types::{Meta, NameToPriceMap, Price, Symbol};
+
+    use super::{changes_stream, watch_coin};
+
+    fn entries(map: &HashMap<String, u32>) -> Vec<(String, u32)> {
+        map.iter().map(|(k, v)| (k.clone(), *v)).collect()
+    }
+
+    #[tokio::test]
+    async fn only_changed_coins_are_yielded() {
+        let initial = HashMap::from([("ETH".to_string(), 1), ("BTC".to_string(), 1)]);
+        let (sender, receiver) = watch::channel(initial);
+
+        let mut stream = Box::pin(changes_stream(receiver, entries));
+
+        let mut first = vec![stream.next().await.unwrap(), stream.next().await.unwrap()];
+        first.sort();
+        assert_eq!(first, vec![("BTC".to_string(), 1), ("ETH".to_string(), 1)]);
+
+        sender.send_modify(|map| {
+            map.insert("ETH".to_string(), 2);
+        });
+        assert_eq!(stream.next().await, Some(("ETH".to_string(), 2)));
+
+        drop(sender);
+        assert_eq!(stream.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn watch_coin_skips_small_moves_and_other_coins() {
+        let price = |value| {
+            Price::new_perp(
+                value,
+                Meta::Perp {
+                    name: "ETH".to_string(),
+                    index: 1,
+                    sz_decimals: 4,
+                    max_leverage: 25,
+                    only_isolated: None,
+                    is_delisted: None,
+                },
+            )
+        };
+
+        let initial: NameToPriceMap = [(Symbol::from_api("ETH"), price(100.0))]
+            .into_iter()
+            .collect();
+        let (sender, receiver) = watch::channel(initial);
+        let mut stream = Box::pin(watch_coin(receiver, "ETH", Some(0.01)));
+
+        assert_eq!(stream.next().await.unwrap().get_value(), 100.0);
+
+        sender.send_modify(|map| {
+            map.insert(Symbol::from_api("BTC"), price(50_000.0));
+        });
+        sender.send_modify(|map| {
+            map.insert(Symbol::from_api("ETH"), price(100.5));
+        });
+        sender.send_modify(|map| {
+            map.insert(Symbol::from_api("ETH"), price(102.0));
+        });
+
+        assert_eq!(stream.next().await.unwrap().get_value(), 102.0);
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, time::Duration};
+
+use alloy::primitives::Address;
+use anyhow::Error;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    account::{
+        get_account_state, get_spot_balances, AccountState, ClearinghouseState, SpotBalance,
+        SpotClearinghouseState,
+    },
+    prices::{build_info_http_client, post_info},
+};
+
+/// State of one account of an entity, the main account or one of its sub-accounts
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct SubAccountState {
+    /// "main" for the main account
+    pub name: String,
+    pub user: Address,
+    pub perps: AccountState,
+    pub spot_balances: Vec<SpotBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubAccount {
+    name: String,
+    sub_account_user: Address,
+    clearinghouse_state: ClearinghouseState,
+    spot_state: SpotClearinghouseState,
+}
+
+impl From<SubAccount> for SubAccountState {
+    fn from(sub_account: SubAccount) -> Self {
+        SubAccountState {
+            name: sub_account.name,
+            user: sub_account.sub_account_user,
+            perps: AccountState::from(sub_account.clearinghouse_state),
+            spot_balances: sub_account.spot_state.balances,
+        }
+    }
+}
+
+/// Every sub-account of `user` with its perps and spot state, from a single `subAccounts` info
+/// request.
+pub async fn get_sub_account_states(
+    client: &Client,
+    user: Address,
+) -> Result<Vec<SubAccountState>, Error> {
+    // null for users without sub-accounts
+    let sub_accounts: Option<Vec<SubAccount>> =
+        post_info(client, json!({ "type": "subAccounts", "user": user })).await?;
+
+    Ok(sub_accounts
+        .unwrap_or_default()
+        .into_iter()
+        .map(SubAccountState::from)
+        .collect())
+}
+
+/// Consolidated view of a main account and its sub-accounts.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct EntityState {
+    /// The main account first
+    pub accounts: Vec<SubAccountState>,
+    /// Net signed perp size per coin
+    pub positions: HashMap<String, f64>,
+    /// Total spot balance per token
+    pub balances: HashMap<String, f64>,
+    /// Sum of the perps account values
+    pub account_value: f64,
+    pub total_margin_used: f64,
+    pub total_notional: f64,
+    pub unrealized_pnl: f64,
+}
+
+impl EntityState {
+    pub fn from_accounts(accounts: Vec<SubAccountState>) -> Self {
+        let mut state = EntityState::default();
+
+        for account in &accounts {
+            for position in &account.perps.positions {
+                *state.positions.entry(position.coin.clone()).or_default() += position.size;
+                state.unrealized_pnl += position.unrealized_pnl;
+            }
+
+            for balance in &account.spot_balances {
+                *state.balances.entry(balance.coin.clone()).or_default() += balance.total;
+            }
+
+            state.account_value += account.perps.account_value;
+            state.total_margin_used += account.perps.total_margin_used;
+            state.total_notional += account.perps.total_notional;
+        }
+
+        // Positions offsetting each other across accounts
+        state.positions.retain(|_, size| *size != 0.0);
+        state.accounts = accounts;
+
+        state
+    }
+
+    /// Fraction of the combined account value used as margin, 0.0 for an empty entity.
+    pub fn get_margin_usage(&self) -> f64 {
+        if self.account_value <= 0.0 {
+            return 0.0_f64;
+        }
+
+        self.total_margin_used / self.account_value
+    }
+
+    pub fn get_account(&self, user: Address) -> Option<&SubAccountState> {
+        self.accounts.iter().find(|account| account.user == user)
+    }
+}
+
+/// State of `user` and all its sub-accounts.
+pub async fn get_entity_state(client: &Client, user: Address) -> Result<EntityState, Error> {
+    let (perps, spot_balances, sub_accounts) = tokio::try_join!(
+        get_account_state(client, user),
+        get_spot_balances(client, user),
+        get_sub_account_states(client, user),
+    )?;
+
+    let main = SubAccountState {
+        name: "main".to_string(),
+        user,
+        perps,
+        spot_balances,
+    };
+
+    Ok(EntityState::from_accounts(
+        std::iter::once(main).chain(sub_accounts).collect(),
+    ))
+}
+
+/// Polls the state of `user` and its sub-accounts every `interval`. Sub-accounts created while
+/// the task runs are picked up on the next poll.
+pub async fn start_entity_state_task(
+    user: Address,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<EntityState>> {
+    let (state_sender, state_recv) = watch::channel(EntityState::default());
+
+    tokio::spawn(async move {
+        loop {
+            info!("entity_state_task: Starting...");
+
+            let client = match build_info_http_client() {
+                Ok(c) => c,
+                Err(e) => {
+                    error!("Error while building the http client: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            let mut interval = tokio::time::interval(interval);
+
+            let err = loop {
+                interval.tick().await;
+
+                match get_entity_state(&client, user).await {
+                    Ok(state) => {
+                        if state_sender.send(state).is_err() {
+                            info!("entity_state_task: All receivers dropped, stopping...");
+                            return;
+                        }
+                    }
+                    Err(err) => break err,
+                }
+            };
+
+            error!("entity_state_task: Error: {err:?}");
+            info!("entity_state_task: Resetting...");
+
+            sleep(Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(state_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Address;
+
+    use crate::account::{AccountState, Position, SpotBalance};
+
+    use super::{EntityState, SubAccountState};
+
+    fn account(name: &str, size: f64, usdc: f64) -> SubAccountState {
+        SubAccountState {
+            name: name.to_string(),
+            user: Address::ZERO,
+            perps: AccountState {
+                positions: vec![Position {
+                    coin: "ETH".to_string(),
+                    size,
+                    ..Default::default()
+                }],
+                account_value: 1000.0,
+                total_margin_used: 250.0,
+                ..Default::default()
+            },
+            spot_balances: vec![SpotBalance {
+                coin: "USDC".to_string(),
+                total: usdc,
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn aggregates_accounts() {
+        let state = EntityState::from_accounts(vec![
+            account("main", 1.0, 100.0),
+            account("hedge", -1.0, 50.0),
+            account("other", 0.5, 0.0),
+        ]);
+
+        assert_eq!(state.positions.get("ETH"), Some(&0.5));
+        assert_eq!(state.balances.get("USDC"), Some(&150.0));
+        assert_eq!(state.account_value, 3000.0);
+        assert_eq!(state.get_margin_usage(), 0.25);
+
+        let state = EntityState::from_accounts(vec![
+            account("main", 1.0, 100.0),
+            account("hedge", -1.0, 50.0),
+        ]);
+        assert!(state.positions.is_empty());
+    }
+}
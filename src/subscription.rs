@@ -0,0 +1,183 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{bail, Error};
+use hyperliquid_rust_sdk::{Message, Subscription};
+use tokio::{
+    runtime::Handle,
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    time::{timeout, Instant},
+};
+use tracing::{error, warn};
+
+use crate::{
+    events::{emit, FeedEventKind},
+    transport::{LiveTransport, Transport},
+};
+
+/// Owns a transport and the ids of its subscriptions. Anything still subscribed when the guard
+/// is dropped (including while unwinding from a panic) is unsubscribed from a spawned task.
+pub struct SubscriptionGuard {
+    transport: Arc<dyn Transport>,
+    sub_ids: Vec<u32>,
+}
+
+impl SubscriptionGuard {
+    /// Subscribes through a new mainnet connection.
+    pub async fn new() -> Result<Self, Error> {
+        Ok(SubscriptionGuard::with_transport(Arc::new(
+            LiveTransport::new().await?,
+        )))
+    }
+
+    pub fn with_transport(transport: Arc<dyn Transport>) -> Self {
+        SubscriptionGuard {
+            transport,
+            sub_ids: vec![],
+        }
+    }
+
+    pub fn get_transport(&self) -> Arc<dyn Transport> {
+        self.transport.clone()
+    }
+
+    pub async fn subscribe(
+        &mut self,
+        subscription: Subscription,
+        sender: UnboundedSender<Message>,
+    ) -> Result<u32, Error> {
+        let sub_id = self.transport.subscribe(subscription, sender).await?;
+
+        self.sub_ids.push(sub_id);
+
+        Ok(sub_id)
+    }
+
+    /// Unsubscribes a single subscription made through this guard.
+    pub async fn unsubscribe(&mut self, sub_id: u32) -> Result<(), Error> {
+        self.transport.unsubscribe(sub_id).await?;
+        self.sub_ids.retain(|id| *id != sub_id);
+
+        Ok(())
+    }
+
+    pub fn get_sub_ids(&self) -> &Vec<u32> {
+        &self.sub_ids
+    }
+
+    pub async fn unsubscribe_all(&mut self) -> Result<(), Error> {
+        while let Some(sub_id) = self.sub_ids.pop() {
+            self.transport.unsubscribe(sub_id).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        if self.sub_ids.is_empty() {
+            return;
+        }
+
+        let sub_ids = std::mem::take(&mut self.sub_ids);
+        let transport = self.transport.clone();
+
+        match Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    for sub_id in sub_ids {
+                        if let Err(err) = transport.unsubscribe(sub_id).await {
+                            error!("Couldn't unsubscribe {sub_id} on drop: {err:?}");
+                        }
+                    }
+                });
+            }
+            Err(_) => warn!("No runtime to unsubscribe {sub_ids:?} on drop"),
+        }
+    }
+}
+
+/// Tracks when a websocket consumer last got a message, so a connection that silently stops
+/// delivering errors out (and goes through the reconnect path) instead of blocking forever. Runs
+/// on tokio's clock, so tests can pause and advance it.
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    timeout: Duration,
+    last_message: Instant,
+    feed: Option<String>,
+}
+
+impl Heartbeat {
+    pub fn new(timeout: Duration) -> Self {
+        Heartbeat {
+            timeout,
+            last_message: Instant::now(),
+            feed: None,
+        }
+    }
+
+    /// Emits a `Stale` feed event under `feed` on every timeout.
+    pub fn with_feed(mut self, feed: &str) -> Self {
+        self.feed = Some(feed.to_string());
+        self
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn get_last_message(&self) -> Instant {
+        self.last_message
+    }
+
+    /// Waits for the next message, failing if none arrives within the timeout.
+    pub async fn recv<T>(
+        &mut self,
+        receiver: &mut UnboundedReceiver<T>,
+    ) -> Result<Option<T>, Error> {
+        match timeout(self.timeout, receiver.recv()).await {
+            Ok(msg) => {
+                self.last_message = Instant::now();
+                Ok(msg)
+            }
+            Err(_) => {
+                let silent = self.last_message.elapsed();
+
+                if let Some(feed) = &self.feed {
+                    emit(
+                        feed,
+                        FeedEventKind::Stale {
+                            silent_ms: silent.as_millis() as i64,
+                        },
+                    );
+                }
+
+                bail!("No message for {silent:?}, the connection is considered dead")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::Heartbeat;
+
+    #[tokio::test]
+    async fn silent_channel_times_out() {
+        let (sender, mut receiver) = unbounded_channel::<u32>();
+        let mut heartbeat = Heartbeat::new(Duration::from_millis(50));
+
+        sender.send(1).unwrap();
+        assert_eq!(heartbeat.recv(&mut receiver).await.unwrap(), Some(1));
+
+        assert!(heartbeat.recv(&mut receiver).await.is_err());
+    }
+}
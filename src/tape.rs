@@ -0,0 +1,228 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::watch, time::sleep};
+use tracing::{error, info};
+
+use crate::{
+    events::{emit, ConnectionEvents, FeedEventKind},
+    trades::{Trade, TradesStream},
+};
+
+pub type CoinToTapeBucketMap = HashMap<String, TapeBucket>;
+
+/// Trades of one coin aggregated over `[start_time, start_time + interval)`, times in ms.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct TapeBucket {
+    pub coin: String,
+    pub start_time: u64,
+    pub end_time: u64,
+    /// Taker buy volume in units of the coin
+    pub buy_volume: f64,
+    /// Taker sell volume in units of the coin
+    pub sell_volume: f64,
+    pub trades: u64,
+    /// Trade with the largest notional in the bucket
+    pub largest_print: Option<Trade>,
+}
+
+impl TapeBucket {
+    fn new(coin: String, start_time: u64, interval_ms: u64) -> Self {
+        TapeBucket {
+            coin,
+            start_time,
+            end_time: start_time + interval_ms,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, trade: Trade) {
+        if trade.is_buy {
+            self.buy_volume += trade.size;
+        } else {
+            self.sell_volume += trade.size;
+        }
+        self.trades += 1;
+
+        let is_largest = match &self.largest_print {
+            Some(largest) => trade.get_notional() > largest.get_notional(),
+            None => true,
+        };
+
+        if is_largest {
+            self.largest_print = Some(trade);
+        }
+    }
+
+    pub fn get_volume(&self) -> f64 {
+        self.buy_volume + self.sell_volume
+    }
+}
+
+/// Buckets trades into fixed intervals aligned on the epoch, one open bucket per coin.
+#[derive(Clone, Debug)]
+pub struct TapeAggregator {
+    interval_ms: u64,
+    open: HashMap<String, TapeBucket>,
+}
+
+impl TapeAggregator {
+    pub fn new(interval: Duration) -> Self {
+        TapeAggregator {
+            interval_ms: (interval.as_millis() as u64).max(1),
+            open: HashMap::new(),
+        }
+    }
+
+    /// Adds `trade` to its coin's bucket. Returns the previous bucket if the trade opened a new
+    /// one. Trades older than the open bucket are counted in it.
+    pub fn push(&mut self, trade: Trade) -> Option<TapeBucket> {
+        let start_time = trade.time - trade.time % self.interval_ms;
+        let mut closed = None;
+
+        match self.open.get_mut(&trade.coin) {
+            Some(bucket) if start_time < bucket.end_time => {
+                bucket.add(trade);
+                return None;
+            }
+            Some(_) => closed = self.open.remove(&trade.coin),
+            None => (),
+        }
+
+        let mut bucket = TapeBucket::new(trade.coin.clone(), start_time, self.interval_ms);
+        bucket.add(trade);
+        self.open.insert(bucket.coin.clone(), bucket);
+
+        closed
+    }
+
+    /// Closes and returns every open bucket that ended at or before `now` (ms since epoch).
+    pub fn flush(&mut self, now: u64) -> Vec<TapeBucket> {
+        let coins: Vec<String> = self
+            .open
+            .iter()
+            .filter(|(_, bucket)| bucket.end_time <= now)
+            .map(|(coin, _)| coin.clone())
+            .collect();
+
+        coins
+            .iter()
+            .filter_map(|coin| self.open.remove(coin))
+            .collect()
+    }
+
+    pub fn get_open_bucket(&self, coin: &str) -> Option<&TapeBucket> {
+        self.open.get(coin)
+    }
+}
+
+/// Publishes the last completed bucket of every coin in `coins`. A coin's entry only changes once
+/// an interval with at least one trade has ended.
+pub async fn start_tape_task(
+    coins: Vec<String>,
+    interval: Duration,
+) -> anyhow::Result<watch::Receiver<CoinToTapeBucketMap>> {
+    let (tape_sender, tape_recv) = watch::channel(CoinToTapeBucketMap::new());
+
+    tokio::spawn(async move {
+        let t_s = tape_sender;
+        let mut aggregator = TapeAggregator::new(interval);
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+
+        let mut events = ConnectionEvents::default();
+        loop {
+            info!("tape_task: Starting...");
+
+            let mut trades_stream = match TradesStream::new(&coins).await {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Error while getting TradesStream: {e:?}");
+                    error!("Sleeping for 5 secs and restarting...");
+                    sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            events.connected("tape_task");
+
+            let err = loop {
+                let closed: Vec<TapeBucket> = tokio::select! {
+                    trades = trades_stream.get_next_trades() => match trades {
+                        Ok(Some(trades)) => trades
+                            .into_iter()
+                            .filter_map(|trade| aggregator.push(trade))
+                            .collect(),
+                        Ok(None) => continue,
+                        Err(err) => break err,
+                    },
+                    _ = ticker.tick() => aggregator.flush(chrono::Utc::now().timestamp_millis() as u64),
+                };
+
+                if !closed.is_empty() {
+                    t_s.send_modify(|map| {
+                        for bucket in closed {
+                            map.insert(bucket.coin.clone(), bucket);
+                        }
+                    });
+                }
+
+                if t_s.is_closed() {
+                    info!("tape_task: All receivers dropped, stopping...");
+                    let _ = trades_stream.unsub().await;
+                    return;
+                }
+            };
+
+            error!("tape_task: Error: {err:?}");
+            emit(
+                "tape_task",
+                FeedEventKind::SubscriptionDropped {
+                    reason: format!("{err:?}"),
+                },
+            );
+            info!("tape_task: Resetting...");
+
+            let _ = trades_stream.unsub().await;
+            sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+
+    Ok(tape_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::trades::Trade;
+
+    use super::TapeAggregator;
+
+    fn trade(is_buy: bool, price: f64, size: f64, time: u64) -> Trade {
+        Trade {
+            coin: "ETH".to_string(),
+            is_buy,
+            price,
+            size,
+            time,
+            tid: time,
+        }
+    }
+
+    #[test]
+    fn buckets_trades_per_interval() {
+        let mut aggregator = TapeAggregator::new(Duration::from_secs(60));
+
+        assert!(aggregator.push(trade(true, 100.0, 2.0, 1_000)).is_none());
+        assert!(aggregator.push(trade(false, 101.0, 5.0, 30_000)).is_none());
+
+        let bucket = aggregator.push(trade(true, 99.0, 1.0, 61_000)).unwrap();
+        assert_eq!(bucket.start_time, 0);
+        assert_eq!(bucket.buy_volume, 2.0);
+        assert_eq!(bucket.sell_volume, 5.0);
+        assert_eq!(bucket.trades, 2);
+        assert_eq!(bucket.largest_print.unwrap().size, 5.0);
+
+        assert!(aggregator.flush(119_999).is_empty());
+        assert_eq!(aggregator.flush(120_000).len(), 1);
+    }
+}
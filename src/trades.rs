@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Error};
+use hyperliquid_rust_sdk::{Message, Subscription, Trade as TradeData};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tracing::error;
+
+use crate::{
+    counters::{Counter, FeedCounter, FeedCounters},
+    latency::record_exchange_time,
+    subscription::SubscriptionGuard,
+    transport::{LiveTransport, Transport},
+};
+
+/// A public trade, `is_buy` being the side of the taker.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Trade {
+    pub coin: String,
+    pub is_buy: bool,
+    pub price: f64,
+    pub size: f64,
+    pub time: u64,
+    pub tid: u64,
+}
+
+impl Trade {
+    /// Size with the sign of the taker's direction, positive for buys.
+    pub fn get_signed_size(&self) -> f64 {
+        if self.is_buy {
+            self.size
+        } else {
+            -self.size
+        }
+    }
+
+    pub fn get_notional(&self) -> f64 {
+        self.price * self.size
+    }
+}
+
+impl TryFrom<TradeData> for Trade {
+    type Error = Error;
+
+    fn try_from(trade: TradeData) -> Result<Self, Self::Error> {
+        Ok(Trade {
+            is_buy: trade.side == "B",
+            price: trade.px.parse::<f64>().context("Couldn't parse trade px")?,
+            size: trade.sz.parse::<f64>().context("Couldn't parse trade sz")?,
+            time: trade.time,
+            tid: trade.tid,
+            coin: trade.coin,
+        })
+    }
+}
+
+pub struct TradesStream {
+    subscriptions: SubscriptionGuard,
+    trades_receiver: UnboundedReceiver<Message>,
+    counter: FeedCounter,
+}
+
+impl TradesStream {
+    /// Subscribes through a new mainnet connection.
+    pub async fn new(coins: &[String]) -> Result<Self, Error> {
+        TradesStream::with_transport(Arc::new(LiveTransport::new().await?), coins).await
+    }
+
+    pub async fn with_transport(
+        transport: Arc<dyn Transport>,
+        coins: &[String],
+    ) -> Result<Self, Error> {
+        let mut subscriptions = SubscriptionGuard::with_transport(transport);
+
+        let (sender, receiver) = unbounded_channel();
+
+        for coin in coins {
+            subscriptions
+                .subscribe(Subscription::Trades { coin: coin.clone() }, sender.clone())
+                .await
+                .with_context(|| format!("Couldn't subscribe to the trades of {coin}"))?;
+        }
+
+        Ok(TradesStream {
+            subscriptions,
+            trades_receiver: receiver,
+            counter: FeedCounter::new("trades"),
+        })
+    }
+
+    /// Counts on `counter` instead of counters of its own, e.g. to keep counting across
+    /// reconnects.
+    pub fn with_counter(mut self, counter: FeedCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    pub fn get_counters(&self) -> FeedCounters {
+        self.counter.get_counters()
+    }
+
+    pub async fn get_next_trades(&mut self) -> anyhow::Result<Option<Vec<Trade>>> {
+        let msg = self.trades_receiver.recv().await;
+        if msg.is_some() {
+            self.counter.count(Counter::Received);
+        }
+
+        match msg {
+            Some(msg) => match msg {
+                Message::NoData => {
+                    error!("Couldn't recieve trades data");
+                    Err(anyhow::anyhow!("No data found"))
+                }
+                Message::HyperliquidError(err) => {
+                    error!("Hyperliquid error while getting trades data: {err:?}");
+                    Err(anyhow::anyhow!("Hyperliquid error found"))
+                }
+                Message::Trades(trades) => {
+                    let received = trades.data.len();
+                    let trades: Vec<Trade> = trades
+                        .data
+                        .into_iter()
+                        .filter_map(|trade| match Trade::try_from(trade) {
+                            Ok(trade) => Some(trade),
+                            Err(err) => {
+                                error!("Skipping malformed trade: {err:?}");
+                                None
+                            }
+                        })
+                        .collect();
+                    self.counter.add(
+                        Counter::DeserializeFailures,
+                        (received - trades.len()) as u64,
+                    );
+
+                    if let Some(time) = trades.iter().map(|trade| trade.time).max() {
+                        record_exchange_time("trades", time);
+                    }
+
+                    Ok(Some(trades))
+                }
+                s => {
+                    error!("Got something else: {s:?}");
+                    self.counter.count(Counter::Dropped);
+                    Ok(None)
+                }
+            },
+            None => Err(anyhow::anyhow!("Trades channel closed")),
+        }
+    }
+
+    pub async fn unsub(&mut self) -> anyhow::Result<()> {
+        self.subscriptions.unsubscribe_all().await
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use anyhow::{anyhow, Context, Error};
+use futures::future::BoxFuture;
+use hyperliquid_rust_sdk::{BaseUrl, InfoClient, Message, Subscription};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::sync::{mpsc::UnboundedSender, RwLock};
+use tracing::warn;
+
+use crate::{
+    breaker::get_info_breaker,
+    endpoints::{get_info_endpoints, MAINNET_ALT_API_URL},
+    prices::{build_info_http_client, get_sdk_base_url, post_info, TESTNET_API_URL},
+    ratelimit::{get_info_weight, get_rest_rate_limiter},
+    websocket::{get_ws_proxy, WsClient},
+};
+
+/// Where info requests and websocket subscriptions go. [`LiveTransport`] talks to mainnet (or the
+/// API set with [`crate::prices::set_api_url`]), [`FakeTransport`] serves canned responses and
+/// messages so code built on [`crate::prices::Prices`] and
+/// [`crate::subscription::SubscriptionGuard`] can be tested offline.
+pub trait Transport: Send + Sync {
+    /// Posts `request` to the info endpoint and returns the raw response.
+    fn post_info(&self, request: Value) -> BoxFuture<'_, Result<Value, Error>>;
+
+    fn subscribe(
+        &self,
+        subscription: Subscription,
+        sender: UnboundedSender<Message>,
+    ) -> BoxFuture<'_, Result<u32, Error>>;
+
+    fn unsubscribe(&self, sub_id: u32) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+/// Posts `request` through `transport` and deserializes the response.
+pub async fn request_info<T: DeserializeOwned>(
+    transport: &dyn Transport,
+    request: Value,
+) -> Result<T, Error> {
+    let response = transport.post_info(request).await?;
+
+    Ok(serde_json::from_value(response)?)
+}
+
+/// Sends info requests over HTTP and subscriptions over the SDK's websocket. Requests the SDK's
+/// `InfoClient` covers without losing fields (currently `spotMeta`) go through it, unless
+/// [`LiveTransport::with_custom_info`] is set. Like every other info request they pass the
+/// crate's circuit breaker and rate limiter, and fall back to the crate's endpoints when the SDK
+/// fails.
+///
+/// The HTTP client (and with it its proxy, see [`crate::prices::HttpConfig::proxy`]) is shared
+/// with the SDK's `InfoClient`. The SDK opens its websocket to its own host for the network and
+/// without a proxy option, so subscriptions go through the crate's [`WsClient`] instead when a
+/// proxy is configured (see [`get_ws_proxy`]) or when the endpoints were probed and another host
+/// is the fastest (see [`crate::endpoints::probe_endpoints`]).
+///
+/// The websocket isn't compressed: the SDK's `InfoClient` connects through tokio-tungstenite,
+/// which doesn't negotiate permessage-deflate, and exposes no option for it. To cut L2 bandwidth,
+/// only subscribe to the books that are actually read, e.g. with a
+/// [`crate::manager::LazyBookManager`].
+pub struct LiveTransport {
+    client: Client,
+    info_client: RwLock<InfoClient>,
+    ws_client: Option<WsClient>,
+    use_custom_info: bool,
+}
+
+impl LiveTransport {
+    /// Connects the websocket right away, like `InfoClient::new`. Info requests go through the
+    /// shared HTTP client.
+    pub async fn new() -> Result<Self, Error> {
+        LiveTransport::with_http_client(build_info_http_client()?).await
+    }
+
+    pub async fn with_http_client(client: Client) -> Result<Self, Error> {
+        Ok(LiveTransport {
+            client,
+            info_client: RwLock::new(
+                InfoClient::new(Some(client.clone()), Some(get_sdk_base_url())).await?,
+            ),
+            ws_client: match get_ws_proxy() {
+                Some(proxy) => Some(WsClient::connect(Some(proxy)).await?),
+                None if is_sdk_host_slower() => Some(WsClient::connect(None).await?),
+                None => None,
+            },
+            use_custom_info: false,
+        })
+    }
+
+    /// Falls back to sending every info request with the crate's own HTTP client.
+    pub fn with_custom_info(mut self, use_custom_info: bool) -> Self {
+        self.use_custom_info = use_custom_info;
+        self
+    }
+
+    /// The SDK's spot meta in the wire format the crate's `SpotMeta` deserializes from. The
+    /// SDK's perps meta has no leverage info, so `meta` always takes the custom path.
+    async fn get_sdk_spot_meta(&self) -> Result<Value, Error> {
+        let spot_meta = self.info_client.read().await.spot_meta().await?;
+
+        let universe: Vec<Value> = spot_meta
+            .universe
+            .iter()
+            .map(|pair| {
+                json!({
+                    "tokens": pair.tokens,
+                    "name": pair.name,
+                    "index": pair.index,
+                    "isCanonical": pair.is_canonical,
+                })
+            })
+            .collect();
+
+        let tokens: Vec<Value> = spot_meta
+            .tokens
+            .iter()
+            .map(|token| {
+                json!({
+                    "name": token.name,
+                    "szDecimals": token.sz_decimals,
+                    "weiDecimals": token.wei_decimals,
+                    "index": token.index,
+                    "tokenId": format!("{:?}", token.token_id),
+                    "isCanonical": token.is_canonical,
+                })
+            })
+            .collect();
+
+        Ok(json!({ "universe": universe, "tokens": tokens }))
+    }
+
+    /// [`LiveTransport::get_sdk_spot_meta`] behind the same circuit breaker and rate limiter as
+    /// [`post_info`], failing over to the crate's endpoints when the SDK errors.
+    async fn post_sdk_info(&self, request: &Value) -> Result<Value, Error> {
+        let weight = get_info_weight(request["type"].as_str().unwrap_or_default());
+
+        let bytes = get_info_breaker()
+            .call(Some(&request.to_string()), || async {
+                get_rest_rate_limiter().acquire(weight).await;
+
+                match self.get_sdk_spot_meta().await {
+                    Ok(spot_meta) => Ok(serde_json::to_vec(&spot_meta)?),
+                    Err(err) => {
+                        warn!("Couldn't get the spot meta through the SDK, falling back: {err:?}");
+                        get_rest_rate_limiter().acquire(weight).await;
+
+                        get_info_endpoints().post_info(&self.client, request).await
+                    }
+                }
+            })
+            .await?;
+
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+/// The endpoints were probed and the fastest isn't the host the SDK's websocket goes to.
+fn is_sdk_host_slower() -> bool {
+    let endpoints = get_info_endpoints();
+    let sdk_host = match get_sdk_base_url() {
+        BaseUrl::Testnet => TESTNET_API_URL,
+        _ => MAINNET_ALT_API_URL,
+    };
+
+    endpoints.is_probed()
+        && endpoints
+            .get_fastest_url()
+            .is_some_and(|url| url != sdk_host)
+}
+
+impl Transport for LiveTransport {
+    fn post_info(&self, request: Value) -> BoxFuture<'_, Result<Value, Error>> {
+        Box::pin(async move {
+            if !self.use_custom_info && request["type"] == "spotMeta" {
+                return self.post_sdk_info(&request).await;
+            }
+
+            post_info(&self.client, request).await
+        })
+    }
+
+    fn subscribe(
+        &self,
+        subscription: Subscription,
+        sender: UnboundedSender<Message>,
+    ) -> BoxFuture<'_, Result<u32, Error>> {
+        Box::pin(async move {
+            if let Some(ws_client) = &self.ws_client {
+                return ws_client.subscribe(&subscription, sender);
+            }
+
+            self.info_client
+                .write()
+                .await
+                .subscribe(subscription, sender)
+                .await
+                .context("Couldn't get subscriptions id")
+        })
+    }
+
+    fn unsubscribe(&self, sub_id: u32) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            if let Some(ws_client) = &self.ws_client {
+                return ws_client.unsubscribe(sub_id);
+            }
+
+            self.info_client.write().await.unsubscribe(sub_id).await?;
+
+            Ok(())
+        })
+    }
+}
+
+/// In-memory transport. Info requests are answered by their `type` from the responses set with
+/// [`FakeTransport::set_response`], and messages given to [`FakeTransport::push`] go to every
+/// active subscription.
+#[derive(Default)]
+pub struct FakeTransport {
+    responses: Mutex<HashMap<String, Value>>,
+    subscribers: Mutex<HashMap<u32, (Value, UnboundedSender<Message>)>>,
+    next_sub_id: AtomicU32,
+}
+
+impl FakeTransport {
+    pub fn new() -> Self {
+        FakeTransport::default()
+    }
+
+    /// Answers every info request of `request_type` (e.g. "meta", "spotMeta") with `response`.
+    pub fn set_response(&self, request_type: &str, response: Value) {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(request_type.to_string(), response);
+    }
+
+    /// Sends `message` to every subscription, returns how many received it.
+    pub fn push(&self, message: Message) -> usize {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(_, sender)| sender.send(message.clone()).is_ok())
+            .count()
+    }
+
+    /// The active subscriptions as they would be sent to the API
+    pub fn get_subscriptions(&self) -> Vec<Value> {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(subscription, _)| subscription.clone())
+            .collect()
+    }
+}
+
+impl Transport for FakeTransport {
+    fn post_info(&self, request: Value) -> BoxFuture<'_, Result<Value, Error>> {
+        let response = request["type"]
+            .as_str()
+            .and_then(|request_type| self.responses.lock().unwrap().get(request_type).cloned())
+            .ok_or_else(|| anyhow!("No fake response for {request}"));
+
+        Box::pin(async move { response })
+    }
+
+    fn subscribe(
+        &self,
+        subscription: Subscription,
+        sender: UnboundedSender<Message>,
+    ) -> BoxFuture<'_, Result<u32, Error>> {
+        let result = serde_json::to_value(&subscription).map(|subscription| {
+            let sub_id = self.next_sub_id.fetch_add(1, Ordering::SeqCst);
+            self.subscribers
+                .lock()
+                .unwrap()
+                .insert(sub_id, (subscription, sender));
+
+            sub_id
+        });
+
+        Box::pin(async move { Ok(result?) })
+    }
+
+    fn unsubscribe(&self, sub_id: u32) -> BoxFuture<'_, Result<(), Error>> {
+        let result = match self.subscribers.lock().unwrap().remove(&sub_id) {
+            Some(_) => Ok(()),
+            None => Err(anyhow!("Unknown subscription {sub_id}")),
+        };
+
+        Box::pin(async move { result })
+    }
+}
@@ -1,14 +1,23 @@
+use anyhow::{anyhow, bail, Error};
 use serde::{Deserialize, Serialize};
 
+use crate::types::Symbol;
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Meta {
     Spot {
         name: String,
+        /// Index of the pair in the spot universe, 0 in metas serialized before it was added
+        #[serde(default)]
+        index: u16,
         quote: SpotAssetMeta,
         base: SpotAssetMeta,
     },
     Perp {
         name: String,
+        /// Index of the coin in the perps universe, 0 in metas serialized before it was added
+        #[serde(default)]
+        index: u16,
         sz_decimals: u16,
         max_leverage: u16,
         only_isolated: Option<bool>,
@@ -38,12 +47,40 @@ impl Meta {
         }
     }
 
+    /// Asset id used by the exchange endpoint. Spot pairs are offset by 10_000.
+    pub fn get_asset_index(&self) -> u32 {
+        match self {
+            Meta::Spot { index, .. } => 10_000 + *index as u32,
+            Meta::Perp { index, .. } => *index as u32,
+        }
+    }
+
+    /// Spot assets can't be levered, so they always return 1.
+    pub fn get_max_leverage(&self) -> u16 {
+        match self {
+            Meta::Spot { .. } => 1,
+            Meta::Perp { max_leverage, .. } => *max_leverage,
+        }
+    }
+
+    /// Whether positions can only be opened with isolated margin. Always false for spot.
+    pub fn is_only_isolated(&self) -> bool {
+        match self {
+            Meta::Spot { .. } => false,
+            Meta::Perp { only_isolated, .. } => only_isolated.unwrap_or(false),
+        }
+    }
+
     pub fn get_name(&self) -> &String {
         match self {
             Meta::Spot { name, .. } => name,
             Meta::Perp { name, .. } => name,
         }
     }
+
+    pub fn get_symbol(&self) -> Symbol {
+        Symbol::from_api(self.get_name())
+    }
 }
 
 #[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
@@ -53,3 +90,83 @@ pub struct SpotAssetMeta {
     pub name: String,
     pub index: u16,
 }
+
+impl SpotAssetMeta {
+    /// The decimal `amount` in the token's smallest unit, e.g. "1.5" USDC (8 wei decimals) is
+    /// 150000000. Parsed digit by digit so large balances don't lose precision, digits past the
+    /// wei decimals are rounded half up. Errors on anything but plain non-negative decimals.
+    pub fn to_wei(&self, amount: &str) -> Result<u128, Error> {
+        let amount = amount.trim();
+        let (int, frac) = amount.split_once('.').unwrap_or((amount, ""));
+
+        if int.is_empty() && frac.is_empty()
+            || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+        {
+            bail!("Invalid amount {amount:?}");
+        }
+
+        let decimals = self.wei_decimals as usize;
+        let (kept, dropped) = frac.split_at(frac.len().min(decimals));
+        let wei: u128 = format!("{int}{kept:0<decimals$}")
+            .parse()
+            .map_err(|_| anyhow!("{amount} overflows in wei"))?;
+
+        let round_up = dropped.starts_with(['5', '6', '7', '8', '9']);
+
+        wei.checked_add(round_up as u128)
+            .ok_or_else(|| anyhow!("{amount} overflows in wei"))
+    }
+
+    pub fn from_wei(&self, wei: u128) -> f64 {
+        wei as f64 / 10_f64.powi(self.wei_decimals as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Meta, SpotAssetMeta};
+
+    #[test]
+    fn metas_without_an_index_still_deserialize() {
+        let meta: Meta = serde_json::from_value(json!({
+            "Perp": {
+                "name": "ETH",
+                "sz_decimals": 4,
+                "max_leverage": 25,
+                "only_isolated": null,
+                "is_delisted": null
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(meta.get_asset_index(), 0);
+        assert_eq!(meta.get_sz_decimals(), 4);
+    }
+
+    #[test]
+    fn decimal_amounts_convert_to_wei_exactly() {
+        let meta = SpotAssetMeta {
+            wei_decimals: 8,
+            ..Default::default()
+        };
+
+        assert_eq!(meta.to_wei("1.5").unwrap(), 150_000_000);
+        assert_eq!(meta.to_wei("14.62548523").unwrap(), 1_462_548_523);
+        assert_eq!(meta.to_wei(".1").unwrap(), 10_000_000);
+        assert_eq!(meta.to_wei("3").unwrap(), 300_000_000);
+        // Past f64's 15-17 significant digits
+        assert_eq!(
+            meta.to_wei("123456789012.12345678").unwrap(),
+            12_345_678_901_212_345_678
+        );
+        assert_eq!(meta.to_wei("0.000000015").unwrap(), 2);
+        assert_eq!(meta.to_wei("0.000000014").unwrap(), 1);
+
+        assert!(meta.to_wei("-1.0").is_err());
+        assert!(meta.to_wei("1e3").is_err());
+        assert!(meta.to_wei(".").is_err());
+        assert!(meta.to_wei("").is_err());
+    }
+}
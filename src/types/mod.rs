@@ -1,21 +1,56 @@
 mod price;
 mod meta;
+mod orderbook;
+mod symbol;
 pub use price::*;
 pub use meta::*;
+pub use orderbook::*;
+pub use symbol::*;
 
-use std::{collections::HashMap, fmt};
+use std::{collections::HashMap, fmt, sync::RwLock};
+
+use anyhow::{anyhow, bail, Error};
 
 use serde::{
     de::{self, Visitor},
     Deserialize, Deserializer, Serialize,
 };
 
+/// Hasher of the per coin maps, std's SipHash unless the `ahash` feature is enabled. Build the
+/// maps with `default()` or `collect()` rather than `new()`/`from()`, which only exist for std's
+/// hasher.
+#[cfg(not(feature = "ahash"))]
+pub type FastHasher = std::collections::hash_map::RandomState;
+#[cfg(feature = "ahash")]
+pub type FastHasher = ahash::RandomState;
+
+pub type FastMap<K, V> = HashMap<K, V, FastHasher>;
+
 pub type PriceIsBuyAndAsset = (f64, bool, String);
-pub type NameToPriceMap = HashMap<String, Price>;
+pub type NameToPriceMap = FastMap<Symbol, Price>;
+/// When each coin of a per coin map (prices, books) last updated, ms since epoch
+pub type NameToUpdateTimeMap = FastMap<Symbol, i64>;
 pub type CoinToOiValueMap = HashMap<String, f64>;
+pub type CoinToMidMap = HashMap<String, f64>;
 pub const BOLD_START_ANSI: &str = "\x1b[1m";
 pub const BOLD_END_ANSI: &str = "\x1b[0m";
 
+/// Orders below this USD value are rejected by the exchange.
+pub const MIN_ORDER_NOTIONAL: f64 = 10.0;
+
+/// "TOKEN1/TOKEN2" to pair name, set once spot meta has been fetched
+static PAIR_TO_NAME: RwLock<Option<HashMap<String, String>>> = RwLock::new(None);
+
+/// Lets [`Pair`] resolve "TOKEN1/TOKEN2" to the pair name (e.g. "@107") while deserializing. The
+/// live spot price data sets this whenever it fetches the spot meta.
+pub fn set_pair_to_name_map(pair_to_name_map: HashMap<String, String>) {
+    if let Ok(mut map) = PAIR_TO_NAME.write() {
+        *map = Some(pair_to_name_map);
+    }
+}
+
+/// A spot position or order given as "TOKEN1/TOKEN2" (or directly by pair name) and a size in
+/// units of the first token.
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Pair {
     #[serde(deserialize_with = "parse_pair_to_name")]
@@ -24,18 +59,73 @@ pub struct Pair {
 }
 
 impl Pair {
-    pub fn convert_to_name(&self, pair_to_name_map: &HashMap<String, String>) -> Self {
-        Pair {
-            name: pair_to_name_map
-                .get(&self.name)
-                .unwrap_or(&"".to_string())
-                .to_string(),
+    /// Fails if `name` is neither a pair in `pair_to_name_map` nor one of its pair names.
+    pub fn convert_to_name(
+        &self,
+        pair_to_name_map: &HashMap<String, String>,
+    ) -> Result<Self, Error> {
+        let name = match pair_to_name_map.get(&self.name) {
+            Some(name) => name.clone(),
+            None if pair_to_name_map.values().any(|name| *name == self.name) => self.name.clone(),
+            None => bail!("Unknown spot pair {}", self.name),
+        };
+
+        Ok(Pair {
+            name,
             size: self.size,
+        })
+    }
+
+    /// Price of the pair, `name` has to be converted to a pair name already.
+    pub fn get_price<'a>(&self, prices: &'a NameToPriceMap) -> Result<&'a Price, Error> {
+        match prices.get(self.name.as_str()) {
+            Some(price) if price.get_value() > 0.0 => Ok(price),
+            _ => Err(anyhow!("No price for the pair {}", self.name)),
+        }
+    }
+
+    /// Value of `size` in the quote token, which is USDC for the canonical pairs.
+    pub fn usd_notional(&self, prices: &NameToPriceMap) -> Result<f64, Error> {
+        Ok(self.size * self.get_price(prices)?.get_value())
+    }
+
+    /// Checks that the size is positive, has no more decimals than the token allows and is worth
+    /// at least [`MIN_ORDER_NOTIONAL`].
+    pub fn validate(&self, prices: &NameToPriceMap) -> Result<(), Error> {
+        let price = self.get_price(prices)?;
+
+        if self.size <= 0.0 {
+            bail!(
+                "Size of {} has to be positive, got {}",
+                self.name,
+                self.size
+            );
+        }
+
+        if price.get_true_size(self.size) != self.size {
+            bail!(
+                "Size {} of {} has more than {} decimals",
+                self.size,
+                self.name,
+                price.get_meta().get_sz_decimals()
+            );
         }
+
+        let notional = self.size * price.get_value();
+        if notional < MIN_ORDER_NOTIONAL {
+            bail!(
+                "Value {notional} of {} is below the minimum of {MIN_ORDER_NOTIONAL}",
+                self.name
+            );
+        }
+
+        Ok(())
     }
 }
 
-// TODO: This is for the future. Need to deserialize from pair to name here.
+/// Resolves "TOKEN1/TOKEN2" to the pair name once [`set_pair_to_name_map`] was called. Before
+/// that, and for names that aren't pairs, the value is kept as is for
+/// [`Pair::convert_to_name`].
 fn parse_pair_to_name<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
@@ -53,10 +143,86 @@ where
         where
             E: de::Error,
         {
-            // TODO: Deserialize into the actual name of the pair
-            Ok(value.to_string())
+            if !value.contains('/') {
+                return Ok(value.to_string());
+            }
+
+            let pair_to_name = PAIR_TO_NAME.read().map_err(de::Error::custom)?;
+
+            match pair_to_name.as_ref() {
+                Some(map) => map
+                    .get(value)
+                    .cloned()
+                    .ok_or_else(|| de::Error::custom(format!("Unknown spot pair {value}"))),
+                None => Ok(value.to_string()),
+            }
         }
     }
 
     deserializer.deserialize_str(StringToStringVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Meta, NameToPriceMap, Pair, Price, SpotAssetMeta, Symbol};
+
+    fn prices() -> NameToPriceMap {
+        let token = |name: &str, index| SpotAssetMeta {
+            sz_decimals: 2,
+            wei_decimals: 8,
+            name: name.to_string(),
+            index,
+        };
+
+        [(
+            Symbol::from_api("@1"),
+            Price::new_spot(
+                20.0,
+                Meta::Spot {
+                    name: "@1".to_string(),
+                    index: 1,
+                    quote: token("PURR", 1),
+                    base: token("USDC", 0),
+                },
+            ),
+        )]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn pair_converts_and_validates() {
+        let pair_to_name = HashMap::from([("PURR/USDC".to_string(), "@1".to_string())]);
+
+        let pair = Pair {
+            name: "PURR/USDC".to_string(),
+            size: 1.0,
+        }
+        .convert_to_name(&pair_to_name)
+        .unwrap();
+        assert_eq!(pair.name, "@1");
+        assert_eq!(pair.usd_notional(&prices()).unwrap(), 20.0);
+        assert!(pair.validate(&prices()).is_ok());
+
+        assert!(Pair {
+            name: "HFUN/USDC".to_string(),
+            size: 1.0,
+        }
+        .convert_to_name(&pair_to_name)
+        .is_err());
+
+        let too_small = Pair {
+            name: "@1".to_string(),
+            size: 0.25,
+        };
+        assert!(too_small.validate(&prices()).is_err());
+
+        let too_precise = Pair {
+            name: "@1".to_string(),
+            size: 1.001,
+        };
+        assert!(too_precise.validate(&prices()).is_err());
+    }
+}
@@ -0,0 +1,364 @@
+#[cfg(feature = "live")]
+use hyperliquid_rust_sdk::L2BookData;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{FastMap, Symbol};
+
+pub type NameToOrderbookMap = FastMap<Symbol, Orderbook>;
+
+/// Most points [`Orderbook::liquidity_curve`] returns, so a tiny step doesn't allocate without
+/// bound
+pub const MAX_LIQUIDITY_CURVE_STEPS: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct BookLevel {
+    pub price: f64,
+    pub size: f64,
+    pub orders: u64,
+}
+
+impl BookLevel {
+    pub fn get_notional(&self) -> f64 {
+        self.price * self.size
+    }
+}
+
+/// Best bid and offer of a book
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Bbo {
+    pub coin: String,
+    pub time: u64,
+    pub bid: Option<BookLevel>,
+    pub ask: Option<BookLevel>,
+}
+
+/// Liquidity resting within `bps` basis points of the mid, one point of
+/// [`Orderbook::liquidity_curve`]
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct LiquidityPoint {
+    pub bps: f64,
+    /// In units of the coin
+    pub bid_size: f64,
+    /// In units of the coin
+    pub ask_size: f64,
+    pub bid_notional: f64,
+    pub ask_notional: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Orderbook {
+    pub coin: String,
+    pub time: u64,
+    /// Sorted best (highest) first
+    pub bids: Vec<BookLevel>,
+    /// Sorted best (lowest) first
+    pub asks: Vec<BookLevel>,
+}
+
+impl Orderbook {
+    pub fn get_symbol(&self) -> Symbol {
+        Symbol::from_api(&self.coin)
+    }
+
+    pub fn best_bid(&self) -> Option<&BookLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&BookLevel> {
+        self.asks.first()
+    }
+
+    pub fn get_mid(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some((bid.price + ask.price) / 2.0),
+            _ => None,
+        }
+    }
+
+    pub fn get_spread(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some(ask.price - bid.price),
+            _ => None,
+        }
+    }
+
+    /// Best bid at or above the best ask, i.e. crossed or locked. A valid book never is.
+    pub fn is_crossed(&self) -> bool {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => bid.price >= ask.price,
+            _ => false,
+        }
+    }
+
+    pub fn get_bbo(&self) -> Bbo {
+        Bbo {
+            coin: self.coin.clone(),
+            time: self.time,
+            bid: self.best_bid().cloned(),
+            ask: self.best_ask().cloned(),
+        }
+    }
+
+    /// Mid where each side's price is the size-weighted average of its first `levels` levels, and
+    /// the two sides are weighted by the opposite side's size so the mid leans towards the thinner
+    /// side. `None` if either side is empty.
+    pub fn get_depth_weighted_mid(&self, levels: usize) -> Option<f64> {
+        let (bid_px, bid_sz) = get_weighted_price(&self.bids, levels)?;
+        let (ask_px, ask_sz) = get_weighted_price(&self.asks, levels)?;
+
+        Some((bid_px * ask_sz + ask_px * bid_sz) / (bid_sz + ask_sz))
+    }
+
+    /// Largest size (in units of the coin) that can be taken from the book with a volume weighted
+    /// fill price at most `max_slippage` (0.01 == 1%) away from the mid, along with that fill
+    /// price. `None` if the book is empty on either side.
+    pub fn get_max_size_within_slippage(
+        &self,
+        is_buy: bool,
+        max_slippage: f64,
+    ) -> Option<(f64, f64)> {
+        let mid = self.get_mid()?;
+        let (levels, limit) = if is_buy {
+            (&self.asks, mid * (1.0 + max_slippage))
+        } else {
+            (&self.bids, mid * (1.0 - max_slippage))
+        };
+
+        let mut size = 0.0;
+        let mut notional = 0.0;
+
+        for level in levels {
+            let within = if is_buy {
+                level.price <= limit
+            } else {
+                level.price >= limit
+            };
+
+            if within {
+                size += level.size;
+                notional += level.get_notional();
+                continue;
+            }
+
+            // Take just enough of this level to bring the average price to the limit
+            let partial =
+                ((limit * size - notional) / (level.price - limit)).clamp(0.0, level.size);
+            size += partial;
+            notional += partial * level.price;
+            break;
+        }
+
+        if size > 0.0 {
+            Some((size, notional / size))
+        } else {
+            Some((0.0, mid))
+        }
+    }
+
+    /// Returns the USD notional resting on the (bid, ask) side within `bps` basis points of the
+    /// mid. Both sides are 0.0 if the book is empty on either side.
+    pub fn get_depth_within_bps(&self, bps: f64) -> (f64, f64) {
+        let mid = match self.get_mid() {
+            Some(mid) => mid,
+            None => return (0.0, 0.0),
+        };
+
+        let distance = mid * bps / 10_000.0;
+
+        let bid_depth = self
+            .bids
+            .iter()
+            .take_while(|level| level.price >= mid - distance)
+            .map(BookLevel::get_notional)
+            .sum();
+
+        let ask_depth = self
+            .asks
+            .iter()
+            .take_while(|level| level.price <= mid + distance)
+            .map(BookLevel::get_notional)
+            .sum();
+
+        (bid_depth, ask_depth)
+    }
+
+    /// Cumulative size and notional on both sides within every multiple of `step_bps` basis
+    /// points of the mid, up to `max_bps` or [`MAX_LIQUIDITY_CURVE_STEPS`] steps, whichever comes
+    /// first. Empty if the book is empty on either side or either bound isn't a finite positive
+    /// number.
+    pub fn liquidity_curve(&self, step_bps: f64, max_bps: f64) -> Vec<LiquidityPoint> {
+        let is_valid = |bps: f64| bps.is_finite() && bps > 0.0;
+
+        let mid = match self.get_mid() {
+            Some(mid) if is_valid(step_bps) && is_valid(max_bps) => mid,
+            _ => return vec![],
+        };
+
+        // Tolerates max_bps not being an exact multiple of step_bps in floating point. The cast
+        // saturates, so huge ratios are capped too.
+        let steps = ((max_bps / step_bps + 1e-9).floor() as usize).min(MAX_LIQUIDITY_CURVE_STEPS);
+
+        let mut bids = self.bids.iter().peekable();
+        let mut asks = self.asks.iter().peekable();
+        let mut point = LiquidityPoint::default();
+
+        (1..=steps)
+            .map(|step| {
+                let bps = step as f64 * step_bps;
+                let distance = mid * bps / 10_000.0;
+
+                while let Some(level) = bids.next_if(|level| level.price >= mid - distance) {
+                    point.bid_size += level.size;
+                    point.bid_notional += level.get_notional();
+                }
+
+                while let Some(level) = asks.next_if(|level| level.price <= mid + distance) {
+                    point.ask_size += level.size;
+                    point.ask_notional += level.get_notional();
+                }
+
+                LiquidityPoint {
+                    bps,
+                    ..point.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Size-weighted average price and total size of the first `levels` levels of a side.
+fn get_weighted_price(side: &[BookLevel], levels: usize) -> Option<(f64, f64)> {
+    let (notional, size) = side
+        .iter()
+        .take(levels)
+        .fold((0.0, 0.0), |(notional, size), level| {
+            (notional + level.get_notional(), size + level.size)
+        });
+
+    if size > 0.0 {
+        Some((notional / size, size))
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "live")]
+impl From<L2BookData> for Orderbook {
+    fn from(data: L2BookData) -> Self {
+        let mut sides = data.levels.into_iter().map(|side| {
+            side.into_iter()
+                .map(|level| BookLevel {
+                    price: level.px.parse::<f64>().unwrap_or(0.0),
+                    size: level.sz.parse::<f64>().unwrap_or(0.0),
+                    orders: level.n,
+                })
+                .collect::<Vec<BookLevel>>()
+        });
+
+        Orderbook {
+            coin: data.coin,
+            time: data.time,
+            bids: sides.next().unwrap_or_default(),
+            asks: sides.next().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BookLevel, LiquidityPoint, Orderbook, MAX_LIQUIDITY_CURVE_STEPS};
+
+    fn level(price: f64, size: f64) -> BookLevel {
+        BookLevel {
+            price,
+            size,
+            orders: 1,
+        }
+    }
+
+    #[test]
+    fn depth_weighted_mid_leans_towards_thin_side() {
+        let book = Orderbook {
+            coin: "ETH".to_string(),
+            time: 0,
+            bids: vec![level(99.0, 1.0), level(98.0, 1.0)],
+            asks: vec![level(101.0, 4.0), level(102.0, 4.0)],
+        };
+
+        // Bid side averages 98.5 over 2.0, ask side 101.5 over 8.0
+        let mid = book.get_depth_weighted_mid(2).unwrap();
+        assert!((mid - (98.5 * 8.0 + 101.5 * 2.0) / 10.0).abs() < 1e-9);
+        assert!(mid < book.get_mid().unwrap());
+
+        assert_eq!(Orderbook::default().get_depth_weighted_mid(2), None);
+    }
+
+    #[test]
+    fn crossed_and_locked_books() {
+        let book = |bid: f64, ask: f64| Orderbook {
+            coin: "ETH".to_string(),
+            time: 0,
+            bids: vec![level(bid, 1.0)],
+            asks: vec![level(ask, 1.0)],
+        };
+
+        assert!(!book(99.0, 101.0).is_crossed());
+        assert!(book(100.0, 100.0).is_crossed());
+        assert!(book(101.0, 99.0).is_crossed());
+        assert!(!Orderbook::default().is_crossed());
+    }
+
+    #[test]
+    fn max_size_within_slippage() {
+        let book = Orderbook {
+            coin: "ETH".to_string(),
+            time: 0,
+            bids: vec![level(99.0, 1.0)],
+            asks: vec![level(101.0, 1.0), level(103.0, 10.0)],
+        };
+
+        // Mid is 100.0, a 2% limit averages 1 @ 101.0 with 1 @ 103.0
+        let (size, avg_price) = book.get_max_size_within_slippage(true, 0.02).unwrap();
+        assert!((size - 2.0).abs() < 1e-9);
+        assert!((avg_price - 102.0).abs() < 1e-9);
+
+        let (size, _) = book.get_max_size_within_slippage(false, 0.005).unwrap();
+        assert_eq!(size, 0.0);
+    }
+
+    #[test]
+    fn liquidity_curve_is_cumulative() {
+        let book = Orderbook {
+            coin: "ETH".to_string(),
+            time: 0,
+            bids: vec![level(99.95, 1.0), level(99.85, 2.0), level(99.0, 5.0)],
+            asks: vec![level(100.05, 1.0), level(100.15, 3.0)],
+        };
+
+        // Mid is 100.0, so 10 bps is 0.1 away
+        let curve = book.liquidity_curve(10.0, 30.0);
+
+        assert_eq!(curve.len(), 3);
+        assert_eq!(curve[0].bps, 10.0);
+        assert_eq!((curve[0].bid_size, curve[0].ask_size), (1.0, 1.0));
+        assert_eq!((curve[1].bid_size, curve[1].ask_size), (3.0, 4.0));
+        assert_eq!(
+            curve[2],
+            LiquidityPoint {
+                bps: 30.0,
+                ..curve[1].clone()
+            }
+        );
+
+        assert!(book.liquidity_curve(0.0, 30.0).is_empty());
+        assert!(Orderbook::default().liquidity_curve(10.0, 30.0).is_empty());
+
+        assert!(book.liquidity_curve(f64::NAN, 30.0).is_empty());
+        assert!(book.liquidity_curve(10.0, f64::INFINITY).is_empty());
+        assert!(book.liquidity_curve(10.0, -30.0).is_empty());
+        assert_eq!(
+            book.liquidity_curve(f64::MIN_POSITIVE, 30.0).len(),
+            MAX_LIQUIDITY_CURVE_STEPS
+        );
+    }
+}
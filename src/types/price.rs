@@ -124,6 +124,21 @@ impl Price {
         }
     }
 
+    /// Same as [`Price::get_true_size`] but rounds down, for sizes that must stay within a
+    /// budget.
+    pub fn get_floored_size(&self, size: f64) -> f64 {
+        let sz_decimals = match self {
+            Price::Spot { meta, .. } | Price::Perp { meta, .. } => meta.get_sz_decimals(),
+            Price::None => return 0.0_f64,
+        };
+
+        let factor = 10_f64.powi(sz_decimals as i32);
+        // Tolerates sizes like 1.9999999999 that are exact in decimal
+        let floored = (size * factor + 1e-9).floor() / factor;
+
+        self.get_true_size(floored)
+    }
+
     /// Receives the USDC size and converts into asset denominated size at the current price of the
     /// asset.
     /// Example:
@@ -141,6 +156,53 @@ impl Price {
         self.get_true_size(ad_size)
     }
 
+    /// Size to trade so that getting stopped out `stop_distance` away from the current price
+    /// loses at most `risk_pct` (0.01 == 1%) of `account_value`, rounded down to the asset's
+    /// sz_decimals.
+    ///
+    /// Example:
+    /// Risking 1% of 10_000 USDC with a stop 50.0 below ETH @ 3000.0
+    ///
+    /// price.position_size_for_risk(10_000.0, 0.01, 50.0) would give 2.0 ETH
+    pub fn position_size_for_risk(
+        &self,
+        account_value: f64,
+        risk_pct: f64,
+        stop_distance: f64,
+    ) -> f64 {
+        if stop_distance <= 0.0 {
+            return 0.0_f64;
+        }
+
+        self.get_floored_size(account_value * risk_pct / stop_distance.abs())
+    }
+
+    /// Same as [`Price::position_size_for_risk`] but the resulting notional is capped at
+    /// `account_value` times `leverage`, which itself is capped at the asset's `max_leverage`.
+    pub fn position_size_for_risk_with_leverage(
+        &self,
+        account_value: f64,
+        risk_pct: f64,
+        stop_distance: f64,
+        leverage: u16,
+    ) -> f64 {
+        let size = self.position_size_for_risk(account_value, risk_pct, stop_distance);
+
+        size.min(self.get_max_position_size(account_value, leverage))
+    }
+
+    /// Largest size `account_value` can open at `leverage`, capped at the asset's
+    /// `max_leverage`.
+    pub fn get_max_position_size(&self, account_value: f64, leverage: u16) -> f64 {
+        if let Price::None = self {
+            return 0.0_f64;
+        }
+
+        let leverage = leverage.min(self.get_meta().get_max_leverage()).max(1);
+
+        self.get_floored_size(account_value * leverage as f64 / self.get_value())
+    }
+
     pub fn get_true_price_for_asset(&self, price: f64) -> f64 {
         match self {
             Price::Spot { meta, .. } => Self::round_price(price, 8, meta.get_sz_decimals()),
@@ -173,6 +235,15 @@ impl Price {
         }
     }
 
+    /// Max decimals a price can have on the exchange before `sz_decimals` are taken off.
+    pub fn get_max_decimals(&self) -> u16 {
+        match self {
+            Price::Spot { .. } => 8,
+            Price::Perp { .. } => 6,
+            Price::None => 0,
+        }
+    }
+
     pub fn get_meta(&self) -> &Meta {
         match self {
             Price::None => panic!("Tried to get meta for no price..."),
@@ -195,3 +266,33 @@ impl std::fmt::Display for Price {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::types::Meta;
+
+    use super::Price;
+
+    #[test]
+    fn risk_sizes_round_down() {
+        let meta = Meta::Perp {
+            name: "ETH".to_string(),
+            index: 1,
+            sz_decimals: 2,
+            max_leverage: 10,
+            only_isolated: None,
+            is_delisted: None,
+        };
+        let price = Price::new_perp(3000.0, meta);
+
+        assert_eq!(price.position_size_for_risk(10_000.0, 0.01, 50.0), 2.0);
+        // 1.9999 would round up to 2.0 and risk more than 100.0
+        assert_eq!(price.position_size_for_risk(9_999.5, 0.01, 50.0), 1.99);
+        // 10x of 1000 at 3000 is 3.333.., capped at max_leverage
+        assert_eq!(price.get_max_position_size(1_000.0, 20), 3.33);
+        assert_eq!(
+            price.position_size_for_risk_with_leverage(1_000.0, 0.5, 100.0, 20),
+            3.33
+        );
+    }
+}
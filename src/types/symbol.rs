@@ -0,0 +1,189 @@
+use std::{borrow::Borrow, fmt, ops::Deref, str::FromStr, sync::Arc};
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Name of a perps coin (e.g. "ETH", "kPEPE") or spot pair (e.g. "@107", "PURR/USDC") as used
+/// by the API. Validated and case normalized on construction, and cheap to clone.
+///
+/// Coins are upper cased, except for the lower case "k" prefix of coins denominated in
+/// thousands, which is kept when followed by an upper case letter ("kPEPE" stays, "eth" becomes
+/// "ETH"). Keys the per coin maps ([`crate::types::NameToPriceMap`],
+/// [`crate::types::NameToOrderbookMap`], ...), which can still be looked up with a `&str` since
+/// it borrows as one.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let name = name.trim();
+
+        if name.is_empty() {
+            bail!("Empty symbol");
+        }
+
+        if let Some(index) = name.strip_prefix('@') {
+            if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+                bail!("Invalid spot pair index in {name:?}");
+            }
+
+            return Ok(Symbol(name.into()));
+        }
+
+        if let Some(c) = name
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && *c != '/' && *c != ':' && *c != '-')
+        {
+            bail!("Invalid character {c:?} in symbol {name:?}");
+        }
+
+        if name.split('/').any(str::is_empty) {
+            bail!("Invalid pair {name:?}, expected TOKEN1/TOKEN2");
+        }
+
+        let normalized = match name.strip_prefix('k') {
+            Some(rest) if rest.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                format!("k{}", rest.to_ascii_uppercase())
+            }
+            _ => name.to_ascii_uppercase(),
+        };
+
+        Ok(Symbol(normalized.into()))
+    }
+
+    /// Names coming from the API are canonical already
+    pub(crate) fn from_api(name: &str) -> Self {
+        Symbol(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Spot pairs, either by index ("@107") or by token names ("PURR/USDC")
+    pub fn is_spot(&self) -> bool {
+        self.0.starts_with('@') || self.0.contains('/')
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Symbol::new(s)
+    }
+}
+
+impl TryFrom<&str> for Symbol {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Symbol::new(value)
+    }
+}
+
+impl TryFrom<String> for Symbol {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Symbol::new(&value)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.0.to_string()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", &*self.0)
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+
+        Symbol::new(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{FastMap, Symbol};
+
+    #[test]
+    fn normalizes_and_validates() {
+        assert_eq!("eth".parse::<Symbol>().unwrap(), "ETH");
+        assert_eq!(" kPEPE ".parse::<Symbol>().unwrap(), "kPEPE");
+        assert_eq!("purr/usdc".parse::<Symbol>().unwrap(), "PURR/USDC");
+        assert!("@107".parse::<Symbol>().unwrap().is_spot());
+
+        assert!("".parse::<Symbol>().is_err());
+        assert!("@".parse::<Symbol>().is_err());
+        assert!("@1a".parse::<Symbol>().is_err());
+        assert!("PURR/".parse::<Symbol>().is_err());
+        assert!("ET H".parse::<Symbol>().is_err());
+    }
+
+    #[test]
+    fn keys_maps_looked_up_by_str() {
+        let map: FastMap<Symbol, u32> = [("eth".parse().unwrap(), 1)].into_iter().collect();
+
+        assert_eq!(map.get("ETH"), Some(&1));
+        assert_eq!(map.get(&Symbol::new("Eth").unwrap()), Some(&1));
+        assert_eq!(map.get("eth"), None);
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "live")]
+use tokio::sync::watch;
+#[cfg(feature = "live")]
+use tracing::info;
+
+use crate::price_data::perps::NameToCtxMap;
+
+/// Rolling 24h notional volume (`dayNtlVlm`) in USD per perp coin
+pub type CoinToVolumeMap = HashMap<String, f64>;
+
+pub fn get_volume_map(ctxs: &NameToCtxMap) -> CoinToVolumeMap {
+    ctxs.iter()
+        .map(|(coin, ctx)| (coin.clone(), ctx.day_ntl_vlm))
+        .collect()
+}
+
+/// Coins that traded at least `min_volume` USD over the last 24h, most traded first.
+pub fn get_liquid_coins(volumes: &CoinToVolumeMap, min_volume: f64) -> Vec<String> {
+    let mut coins: Vec<(&String, f64)> = volumes
+        .iter()
+        .filter(|(_, volume)| **volume >= min_volume)
+        .map(|(coin, volume)| (coin, *volume))
+        .collect();
+    coins.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    coins.into_iter().map(|(coin, _)| coin.clone()).collect()
+}
+
+/// Publishes the 24h notional volume of every perp every time the asset contexts of
+/// `ctx_receiver` (see [`crate::prices::start_asset_ctx_task`]) change.
+#[cfg(feature = "live")]
+pub async fn start_volume_task(
+    mut ctx_receiver: watch::Receiver<NameToCtxMap>,
+) -> anyhow::Result<watch::Receiver<CoinToVolumeMap>> {
+    let (volume_sender, volume_recv) = watch::channel(CoinToVolumeMap::new());
+
+    // The contexts already published are used right away
+    ctx_receiver.mark_changed();
+
+    tokio::spawn(async move {
+        info!("volume_task: Starting...");
+
+        loop {
+            if ctx_receiver.changed().await.is_err() {
+                info!("volume_task: Asset context channel closed, stopping...");
+                return;
+            }
+
+            let volumes = get_volume_map(&ctx_receiver.borrow_and_update());
+
+            if volume_sender.send(volumes).is_err() {
+                info!("volume_task: All receivers dropped, stopping...");
+                return;
+            }
+        }
+    });
+
+    Ok(volume_recv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_liquid_coins, CoinToVolumeMap};
+
+    #[test]
+    fn liquid_coins_by_volume() {
+        let volumes = CoinToVolumeMap::from([
+            ("ETH".to_string(), 5e8),
+            ("BTC".to_string(), 1e9),
+            ("PURR".to_string(), 1e5),
+        ]);
+
+        assert_eq!(
+            get_liquid_coins(&volumes, 1e6),
+            vec!["BTC".to_string(), "ETH".to_string()]
+        );
+    }
+
+    #[cfg(feature = "live")]
+    #[tokio::test]
+    async fn volumes_follow_the_asset_contexts() -> anyhow::Result<()> {
+        use crate::price_data::perps::{NameToCtxMap, PerpsAssetCtx};
+
+        let ctxs = |volume| {
+            NameToCtxMap::from([(
+                "ETH".to_string(),
+                PerpsAssetCtx {
+                    day_ntl_vlm: volume,
+                    ..Default::default()
+                },
+            )])
+        };
+        let (ctx_sender, ctx_receiver) = tokio::sync::watch::channel(ctxs(5e8));
+
+        let mut volumes = super::start_volume_task(ctx_receiver).await?;
+        volumes
+            .wait_for(|volumes| volumes.get("ETH") == Some(&5e8))
+            .await?;
+
+        ctx_sender.send(ctxs(6e8))?;
+        volumes
+            .wait_for(|volumes| volumes.get("ETH") == Some(&6e8))
+            .await?;
+
+        drop(ctx_sender);
+        assert!(volumes.changed().await.is_err());
+
+        Ok(())
+    }
+}